@@ -0,0 +1,75 @@
+use std::borrow::Cow;
+use std::sync::LazyLock;
+
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD;
+use regex::{Captures, Regex};
+
+/// An image inlined in a message as `![<source>](data:<media type>;base64,<data>)`, so it
+/// travels with the text through history, truncation and failover until a provider turns it
+/// into its own image part.
+static INLINE_IMAGE: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"!\[([^\]\n]*)\]\(data:(image/[A-Za-z0-9.+-]+);base64,([A-Za-z0-9+/=]+)\)").unwrap());
+
+/// What is stored in place of an inline image: a reference to where it came from.
+static IMAGE_REFERENCE: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"\[image: ([^\]\n]+)\]").unwrap());
+
+/// Media type from the file's magic bytes, for the formats vision models accept.
+fn sniff(bytes: &[u8]) -> Option<&'static str> {
+    match bytes {
+        [0x89, b'P', b'N', b'G', ..] => Some("image/png"),
+        [0xFF, 0xD8, 0xFF, ..] => Some("image/jpeg"),
+        [b'G', b'I', b'F', b'8', ..] => Some("image/gif"),
+        [b'R', b'I', b'F', b'F', _, _, _, _, b'W', b'E', b'B', b'P', ..] => Some("image/webp"),
+        _ => None,
+    }
+}
+
+fn inline(source: &str, bytes: &[u8]) -> Result<String, String> {
+    let media_type = sniff(bytes).ok_or("not a PNG, JPEG, GIF or WebP image")?;
+    let label = source.replace([']', '\n'], "_");
+    Ok(format!("![{}](data:{};base64,{})", label, media_type, STANDARD.encode(bytes)))
+}
+
+/// Reads an image from a local path or an http(s) URL and returns it inlined, ready to append
+/// to a message.
+pub async fn load(client: &reqwest::Client, source: &str) -> Result<String, String> {
+    let bytes = if source.starts_with("http://") || source.starts_with("https://") {
+        let response = client.get(source).send().await.map_err(|e| e.to_string())?;
+        if !response.status().is_success() {
+            return Err(format!("{} returned {}", source, response.status()));
+        }
+        response.bytes().await.map_err(|e| e.to_string())?.to_vec()
+    } else {
+        std::fs::read(source).map_err(|e| format!("could not read {} ({})", source, e))?
+    };
+    inline(source, &bytes)
+}
+
+/// Text of a message with every inline image taken out, and the images as media type and
+/// base64 data, in order.
+pub fn split(content: &str) -> (String, Vec<(String, String)>) {
+    let images = INLINE_IMAGE.captures_iter(content).map(|c| (c[2].to_string(), c[3].to_string())).collect();
+    (INLINE_IMAGE.replace_all(content, "").trim().to_string(), images)
+}
+
+/// The message with each inline image replaced by `[image: <source>]`, as it is stored, logged
+/// and measured against the context budget.
+pub fn without_images(content: &str) -> Cow<'_, str> {
+    INLINE_IMAGE.replace_all(content, |c: &Captures| format!("[image: {}]", &c[1]))
+}
+
+/// The sources of a message's inline images, for `messages.attachments`.
+pub fn sources(content: &str) -> Vec<String> {
+    INLINE_IMAGE.captures_iter(content).map(|c| c[1].to_string()).collect()
+}
+
+/// A stored message with its local image references read back in, for histories that are sent
+/// to a model again. URLs and files that can no longer be read stay as references.
+pub fn reattach(content: &str) -> String {
+    IMAGE_REFERENCE
+        .replace_all(content, |c: &Captures| {
+            std::fs::read(&c[1]).ok().and_then(|bytes| inline(&c[1], &bytes).ok()).unwrap_or_else(|| c[0].to_string())
+        })
+        .into_owned()
+}
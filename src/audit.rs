@@ -0,0 +1,97 @@
+use std::env;
+use std::io::Write;
+use std::process::{Command, Stdio};
+
+use crate::events::{Event, Subscriber};
+
+/// Security-relevant events forwarded to the system log when `AUDIT_LOG` is set.
+#[derive(Debug)]
+pub enum AuditEvent<'a> {
+    CommandExecuted { session_id: &'a str, command: &'a str, exit_code: Option<i32> },
+    ToolDenied { session_id: &'a str, user: &'a str, role: &'a str, tool: &'a str },
+}
+
+impl AuditEvent<'_> {
+    fn name(&self) -> &'static str {
+        match self {
+            AuditEvent::CommandExecuted { .. } => "command_executed",
+            AuditEvent::ToolDenied { .. } => "tool_denied",
+        }
+    }
+
+    fn fields(&self) -> Vec<(&'static str, String)> {
+        match self {
+            AuditEvent::CommandExecuted { session_id, command, exit_code } => vec![
+                ("SESSION_ID", session_id.to_string()),
+                ("COMMAND", command.to_string()),
+                ("EXIT_CODE", exit_code.map(|c| c.to_string()).unwrap_or_else(|| "signal".to_string())),
+            ],
+            AuditEvent::ToolDenied { session_id, user, role, tool } => vec![
+                ("SESSION_ID", session_id.to_string()),
+                ("USER", user.to_string()),
+                ("ROLE", role.to_string()),
+                ("TOOL", tool.to_string()),
+            ],
+        }
+    }
+}
+
+/// Sends `event` to syslog (`AUDIT_LOG=syslog`) or journald with structured fields
+/// (`AUDIT_LOG=journald`) through the system `logger` utility. Does nothing when unset.
+pub fn emit(event: AuditEvent) {
+    let target = env::var("AUDIT_LOG").unwrap_or_default();
+    let fields = event.fields();
+    let summary: String = fields
+        .iter()
+        .map(|(key, value)| format!(" {}={:?}", key.to_lowercase(), value))
+        .collect();
+    let message = format!("event={}{}", event.name(), summary);
+
+    let result = match target.trim() {
+        "syslog" => Command::new("logger")
+            .args(["-t", "agent_bench", "-p", "authpriv.notice", "--", &message])
+            .status()
+            .map(|_| ()),
+        "journald" => {
+            let mut record = format!(
+                "MESSAGE={}\nSYSLOG_IDENTIFIER=agent_bench\nAGENT_BENCH_EVENT={}\n",
+                message.replace('\n', " "),
+                event.name()
+            );
+            for (key, value) in &fields {
+                record.push_str(&format!("AGENT_BENCH_{}={}\n", key, value.replace('\n', " ")));
+            }
+            Command::new("logger")
+                .arg("--journald")
+                .stdin(Stdio::piped())
+                .spawn()
+                .and_then(|mut child| {
+                    if let Some(mut stdin) = child.stdin.take() {
+                        stdin.write_all(record.as_bytes())?;
+                    }
+                    child.wait().map(|_| ())
+                })
+        }
+        _ => return,
+    };
+    if let Err(e) = result {
+        eprintln!("Could not write audit event to {}: {}", target.trim(), e);
+    }
+}
+
+/// Forwards the audit-relevant session events to [`emit`].
+pub struct AuditLogger;
+
+impl Subscriber for AuditLogger {
+    fn handle(&mut self, session_id: &str, event: &Event) {
+        match event {
+            Event::ToolCompleted { tool, input, exit_code, .. } if tool == "run_command" => emit(AuditEvent::CommandExecuted {
+                session_id,
+                command: input,
+                exit_code: *exit_code,
+            }),
+            Event::ToolDenied { tool, user, role } => emit(AuditEvent::ToolDenied { session_id, user, role, tool }),
+            _ => {}
+        }
+    }
+}
@@ -0,0 +1,139 @@
+use std::collections::HashMap;
+use std::env;
+use std::process::Command;
+use std::sync::{Mutex, OnceLock};
+use std::time::{Duration, Instant};
+
+/// Tokens are refreshed this long before the expiry the token endpoint reported.
+const REFRESH_MARGIN: Duration = Duration::from_secs(60);
+
+/// How a provider's credential is obtained, from `AUTH_<PROVIDER>`: the static
+/// `<PROVIDER>_API_KEY` (default), an OAuth2 client-credentials token, or the output of a
+/// command (for gateways with their own signing or token tooling).
+#[derive(Debug, Clone, Default)]
+pub enum Method {
+    #[default]
+    Static,
+    OAuth2 {
+        token_url: String,
+        client_id: String,
+        client_secret: String,
+        scope: Option<String>,
+    },
+    Command(String),
+}
+
+/// True when `AUTH_<PROVIDER>` selects a plugin, so no static key is required.
+pub fn uses_plugin(provider: &str) -> bool {
+    env::var(format!("AUTH_{}", provider)).is_ok_and(|m| !m.trim().is_empty() && !m.trim().eq_ignore_ascii_case("static"))
+}
+
+impl Method {
+    /// Reads `AUTH_<PROVIDER>` and its settings (`AUTH_<PROVIDER>_TOKEN_URL`, `_CLIENT_ID`,
+    /// `_CLIENT_SECRET`, `_SCOPE` for `oauth2`; `AUTH_<PROVIDER>_COMMAND` for `command`).
+    pub fn from_env(provider: &str) -> Result<Self, String> {
+        let setting = |name: &str| env::var(format!("AUTH_{}_{}", provider, name)).ok().filter(|v| !v.trim().is_empty());
+        let required = |name: &str| setting(name).ok_or_else(|| format!("AUTH_{}_{} is not set", provider, name));
+        match env::var(format!("AUTH_{}", provider)).unwrap_or_default().trim().to_lowercase().as_str() {
+            "" | "static" => Ok(Method::Static),
+            "oauth2" => Ok(Method::OAuth2 {
+                token_url: required("TOKEN_URL")?,
+                client_id: required("CLIENT_ID")?,
+                client_secret: required("CLIENT_SECRET")?,
+                scope: setting("SCOPE"),
+            }),
+            "command" => Ok(Method::Command(required("COMMAND")?)),
+            other => Err(format!("unknown AUTH_{} '{}' (use static, oauth2 or command)", provider, other)),
+        }
+    }
+
+    pub fn is_static(&self) -> bool {
+        matches!(self, Method::Static)
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Method::Static => "static",
+            Method::OAuth2 { .. } => "oauth2",
+            Method::Command(_) => "command",
+        }
+    }
+}
+
+struct CachedToken {
+    token: String,
+    refresh_at: Instant,
+}
+
+fn token_cache() -> &'static Mutex<HashMap<String, CachedToken>> {
+    static CACHE: OnceLock<Mutex<HashMap<String, CachedToken>>> = OnceLock::new();
+    CACHE.get_or_init(|| Mutex::new(HashMap::new()))
+}
+
+/// A provider's cached token, unless it is due for refresh.
+pub fn cached_token(provider: &str) -> Option<String> {
+    token_cache().lock().unwrap().get(provider).filter(|c| Instant::now() < c.refresh_at).map(|c| c.token.clone())
+}
+
+/// Caches a token valid for `lifetime`, to be refreshed `REFRESH_MARGIN` before it expires.
+pub fn store_token(provider: &str, token: &str, lifetime: Duration) {
+    let cached = CachedToken { token: token.to_string(), refresh_at: Instant::now() + lifetime.saturating_sub(REFRESH_MARGIN) };
+    token_cache().lock().unwrap().insert(provider.to_string(), cached);
+}
+
+/// Drops a cached token, e.g. after the provider rejected it, so the next request fetches a
+/// new one.
+pub fn invalidate(provider: &str) {
+    token_cache().lock().unwrap().remove(provider);
+}
+
+async fn client_credentials_token(
+    client: &reqwest::Client,
+    token_url: &str,
+    client_id: &str,
+    client_secret: &str,
+    scope: Option<&str>,
+) -> Result<(String, Duration), String> {
+    let mut form = vec![("grant_type", "client_credentials"), ("client_id", client_id), ("client_secret", client_secret)];
+    if let Some(scope) = scope {
+        form.push(("scope", scope));
+    }
+    let res = client.post(token_url).form(&form).send().await.map_err(|e| e.to_string())?;
+    let status = res.status();
+    let body: serde_json::Value = res.json().await.unwrap_or_default();
+    if !status.is_success() {
+        let reason = body["error_description"].as_str().or(body["error"].as_str()).unwrap_or("no details");
+        return Err(format!("token endpoint returned {} ({})", status, reason));
+    }
+    let token = body["access_token"].as_str().ok_or("token response has no access_token")?.to_string();
+    Ok((token, Duration::from_secs(body["expires_in"].as_u64().unwrap_or(3600))))
+}
+
+/// The credential to send for one request: the static key, a cached OAuth2 token (refreshed
+/// near expiry), or the trimmed output of the configured command.
+pub async fn credential(client: &reqwest::Client, provider: &str, method: &Method, static_key: &str) -> Result<String, String> {
+    match method {
+        Method::Static => Ok(static_key.to_string()),
+        Method::OAuth2 { token_url, client_id, client_secret, scope } => {
+            if let Some(token) = cached_token(provider) {
+                return Ok(token);
+            }
+            let (token, lifetime) = client_credentials_token(client, token_url, client_id, client_secret, scope.as_deref())
+                .await
+                .map_err(|e| format!("OAuth2 token request failed: {}", e))?;
+            store_token(provider, &token, lifetime);
+            Ok(token)
+        }
+        Method::Command(command) => {
+            let output = Command::new("sh").arg("-c").arg(command).output().map_err(|e| format!("auth command failed: {}", e))?;
+            if !output.status.success() {
+                return Err(format!("auth command failed: {}", String::from_utf8_lossy(&output.stderr).trim()));
+            }
+            let token = String::from_utf8_lossy(&output.stdout).trim().to_string();
+            if token.is_empty() {
+                return Err("auth command printed no token".to_string());
+            }
+            Ok(token)
+        }
+    }
+}
@@ -0,0 +1,91 @@
+use std::env;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+use rusqlite::{params, Connection};
+use sha2::{Digest, Sha256};
+
+/// A file captured from a task, stored once per distinct content.
+#[derive(Debug)]
+pub struct Artifact {
+    /// As listed in the task.
+    pub path: String,
+    /// SHA-256 of the content, which names the stored copy.
+    pub hash: String,
+    pub size: u64,
+}
+
+/// Store directory (`ARTIFACT_DIR`, default `artifacts`), kept apart from any workspace the
+/// task's files were written to.
+fn store_dir() -> PathBuf {
+    PathBuf::from(env::var("ARTIFACT_DIR").unwrap_or_else(|_| "artifacts".to_string()))
+}
+
+/// Where the content with `hash` is kept.
+pub fn stored_path(hash: &str) -> PathBuf {
+    store_dir().join(&hash[..2]).join(hash)
+}
+
+/// Copies the task's listed files into the store and links them to the result. Files missing or
+/// last written before `since` (left over from an earlier task) are skipped and reported.
+pub fn capture(conn: &Connection, result_id: i64, paths: &[String], since: SystemTime) -> (Vec<Artifact>, Vec<String>) {
+    let mut captured = Vec::new();
+    let mut skipped = Vec::new();
+    for path in paths {
+        match store(Path::new(path), since) {
+            Ok((hash, size)) => {
+                conn.execute(
+                    "INSERT INTO artifacts (result_id, path, hash, size) VALUES (?1, ?2, ?3, ?4)",
+                    params![result_id, path, hash, size as i64],
+                ).unwrap();
+                captured.push(Artifact { path: path.clone(), hash, size });
+            }
+            Err(e) => skipped.push(format!("{} ({})", path, e)),
+        }
+    }
+    (captured, skipped)
+}
+
+fn store(path: &Path, since: SystemTime) -> Result<(String, u64), String> {
+    let modified = std::fs::metadata(path).and_then(|m| m.modified()).map_err(|e| e.to_string())?;
+    if modified < since {
+        return Err("not written by this task".to_string());
+    }
+    let content = std::fs::read(path).map_err(|e| e.to_string())?;
+    let hash: String = Sha256::digest(&content).iter().map(|b| format!("{:02x}", b)).collect();
+    let target = stored_path(&hash);
+    if !target.exists() {
+        std::fs::create_dir_all(target.parent().unwrap()).map_err(|e| e.to_string())?;
+        std::fs::write(&target, &content).map_err(|e| e.to_string())?;
+    }
+    Ok((hash, content.len() as u64))
+}
+
+/// The artifacts captured for a result, in the order the task lists them.
+pub fn list(conn: &Connection, result_id: i64) -> Vec<Artifact> {
+    let mut stmt = conn.prepare("SELECT path, hash, size FROM artifacts WHERE result_id = ?1 ORDER BY id").unwrap();
+    stmt.query_map(params![result_id], |row| Ok(Artifact { path: row.get(0)?, hash: row.get(1)?, size: row.get::<_, i64>(2)? as u64 }))
+        .unwrap()
+        .filter_map(Result::ok)
+        .collect()
+}
+
+/// Writes the stored copies back under `dir`, at their listed paths, returning the files written.
+pub fn restore(artifacts: &[Artifact], dir: &Path) -> Result<Vec<PathBuf>, String> {
+    let mut written = Vec::new();
+    for artifact in artifacts {
+        // Listed paths may be absolute or climb out of the run directory; keep them under `dir`.
+        let relative: PathBuf = Path::new(&artifact.path)
+            .components()
+            .filter(|c| matches!(c, std::path::Component::Normal(_)))
+            .collect();
+        let target = dir.join(relative);
+        let content = std::fs::read(stored_path(&artifact.hash)).map_err(|e| format!("{} ({})", artifact.path, e))?;
+        if let Some(parent) = target.parent() {
+            std::fs::create_dir_all(parent).map_err(|e| e.to_string())?;
+        }
+        std::fs::write(&target, content).map_err(|e| format!("{} ({})", target.display(), e))?;
+        written.push(target);
+    }
+    Ok(written)
+}
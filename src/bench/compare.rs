@@ -0,0 +1,266 @@
+use std::env;
+
+use rusqlite::{params, Connection};
+
+/// Largest drop in a task's mean score (0 to 1) not counted as a regression, by default.
+const DEFAULT_MAX_SCORE_DROP: f64 = 0.05;
+/// Largest rise in a model's mean latency, in percent, not counted as a regression, by default.
+const DEFAULT_MAX_LATENCY_INCREASE: f64 = 20.0;
+
+/// How much worse the second run may be before a change counts as a regression.
+#[derive(Debug, Clone, Copy)]
+pub struct Thresholds {
+    pub max_score_drop: f64,
+    /// In percent of the first run's mean latency.
+    pub max_latency_increase: f64,
+}
+
+impl Thresholds {
+    /// From `--max-score-drop` and `--max-latency-increase`, else `BENCH_MAX_SCORE_DROP` and
+    /// `BENCH_MAX_LATENCY_INCREASE`.
+    pub fn from_args(args: &[String]) -> Result<Self, String> {
+        Ok(Thresholds {
+            max_score_drop: threshold(args, "--max-score-drop", "BENCH_MAX_SCORE_DROP", DEFAULT_MAX_SCORE_DROP)?,
+            max_latency_increase: threshold(args, "--max-latency-increase", "BENCH_MAX_LATENCY_INCREASE", DEFAULT_MAX_LATENCY_INCREASE)?,
+        })
+    }
+}
+
+fn threshold(args: &[String], flag: &str, var: &str, default: f64) -> Result<f64, String> {
+    let value = match args.iter().position(|a| a == flag) {
+        Some(i) => args.get(i + 1).cloned().ok_or_else(|| format!("{} needs a number", flag))?,
+        None => match env::var(var) {
+            Ok(value) if !value.trim().is_empty() => value,
+            _ => return Ok(default),
+        },
+    };
+    value
+        .trim()
+        .parse()
+        .ok()
+        .filter(|n: &f64| n.is_finite() && *n >= 0.0)
+        .ok_or_else(|| format!("{} must be a number of 0 or more, not '{}'", flag, value))
+}
+
+/// The run whose id is or starts with `id`.
+pub fn resolve_run(conn: &Connection, id: &str) -> Result<String, String> {
+    let mut stmt = conn.prepare("SELECT id FROM runs WHERE id = ?1 OR id LIKE ?2 ESCAPE '\\' ORDER BY id = ?1 DESC LIMIT 2").unwrap();
+    let pattern = format!("{}%", id.replace('\\', "\\\\").replace('%', "\\%").replace('_', "\\_"));
+    let ids: Vec<String> = stmt.query_map(params![id, pattern], |row| row.get(0)).unwrap().filter_map(Result::ok).collect();
+    match ids.as_slice() {
+        [] => Err(format!("no run '{}'", id)),
+        [only] => Ok(only.clone()),
+        [exact, _] if exact == id => Ok(exact.clone()),
+        _ => Err(format!("'{}' matches more than one run", id)),
+    }
+}
+
+/// One task's results from one model in a run.
+#[derive(Debug, Clone)]
+struct TaskStats {
+    task_id: String,
+    provider: String,
+    prompt_hash: Option<String>,
+    results: i64,
+    errors: i64,
+    /// Mean over the graded results.
+    score: Option<f64>,
+    /// Total latency of the answered results.
+    latency_ms: i64,
+}
+
+impl TaskStats {
+    fn answered(&self) -> i64 {
+        self.results - self.errors
+    }
+
+    fn error_rate(&self) -> f64 {
+        self.errors as f64 / self.results as f64
+    }
+}
+
+fn task_stats(conn: &Connection, run_id: &str) -> Vec<TaskStats> {
+    let mut stmt = conn
+        .prepare(
+            "SELECT task_id, CASE WHEN model = '' THEN provider ELSE provider || '/' || model END, MAX(prompt_hash), COUNT(*), COUNT(error),
+                    AVG(score), COALESCE(SUM(CASE WHEN error IS NULL THEN latency_ms END), 0)
+             FROM results WHERE run_id = ?1
+             GROUP BY 1, 2 ORDER BY MIN(id)",
+        )
+        .unwrap();
+    stmt.query_map(params![run_id], |row| {
+        Ok(TaskStats {
+            task_id: row.get(0)?,
+            provider: row.get(1)?,
+            prompt_hash: row.get(2)?,
+            results: row.get(3)?,
+            errors: row.get(4)?,
+            score: row.get(5)?,
+            latency_ms: row.get(6)?,
+        })
+    })
+    .unwrap()
+    .filter_map(Result::ok)
+    .collect()
+}
+
+/// A task answered by the same model with the same prompts in both runs.
+#[derive(Debug)]
+pub struct TaskChange {
+    pub task_id: String,
+    pub provider: String,
+    pub score: (Option<f64>, Option<f64>),
+    /// Errors out of results, in each run.
+    pub errors: ((i64, i64), (i64, i64)),
+    /// Why this counts as a regression.
+    pub regression: Option<String>,
+}
+
+/// A model's mean latency over the tasks it answered in both runs.
+#[derive(Debug)]
+pub struct LatencyChange {
+    pub provider: String,
+    pub mean_ms: (f64, f64),
+    pub regression: bool,
+}
+
+impl LatencyChange {
+    pub fn increase_percent(&self) -> f64 {
+        (self.mean_ms.1 - self.mean_ms.0) / self.mean_ms.0 * 100.0
+    }
+}
+
+/// How the second run differs from the first.
+#[derive(Debug, Default)]
+pub struct Comparison {
+    pub tasks: Vec<TaskChange>,
+    pub latencies: Vec<LatencyChange>,
+    /// `task provider` pairs whose prompts changed between the runs, and so are not compared.
+    pub changed_prompts: Vec<String>,
+    /// Pairs found in only one of the runs.
+    pub only_first: Vec<String>,
+    pub only_second: Vec<String>,
+}
+
+impl Comparison {
+    pub fn regressions(&self) -> usize {
+        self.tasks.iter().filter(|t| t.regression.is_some()).count() + self.latencies.iter().filter(|l| l.regression).count()
+    }
+}
+
+/// Compares run `second` against the baseline `first`, task by task and model by model. Only
+/// results with the same prompt hash are compared.
+pub fn compare(conn: &Connection, first: &str, second: &str, thresholds: &Thresholds) -> Comparison {
+    let before = task_stats(conn, first);
+    let after = task_stats(conn, second);
+    let mut comparison = Comparison::default();
+    // Per model: answered results and their total latency in each run.
+    let mut latency: Vec<(String, [i64; 4])> = Vec::new();
+    for a in &before {
+        let label = format!("{} {}", a.task_id, a.provider);
+        let Some(b) = after.iter().find(|b| b.task_id == a.task_id && b.provider == a.provider) else {
+            comparison.only_first.push(label);
+            continue;
+        };
+        if a.prompt_hash != b.prompt_hash {
+            comparison.changed_prompts.push(label);
+            continue;
+        }
+        let regression = match (a.score, b.score) {
+            (Some(x), Some(y)) if x - y > thresholds.max_score_drop => Some(format!("score fell by {:.2}", x - y)),
+            _ if b.error_rate() > a.error_rate() => Some(format!("{} of {} failed", b.errors, b.results)),
+            _ => None,
+        };
+        comparison.tasks.push(TaskChange {
+            task_id: a.task_id.clone(),
+            provider: a.provider.clone(),
+            score: (a.score, b.score),
+            errors: ((a.errors, a.results), (b.errors, b.results)),
+            regression,
+        });
+        if a.answered() > 0 && b.answered() > 0 {
+            let index = match latency.iter().position(|(provider, _)| *provider == a.provider) {
+                Some(index) => index,
+                None => {
+                    latency.push((a.provider.clone(), [0; 4]));
+                    latency.len() - 1
+                }
+            };
+            let totals = &mut latency[index].1;
+            totals[0] += a.answered();
+            totals[1] += a.latency_ms;
+            totals[2] += b.answered();
+            totals[3] += b.latency_ms;
+        }
+    }
+    for b in &after {
+        if !before.iter().any(|a| a.task_id == b.task_id && a.provider == b.provider) {
+            comparison.only_second.push(format!("{} {}", b.task_id, b.provider));
+        }
+    }
+    for (provider, [n1, ms1, n2, ms2]) in latency {
+        let mean_ms = (ms1 as f64 / n1 as f64, ms2 as f64 / n2 as f64);
+        // A zero baseline (a mocked or cached provider) gives no meaningful percentage.
+        let regression = mean_ms.0 > 0.0 && (mean_ms.1 - mean_ms.0) / mean_ms.0 * 100.0 > thresholds.max_latency_increase;
+        comparison.latencies.push(LatencyChange { provider, mean_ms, regression });
+    }
+    comparison
+}
+
+#[cfg(test)]
+mod tests {
+    use rusqlite::{params, Connection};
+
+    use super::{compare, Thresholds};
+
+    /// A task's result: id, prompt hash, score, whether it failed, latency in ms.
+    type Row = (&'static str, &'static str, Option<f64>, bool, i64);
+
+    fn store(conn: &Connection, run_id: &str, rows: &[Row]) {
+        conn.execute("INSERT INTO runs (id, suite) VALUES (?1, 'suite')", params![run_id]).unwrap();
+        for (task_id, hash, score, failed, latency_ms) in rows {
+            conn.execute(
+                "INSERT INTO results (run_id, task_id, provider, model, prompt_hash, score, error, latency_ms) VALUES (?1, ?2, 'groq', 'm', ?3, ?4, ?5, ?6)",
+                params![run_id, task_id, hash, score, failed.then_some("timeout"), latency_ms],
+            )
+            .unwrap();
+        }
+    }
+
+    #[test]
+    fn flags_regressions_past_the_thresholds() {
+        let thresholds = Thresholds { max_score_drop: 0.05, max_latency_increase: 20.0 };
+        // The task in the baseline and the new run; its regression; the latency regression.
+        let cases: [(Row, Row, Option<&str>, bool); 6] = [
+            (("t", "h", Some(1.0), false, 100), ("t", "h", Some(1.0), false, 110), None, false),
+            (("t", "h", Some(1.0), false, 100), ("t", "h", Some(0.9), false, 100), Some("score fell by 0.10"), false),
+            (("t", "h", Some(1.0), false, 100), ("t", "h", Some(0.97), false, 100), None, false),
+            (("t", "h", Some(1.0), false, 100), ("t", "h", None, true, 100), Some("1 of 1 failed"), false),
+            (("t", "h", Some(1.0), false, 100), ("t", "h", Some(1.0), false, 130), None, true),
+            (("t", "h", Some(1.0), false, 0), ("t", "h", Some(1.0), false, 50), None, false),
+        ];
+        for (i, (before, after, regression, slower)) in cases.into_iter().enumerate() {
+            let conn = Connection::open_in_memory().unwrap();
+            crate::init_db(&conn);
+            store(&conn, "a", &[before]);
+            store(&conn, "b", &[after]);
+            let comparison = compare(&conn, "a", "b", &thresholds);
+            assert_eq!(comparison.tasks[0].regression.as_deref(), regression, "case {}", i);
+            assert_eq!(comparison.latencies.first().is_some_and(|l| l.regression), slower, "case {}", i);
+            assert_eq!(comparison.regressions(), usize::from(regression.is_some()) + usize::from(slower), "case {}", i);
+        }
+    }
+
+    #[test]
+    fn compares_only_matching_prompts() {
+        let conn = Connection::open_in_memory().unwrap();
+        crate::init_db(&conn);
+        store(&conn, "a", &[("same", "h", Some(1.0), false, 100), ("edited", "h1", Some(1.0), false, 100), ("dropped", "h", Some(1.0), false, 100)]);
+        store(&conn, "b", &[("same", "h", Some(0.0), false, 100), ("edited", "h2", Some(0.0), false, 100), ("added", "h", Some(1.0), false, 100)]);
+        let comparison = compare(&conn, "a", "b", &Thresholds { max_score_drop: 0.05, max_latency_increase: 20.0 });
+        assert_eq!(comparison.tasks.iter().map(|t| t.task_id.as_str()).collect::<Vec<_>>(), ["same"]);
+        assert_eq!(comparison.changed_prompts, ["edited groq/m"]);
+        assert_eq!(comparison.only_first, ["dropped groq/m"]);
+        assert_eq!(comparison.only_second, ["added groq/m"]);
+    }
+}
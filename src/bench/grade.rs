@@ -0,0 +1,125 @@
+use std::sync::LazyLock;
+
+use regex::Regex;
+use serde::Deserialize;
+
+/// Numbers as models write them: optional sign, thousands separators, decimals and exponent.
+static NUMBER: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"[-+]?\d[\d,]*(?:\.\d+)?(?:[eE][-+]?\d+)?").unwrap());
+
+/// How a task's final reply is compared with its `expected` answer.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Grader {
+    /// The reply, trimmed, is exactly the expected answer.
+    #[default]
+    Exact,
+    /// The expected text appears somewhere in the reply.
+    Contains,
+    /// The expected answer is a regular expression matching somewhere in the reply.
+    Regex,
+    /// The last number in the reply is within `tolerance` of the expected one.
+    Numeric,
+    /// A judge model scores the reply against `expected` or the task's rubric.
+    Judge,
+}
+
+impl Grader {
+    pub fn name(self) -> &'static str {
+        match self {
+            Grader::Exact => "exact",
+            Grader::Contains => "contains",
+            Grader::Regex => "regex",
+            Grader::Numeric => "numeric",
+            Grader::Judge => "judge",
+        }
+    }
+
+    /// Whether `expected` (and `tolerance`) make sense for this grader, checked when the suite
+    /// is loaded so a run never fails halfway on a bad pattern.
+    pub fn validate(self, expected: &str, tolerance: Option<f64>) -> Result<(), String> {
+        if tolerance.is_some() && self != Grader::Numeric {
+            return Err("sets a tolerance, which only the numeric grader uses".to_string());
+        }
+        match self {
+            Grader::Regex => Regex::new(expected).map(|_| ()).map_err(|e| format!("has an invalid expected pattern ({})", e)),
+            Grader::Numeric => {
+                number(expected.trim()).ok_or_else(|| format!("expects '{}', which is not a number", expected))?;
+                match tolerance {
+                    Some(tolerance) if tolerance < 0.0 => Err("needs a tolerance of 0 or more".to_string()),
+                    _ => Ok(()),
+                }
+            }
+            Grader::Exact | Grader::Contains | Grader::Judge => Ok(()),
+        }
+    }
+
+    /// Whether `output` passes, or `None` for the judge, which needs a model call. Patterns and
+    /// numbers are assumed valid, as [`Grader::validate`] checked them.
+    pub fn grade(self, expected: &str, tolerance: Option<f64>, output: &str) -> Option<bool> {
+        Some(match self {
+            Grader::Exact => output.trim() == expected.trim(),
+            Grader::Contains => output.contains(expected.trim()),
+            Grader::Regex => Regex::new(expected).is_ok_and(|pattern| pattern.is_match(output)),
+            Grader::Numeric => {
+                let answer = NUMBER.find_iter(output).last().and_then(|m| number(m.as_str()));
+                match (answer, number(expected.trim())) {
+                    (Some(answer), Some(expected)) => (answer - expected).abs() <= tolerance.unwrap_or(0.0),
+                    _ => false,
+                }
+            }
+            Grader::Judge => return None,
+        })
+    }
+}
+
+/// A number written with optional `,` thousands separators.
+fn number(text: &str) -> Option<f64> {
+    text.replace(',', "").parse().ok().filter(|n: &f64| n.is_finite())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Grader;
+
+    #[test]
+    fn grades() {
+        let cases = [
+            (Grader::Exact, "Paris", None, " Paris\n", Some(true)),
+            (Grader::Exact, "Paris", None, "paris", Some(false)),
+            (Grader::Contains, "Paris", None, "It is Paris.", Some(true)),
+            (Grader::Contains, "Paris", None, "It is Lyon.", Some(false)),
+            (Grader::Regex, "(?i)^paris\\.?$", None, "PARIS.", Some(true)),
+            (Grader::Regex, "^\\d+$", None, "42 apples", Some(false)),
+            (Grader::Numeric, "42", None, "17 + 25 = 42", Some(true)),
+            (Grader::Numeric, "1,000", None, "About 1000.0", Some(true)),
+            (Grader::Numeric, "3.14", Some(0.01), "pi is 3.1416", Some(true)),
+            (Grader::Numeric, "3.14", None, "pi is 3.1416", Some(false)),
+            (Grader::Numeric, "42", None, "42, no wait, 41", Some(false)),
+            (Grader::Numeric, "-2.5e3", None, "-2,500", Some(true)),
+            (Grader::Numeric, "42", None, "no number here", Some(false)),
+            (Grader::Judge, "Paris", None, "Paris", None),
+        ];
+        for (grader, expected, tolerance, output, passed) in cases {
+            assert_eq!(grader.grade(expected, tolerance, output), passed, "{} {:?} {:?}", grader.name(), expected, output);
+        }
+    }
+
+    #[test]
+    fn validates_expected_answers() {
+        let cases = [
+            (Grader::Regex, "(unclosed", None, Some("has an invalid expected pattern")),
+            (Grader::Numeric, "forty-two", None, Some("expects 'forty-two', which is not a number")),
+            (Grader::Numeric, "42", Some(-1.0), Some("needs a tolerance of 0 or more")),
+            (Grader::Exact, "42", Some(0.5), Some("sets a tolerance, which only the numeric grader uses")),
+            (Grader::Numeric, "1,234.5", Some(0.5), None),
+            (Grader::Contains, "anything", None, None),
+        ];
+        for (grader, expected, tolerance, error) in cases {
+            let result = grader.validate(expected, tolerance);
+            match error {
+                Some(error) => assert!(result.as_ref().is_err_and(|e| e.starts_with(error)), "{:?}: {:?}", expected, result),
+                None => assert_eq!(result, Ok(()), "{:?}", expected),
+            }
+        }
+    }
+}
@@ -0,0 +1,75 @@
+use rusqlite::{params, Connection};
+
+use super::suite::Task;
+use crate::judge_templates::{self, Template};
+use crate::pricing::Catalog;
+use crate::usage::TokenUsage;
+use crate::{ApiConfig, Message, call_llm_detailed};
+
+/// Score at or above which a judged result passes, unless the task sets `min_score`.
+pub const DEFAULT_MIN_SCORE: u8 = 7;
+
+/// A judge model's score for one output, with the exact prompt wording it was given.
+#[derive(Debug)]
+pub struct Judgement {
+    pub score: u8,
+    pub template: Template,
+    /// `provider/model` of the judge.
+    pub judge: String,
+    /// The judge's whole reply, reasoning included.
+    pub reply: String,
+    /// Tokens the judge reported, and what they cost at the catalog price of its model.
+    pub usage: Option<TokenUsage>,
+    pub cost: Option<f64>,
+}
+
+/// Asks the judge to score `output`: against the task's `expected` answer with the `reference`
+/// template, otherwise against its `rubric`.
+pub async fn judge(client: &reqwest::Client, config: &ApiConfig, task: &Task, output: &str) -> Result<Judgement, String> {
+    let prompts = task.prompts.join("\n\n");
+    let question = match &task.system {
+        Some(system) => format!("{}\n\n{}", system, prompts),
+        None => prompts,
+    };
+    let (name, criteria) = match (&task.expected, &task.rubric) {
+        (Some(expected), _) => ("reference", ("reference", expected.as_str())),
+        (None, Some(rubric)) => ("rubric", ("rubric", rubric.as_str())),
+        (None, None) => return Err("the task has neither an expected answer nor a rubric".to_string()),
+    };
+    let template = judge_templates::for_model(name, &config.model_name)?;
+    let prompt = template.render(&[("task", &question), criteria, ("response", output)]);
+    let reply = call_llm_detailed(client, config, &[Message { role: "user".to_string(), content: prompt }])
+        .await
+        .map_err(|e| format!("judge {} failed ({})", config.describe(), e))?;
+    let judge = reply.failover.map_or_else(|| config.describe(), |failover| failover.answered_by);
+    let score = judge_templates::parse_score(&reply.text).ok_or_else(|| format!("judge {} gave no 1-10 score", judge))?;
+    let model = judge.split_once('/').map_or(judge.as_str(), |(_, model)| model);
+    let cost = reply.usage.and_then(|usage| Catalog::load().cost(model, &usage));
+    Ok(Judgement { score, template, judge, reply: reply.text, usage: reply.usage, cost })
+}
+
+/// Stores a judgement with its result, and the template text under its version so the grade can
+/// be traced to the prompt that produced it.
+pub fn record(conn: &Connection, result_id: i64, judgement: &Judgement) {
+    let template = &judgement.template;
+    conn.execute(
+        "INSERT OR IGNORE INTO judge_prompts (version, name, variant, text) VALUES (?1, ?2, ?3, ?4)",
+        params![template.version, template.name, template.variant, template.text],
+    ).unwrap();
+    conn.execute(
+        "UPDATE results SET judge_score = ?2, judge = ?3, judge_template = ?4, judge_version = ?5, judge_reply = ?6, judge_prompt_tokens = ?7,
+                            judge_completion_tokens = ?8, judge_cost = ?9
+         WHERE id = ?1",
+        params![
+            result_id,
+            judgement.score,
+            judgement.judge,
+            template.name,
+            template.version,
+            judgement.reply,
+            judgement.usage.map(|u| u.prompt_tokens as i64),
+            judgement.usage.map(|u| u.completion_tokens as i64),
+            judgement.cost,
+        ],
+    ).unwrap();
+}
@@ -0,0 +1,152 @@
+use std::cmp::Ordering;
+
+use rusqlite::{params, Connection};
+use serde::Serialize;
+
+
+/// How a leaderboard is exported.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Format {
+    Json,
+    Csv,
+    Markdown,
+}
+
+impl Format {
+    pub fn parse(name: &str) -> Option<Self> {
+        match name {
+            "json" => Some(Format::Json),
+            "csv" => Some(Format::Csv),
+            "md" | "markdown" => Some(Format::Markdown),
+            _ => None,
+        }
+    }
+
+    /// From `--format`, or `None` when it is not given.
+    pub fn from_args(args: &[String]) -> Result<Option<Self>, String> {
+        match args.iter().position(|a| a == "--format") {
+            None => Ok(None),
+            Some(i) => args
+                .get(i + 1)
+                .and_then(|name| Format::parse(name))
+                .map(Some)
+                .ok_or_else(|| "--format needs json, csv or md".to_string()),
+        }
+    }
+}
+
+/// One model's place in a run.
+#[derive(Debug, Clone, Serialize)]
+pub struct Entry {
+    pub rank: usize,
+    /// `provider/model`.
+    pub provider: String,
+    pub results: i64,
+    pub errors: i64,
+    /// Share of the graded results that passed.
+    pub accuracy: Option<f64>,
+    pub mean_latency_ms: Option<f64>,
+    /// Of the run's reported tokens at the price when the run finished, in `currency`; `None`
+    /// when the model had no price.
+    pub cost: Option<f64>,
+    pub currency: String,
+    pub tokens_per_sec: Option<f64>,
+}
+
+/// The run's models ranked by accuracy, then mean latency, then cost; models without a value
+/// rank below those with one.
+pub fn load(conn: &Connection, run_id: &str) -> Vec<Entry> {
+    let mut stmt = conn
+        .prepare(
+            "SELECT provider, model, results, errors, graded, passed, mean_ms, cost, COALESCE(currency, ''), tokens_per_sec
+             FROM run_summaries WHERE run_id = ?1 ORDER BY rowid",
+        )
+        .unwrap();
+    let mut entries: Vec<Entry> = stmt
+        .query_map(params![run_id], |row| {
+            let (provider, model): (String, String) = (row.get(0)?, row.get(1)?);
+            let (graded, passed): (i64, i64) = (row.get(4)?, row.get(5)?);
+            Ok(Entry {
+                rank: 0,
+                provider: if model.is_empty() { provider } else { format!("{}/{}", provider, model) },
+                results: row.get(2)?,
+                errors: row.get(3)?,
+                accuracy: (graded > 0).then(|| passed as f64 / graded as f64),
+                mean_latency_ms: row.get(6)?,
+                cost: row.get(7)?,
+                currency: row.get(8)?,
+                tokens_per_sec: row.get(9)?,
+            })
+        })
+        .unwrap()
+        .filter_map(Result::ok)
+        .collect();
+    entries.sort_by(|a, b| {
+        descending(a.accuracy, b.accuracy)
+            .then_with(|| ascending(a.mean_latency_ms, b.mean_latency_ms))
+            .then_with(|| ascending(a.cost, b.cost))
+    });
+    for (i, entry) in entries.iter_mut().enumerate() {
+        entry.rank = i + 1;
+    }
+    entries
+}
+
+fn ascending(a: Option<f64>, b: Option<f64>) -> Ordering {
+    match (a, b) {
+        (Some(a), Some(b)) => a.total_cmp(&b),
+        (Some(_), None) => Ordering::Less,
+        (None, Some(_)) => Ordering::Greater,
+        (None, None) => Ordering::Equal,
+    }
+}
+
+fn descending(a: Option<f64>, b: Option<f64>) -> Ordering {
+    match (a, b) {
+        (Some(a), Some(b)) => b.total_cmp(&a),
+        _ => ascending(a, b),
+    }
+}
+
+const COLUMNS: [&str; 9] = ["rank", "provider", "results", "errors", "accuracy", "mean_latency_ms", "cost", "currency", "tokens_per_sec"];
+
+/// The leaderboard as a JSON array, CSV with a header row, or a Markdown table.
+pub fn render(entries: &[Entry], format: Format) -> String {
+    let number = |value: Option<f64>, decimals: usize| value.map_or(String::new(), |v| format!("{:.*}", decimals, v));
+    let rows = entries.iter().map(|e| {
+        [
+            e.rank.to_string(),
+            e.provider.clone(),
+            e.results.to_string(),
+            e.errors.to_string(),
+            number(e.accuracy, 4),
+            number(e.mean_latency_ms, 1),
+            number(e.cost, 6),
+            e.currency.clone(),
+            number(e.tokens_per_sec, 1),
+        ]
+    });
+    match format {
+        Format::Json => serde_json::to_string_pretty(entries).unwrap() + "\n",
+        Format::Csv => std::iter::once(COLUMNS.map(String::from))
+            .chain(rows)
+            .map(|row| row.iter().map(|field| csv_field(field)).collect::<Vec<_>>().join(","))
+            .map(|line| line + "\n")
+            .collect(),
+        Format::Markdown => {
+            let mut table = format!("| {} |\n|{}\n", COLUMNS.join(" | "), " --- |".repeat(COLUMNS.len()));
+            for row in rows {
+                table.push_str(&format!("| {} |\n", row.map(|field| field.replace('|', "\\|")).join(" | ")));
+            }
+            table
+        }
+    }
+}
+
+fn csv_field(field: &str) -> String {
+    if field.contains([',', '"', '\n']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
@@ -0,0 +1,560 @@
+pub mod artifacts;
+pub mod compare;
+pub mod grade;
+pub mod judge;
+pub mod leaderboard;
+pub mod stats;
+pub mod suite;
+
+use std::env;
+use std::io::Write;
+use std::process::{Command, Stdio};
+use std::time::{Duration, Instant};
+
+use rusqlite::{params, Connection};
+use uuid::Uuid;
+
+use crate::permissions::ToolPolicy;
+use crate::pricing::Catalog;
+use crate::reasoning::ReasoningConfig;
+use crate::sampling::SamplingConfig;
+use crate::tool_args::{self, ToolCall};
+use crate::usage::{self, TokenUsage};
+use crate::{ApiConfig, Message, calculate, call_llm_detailed, native_tool_calling, postprocess, web_search};
+use grade::Grader;
+use stats::LatencyStats;
+use suite::{Suite, Task};
+
+/// At most this many tasks in flight at once.
+const MAX_CONCURRENCY: usize = 64;
+
+/// How many tasks run at once (`--concurrency` or `BENCH_CONCURRENCY`); 1, one after another, by
+/// default.
+pub fn concurrency(args: &[String]) -> Result<usize, String> {
+    let value = match args.iter().position(|a| a == "--concurrency") {
+        Some(i) => args.get(i + 1).cloned().ok_or("--concurrency needs a number of tasks")?,
+        None => match env::var("BENCH_CONCURRENCY") {
+            Ok(value) if !value.trim().is_empty() => value,
+            _ => return Ok(1),
+        },
+    };
+    value
+        .trim()
+        .parse()
+        .ok()
+        .filter(|n| (1..=MAX_CONCURRENCY).contains(n))
+        .ok_or_else(|| format!("concurrency must be a whole number from 1 to {}, not '{}'", MAX_CONCURRENCY, value))
+}
+
+/// Tool calls answered in one user turn before the reply is taken as it stands.
+const MAX_TOOL_ROUNDS: usize = 5;
+
+/// How a model calls each benchmark tool when it has no native function calling.
+const TOOL_FORMATS: &[(&str, &str)] = &[
+    ("run_command", "Run a shell command: `[RUN_COMMAND <command to run>]`"),
+    ("web_search", "Search the web: `[SEARCH: your query]`"),
+    ("calculate", "Calculate exactly (units and dates too): `[CALCULATE: 2 h + 30 min to min]`"),
+];
+
+/// One repetition of a task's answer from one provider, or why there is none.
+#[derive(Debug)]
+pub struct TaskResult {
+    pub task_id: String,
+    /// Of the prompts sent, as [`Task::prompt_hash`].
+    pub prompt_hash: String,
+    /// Starting at 1.
+    pub repetition: u32,
+    /// `provider/model` that answered.
+    pub provider: String,
+    /// The final reply.
+    pub output: Option<String>,
+    pub error: Option<String>,
+    /// Whether the output passed the task's checker or its grader; `None` when the task has
+    /// neither or there is no output. A checker that cannot run is an `Err`.
+    pub verdict: Option<Result<bool, String>>,
+    /// The judge's score, for tasks graded by one.
+    pub judgement: Option<judge::Judgement>,
+    pub tool_calls: usize,
+    /// The tool calls that ran, each timed and costed.
+    pub tool_runs: Vec<ToolRun>,
+    /// From sending the first request to having the last reply, tool runs included.
+    pub latency: Duration,
+    /// Spent in provider calls, from sending each request to having its whole reply.
+    pub generation: Duration,
+    pub usage: Option<TokenUsage>,
+}
+
+/// One tool run during a task.
+#[derive(Debug)]
+pub struct ToolRun {
+    pub tool: String,
+    pub duration: Duration,
+    pub success: bool,
+    /// From `TOOL_COST_<TOOL>`.
+    pub cost: f64,
+}
+
+impl TaskResult {
+    /// From 0 to 1: the judge's score out of 10 for judged tasks, else 1 for a pass and 0 for a
+    /// fail; `None` without a verdict.
+    pub fn score(&self) -> Option<f64> {
+        match (&self.judgement, &self.verdict) {
+            (Some(judgement), _) => Some(f64::from(judgement.score) / 10.0),
+            (None, Some(Ok(passed))) => Some(if *passed { 1.0 } else { 0.0 }),
+            _ => None,
+        }
+    }
+
+    /// Completion tokens the provider reported per second of generation.
+    pub fn tokens_per_sec(&self) -> Option<f64> {
+        let seconds = self.generation.as_secs_f64();
+        let tokens = self.usage?.completion_tokens;
+        (seconds > 0.0 && tokens > 0).then(|| tokens as f64 / seconds)
+    }
+
+    pub fn tool_cost(&self) -> f64 {
+        self.tool_runs.iter().map(|run| run.cost).sum()
+    }
+}
+
+/// What a run was started with, stored with it so its results can be interpreted later.
+pub struct RunSettings<'a> {
+    pub suite: &'a Suite,
+    pub path: &'a str,
+    /// `provider/model` of each benchmarked provider.
+    pub providers: Vec<String>,
+    pub judge: Option<String>,
+    pub reasoning: &'a ReasoningConfig,
+    pub sampling: &'a SamplingConfig,
+    pub concurrency: usize,
+    /// The `--tags` filter, when one was given.
+    pub tags: Option<&'a str>,
+}
+
+/// Starts a run, storing its settings and the tasks it will send, and returns its id.
+pub fn start_run(conn: &Connection, settings: &RunSettings) -> String {
+    let run_id = Uuid::new_v4().to_string();
+    conn.execute(
+        "INSERT INTO runs (id, suite, suite_path, providers, judge, reasoning, sampling, concurrency, tags)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)",
+        params![
+            run_id,
+            settings.suite.name,
+            settings.path,
+            settings.providers.join(","),
+            settings.judge,
+            Some(settings.reasoning.describe()).filter(|s| !s.is_empty()),
+            Some(settings.sampling.describe()).filter(|s| !s.is_empty()),
+            settings.concurrency as i64,
+            settings.tags,
+        ],
+    ).unwrap();
+    for task in &settings.suite.tasks {
+        conn.execute(
+            "INSERT INTO tasks (run_id, task_id, prompt_hash, system, prompts, expected, rubric, grader, checker, tags, repetitions, tools)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12)",
+            params![
+                run_id,
+                task.id,
+                task.prompt_hash(),
+                task.system,
+                serde_json::to_string(&task.prompts).unwrap(),
+                task.expected,
+                task.rubric,
+                task.grader.map(Grader::name),
+                task.checker,
+                serde_json::to_string(&task.tags).unwrap(),
+                task.repetitions,
+                serde_json::to_string(&task.tools).unwrap(),
+            ],
+        ).unwrap();
+    }
+    run_id
+}
+
+pub fn finish_run(conn: &Connection, run_id: &str) {
+    conn.execute("UPDATE runs SET finished_at = CURRENT_TIMESTAMP WHERE id = ?1", params![run_id]).unwrap();
+}
+
+/// Sends one repetition of a task to one provider, without fallbacks, so every result reflects
+/// the provider named. Each prompt goes in turn; tool calls the task allows are run and their
+/// results sent back, if `policy` lets the user run them. `judge` scores tasks graded by a judge
+/// model.
+pub async fn run_task(
+    client: &reqwest::Client,
+    config: &ApiConfig,
+    task: &Task,
+    repetition: u32,
+    judge: Option<&ApiConfig>,
+    policy: &ToolPolicy,
+) -> TaskResult {
+    let tools: Vec<&'static str> = suite::TOOLS.iter().copied().filter(|tool| task.tools.iter().any(|t| t == tool)).collect();
+    let native = !tools.is_empty() && native_tool_calling(config);
+    let config = config.for_turn(if native { &tools } else { &[] }, None);
+    let postprocess = postprocess::Pipeline::for_model(&config.model_name).unwrap_or_default();
+
+    let mut system = task.system.clone().unwrap_or_default();
+    if !tools.is_empty() && !native {
+        let formats: Vec<&str> = TOOL_FORMATS.iter().filter(|(tool, _)| tools.contains(tool)).map(|(_, format)| *format).collect();
+        system = format!("{}\n\nTo use a tool, reply with only the call:\n- {}", system, formats.join("\n- ")).trim_start().to_string();
+    }
+    let mut history = Vec::new();
+    if !system.is_empty() {
+        history.push(Message { role: "system".to_string(), content: system });
+    }
+
+    let mut result = TaskResult {
+        task_id: task.id.clone(),
+        prompt_hash: task.prompt_hash(),
+        repetition,
+        provider: config.describe(),
+        output: None,
+        error: None,
+        verdict: None,
+        judgement: None,
+        tool_calls: 0,
+        tool_runs: Vec::new(),
+        latency: Duration::ZERO,
+        generation: Duration::ZERO,
+        usage: None,
+    };
+    let started = Instant::now();
+    'prompts: for prompt in &task.prompts {
+        history.push(Message { role: "user".to_string(), content: prompt.clone() });
+        let mut rounds = 0;
+        loop {
+            let reply = match call_llm_detailed(client, &config, &history).await {
+                Ok(reply) => reply,
+                Err(e) => {
+                    result.error = Some(e.to_string());
+                    break 'prompts;
+                }
+            };
+            result.usage = usage::sum(result.usage, reply.usage);
+            result.generation += reply.timing.generation();
+            let text = postprocess.apply(&reply.text);
+            history.push(Message { role: "assistant".to_string(), content: text.clone() });
+            let call = if tools.is_empty() || rounds == MAX_TOOL_ROUNDS { Ok(None) } else { tool_args::parse(&text, tools.contains(&"web_search")) };
+            rounds += 1;
+            match call {
+                Ok(Some(call)) => {
+                    result.tool_calls += 1;
+                    let output = match refusal(&call, &tools, policy) {
+                        Some(refusal) => refusal,
+                        None => {
+                            let started = Instant::now();
+                            let (output, success) = run_tool(&call).await;
+                            let tool = call.name().to_string();
+                            result.tool_runs.push(ToolRun { cost: usage::tool_cost(&tool), tool, duration: started.elapsed(), success });
+                            output
+                        }
+                    };
+                    history.push(Message { role: "system".to_string(), content: output });
+                }
+                Err(e) => history.push(Message { role: "system".to_string(), content: format!("Invalid tool call ({}). Fix the call or answer without it.", e) }),
+                Ok(None) => {
+                    result.output = Some(text);
+                    break;
+                }
+            }
+        }
+    }
+    result.latency = started.elapsed();
+    if result.error.is_some() {
+        result.output = None;
+    }
+    if let Some(output) = &result.output {
+        result.verdict = check(task, output).await;
+        if let (Some(Grader::Judge), Some(judge)) = (task.grader, judge) {
+            let judgement = judge::judge(client, judge, task, output).await;
+            let min_score = task.min_score.unwrap_or(judge::DEFAULT_MIN_SCORE);
+            result.verdict = Some(judgement.as_ref().map(|judgement| judgement.score >= min_score).map_err(Clone::clone));
+            result.judgement = judgement.ok();
+        }
+    }
+    result
+}
+
+/// The message telling the model a call may not run: when the task does not list the tool, or
+/// the user's tool policy denies it as in a chat session, as the commands are the model's choice.
+fn refusal(call: &ToolCall, tools: &[&str], policy: &ToolPolicy) -> Option<String> {
+    if !tools.contains(&call.name()) {
+        return Some(format!("The {} tool is not permitted for this task. Answer without it.", call.name()));
+    }
+    if !policy.allows(call.name()) {
+        return Some(format!("The {} tool is not permitted for this user. Answer without it.", call.name()));
+    }
+    None
+}
+
+/// Runs an allowed tool call, returning the message that tells the model its result and whether
+/// the tool succeeded.
+async fn run_tool(call: &ToolCall) -> (String, bool) {
+    match call {
+        ToolCall::RunCommand(args) => {
+            let mut command = Command::new("sh");
+            command.arg("-c").arg(&args.command);
+            if let Some(workdir) = &args.workdir {
+                command.current_dir(workdir);
+            }
+            // Commands may be slow; keep them off the runtime's worker threads.
+            let (output, success) = match tokio::task::spawn_blocking(move || command.output()).await.expect("command thread panicked") {
+                Ok(output) if output.status.success() => (String::from_utf8_lossy(&output.stdout).to_string(), true),
+                Ok(output) => (String::from_utf8_lossy(&output.stderr).to_string(), false),
+                Err(e) => (format!("Failed to run the command: {}", e), false),
+            };
+            (format!("Command output:\n{}", output), success)
+        }
+        ToolCall::WebSearch(args) => match web_search(&args.query).await {
+            Ok(body) => (format!("Web search results for '{}':\n{}", args.query, body), true),
+            Err(e) => (format!("Failed to perform web search: {}", e), false),
+        },
+        ToolCall::Calculate(args) => match calculate::evaluate(&args.expression) {
+            Ok(value) => (format!("Result of {}: {}", args.expression, value), true),
+            Err(e) => (format!("Could not calculate {}: {}", args.expression, e), false),
+        },
+        other => (format!("The {} tool is not permitted for this task. Answer without it.", other.name()), false),
+    }
+}
+
+/// Judges a final reply: the checker's exit status when the task has one, else its grader's
+/// comparison with `expected`.
+async fn check(task: &Task, output: &str) -> Option<Result<bool, String>> {
+    let Some(checker) = &task.checker else {
+        let grader = task.grader.unwrap_or_default();
+        return task.expected.as_ref().and_then(|expected| grader.grade(expected, task.tolerance, output)).map(Ok);
+    };
+    let (checker, expected, output) = (checker.clone(), task.expected.clone().unwrap_or_default(), output.to_string());
+    let run = move || -> std::io::Result<bool> {
+        let mut child = Command::new("sh")
+            .arg("-c")
+            .arg(checker)
+            .env("EXPECTED", expected)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::null())
+            .spawn()?;
+        // A checker that exits without reading its input is fine.
+        let _ = child.stdin.take().unwrap().write_all(output.as_bytes());
+        Ok(child.wait()?.success())
+    };
+    let passed = tokio::task::spawn_blocking(run).await.expect("checker thread panicked");
+    Some(passed.map_err(|e| format!("checker could not run ({})", e)))
+}
+
+/// A past run with its result counts.
+#[derive(Debug)]
+pub struct RunListing {
+    pub id: String,
+    pub suite: String,
+    pub started_at: String,
+    pub finished: bool,
+    pub providers: Option<String>,
+    pub results: i64,
+    pub errors: i64,
+    pub graded: i64,
+    pub passed: i64,
+}
+
+/// Runs, newest first.
+pub fn list_runs(conn: &Connection) -> Vec<RunListing> {
+    let mut stmt = conn
+        .prepare(
+            "SELECT r.id, r.suite, r.started_at, r.finished_at IS NOT NULL, r.providers, COUNT(s.id), COUNT(s.error), COUNT(s.passed),
+                    COALESCE(SUM(s.passed), 0)
+             FROM runs r LEFT JOIN results s ON s.run_id = r.id
+             GROUP BY r.id ORDER BY r.started_at DESC, r.rowid DESC",
+        )
+        .unwrap();
+    stmt.query_map([], |row| {
+        Ok(RunListing {
+            id: row.get(0)?,
+            suite: row.get(1)?,
+            started_at: row.get(2)?,
+            finished: row.get(3)?,
+            providers: row.get(4)?,
+            results: row.get(5)?,
+            errors: row.get(6)?,
+            graded: row.get(7)?,
+            passed: row.get(8)?,
+        })
+    })
+    .unwrap()
+    .filter_map(Result::ok)
+    .collect()
+}
+
+/// Stores a result under its run, with its tool runs, returning its id.
+pub fn record(conn: &Connection, run_id: &str, result: &TaskResult) -> i64 {
+    let (provider, model) = result.provider.split_once('/').unwrap_or((&result.provider, ""));
+    conn.execute(
+        "INSERT INTO results (run_id, task_id, prompt_hash, repetition, provider, model, output, error, passed, score, tool_calls, latency_ms,
+                              generation_ms, tokens_per_sec, prompt_tokens, completion_tokens, tool_cost)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15, ?16, ?17)",
+        params![
+            run_id,
+            result.task_id,
+            result.prompt_hash,
+            result.repetition,
+            provider,
+            model,
+            result.output,
+            result.error,
+            result.verdict.as_ref().and_then(|verdict| verdict.clone().ok()),
+            result.score(),
+            result.tool_calls as i64,
+            result.latency.as_millis() as i64,
+            result.generation.as_millis() as i64,
+            result.tokens_per_sec(),
+            result.usage.map(|u| u.prompt_tokens as i64),
+            result.usage.map(|u| u.completion_tokens as i64),
+            result.tool_cost(),
+        ],
+    ).unwrap();
+    let result_id = conn.last_insert_rowid();
+    for run in &result.tool_runs {
+        usage::record_for_run(conn, run_id, &run.tool, run.duration, run.success, run.cost);
+    }
+    if let Some(judgement) = &result.judgement {
+        judge::record(conn, result_id, judgement);
+    }
+    result_id
+}
+
+/// Results, errors, verdicts, latency and tokens for one provider in a run.
+#[derive(Debug, Default)]
+pub struct ProviderSummary {
+    pub provider: String,
+    pub results: usize,
+    pub errors: usize,
+    /// Results with a verdict, and those that passed.
+    pub graded: usize,
+    pub passed: usize,
+    /// Of the answered tasks.
+    pub latencies: Vec<Duration>,
+    /// Per answered task with reported completion tokens.
+    pub throughputs: Vec<f64>,
+    /// Generation time and completion tokens of those tasks.
+    pub generation: Duration,
+    pub generated_tokens: u64,
+    pub prompt_tokens: u64,
+    pub completion_tokens: u64,
+    pub tool_runs: usize,
+    pub tool_cost: f64,
+    /// Tokens of the judge calls that scored this provider's results, and the cost of those
+    /// whose judge model has a price.
+    pub judge_tokens: u64,
+    pub judge_cost: Option<f64>,
+}
+
+impl ProviderSummary {
+    pub fn latency(&self) -> Option<LatencyStats> {
+        LatencyStats::from(&self.latencies)
+    }
+
+    /// Completion tokens per second over all generation time, so long replies weigh more than
+    /// short ones.
+    pub fn tokens_per_sec(&self) -> Option<f64> {
+        let seconds = self.generation.as_secs_f64();
+        (seconds > 0.0 && self.generated_tokens > 0).then(|| self.generated_tokens as f64 / seconds)
+    }
+
+    /// Median of the per-response rates.
+    pub fn median_tokens_per_sec(&self) -> Option<f64> {
+        let mut rates = self.throughputs.clone();
+        rates.sort_by(f64::total_cmp);
+        let n = rates.len();
+        match n {
+            0 => None,
+            _ if n % 2 == 1 => Some(rates[n / 2]),
+            _ => Some((rates[n / 2 - 1] + rates[n / 2]) / 2.0),
+        }
+    }
+
+    /// Share of the graded results that passed, or `None` when none were graded.
+    pub fn accuracy(&self) -> Option<f64> {
+        (self.graded > 0).then(|| self.passed as f64 / self.graded as f64)
+    }
+}
+
+/// Per-provider totals, in the order providers first appear in `results`.
+pub fn summarize(results: &[TaskResult]) -> Vec<ProviderSummary> {
+    let mut summaries: Vec<ProviderSummary> = Vec::new();
+    for result in results {
+        let index = match summaries.iter().position(|s| s.provider == result.provider) {
+            Some(index) => index,
+            None => {
+                summaries.push(ProviderSummary { provider: result.provider.clone(), ..Default::default() });
+                summaries.len() - 1
+            }
+        };
+        let summary = &mut summaries[index];
+        summary.results += 1;
+        if result.error.is_some() {
+            summary.errors += 1;
+        } else {
+            summary.latencies.push(result.latency);
+            if let Some(rate) = result.tokens_per_sec() {
+                summary.throughputs.push(rate);
+                summary.generation += result.generation;
+                summary.generated_tokens += result.usage.map_or(0, |u| u.completion_tokens);
+            }
+        }
+        if let Some(Ok(passed)) = result.verdict {
+            summary.graded += 1;
+            summary.passed += usize::from(passed);
+        }
+        if let Some(usage) = result.usage {
+            summary.prompt_tokens += usage.prompt_tokens;
+            summary.completion_tokens += usage.completion_tokens;
+        }
+        summary.tool_runs += result.tool_runs.len();
+        summary.tool_cost += result.tool_cost();
+        if let Some(judgement) = &result.judgement {
+            summary.judge_tokens += judgement.usage.map_or(0, |u| u.prompt_tokens + u.completion_tokens);
+            if let Some(cost) = judgement.cost {
+                *summary.judge_cost.get_or_insert(0.0) += cost;
+            }
+        }
+    }
+    summaries
+}
+
+/// Stores a provider's totals and latency statistics for the run, with what its tokens cost at
+/// the catalog's current price so later reports show the price the run was made at.
+pub fn record_summary(conn: &Connection, run_id: &str, summary: &ProviderSummary, catalog: &Catalog) {
+    let (provider, model) = summary.provider.split_once('/').unwrap_or((&summary.provider, ""));
+    let usage = TokenUsage { prompt_tokens: summary.prompt_tokens, completion_tokens: summary.completion_tokens };
+    let cost = (!model.is_empty()).then(|| catalog.cost(model, &usage)).flatten();
+    let latency = summary.latency();
+    let ms = |pick: fn(&LatencyStats) -> Duration| latency.as_ref().map(|stats| pick(stats).as_secs_f64() * 1000.0);
+    conn.execute(
+        "INSERT OR REPLACE INTO run_summaries
+         (run_id, provider, model, results, errors, graded, passed, mean_ms, stddev_ms, p50_ms, p90_ms, p99_ms, prompt_tokens, completion_tokens,
+          tokens_per_sec, median_tokens_per_sec, tool_runs, tool_cost, judge_tokens, judge_cost, cost, currency)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15, ?16, ?17, ?18, ?19, ?20, ?21, ?22)",
+        params![
+            run_id,
+            provider,
+            model,
+            summary.results as i64,
+            summary.errors as i64,
+            summary.graded as i64,
+            summary.passed as i64,
+            ms(|s| s.mean),
+            ms(|s| s.stddev),
+            ms(|s| s.p50),
+            ms(|s| s.p90),
+            ms(|s| s.p99),
+            summary.prompt_tokens as i64,
+            summary.completion_tokens as i64,
+            summary.tokens_per_sec(),
+            summary.median_tokens_per_sec(),
+            summary.tool_runs as i64,
+            summary.tool_cost,
+            summary.judge_tokens as i64,
+            summary.judge_cost,
+            cost,
+            catalog.currency,
+        ],
+    ).unwrap();
+}
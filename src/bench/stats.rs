@@ -0,0 +1,78 @@
+use std::time::Duration;
+
+/// Spread of a provider's latencies over a run.
+#[derive(Debug, Clone, Copy)]
+pub struct LatencyStats {
+    pub samples: usize,
+    pub mean: Duration,
+    /// Sample standard deviation; zero for a single sample.
+    pub stddev: Duration,
+    pub p50: Duration,
+    pub p90: Duration,
+    pub p99: Duration,
+}
+
+impl LatencyStats {
+    /// Statistics of `latencies`, or `None` when there are none. Percentiles use the
+    /// nearest-rank method, so each is one of the measured latencies.
+    pub fn from(latencies: &[Duration]) -> Option<Self> {
+        if latencies.is_empty() {
+            return None;
+        }
+        let mut sorted = latencies.to_vec();
+        sorted.sort();
+        let n = sorted.len();
+        let seconds: Vec<f64> = sorted.iter().map(Duration::as_secs_f64).collect();
+        let mean = seconds.iter().sum::<f64>() / n as f64;
+        let variance = if n > 1 { seconds.iter().map(|s| (s - mean).powi(2)).sum::<f64>() / (n - 1) as f64 } else { 0.0 };
+        let percentile = |p: f64| sorted[((p / 100.0 * n as f64).ceil() as usize).clamp(1, n) - 1];
+        Some(LatencyStats {
+            samples: n,
+            mean: Duration::from_secs_f64(mean),
+            stddev: Duration::from_secs_f64(variance.sqrt()),
+            p50: percentile(50.0),
+            p90: percentile(90.0),
+            p99: percentile(99.0),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::LatencyStats;
+
+    fn ms(values: &[u64]) -> Vec<Duration> {
+        values.iter().copied().map(Duration::from_millis).collect()
+    }
+
+    #[test]
+    fn nearest_rank_percentiles() {
+        let one_to_hundred: Vec<u64> = (1..=100).collect();
+        let tens: Vec<u64> = (1..=10).map(|i| i * 10).collect();
+        let cases: [(&[u64], [u64; 3]); 5] = [
+            (&[100], [100, 100, 100]),
+            (&[100, 200], [100, 200, 200]),
+            (&[30, 10, 20], [20, 30, 30]),
+            (&tens, [50, 90, 100]),
+            (&one_to_hundred, [50, 90, 99]),
+        ];
+        for (latencies, [p50, p90, p99]) in cases {
+            let stats = LatencyStats::from(&ms(latencies)).unwrap();
+            assert_eq!(stats.samples, latencies.len());
+            assert_eq!([stats.p50, stats.p90, stats.p99], ms(&[p50, p90, p99])[..], "{:?}", latencies);
+        }
+    }
+
+    #[test]
+    fn mean_and_sample_stddev() {
+        let cases: [(&[u64], f64, f64); 3] = [(&[100], 100.0, 0.0), (&[100, 200], 150.0, 70.710678), (&[10, 20, 30, 40], 25.0, 12.909944)];
+        for (latencies, mean, stddev) in cases {
+            let stats = LatencyStats::from(&ms(latencies)).unwrap();
+            assert!((stats.mean.as_secs_f64() * 1000.0 - mean).abs() < 1e-3, "{:?}", latencies);
+            assert!((stats.stddev.as_secs_f64() * 1000.0 - stddev).abs() < 1e-3, "{:?}", latencies);
+        }
+        assert!(LatencyStats::from(&[]).is_none());
+    }
+}
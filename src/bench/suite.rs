@@ -0,0 +1,220 @@
+use std::collections::HashSet;
+use std::path::Path;
+
+use serde::Deserialize;
+use sha2::{Digest, Sha256};
+
+use super::grade::Grader;
+use crate::toml;
+
+/// Tools a task may be allowed; the others act outside the run (scheduling, email) or need an
+/// optional build feature.
+pub const TOOLS: &[&str] = &["run_command", "web_search", "calculate"];
+
+/// At most this many repetitions of one task.
+const MAX_REPETITIONS: u32 = 100;
+
+/// A benchmark suite: tasks sent to each provider in turn, read from a TOML file.
+///
+/// ```toml
+/// name = "geography"
+/// providers = ["groq:llama-3.1-8b-instant", "ollama:llama3.2"]
+/// judge = "anthropic:claude-sonnet-4-20250514"
+///
+/// [[tasks]]
+/// id = "capital-fr"
+/// system = "Answer with a single word."
+/// prompt = "What is the capital of France?"
+/// expected = "(?i)paris"
+/// grader = "regex"
+/// tags = ["geography", "easy"]
+/// repetitions = 3
+///
+/// [[tasks]]
+/// id = "distance"
+/// prompt = "How many km is a marathon?"
+/// expected = "42.195"
+/// grader = "numeric"
+/// tolerance = 0.01
+///
+/// [[tasks]]
+/// id = "explain"
+/// prompt = "Explain why the sky is blue to a ten-year-old."
+/// grader = "judge"
+/// rubric = "Mentions scattering of sunlight. Uses no jargon. At most five sentences."
+/// min_score = 8
+///
+/// [[tasks]]
+/// id = "report"
+/// prompts = ["List the files in out/.", "Summarize them in out/report.md."]
+/// checker = "test -s out/report.md"
+/// tools = ["run_command"]
+/// artifacts = ["out/report.md"]
+/// ```
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Suite {
+    /// Defaults to the file's stem.
+    #[serde(default)]
+    pub name: String,
+    /// Providers in the `PROVIDER_FALLBACKS` format, used unless `--providers` is given.
+    #[serde(default)]
+    pub providers: Vec<String>,
+    /// Judge model for tasks graded by `judge`, in the same format, unless `--judge` is given.
+    pub judge: Option<String>,
+    #[serde(default)]
+    pub tasks: Vec<Task>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Task {
+    pub id: String,
+    /// System prompt for this task; none is sent otherwise.
+    pub system: Option<String>,
+    /// The user prompt, for tasks with a single one.
+    #[serde(default)]
+    prompt: Option<String>,
+    /// User prompts sent in turn, each after the reply to the one before. Holds `prompt` once
+    /// the suite is loaded.
+    #[serde(default)]
+    pub prompts: Vec<String>,
+    /// The answer the final reply is graded against, unless a checker decides.
+    pub expected: Option<String>,
+    /// How the reply is compared with `expected`; exact by default.
+    pub grader: Option<Grader>,
+    /// For the numeric grader: the largest accepted difference, 0 by default.
+    pub tolerance: Option<f64>,
+    /// For the judge grader without an expected answer: what a good reply does.
+    pub rubric: Option<String>,
+    /// For the judge grader: the lowest passing score out of 10, 7 by default.
+    pub min_score: Option<u8>,
+    /// Shell command that gets the final reply on stdin (and `expected` as `EXPECTED`) and exits
+    /// 0 when it passes.
+    pub checker: Option<String>,
+    #[serde(default)]
+    pub tags: Vec<String>,
+    /// Times the task is sent to each provider.
+    #[serde(default = "one")]
+    pub repetitions: u32,
+    /// Tools the model may call during the task, from [`TOOLS`]; none by default.
+    #[serde(default)]
+    pub tools: Vec<String>,
+    /// Files the task is expected to write, relative to the directory the run starts in, kept in
+    /// the artifact store with its result.
+    #[serde(default)]
+    pub artifacts: Vec<String>,
+}
+
+fn one() -> u32 {
+    1
+}
+
+impl Suite {
+    pub fn load(path: &str) -> Result<Self, String> {
+        let text = std::fs::read_to_string(path).map_err(|e| format!("could not read {} ({})", path, e))?;
+        // Until the `toml` crate is available, see `crate::toml::parse`.
+        let value = toml::parse(&text).map_err(|e| format!("{}: {}", path, e))?;
+        let mut suite: Suite = serde_json::from_value(value).map_err(|e| format!("{}: {}", path, e))?;
+        if suite.name.trim().is_empty() {
+            suite.name = Path::new(path).file_stem().and_then(|s| s.to_str()).unwrap_or("suite").to_string();
+        }
+        suite.validate().map_err(|e| format!("{}: {}", path, e))?;
+        for task in &mut suite.tasks {
+            if let Some(prompt) = task.prompt.take() {
+                task.prompts.push(prompt);
+            }
+        }
+        Ok(suite)
+    }
+
+    /// Whether any task is graded by a judge model.
+    pub fn needs_judge(&self) -> bool {
+        self.tasks.iter().any(|task| task.grader == Some(Grader::Judge))
+    }
+
+    /// Keeps only the tasks tagged with one of `tags`; an empty list keeps them all.
+    pub fn retain_tagged(&mut self, tags: &[String]) {
+        if !tags.is_empty() {
+            self.tasks.retain(|task| task.tags.iter().any(|tag| tags.contains(tag)));
+        }
+    }
+
+    fn validate(&self) -> Result<(), String> {
+        if self.tasks.is_empty() {
+            return Err("no tasks (add [[tasks]] entries)".to_string());
+        }
+        let mut ids = HashSet::new();
+        for (i, task) in self.tasks.iter().enumerate() {
+            if task.id.trim().is_empty() {
+                return Err(format!("task {} has no id", i + 1));
+            }
+            if !ids.insert(task.id.as_str()) {
+                return Err(format!("task id '{}' is used twice", task.id));
+            }
+            task.validate().map_err(|e| format!("task '{}' {}", task.id, e))?;
+        }
+        Ok(())
+    }
+}
+
+impl Task {
+    /// Whether the task works in the shared working directory, running commands or capturing
+    /// artifacts, so it must not run alongside another such task.
+    pub fn uses_workdir(&self) -> bool {
+        !self.artifacts.is_empty() || self.tools.iter().any(|tool| tool == "run_command")
+    }
+
+    /// The first 12 hex digits of the SHA-256 of the system prompt and prompts, so results of
+    /// different runs can be matched to the exact wording they answered.
+    pub fn prompt_hash(&self) -> String {
+        let text = serde_json::to_string(&(&self.system, &self.prompts)).unwrap();
+        Sha256::digest(text.as_bytes()).iter().take(6).map(|b| format!("{:02x}", b)).collect()
+    }
+
+    fn validate(&self) -> Result<(), String> {
+        match (&self.prompt, self.prompts.is_empty()) {
+            (None, true) => return Err("has no prompt (set prompt or prompts)".to_string()),
+            (Some(_), false) => return Err("sets both prompt and prompts".to_string()),
+            _ => {}
+        }
+        if self.prompt.iter().chain(&self.prompts).any(|prompt| prompt.trim().is_empty()) {
+            return Err("has an empty prompt".to_string());
+        }
+        if self.checker.as_ref().is_some_and(|checker| checker.trim().is_empty()) {
+            return Err("has an empty checker".to_string());
+        }
+        if self.checker.is_some() && self.grader.is_some() {
+            return Err("sets both a checker and a grader".to_string());
+        }
+        let judged = self.grader == Some(Grader::Judge);
+        match &self.expected {
+            Some(expected) => self.grader.unwrap_or_default().validate(expected, self.tolerance)?,
+            None if judged && self.rubric.is_none() => return Err("is judged but has neither an expected answer nor a rubric".to_string()),
+            None if !judged && (self.grader.is_some() || self.tolerance.is_some()) => return Err("sets a grader but no expected answer".to_string()),
+            None => {}
+        }
+        if !judged && (self.rubric.is_some() || self.min_score.is_some()) {
+            return Err("sets a rubric or min_score, which only the judge grader uses".to_string());
+        }
+        if self.rubric.as_ref().is_some_and(|rubric| rubric.trim().is_empty()) {
+            return Err("has an empty rubric".to_string());
+        }
+        if self.min_score.is_some_and(|score| !(1..=10).contains(&score)) {
+            return Err("needs a min_score from 1 to 10".to_string());
+        }
+        if self.tags.iter().any(|tag| tag.trim().is_empty()) {
+            return Err("has an empty tag".to_string());
+        }
+        if !(1..=MAX_REPETITIONS).contains(&self.repetitions) {
+            return Err(format!("needs between 1 and {} repetitions", MAX_REPETITIONS));
+        }
+        if let Some(tool) = self.tools.iter().find(|tool| !TOOLS.contains(&tool.as_str())) {
+            return Err(format!("lists unknown tool '{}' (use {})", tool, TOOLS.join(", ")));
+        }
+        if self.artifacts.iter().any(|path| path.trim().is_empty()) {
+            return Err("lists an empty artifact path".to_string());
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,348 @@
+use std::env;
+use std::time::Duration;
+
+/// A step run after the page loads.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Action {
+    /// Clicks the first element matching a CSS selector.
+    Click(String),
+    /// Scrolls down by this many screen heights.
+    Scroll(u32),
+    /// Waits this many milliseconds.
+    Wait(u64),
+}
+
+const MAX_SCROLL_SCREENS: u32 = 10;
+const MAX_WAIT_MS: u64 = 5000;
+
+/// Limits on a single `browse` call: `BROWSER_MAX_ACTIONS` (default 5), `BROWSER_TIMEOUT_SECS`
+/// for the whole call including start-up (default 30) and `BROWSER_MAX_CHARS` of returned text
+/// (default 8000).
+pub struct Limits {
+    max_actions: usize,
+    #[cfg_attr(not(all(feature = "browser", unix)), allow(dead_code))]
+    timeout: Duration,
+    max_chars: usize,
+}
+
+impl Limits {
+    pub fn from_env() -> Self {
+        let var = |name: &str, default: u64| env::var(name).ok().and_then(|v| v.trim().parse().ok()).filter(|&v| v > 0).unwrap_or(default);
+        Limits {
+            max_actions: var("BROWSER_MAX_ACTIONS", 5) as usize,
+            timeout: Duration::from_secs(var("BROWSER_TIMEOUT_SECS", 30)),
+            max_chars: var("BROWSER_MAX_CHARS", 8000) as usize,
+        }
+    }
+}
+
+/// Whether this build can browse at all.
+pub fn enabled() -> bool {
+    cfg!(all(feature = "browser", unix))
+}
+
+pub fn check_url(url: &str) -> Result<(), String> {
+    match reqwest::Url::parse(url) {
+        Ok(parsed) if matches!(parsed.scheme(), "http" | "https") => Ok(()),
+        Ok(_) => Err("only http and https URLs can be browsed".to_string()),
+        Err(e) => Err(format!("not a valid URL: {}", e)),
+    }
+}
+
+/// Parses `click <css selector>; scroll <screens>; wait <ms>`, enforcing the limits.
+pub fn parse_actions(spec: &str, limits: &Limits) -> Result<Vec<Action>, String> {
+    let mut actions = Vec::new();
+    for step in spec.split(';').map(str::trim).filter(|s| !s.is_empty()) {
+        let (verb, arg) = step.split_once(char::is_whitespace).map_or((step, ""), |(v, a)| (v, a.trim()));
+        let action = match verb.to_ascii_lowercase().as_str() {
+            "click" if !arg.is_empty() => Action::Click(arg.to_string()),
+            "click" => return Err("click needs a CSS selector".to_string()),
+            "scroll" => match arg.parse::<u32>() {
+                Ok(screens) if (1..=MAX_SCROLL_SCREENS).contains(&screens) => Action::Scroll(screens),
+                _ if arg.is_empty() => Action::Scroll(1),
+                _ => return Err(format!("scroll takes 1 to {} screens", MAX_SCROLL_SCREENS)),
+            },
+            "wait" => match arg.parse::<u64>() {
+                Ok(ms) if ms <= MAX_WAIT_MS => Action::Wait(ms),
+                _ => return Err(format!("wait takes 0 to {} milliseconds", MAX_WAIT_MS)),
+            },
+            other => return Err(format!("unknown action '{}' (expected click, scroll or wait)", other)),
+        };
+        actions.push(action);
+    }
+    if actions.len() > limits.max_actions {
+        return Err(format!("at most {} actions per call (BROWSER_MAX_ACTIONS)", limits.max_actions));
+    }
+    Ok(actions)
+}
+
+/// The rendered page after all actions ran.
+struct Page {
+    title: String,
+    url: String,
+    text: String,
+}
+
+/// Loads `url` in headless Chromium, runs `actions` and returns the title, final URL and
+/// rendered text, cut at `BROWSER_MAX_CHARS`.
+pub fn browse(url: &str, actions: &str) -> Result<String, String> {
+    check_url(url)?;
+    let limits = Limits::from_env();
+    let actions = parse_actions(actions, &limits)?;
+    let page = render(url, &actions, &limits)?;
+    let text = page.text.trim();
+    let mut output = format!("Title: {}\nURL: {}\n\n", page.title, page.url);
+    match text.char_indices().nth(limits.max_chars) {
+        Some((cut, _)) => output.push_str(&format!("{}\n[truncated at {} characters]", &text[..cut], limits.max_chars)),
+        None => output.push_str(text),
+    }
+    Ok(output)
+}
+
+#[cfg(all(feature = "browser", unix))]
+fn render(url: &str, actions: &[Action], limits: &Limits) -> Result<Page, String> {
+    let mut browser = cdp::Browser::launch(limits.timeout)?;
+    let page = browser.render(url, actions);
+    browser.close();
+    page
+}
+
+#[cfg(not(all(feature = "browser", unix)))]
+fn render(_url: &str, _actions: &[Action], _limits: &Limits) -> Result<Page, String> {
+    Err("the browse tool needs a build with `--features browser` on Linux or macOS".to_string())
+}
+
+/// Chrome DevTools Protocol over `--remote-debugging-pipe`: Chromium reads NUL-terminated JSON
+/// commands on fd 3 and writes replies and events on fd 4.
+#[cfg(all(feature = "browser", unix))]
+mod cdp {
+    use std::env;
+    use std::fs::{self, File};
+    use std::io::{self, BufRead, BufReader, Write};
+    use std::os::fd::{FromRawFd, RawFd};
+    use std::os::unix::process::CommandExt;
+    use std::path::PathBuf;
+    use std::process::{Child, Command, Stdio};
+    use std::sync::mpsc::{self, Receiver};
+    use std::thread;
+    use std::time::{Duration, Instant};
+
+    use serde_json::{Value, json};
+
+    use super::{Action, Page};
+
+    /// How long to let scripts run after a load, click or scroll.
+    const SETTLE: Duration = Duration::from_millis(1000);
+
+    const BINARIES: &[&str] = &["chromium", "chromium-browser", "google-chrome", "google-chrome-stable"];
+
+    /// `BROWSER_PATH`, otherwise the first Chromium or Chrome binary on `PATH`.
+    fn binary() -> Result<PathBuf, String> {
+        if let Ok(path) = env::var("BROWSER_PATH") {
+            return Ok(PathBuf::from(path));
+        }
+        let path = env::var_os("PATH").unwrap_or_default();
+        env::split_paths(&path)
+            .flat_map(|dir| BINARIES.iter().map(move |name| dir.join(name)))
+            .find(|candidate| candidate.is_file())
+            .ok_or_else(|| format!("no Chromium found (tried {}); set BROWSER_PATH", BINARIES.join(", ")))
+    }
+
+    /// A pipe whose ends are closed on exec, so only the descriptors moved to 3 and 4 reach the
+    /// browser.
+    fn pipe() -> Result<(RawFd, RawFd), String> {
+        let mut fds = [0; 2];
+        // SAFETY: `fds` has room for the two descriptors `pipe` writes.
+        if unsafe { libc::pipe(fds.as_mut_ptr()) } != 0 {
+            return Err(format!("could not create a pipe: {}", io::Error::last_os_error()));
+        }
+        for fd in fds {
+            // SAFETY: `fd` was just returned by `pipe`.
+            unsafe { libc::fcntl(fd, libc::F_SETFD, libc::FD_CLOEXEC) };
+        }
+        Ok((fds[0], fds[1]))
+    }
+
+    pub struct Browser {
+        child: Child,
+        commands: File,
+        messages: Receiver<Value>,
+        /// Events received while waiting for a command's reply.
+        events: Vec<Value>,
+        next_id: u64,
+        deadline: Instant,
+        timeout: Duration,
+        profile: PathBuf,
+    }
+
+    impl Browser {
+        pub fn launch(timeout: Duration) -> Result<Self, String> {
+            let mut command = Command::new(binary()?);
+            let profile = env::temp_dir().join(format!("agent-bench-browser-{}", uuid::Uuid::new_v4()));
+            let (command_read, command_write) = pipe()?;
+            let (reply_read, reply_write) = pipe()?;
+            command
+                .args(["--headless=new", "--remote-debugging-pipe", "--no-first-run", "--no-default-browser-check", "--disable-gpu"])
+                .args(["--disable-extensions", "--disable-background-networking", "--mute-audio", "--window-size=1280,2000"])
+                .arg(format!("--user-data-dir={}", profile.display()))
+                .stdin(Stdio::null())
+                .stdout(Stdio::null())
+                .stderr(Stdio::null());
+            // Containers running as root need this; it is never the default.
+            if env::var("BROWSER_NO_SANDBOX").is_ok_and(|v| v == "1" || v.eq_ignore_ascii_case("true")) {
+                command.arg("--no-sandbox");
+            }
+            // SAFETY: only async-signal-safe calls between fork and exec. Both ends are first
+            // moved above 10 so neither can be overwritten by the other's dup2.
+            unsafe {
+                command.pre_exec(move || {
+                    let read = libc::fcntl(command_read, libc::F_DUPFD, 10);
+                    let write = libc::fcntl(reply_write, libc::F_DUPFD, 10);
+                    if read < 0 || write < 0 || libc::dup2(read, 3) < 0 || libc::dup2(write, 4) < 0 {
+                        return Err(io::Error::last_os_error());
+                    }
+                    Ok(())
+                });
+            }
+            let spawned = command.spawn();
+            // SAFETY: the descriptors came from `pipe` and are owned here from now on; the
+            // child's ends are closed once it has its own copies.
+            let (commands, replies) = unsafe {
+                libc::close(command_read);
+                libc::close(reply_write);
+                (File::from_raw_fd(command_write), File::from_raw_fd(reply_read))
+            };
+            let child = spawned.map_err(|e| format!("could not start the browser: {}", e))?;
+
+            let (sender, messages) = mpsc::channel();
+            thread::spawn(move || {
+                let mut reader = BufReader::new(replies);
+                let mut buffer = Vec::new();
+                while reader.read_until(0, &mut buffer).is_ok_and(|n| n > 0) {
+                    if let Ok(message) = serde_json::from_slice::<Value>(buffer.strip_suffix(&[0]).unwrap_or(&buffer))
+                        && sender.send(message).is_err()
+                    {
+                        break;
+                    }
+                    buffer.clear();
+                }
+            });
+            Ok(Browser { child, commands, messages, events: Vec::new(), next_id: 0, deadline: Instant::now() + timeout, timeout, profile })
+        }
+
+        fn remaining(&self) -> Result<Duration, String> {
+            self.deadline
+                .checked_duration_since(Instant::now())
+                .filter(|d| !d.is_zero())
+                .ok_or_else(|| format!("browsing timed out after {}s (BROWSER_TIMEOUT_SECS)", self.timeout.as_secs()))
+        }
+
+        /// Sends a command and waits for its reply, keeping any events that arrive first.
+        fn call(&mut self, method: &str, params: Value, session: Option<&str>) -> Result<Value, String> {
+            self.next_id += 1;
+            let id = self.next_id;
+            let mut message = json!({"id": id, "method": method, "params": params});
+            if let Some(session) = session {
+                message["sessionId"] = json!(session);
+            }
+            let mut bytes = message.to_string().into_bytes();
+            bytes.push(0);
+            self.commands.write_all(&bytes).map_err(|e| format!("browser connection lost: {}", e))?;
+            loop {
+                let message = self.messages.recv_timeout(self.remaining()?).map_err(|e| match e {
+                    mpsc::RecvTimeoutError::Timeout => format!("browsing timed out after {}s (BROWSER_TIMEOUT_SECS)", self.timeout.as_secs()),
+                    mpsc::RecvTimeoutError::Disconnected => "the browser exited unexpectedly".to_string(),
+                })?;
+                if message["id"].as_u64() != Some(id) {
+                    self.events.push(message);
+                    continue;
+                }
+                if let Some(error) = message["error"]["message"].as_str() {
+                    return Err(format!("{} failed: {}", method, error));
+                }
+                return Ok(message["result"].clone());
+            }
+        }
+
+        /// Waits up to `wait` for `method` on `session`. Returns whether it arrived.
+        fn wait_for(&mut self, method: &str, session: &str, wait: Duration) -> bool {
+            let matches = |event: &Value| event["method"] == method && event["sessionId"] == session;
+            if let Some(position) = self.events.iter().position(matches) {
+                self.events.remove(position);
+                return true;
+            }
+            let until = Instant::now() + wait;
+            while let Some(left) = until.checked_duration_since(Instant::now()).filter(|d| !d.is_zero()) {
+                match self.messages.recv_timeout(left.min(self.remaining().unwrap_or_default())) {
+                    Ok(event) if matches(&event) => return true,
+                    Ok(event) => self.events.push(event),
+                    Err(_) => return false,
+                }
+            }
+            false
+        }
+
+        fn evaluate(&mut self, session: &str, expression: &str) -> Result<Value, String> {
+            let result = self.call("Runtime.evaluate", json!({"expression": expression, "returnByValue": true, "awaitPromise": true}), Some(session))?;
+            if let Some(exception) = result["exceptionDetails"]["exception"]["description"].as_str().or(result["exceptionDetails"]["text"].as_str()) {
+                return Err(format!("page script failed: {}", exception));
+            }
+            Ok(result["result"]["value"].clone())
+        }
+
+        /// Gives scripts time to run, returning early when a navigation finishes loading.
+        fn settle(&mut self, session: &str) {
+            self.wait_for("Page.loadEventFired", session, SETTLE);
+        }
+
+        pub fn render(&mut self, url: &str, actions: &[Action]) -> Result<Page, String> {
+            let target = self.call("Target.createTarget", json!({"url": "about:blank"}), None)?;
+            let target_id = target["targetId"].as_str().ok_or("the browser did not open a page")?.to_string();
+            let attached = self.call("Target.attachToTarget", json!({"targetId": target_id, "flatten": true}), None)?;
+            let session = attached["sessionId"].as_str().ok_or("could not attach to the page")?.to_string();
+            self.call("Page.enable", json!({}), Some(&session))?;
+            let navigation = self.call("Page.navigate", json!({"url": url}), Some(&session))?;
+            if let Some(error) = navigation["errorText"].as_str() {
+                return Err(format!("could not load {}: {}", url, error));
+            }
+            // Pages that never finish loading (long polling) are read as they are at the deadline.
+            let load_wait = self.remaining()?.saturating_sub(Duration::from_secs(2));
+            self.wait_for("Page.loadEventFired", &session, load_wait);
+            self.settle(&session);
+
+            for action in actions {
+                match action {
+                    Action::Click(selector) => {
+                        let script = format!(
+                            "(() => {{ const el = document.querySelector({}); if (!el) return false; el.scrollIntoView({{block: 'center'}}); el.click(); return true; }})()",
+                            json!(selector)
+                        );
+                        if self.evaluate(&session, &script)? != json!(true) {
+                            return Err(format!("no element matches '{}'", selector));
+                        }
+                    }
+                    Action::Scroll(screens) => {
+                        self.evaluate(&session, &format!("window.scrollBy(0, window.innerHeight * {})", screens))?;
+                    }
+                    Action::Wait(ms) => thread::sleep(Duration::from_millis(*ms).min(self.remaining()?)),
+                }
+                self.settle(&session);
+            }
+
+            let page = self.evaluate(&session, "({title: document.title, url: location.href, text: document.body ? document.body.innerText : ''})")?;
+            Ok(Page {
+                title: page["title"].as_str().unwrap_or_default().to_string(),
+                url: page["url"].as_str().unwrap_or(url).to_string(),
+                text: page["text"].as_str().unwrap_or_default().to_string(),
+            })
+        }
+
+        pub fn close(mut self) {
+            self.deadline = self.deadline.max(Instant::now() + Duration::from_secs(2));
+            let _ = self.call("Browser.close", json!({}), None);
+            let _ = self.child.kill();
+            let _ = self.child.wait();
+            let _ = fs::remove_dir_all(&self.profile);
+        }
+    }
+}
@@ -0,0 +1,574 @@
+use chrono::{Local, NaiveDate, TimeDelta};
+
+/// Exponents of length, mass, time and information, in that order.
+type Dims = [i8; 4];
+
+const SCALAR: Dims = [0; 4];
+const TIME: Dims = [0, 0, 1, 0];
+const BASE_UNITS: [&str; 4] = ["m", "kg", "s", "B"];
+const SECONDS_PER_DAY: f64 = 86_400.0;
+const UNIT_EXPONENT_RANGE: &str = "unit exponent out of range";
+
+/// Units the calculator knows, with their size in base units. Lookups also try the name
+/// without a trailing `s`/`es` and ignoring case, so `hours`, `Km` and `inches` work.
+const UNITS: &[(&str, f64, Dims)] = &[
+    ("m", 1.0, [1, 0, 0, 0]),
+    ("meter", 1.0, [1, 0, 0, 0]),
+    ("metre", 1.0, [1, 0, 0, 0]),
+    ("km", 1_000.0, [1, 0, 0, 0]),
+    ("kilometer", 1_000.0, [1, 0, 0, 0]),
+    ("cm", 0.01, [1, 0, 0, 0]),
+    ("mm", 0.001, [1, 0, 0, 0]),
+    ("mi", 1_609.344, [1, 0, 0, 0]),
+    ("mile", 1_609.344, [1, 0, 0, 0]),
+    ("yd", 0.9144, [1, 0, 0, 0]),
+    ("ft", 0.3048, [1, 0, 0, 0]),
+    ("foot", 0.3048, [1, 0, 0, 0]),
+    ("feet", 0.3048, [1, 0, 0, 0]),
+    ("in", 0.0254, [1, 0, 0, 0]),
+    ("inch", 0.0254, [1, 0, 0, 0]),
+    ("nmi", 1_852.0, [1, 0, 0, 0]),
+    ("kg", 1.0, [0, 1, 0, 0]),
+    ("g", 0.001, [0, 1, 0, 0]),
+    ("gram", 0.001, [0, 1, 0, 0]),
+    ("mg", 1e-6, [0, 1, 0, 0]),
+    ("t", 1_000.0, [0, 1, 0, 0]),
+    ("lb", 0.453_592_37, [0, 1, 0, 0]),
+    ("pound", 0.453_592_37, [0, 1, 0, 0]),
+    ("oz", 0.028_349_523_125, [0, 1, 0, 0]),
+    ("s", 1.0, TIME),
+    ("sec", 1.0, TIME),
+    ("second", 1.0, TIME),
+    ("ms", 0.001, TIME),
+    ("min", 60.0, TIME),
+    ("minute", 60.0, TIME),
+    ("h", 3_600.0, TIME),
+    ("hr", 3_600.0, TIME),
+    ("hour", 3_600.0, TIME),
+    ("d", SECONDS_PER_DAY, TIME),
+    ("day", SECONDS_PER_DAY, TIME),
+    ("week", 7.0 * SECONDS_PER_DAY, TIME),
+    ("B", 1.0, [0, 0, 0, 1]),
+    ("byte", 1.0, [0, 0, 0, 1]),
+    ("bit", 0.125, [0, 0, 0, 1]),
+    ("KB", 1e3, [0, 0, 0, 1]),
+    ("MB", 1e6, [0, 0, 0, 1]),
+    ("GB", 1e9, [0, 0, 0, 1]),
+    ("TB", 1e12, [0, 0, 0, 1]),
+    ("KiB", 1_024.0, [0, 0, 0, 1]),
+    ("MiB", 1_048_576.0, [0, 0, 0, 1]),
+    ("GiB", 1_073_741_824.0, [0, 0, 0, 1]),
+    ("TiB", 1_099_511_627_776.0, [0, 0, 0, 1]),
+];
+
+fn unit(name: &str) -> Option<(f64, Dims)> {
+    let candidates = [Some(name), name.strip_suffix('s'), name.strip_suffix("es")];
+    let find = |exact: bool| {
+        candidates.iter().flatten().find_map(|candidate| {
+            UNITS
+                .iter()
+                .find(|(unit, _, _)| if exact { unit == candidate } else { unit.eq_ignore_ascii_case(candidate) })
+                .map(|(_, factor, dims)| (*factor, *dims))
+        })
+    };
+    find(true).or_else(|| find(false))
+}
+
+/// Name and size of the unit a dimension is shown in.
+type DisplayUnits = [Option<(String, f64)>; 4];
+
+/// A number with dimensions, stored in base units. Each dimension is shown in the first unit
+/// written for it, so `90 km/h * 2.5 h` comes out in km.
+#[derive(Debug, Clone)]
+struct Quantity {
+    value: f64,
+    dims: Dims,
+    units: DisplayUnits,
+}
+
+#[derive(Debug, Clone)]
+enum Value {
+    Quantity(Quantity),
+    Date(NaiveDate),
+}
+
+fn scalar(value: f64) -> Value {
+    Value::Quantity(Quantity { value, dims: SCALAR, units: Default::default() })
+}
+
+fn format_number(value: f64) -> String {
+    if value == 0.0 {
+        return "0".to_string();
+    }
+    if value.abs() >= 1e15 || value.abs() < 1e-6 {
+        return format!("{:e}", value);
+    }
+    let text = format!("{:.10}", value);
+    text.trim_end_matches('0').trim_end_matches('.').to_string()
+}
+
+fn unit_name(dims: Dims, units: &DisplayUnits) -> String {
+    let names: Vec<&str> = BASE_UNITS.iter().zip(units).map(|(base, unit)| unit.as_ref().map_or(*base, |(name, _)| name.as_str())).collect();
+    let part = |(name, exp): (&&str, i8)| if exp == 1 { name.to_string() } else { format!("{}^{}", name, exp) };
+    let numerator: Vec<String> = names.iter().zip(dims).filter(|(_, e)| *e > 0).map(part).collect();
+    let denominator: Vec<String> = names.iter().zip(dims).filter(|(_, e)| *e < 0).map(|(n, e)| part((n, -e))).collect();
+    match (numerator.is_empty(), denominator.is_empty()) {
+        (_, true) => numerator.join("*"),
+        (true, false) => format!("1/{}", denominator.join("*")),
+        (false, false) => format!("{}/{}", numerator.join("*"), denominator.join("*")),
+    }
+}
+
+impl Quantity {
+    fn display(&self) -> String {
+        if self.dims == SCALAR {
+            return format_number(self.value);
+        }
+        let factor: f64 = self.units.iter().zip(self.dims).map(|(unit, exp)| unit.as_ref().map_or(1.0, |(_, f)| f.powi(exp.into()))).product();
+        format!("{} {}", format_number(self.value / factor), unit_name(self.dims, &self.units))
+    }
+}
+
+/// Display units of `a`, falling back to those of `b` for dimensions `a` does not name.
+fn merge_units(a: &DisplayUnits, b: &DisplayUnits) -> DisplayUnits {
+    std::array::from_fn(|i| a[i].clone().or_else(|| b[i].clone()))
+}
+
+impl Value {
+    fn display(&self) -> String {
+        match self {
+            Value::Quantity(q) => q.display(),
+            Value::Date(date) => date.format("%Y-%m-%d (%A)").to_string(),
+        }
+    }
+
+    fn describe(&self) -> String {
+        match self {
+            Value::Quantity(q) if q.dims == SCALAR => "a number".to_string(),
+            Value::Quantity(q) => format!("a quantity in {}", unit_name(q.dims, &Default::default())),
+            Value::Date(_) => "a date".to_string(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Number(f64),
+    Date(NaiveDate),
+    Ident(String),
+    Op(char),
+}
+
+impl Token {
+    fn text(&self) -> String {
+        match self {
+            Token::Number(n) => format_number(*n),
+            Token::Date(date) => date.to_string(),
+            Token::Ident(name) => name.clone(),
+            Token::Op(op) => op.to_string(),
+        }
+    }
+}
+
+fn tokenize(expression: &str) -> Result<Vec<Token>, String> {
+    let chars: Vec<char> = expression.chars().collect();
+    let mut tokens = Vec::new();
+    let mut i = 0;
+    while i < chars.len() {
+        let c = chars[i];
+        if c.is_whitespace() {
+            i += 1;
+        } else if c.is_ascii_digit() || (c == '.' && chars.get(i + 1).is_some_and(char::is_ascii_digit)) {
+            let rest: String = chars[i..].iter().take(11).collect();
+            let is_date = rest.char_indices().take(10).all(|(at, c)| if at == 4 || at == 7 { c == '-' } else { c.is_ascii_digit() })
+                && rest.len() >= 10
+                && !rest[10..].starts_with(|c: char| c.is_ascii_digit());
+            if is_date {
+                let date = NaiveDate::parse_from_str(&rest[..10], "%Y-%m-%d").map_err(|_| format!("invalid date '{}'", &rest[..10]))?;
+                tokens.push(Token::Date(date));
+                i += 10;
+                continue;
+            }
+            let start = i;
+            while i < chars.len() && (chars[i].is_ascii_digit() || chars[i] == '.' || chars[i] == '_') {
+                i += 1;
+            }
+            // Exponent, as in 1.5e3, but not a following unit or constant named e.
+            if i < chars.len() && (chars[i] == 'e' || chars[i] == 'E') {
+                let digits_at = if matches!(chars.get(i + 1), Some('+' | '-')) { i + 2 } else { i + 1 };
+                if chars.get(digits_at).is_some_and(char::is_ascii_digit) {
+                    i = digits_at;
+                    while i < chars.len() && chars[i].is_ascii_digit() {
+                        i += 1;
+                    }
+                }
+            }
+            let text: String = chars[start..i].iter().filter(|c| **c != '_').collect();
+            tokens.push(Token::Number(text.parse().map_err(|_| format!("invalid number '{}'", text))?));
+        } else if c.is_alphabetic() {
+            let start = i;
+            while i < chars.len() && (chars[i].is_alphanumeric() || chars[i] == '_') {
+                i += 1;
+            }
+            tokens.push(Token::Ident(chars[start..i].iter().collect()));
+        } else if c == '*' && chars.get(i + 1) == Some(&'*') {
+            tokens.push(Token::Op('^'));
+            i += 2;
+        } else if "+-*/^%(),".contains(c) {
+            tokens.push(Token::Op(c));
+            i += 1;
+        } else if c == '×' || c == '÷' {
+            tokens.push(Token::Op(if c == '×' { '*' } else { '/' }));
+            i += 1;
+        } else {
+            return Err(format!("unexpected character '{}'", c));
+        }
+    }
+    Ok(tokens)
+}
+
+fn add(a: Value, b: Value, subtract: bool) -> Result<Value, String> {
+    let sign = if subtract { -1.0 } else { 1.0 };
+    match (a, b) {
+        (Value::Quantity(a), Value::Quantity(b)) if a.dims == b.dims => Ok(Value::Quantity(Quantity {
+            value: a.value + sign * b.value,
+            dims: a.dims,
+            units: merge_units(&a.units, &b.units),
+        })),
+        (Value::Date(date), Value::Quantity(q)) if q.dims == TIME => {
+            let days = sign * q.value / SECONDS_PER_DAY;
+            if !days.is_finite() || days.abs() > i64::MAX as f64 {
+                return Err("date out of range".to_string());
+            }
+            if days.fract() != 0.0 {
+                return Err("dates can only move by whole days".to_string());
+            }
+            let moved = TimeDelta::try_days(days as i64).and_then(|delta| date.checked_add_signed(delta));
+            moved.map(Value::Date).ok_or_else(|| "date out of range".to_string())
+        }
+        (Value::Quantity(q), Value::Date(date)) if q.dims == TIME && !subtract => add(Value::Date(date), Value::Quantity(q), false),
+        (Value::Date(a), Value::Date(b)) if subtract => Ok(Value::Quantity(Quantity {
+            value: (a - b).num_days() as f64 * SECONDS_PER_DAY,
+            dims: TIME,
+            units: [None, None, Some(("days".to_string(), SECONDS_PER_DAY)), None],
+        })),
+        (a, b) => Err(format!("cannot {} {} and {}", if subtract { "subtract" } else { "add" }, a.describe(), b.describe())),
+    }
+}
+
+fn multiply(a: Value, b: Value, divide: bool) -> Result<Value, String> {
+    let (Value::Quantity(a), Value::Quantity(b)) = (&a, &b) else {
+        return Err(format!("cannot {} {} and {}", if divide { "divide" } else { "multiply" }, a.describe(), b.describe()));
+    };
+    if divide && b.value == 0.0 {
+        return Err("division by zero".to_string());
+    }
+    let mut dims = a.dims;
+    for (dim, exp) in dims.iter_mut().zip(b.dims) {
+        *dim = if divide { dim.checked_sub(exp) } else { dim.checked_add(exp) }.ok_or(UNIT_EXPONENT_RANGE)?;
+    }
+    let value = if divide { a.value / b.value } else { a.value * b.value };
+    Ok(Value::Quantity(Quantity { value, dims, units: merge_units(&a.units, &b.units) }))
+}
+
+fn power(base: Value, exponent: Value) -> Result<Value, String> {
+    let (Value::Quantity(base), Value::Quantity(exponent)) = (&base, &exponent) else {
+        return Err("exponents only apply to numbers".to_string());
+    };
+    if exponent.dims != SCALAR {
+        return Err("the exponent must be a plain number".to_string());
+    }
+    let n = exponent.value;
+    if base.dims == SCALAR {
+        return Ok(scalar(base.value.powf(n)));
+    }
+    if n.fract() != 0.0 {
+        return Err("a quantity with units can only be raised to a whole power".to_string());
+    }
+    let n = i8::try_from(n as i64).map_err(|_| UNIT_EXPONENT_RANGE)?;
+    let mut dims = base.dims;
+    for dim in dims.iter_mut() {
+        *dim = dim.checked_mul(n).ok_or(UNIT_EXPONENT_RANGE)?;
+    }
+    Ok(Value::Quantity(Quantity { value: base.value.powf(n.into()), dims, units: base.units.clone() }))
+}
+
+fn call(name: &str, args: Vec<Value>) -> Result<Value, String> {
+    let numbers = args
+        .iter()
+        .map(|arg| match arg {
+            Value::Quantity(q) if q.dims == SCALAR => Ok(q.value),
+            other => Err(format!("{}() takes numbers, not {}", name, other.describe())),
+        })
+        .collect::<Result<Vec<f64>, String>>()?;
+    let one = |f: fn(f64) -> f64| match numbers.as_slice() {
+        [x] => Ok(scalar(f(*x))),
+        _ => Err(format!("{}() takes one argument", name)),
+    };
+    match name {
+        "sqrt" => one(f64::sqrt),
+        "abs" => one(f64::abs),
+        "round" => one(f64::round),
+        "floor" => one(f64::floor),
+        "ceil" => one(f64::ceil),
+        "ln" => one(f64::ln),
+        "log" | "log10" => one(f64::log10),
+        "log2" => one(f64::log2),
+        "exp" => one(f64::exp),
+        "sin" => one(f64::sin),
+        "cos" => one(f64::cos),
+        "tan" => one(f64::tan),
+        "min" | "max" if !numbers.is_empty() => {
+            let pick = if name == "min" { f64::min } else { f64::max };
+            Ok(scalar(numbers.into_iter().reduce(pick).unwrap_or_default()))
+        }
+        "min" | "max" => Err(format!("{}() needs at least one argument", name)),
+        _ => Err(format!("unknown function '{}'", name)),
+    }
+}
+
+struct Parser {
+    tokens: Vec<Token>,
+    pos: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos)
+    }
+
+    fn next(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.pos).cloned();
+        self.pos += 1;
+        token
+    }
+
+    fn eat(&mut self, op: char) -> bool {
+        if self.peek() == Some(&Token::Op(op)) {
+            self.pos += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn at_keyword(&self) -> bool {
+        matches!(self.peek(), Some(Token::Ident(word)) if word == "to")
+    }
+
+    fn expr(&mut self) -> Result<Value, String> {
+        let mut value = self.term()?;
+        loop {
+            if self.eat('+') {
+                value = add(value, self.term()?, false)?;
+            } else if self.eat('-') {
+                value = add(value, self.term()?, true)?;
+            } else {
+                return Ok(value);
+            }
+        }
+    }
+
+    fn term(&mut self) -> Result<Value, String> {
+        let mut value = self.unary()?;
+        loop {
+            if self.eat('*') {
+                value = multiply(value, self.unary()?, false)?;
+            } else if self.eat('/') {
+                value = multiply(value, self.unary()?, true)?;
+            } else if self.eat('%') {
+                value = match (value, self.unary()?) {
+                    (Value::Quantity(a), Value::Quantity(b)) if a.dims == b.dims && b.value != 0.0 => {
+                        Value::Quantity(Quantity { value: a.value % b.value, ..a })
+                    }
+                    _ => return Err("% needs two non-zero numbers of the same unit".to_string()),
+                };
+            } else {
+                return Ok(value);
+            }
+        }
+    }
+
+    fn unary(&mut self) -> Result<Value, String> {
+        if self.eat('-') {
+            return multiply(scalar(-1.0), self.unary()?, false);
+        }
+        if self.eat('+') {
+            return self.unary();
+        }
+        let base = self.postfix()?;
+        if self.eat('^') { power(base, self.unary()?) } else { Ok(base) }
+    }
+
+    /// A primary value followed by an optional unit, as in `5 km` or `3 m^2`.
+    fn postfix(&mut self) -> Result<Value, String> {
+        let value = self.primary()?;
+        if let Value::Quantity(q) = &value
+            && q.dims == SCALAR
+            && !self.at_keyword()
+            && let Some(Token::Ident(name)) = self.peek()
+            && self.tokens.get(self.pos + 1) != Some(&Token::Op('('))
+            && unit(name).is_some()
+        {
+            let name = name.clone();
+            self.pos += 1;
+            let mut unit_value = self.unit_value(&name)?;
+            if self.eat('^') {
+                let exponent = match self.next() {
+                    Some(Token::Number(n)) => n,
+                    _ => return Err(format!("expected a number after {}^", name)),
+                };
+                unit_value = power(unit_value, scalar(exponent))?;
+            }
+            return multiply(value, unit_value, false);
+        }
+        Ok(value)
+    }
+
+    fn unit_value(&self, name: &str) -> Result<Value, String> {
+        let (factor, dims) = unit(name).ok_or_else(|| format!("unknown unit '{}'", name))?;
+        let mut units = DisplayUnits::default();
+        if let Some(dim) = dims.iter().position(|exp| *exp != 0) {
+            units[dim] = Some((name.to_string(), factor));
+        }
+        Ok(Value::Quantity(Quantity { value: factor, dims, units }))
+    }
+
+    fn primary(&mut self) -> Result<Value, String> {
+        match self.next() {
+            Some(Token::Number(n)) => Ok(scalar(n)),
+            Some(Token::Date(date)) => Ok(Value::Date(date)),
+            Some(Token::Op('(')) => {
+                let value = self.expr()?;
+                if !self.eat(')') {
+                    return Err("missing ')'".to_string());
+                }
+                Ok(value)
+            }
+            Some(Token::Ident(name)) if self.eat('(') => {
+                let mut args = Vec::new();
+                if !self.eat(')') {
+                    loop {
+                        args.push(self.expr()?);
+                        if self.eat(')') {
+                            break;
+                        }
+                        if !self.eat(',') {
+                            return Err(format!("missing ')' after the arguments of {}()", name));
+                        }
+                    }
+                }
+                call(&name, args)
+            }
+            Some(Token::Ident(name)) => match name.as_str() {
+                "pi" => Ok(scalar(std::f64::consts::PI)),
+                "e" => Ok(scalar(std::f64::consts::E)),
+                "today" => Ok(Value::Date(Local::now().date_naive())),
+                _ if unit(&name).is_some() => self.unit_value(&name),
+                _ => Err(format!("unknown name '{}'", name)),
+            },
+            Some(Token::Op(op)) => Err(format!("unexpected '{}'", op)),
+            None => Err("unexpected end of expression".to_string()),
+        }
+    }
+}
+
+/// Evaluates an arithmetic expression with optional units and dates, e.g. `2^10 / 3`,
+/// `5 km + 300 m to mi`, `90 km/h * 2.5 h`, `2025-03-01 - 2024-12-25` or `today + 90 days`.
+/// A trailing `to <unit>` converts the result. Deterministic apart from `today`.
+pub fn evaluate(expression: &str) -> Result<String, String> {
+    let mut parser = Parser { tokens: tokenize(expression)?, pos: 0 };
+    let value = parser.expr()?;
+    let result = if parser.at_keyword() {
+        parser.pos += 1;
+        let target_start = parser.pos;
+        let target = parser.expr()?;
+        let target_name = parser.tokens[target_start..parser.pos].iter().map(Token::text).collect::<String>();
+        match (&value, &target) {
+            (Value::Quantity(v), Value::Quantity(t)) if v.dims == t.dims && t.dims != SCALAR => {
+                format!("{} {}", format_number(v.value / t.value), target_name)
+            }
+            _ => return Err(format!("cannot convert {} to {}", value.describe(), target_name)),
+        }
+    } else {
+        value.display()
+    };
+    match parser.peek() {
+        None => Ok(result),
+        Some(token) => Err(format!("unexpected '{}' after the expression", token.text())),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::evaluate;
+
+    fn check(cases: &[(&str, &str)]) {
+        for (expression, expected) in cases {
+            assert_eq!(evaluate(expression).as_deref(), Ok(*expected), "{}", expression);
+        }
+    }
+
+    fn check_err(cases: &[(&str, &str)]) {
+        for (expression, expected) in cases {
+            assert_eq!(evaluate(expression), Err(expected.to_string()), "{}", expression);
+        }
+    }
+
+    #[test]
+    fn precedence() {
+        check(&[
+            ("2 + 3 * 4 ^ 2", "50"),
+            ("(2 + 3) * 4", "20"),
+            ("2 ^ 3 ^ 2", "512"),
+            ("-2^2", "-4"),
+            ("10 - 4 - 3", "3"),
+            ("2 ** 10 / 4", "256"),
+            ("10 % 4", "2"),
+            ("max(1, 7, 3) + sqrt(16)", "11"),
+            ("1_000 × 3 ÷ 2", "1500"),
+        ]);
+        check_err(&[("1 / 0", "division by zero"), ("(1 + 2", "missing ')'"), ("1 2", "unexpected '2' after the expression")]);
+    }
+
+    #[test]
+    fn units() {
+        check(&[
+            ("5 km + 300 m to mi", "3.2932673189 mi"),
+            ("90 km/h * 2.5 h", "225 km"),
+            ("42.195 km / (12 km/h) to min", "210.975 min"),
+            ("(2 m)^3", "8 m^3"),
+            ("1 GiB to MB", "1073.741824 MB"),
+            ("3 ft + 1 m", "6.280839895 ft"),
+        ]);
+        check_err(&[("1 m + 1 s", "cannot add a quantity in m and a quantity in s"), ("1 m to s", "cannot convert a quantity in m to s")]);
+    }
+
+    #[test]
+    fn dates() {
+        check(&[
+            ("2025-03-01 - 2024-12-25", "66 days"),
+            ("2024-12-25 + 10 days", "2025-01-04 (Saturday)"),
+            ("2 weeks + 2024-02-20", "2024-03-05 (Tuesday)"),
+            ("2024-03-01 - 1 day", "2024-02-29 (Thursday)"),
+        ]);
+        check_err(&[
+            ("2024-01-01 + 1.5 days", "dates can only move by whole days"),
+            ("2024-13-01", "invalid date '2024-13-01'"),
+            ("2024-01-01 + 2024-01-02", "cannot add a date and a date"),
+        ]);
+    }
+
+    #[test]
+    fn out_of_range() {
+        check_err(&[
+            ("(1 m^2)^100", "unit exponent out of range"),
+            ("m^100 * m^100", "unit exponent out of range"),
+            ("m^100 / m^-100", "unit exponent out of range"),
+            ("(1 m)^1e10", "unit exponent out of range"),
+            ("today + 1e300 days", "date out of range"),
+            ("today - 1e300 days", "date out of range"),
+            ("2024-01-01 + 1e9 days", "date out of range"),
+        ]);
+    }
+}
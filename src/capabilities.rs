@@ -0,0 +1,92 @@
+use std::env;
+
+use crate::prompts::model_key;
+
+/// What a model accepts. `None` means unknown, in which case requests are sent and the
+/// provider decides.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Capabilities {
+    /// Context window in tokens.
+    pub context_tokens: Option<usize>,
+    pub vision: Option<bool>,
+    pub streaming: Option<bool>,
+    pub tools: Option<bool>,
+}
+
+const fn known(context_tokens: usize, vision: bool, tools: Option<bool>) -> Capabilities {
+    Capabilities { context_tokens: Some(context_tokens), vision: Some(vision), streaming: Some(true), tools }
+}
+
+/// Known models, matched by name prefix, first match wins.
+const CAPABILITIES: &[(&str, Capabilities)] = &[
+    ("gpt-4-turbo", known(128_000, true, Some(true))),
+    ("gpt-4o", known(128_000, true, Some(true))),
+    ("gpt-4.1", known(1_047_576, true, Some(true))),
+    ("Meta-Llama-3.2-1B-Instruct", known(16_384, false, Some(false))),
+    ("gemini-1.5-pro", known(2_097_152, true, Some(true))),
+    ("gemini-", known(1_048_576, true, Some(true))),
+    ("claude-", known(200_000, true, Some(true))),
+    ("llama-3.3-70b-versatile", known(131_072, false, Some(true))),
+    ("llama-3.1-8b-instant", known(131_072, false, Some(true))),
+    ("mixtral-8x7b-32768", known(32_768, false, Some(true))),
+    ("gemma2-9b-it", known(8_192, false, None)),
+    ("mistral-large", known(131_072, false, Some(true))),
+    ("pixtral-", known(131_072, true, Some(true))),
+    ("codestral", known(256_000, false, None)),
+    ("deepseek-chat", known(128_000, false, Some(true))),
+    ("deepseek-reasoner", known(128_000, false, Some(false))),
+    ("deepseek-", known(128_000, false, None)),
+    ("command-r", known(128_000, false, Some(true))),
+    ("meta-llama/Llama-3.1-", known(131_072, false, None)),
+];
+
+/// Capabilities of `model_name`: the built-in table, then `CAPABILITIES_<MODEL_KEY>` (a comma
+/// list of `context=<tokens>`, `vision`, `streaming`, `tools`, each of the last three also as
+/// `no-<name>`), then `MODEL_CONTEXT_TOKENS` for the context window.
+pub fn for_model(model_name: &str) -> Capabilities {
+    let mut capabilities = CAPABILITIES
+        .iter()
+        .find(|(prefix, _)| model_name.starts_with(prefix))
+        .map(|(_, capabilities)| *capabilities)
+        .unwrap_or_default();
+    if let Ok(overrides) = env::var(format!("CAPABILITIES_{}", model_key(model_name))) {
+        for flag in overrides.split(',').map(|f| f.trim().to_lowercase()) {
+            let (name, enabled) = match flag.strip_prefix("no-") {
+                Some(name) => (name.to_string(), false),
+                None => (flag.clone(), true),
+            };
+            match name.as_str() {
+                "vision" => capabilities.vision = Some(enabled),
+                "streaming" => capabilities.streaming = Some(enabled),
+                "tools" => capabilities.tools = Some(enabled),
+                _ => {
+                    if let Some(tokens) = flag.strip_prefix("context=").and_then(|t| t.parse().ok()) {
+                        capabilities.context_tokens = Some(tokens);
+                    }
+                }
+            }
+        }
+    }
+    if let Some(tokens) = env::var("MODEL_CONTEXT_TOKENS").ok().and_then(|v| v.trim().parse().ok()) {
+        capabilities.context_tokens = Some(tokens);
+    }
+    capabilities
+}
+
+impl Capabilities {
+    /// `128000-token context, vision, no tools`, leaving out what is unknown.
+    pub fn describe(&self) -> String {
+        let mut parts = Vec::new();
+        if let Some(tokens) = self.context_tokens {
+            parts.push(format!("{}-token context", tokens));
+        }
+        for (name, supported) in [("vision", self.vision), ("streaming", self.streaming), ("tools", self.tools)] {
+            match supported {
+                Some(true) => parts.push(name.to_string()),
+                Some(false) => parts.push(format!("no {}", name)),
+                None => {}
+            }
+        }
+        if parts.is_empty() { "unknown".to_string() } else { parts.join(", ") }
+    }
+}
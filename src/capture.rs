@@ -0,0 +1,169 @@
+use std::env;
+use std::fs;
+use std::path::Path;
+use std::process::Command;
+
+use uuid::Uuid;
+
+/// Commands tried in order to read the system clipboard when `CLIPBOARD_COMMAND` is not set.
+const CLIPBOARD_COMMANDS: &[&str] = &[
+    "pbpaste",
+    "wl-paste --no-newline",
+    "xclip -selection clipboard -o",
+    "xsel --clipboard --output",
+    "powershell.exe -NoProfile -Command Get-Clipboard",
+];
+
+fn run(command: &str) -> Result<String, String> {
+    let output = Command::new("sh")
+        .arg("-c")
+        .arg(command)
+        .output()
+        .map_err(|e| e.to_string())?;
+    if !output.status.success() {
+        return Err(String::from_utf8_lossy(&output.stderr).trim().to_string());
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).to_string())
+}
+
+/// Reads the current text contents of the system clipboard.
+pub fn read_clipboard() -> Result<String, String> {
+    if let Ok(command) = env::var("CLIPBOARD_COMMAND") {
+        return run(&command);
+    }
+    CLIPBOARD_COMMANDS
+        .iter()
+        .find_map(|command| run(command).ok())
+        .ok_or_else(|| "no clipboard tool found (set CLIPBOARD_COMMAND)".to_string())
+}
+
+/// Most characters of clipboard text `/paste` attaches: `PASTE_MAX_CHARS`, default 20000.
+pub fn paste_max_chars() -> usize {
+    env::var("PASTE_MAX_CHARS").ok().and_then(|v| v.trim().parse().ok()).filter(|n| *n > 0).unwrap_or(20_000)
+}
+
+/// The first `max_chars` characters of `text`, or `None` when it is no longer than that.
+pub fn cut_at_chars(text: &str, max_chars: usize) -> Option<&str> {
+    text.char_indices().nth(max_chars).map(|(cut, _)| &text[..cut])
+}
+
+/// Captures the visible contents and recent scrollback of a tmux pane (`TMUX_CAPTURE_LINES`
+/// lines of history, 200 by default).
+pub fn capture_tmux_pane(target: &str) -> Result<String, String> {
+    let lines = env::var("TMUX_CAPTURE_LINES")
+        .ok()
+        .and_then(|v| v.trim().parse::<u32>().ok())
+        .unwrap_or(200);
+    let output = Command::new("tmux")
+        .args(["capture-pane", "-p", "-J", "-t", target, "-S", &format!("-{}", lines)])
+        .output()
+        .map_err(|e| e.to_string())?;
+    if !output.status.success() {
+        return Err(String::from_utf8_lossy(&output.stderr).trim().to_string());
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).to_string())
+}
+
+/// Splits a command's target (a pane, path or URL) from the prompt after it. The target is the
+/// first word, or, to include spaces, a quoted string (`"my shot.png" what is this?`) or
+/// everything before a ` -- ` separator (`my shot.png -- what is this?`).
+pub fn split_argument(rest: &str) -> (&str, &str) {
+    let rest = rest.trim();
+    if let Some(quote) = rest.chars().next().filter(|c| matches!(c, '"' | '\''))
+        && let Some(end) = rest[1..].find(quote)
+    {
+        let prompt = rest[end + 2..].trim();
+        let prompt = if prompt == "--" { "" } else { prompt.strip_prefix("-- ").map_or(prompt, str::trim_start) };
+        return (&rest[1..end + 1], prompt);
+    }
+    if let Some((target, prompt)) = rest.split_once(" -- ") {
+        return (target.trim(), prompt.trim());
+    }
+    if let Some(target) = rest.strip_suffix(" --") {
+        return (target.trim(), "");
+    }
+    rest.split_once(' ').map_or((rest, ""), |(target, prompt)| (target, prompt.trim()))
+}
+
+/// Wraps captured text in a fenced block under `label`, optionally preceded by the user's prompt.
+pub fn as_context_message(label: &str, text: &str, prompt: &str) -> String {
+    let block = format!("{}:\n```\n{}\n```", label, text.trim_end());
+    if prompt.is_empty() {
+        block
+    } else {
+        format!("{}\n\n{}", prompt, block)
+    }
+}
+
+/// Screenshot commands tried in order when `SCREENSHOT_COMMAND` is not set; `{file}` is the PNG
+/// to write.
+const SCREENSHOT_COMMANDS: &[&str] = &[
+    "screencapture -x {file}",
+    "grim {file}",
+    "gnome-screenshot -f {file}",
+    "spectacle -b -n -o {file}",
+    "scrot -o {file}",
+    "import -window root {file}",
+];
+
+const DEFAULT_OCR_COMMAND: &str = "tesseract {file} stdout";
+
+/// `path` quoted for `sh -c`, so image paths with spaces survive substitution.
+fn quoted(path: &Path) -> String {
+    format!("'{}'", path.to_string_lossy().replace('\'', r"'\''"))
+}
+
+/// Captures the whole screen and returns the text OCR finds in it.
+pub fn screenshot_text() -> Result<String, String> {
+    let path = env::temp_dir().join(format!("agent_bench_screenshot_{}.png", Uuid::new_v4()));
+    let file = quoted(&path);
+    let captured = match env::var("SCREENSHOT_COMMAND") {
+        Ok(command) => run(&command.replace("{file}", &file)).map(|_| ()),
+        Err(_) => SCREENSHOT_COMMANDS
+            .iter()
+            .find_map(|command| run(&command.replace("{file}", &file)).ok().filter(|_| path.exists()))
+            .map(|_| ())
+            .ok_or_else(|| "no screenshot tool found (set SCREENSHOT_COMMAND)".to_string()),
+    };
+    let text = captured.and_then(|_| image_text(&path));
+    let _ = fs::remove_file(&path);
+    text
+}
+
+/// Runs OCR on an image with `OCR_COMMAND` (tesseract by default).
+pub fn image_text(path: &Path) -> Result<String, String> {
+    if !path.is_file() {
+        return Err(format!("{} is not a file", path.display()));
+    }
+    let command = env::var("OCR_COMMAND").unwrap_or_else(|_| DEFAULT_OCR_COMMAND.to_string());
+    run(&command.replace("{file}", &quoted(path))).map_err(|e| if e.is_empty() { "OCR failed".to_string() } else { format!("OCR failed: {}", e) })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{cut_at_chars, split_argument};
+
+    #[test]
+    fn cuts_long_text_at_a_character_count() {
+        assert_eq!(cut_at_chars("héllo", 2), Some("hé"));
+        assert_eq!(cut_at_chars("héllo", 5), None);
+        assert_eq!(cut_at_chars("", 1), None);
+    }
+
+    #[test]
+    fn splits_the_target_from_the_prompt() {
+        let cases = [
+            ("1.0 why does this fail?", ("1.0", "why does this fail?")),
+            ("src", ("src", "")),
+            ("\"my shots/err 1.png\" what is this?", ("my shots/err 1.png", "what is this?")),
+            ("'my project'", ("my project", "")),
+            ("\"my session:1\" -- why?", ("my session:1", "why?")),
+            ("my shots/err 1.png -- what is this?", ("my shots/err 1.png", "what is this?")),
+            ("my project --", ("my project", "")),
+            ("\"unclosed quote", ("\"unclosed", "quote")),
+        ];
+        for (rest, expected) in cases {
+            assert_eq!(split_argument(rest), expected, "{}", rest);
+        }
+    }
+}
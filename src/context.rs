@@ -0,0 +1,118 @@
+use std::env;
+use std::sync::Arc;
+
+use crate::Message;
+use crate::{attachments, capabilities};
+use crate::tokenizer::{self, Tokenizer};
+
+/// How the in-memory chat history is trimmed once it outgrows the context budget.
+/// The first message (the system prompt) is never removed, and neither is the latest one.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TruncationStrategy {
+    DropOldest,
+    SummarizeOldest,
+    ImportanceWeighted,
+    SlidingWindow { overlap: usize },
+}
+
+impl TruncationStrategy {
+    /// `CONTEXT_STRATEGY`, drop-oldest when unset. Any other value is an error naming the
+    /// accepted ones.
+    pub fn from_env() -> Result<Self, String> {
+        let overlap = env::var("CONTEXT_WINDOW_OVERLAP")
+            .ok()
+            .and_then(|v| v.trim().parse().ok())
+            .unwrap_or(4);
+        match env::var("CONTEXT_STRATEGY").unwrap_or_default().trim().to_lowercase().as_str() {
+            "" | "drop-oldest" => Ok(TruncationStrategy::DropOldest),
+            "summarize-oldest" => Ok(TruncationStrategy::SummarizeOldest),
+            "importance-weighted" => Ok(TruncationStrategy::ImportanceWeighted),
+            "sliding-window" => Ok(TruncationStrategy::SlidingWindow { overlap }),
+            other => Err(format!(
+                "CONTEXT_STRATEGY '{}' is not one of drop-oldest, summarize-oldest, importance-weighted or sliding-window",
+                other
+            )),
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            TruncationStrategy::DropOldest => "drop-oldest",
+            TruncationStrategy::SummarizeOldest => "summarize-oldest",
+            TruncationStrategy::ImportanceWeighted => "importance-weighted",
+            TruncationStrategy::SlidingWindow { .. } => "sliding-window",
+        }
+    }
+}
+
+/// How much history is sent to the provider: `CONTEXT_MAX_TOKENS` counted with the model's
+/// tokenizer when set, otherwise `CONTEXT_MAX_CHARS` characters, otherwise three quarters of the
+/// model's known context window (leaving room for the reply), otherwise 32,000 characters.
+pub struct ContextBudget {
+    limit: usize,
+    tokenizer: Option<Arc<Tokenizer>>,
+}
+
+impl ContextBudget {
+    pub fn from_env(model_name: &str) -> Self {
+        let read = |var: &str| env::var(var).ok().and_then(|v| v.trim().parse().ok());
+        match read("CONTEXT_MAX_TOKENS") {
+            Some(limit) => ContextBudget { limit, tokenizer: Some(tokenizer::for_model(model_name)) },
+            None => match (read("CONTEXT_MAX_CHARS"), capabilities::for_model(model_name).context_tokens) {
+                (Some(limit), _) => ContextBudget { limit, tokenizer: None },
+                (None, Some(window)) => ContextBudget { limit: window / 4 * 3, tokenizer: Some(tokenizer::for_model(model_name)) },
+                (None, None) => ContextBudget { limit: 32_000, tokenizer: None },
+            },
+        }
+    }
+
+    pub fn describe(&self) -> String {
+        match &self.tokenizer {
+            Some(tokenizer) => format!("{} tokens ({})", self.limit, tokenizer.name()),
+            None => format!("{} characters", self.limit),
+        }
+    }
+
+    fn history_size(&self, history: &[Message]) -> usize {
+        match &self.tokenizer {
+            // Images are measured by their reference, not their base64 data.
+            Some(tokenizer) => history.iter().map(|m| tokenizer.count(&attachments::without_images(&m.content))).sum(),
+            None => history.iter().map(|m| attachments::without_images(&m.content).len()).sum(),
+        }
+    }
+}
+
+/// Removes messages from `history` until it fits in `budget` and returns the removed ones.
+/// `SummarizeOldest` trims like `DropOldest`; the caller folds the returned messages
+/// into a summary.
+pub fn truncate_history(history: &mut Vec<Message>, strategy: TruncationStrategy, budget: &ContextBudget) -> Vec<Message> {
+    let mut removed = Vec::new();
+    if budget.history_size(history) <= budget.limit {
+        return removed;
+    }
+
+    match strategy {
+        TruncationStrategy::DropOldest | TruncationStrategy::SummarizeOldest => {}
+        TruncationStrategy::ImportanceWeighted => {
+            // Tool results and other system messages are kept; plain turns go first.
+            while budget.history_size(history) > budget.limit {
+                let last = history.len() - 1;
+                match (1..last).find(|&i| history[i].role != "system") {
+                    Some(i) => removed.push(history.remove(i)),
+                    None => break,
+                }
+            }
+        }
+        TruncationStrategy::SlidingWindow { overlap } => {
+            // Start a fresh window that carries over the tail of the previous one, so the
+            // history is cut once per window instead of on every turn.
+            let keep_from = history.len().saturating_sub(overlap.max(1)).max(1);
+            removed.extend(history.drain(1..keep_from));
+        }
+    }
+
+    while budget.history_size(history) > budget.limit && history.len() > 2 {
+        removed.push(history.remove(1));
+    }
+    removed
+}
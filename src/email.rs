@@ -0,0 +1,248 @@
+use std::env;
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::{TcpStream, ToSocketAddrs};
+use std::time::Duration;
+
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD;
+
+/// How the SMTP connection is secured: `tls` from the first byte (port 465), `starttls` after
+/// the greeting (port 587) or `none` for a local relay.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Security {
+    Tls,
+    StartTls,
+    None,
+}
+
+/// SMTP settings and recipient allowlist for the `send_email` tool.
+///
+/// The tool is off unless `SMTP_HOST` is set. `EMAIL_FROM` is the sender and
+/// `EMAIL_ALLOWED_RECIPIENTS` lists the addresses (`ops@example.com`) or whole domains
+/// (`@example.com`) mail may go to, separated by commas.
+#[derive(Debug, Clone)]
+pub struct SmtpConfig {
+    pub host: String,
+    pub port: u16,
+    pub security: Security,
+    username: Option<String>,
+    password: Option<String>,
+    pub from: String,
+    allowed: Vec<String>,
+}
+
+impl SmtpConfig {
+    /// `Ok(None)` when `SMTP_HOST` is unset; an error when it is set but the rest of the
+    /// configuration is incomplete.
+    pub fn from_env() -> Result<Option<Self>, String> {
+        let var = |name: &str| env::var(name).ok().map(|v| v.trim().to_string()).filter(|v| !v.is_empty());
+        let Some(host) = var("SMTP_HOST") else {
+            return Ok(None);
+        };
+        let security = match var("SMTP_SECURITY").map(|v| v.to_ascii_lowercase()).as_deref() {
+            Some("tls") => Security::Tls,
+            Some("starttls") => Security::StartTls,
+            Some("none") => Security::None,
+            Some(other) => return Err(format!("SMTP_SECURITY must be tls, starttls or none, not '{}'", other)),
+            None if var("SMTP_PORT").as_deref() == Some("465") => Security::Tls,
+            None => Security::StartTls,
+        };
+        let port = match var("SMTP_PORT") {
+            Some(port) => port.parse().map_err(|_| format!("SMTP_PORT '{}' is not a port number", port))?,
+            None => match security {
+                Security::Tls => 465,
+                Security::StartTls => 587,
+                Security::None => 25,
+            },
+        };
+        let username = var("SMTP_USERNAME");
+        let password = var("SMTP_PASSWORD");
+        if username.is_some() && security == Security::None {
+            return Err("SMTP_USERNAME needs SMTP_SECURITY=tls or starttls; the password would be sent in the clear".to_string());
+        }
+        if username.is_some() != password.is_some() {
+            return Err("SMTP_USERNAME and SMTP_PASSWORD must be set together".to_string());
+        }
+        let from = var("EMAIL_FROM").ok_or("EMAIL_FROM not set in .env")?;
+        check_address(&from).map_err(|e| format!("EMAIL_FROM: {}", e))?;
+        let allowed: Vec<String> = var("EMAIL_ALLOWED_RECIPIENTS")
+            .unwrap_or_default()
+            .split(',')
+            .map(|a| a.trim().to_ascii_lowercase())
+            .filter(|a| !a.is_empty())
+            .collect();
+        if allowed.is_empty() {
+            return Err("EMAIL_ALLOWED_RECIPIENTS must list at least one address or @domain".to_string());
+        }
+        Ok(Some(SmtpConfig { host, port, security, username, password, from, allowed }))
+    }
+
+    pub fn allows(&self, address: &str) -> bool {
+        let address = address.to_ascii_lowercase();
+        self.allowed.iter().any(|a| if a.starts_with('@') { address.ends_with(a.as_str()) } else { *a == address })
+    }
+
+    /// The allowlist as given, for the prompt and the session header.
+    pub fn describe_allowed(&self) -> String {
+        self.allowed.join(", ")
+    }
+
+    /// Splits `to` into addresses and checks each against the allowlist.
+    pub fn recipients(&self, to: &str) -> Result<Vec<String>, String> {
+        let recipients: Vec<String> = to.split([',', ';', ' ']).map(str::trim).filter(|a| !a.is_empty()).map(str::to_string).collect();
+        if recipients.is_empty() {
+            return Err("no recipient given".to_string());
+        }
+        for address in &recipients {
+            check_address(address).map_err(|e| format!("{}: {}", address, e))?;
+            if !self.allows(address) {
+                return Err(format!("{} is not in EMAIL_ALLOWED_RECIPIENTS ({})", address, self.describe_allowed()));
+            }
+        }
+        Ok(recipients)
+    }
+
+    /// Sends a plain-text email and returns the server's acceptance line.
+    pub fn send(&self, to: &[String], subject: &str, body: &str) -> Result<String, String> {
+        if subject.contains(['\r', '\n']) {
+            return Err("the subject must be a single line".to_string());
+        }
+        let addr = (self.host.as_str(), self.port)
+            .to_socket_addrs()
+            .map_err(|e| format!("could not resolve {}: {}", self.host, e))?
+            .next()
+            .ok_or_else(|| format!("could not resolve {}", self.host))?;
+        let tcp = TcpStream::connect_timeout(&addr, Duration::from_secs(15)).map_err(|e| format!("could not connect to {}:{}: {}", self.host, self.port, e))?;
+        tcp.set_read_timeout(Some(Duration::from_secs(60))).map_err(|e| e.to_string())?;
+        let stream = match self.security {
+            Security::Tls => Stream::Tls(Box::new(tls_connect(&self.host, tcp)?)),
+            _ => Stream::Plain(tcp),
+        };
+        let mut smtp = Smtp { conn: BufReader::new(stream) };
+        smtp.expect("greeting", &[220])?;
+        smtp.command("EHLO agent-bench", &[250])?;
+        if self.security == Security::StartTls {
+            smtp.command("STARTTLS", &[220])?;
+            let Stream::Plain(tcp) = smtp.conn.into_inner() else {
+                unreachable!("STARTTLS is only sent on a plain connection")
+            };
+            smtp = Smtp { conn: BufReader::new(Stream::Tls(Box::new(tls_connect(&self.host, tcp)?))) };
+            smtp.command("EHLO agent-bench", &[250])?;
+        }
+        if let (Some(username), Some(password)) = (&self.username, &self.password) {
+            let token = STANDARD.encode(format!("\0{}\0{}", username, password));
+            smtp.command(&format!("AUTH PLAIN {}", token), &[235])?;
+        }
+        smtp.command(&format!("MAIL FROM:<{}>", self.from), &[250])?;
+        for address in to {
+            smtp.command(&format!("RCPT TO:<{}>", address), &[250, 251])?;
+        }
+        smtp.command("DATA", &[354])?;
+        let accepted = smtp.command(&format!("{}.", self.message(to, subject, body)), &[250])?;
+        // The message is accepted at this point; a failed QUIT changes nothing.
+        let _ = smtp.command("QUIT", &[221]);
+        Ok(accepted)
+    }
+
+    /// RFC 5322 message with CRLF line endings and dot-stuffed body lines.
+    fn message(&self, to: &[String], subject: &str, body: &str) -> String {
+        let domain = self.from.rsplit('@').next().unwrap_or("localhost");
+        let subject = if subject.is_ascii() { subject.to_string() } else { format!("=?UTF-8?B?{}?=", STANDARD.encode(subject)) };
+        let mut message = format!(
+            "From: <{}>\r\nTo: {}\r\nSubject: {}\r\nDate: {}\r\nMessage-ID: <{}@{}>\r\nMIME-Version: 1.0\r\nContent-Type: text/plain; charset=utf-8\r\nContent-Transfer-Encoding: 8bit\r\n\r\n",
+            self.from,
+            to.iter().map(|a| format!("<{}>", a)).collect::<Vec<_>>().join(", "),
+            subject,
+            chrono::Local::now().to_rfc2822(),
+            uuid::Uuid::new_v4(),
+            domain,
+        );
+        for line in body.lines() {
+            if line.starts_with('.') {
+                message.push('.');
+            }
+            message.push_str(line);
+            message.push_str("\r\n");
+        }
+        message
+    }
+}
+
+/// Rejects anything but a plain `local@domain` address, so nothing can be smuggled into an
+/// SMTP command or header.
+fn check_address(address: &str) -> Result<(), String> {
+    let valid = address.split_once('@').is_some_and(|(local, domain)| {
+        !local.is_empty() && domain.contains('.') && !domain.contains('@') && !address.contains(|c: char| c.is_whitespace() || c.is_control() || "<>,;\"".contains(c))
+    });
+    if valid { Ok(()) } else { Err("not a valid email address".to_string()) }
+}
+
+fn tls_connect(host: &str, tcp: TcpStream) -> Result<native_tls::TlsStream<TcpStream>, String> {
+    let connector = native_tls::TlsConnector::new().map_err(|e| e.to_string())?;
+    connector.connect(host, tcp).map_err(|e| format!("TLS handshake with {} failed: {}", host, e))
+}
+
+enum Stream {
+    Plain(TcpStream),
+    Tls(Box<native_tls::TlsStream<TcpStream>>),
+}
+
+impl Read for Stream {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        match self {
+            Stream::Plain(s) => s.read(buf),
+            Stream::Tls(s) => s.read(buf),
+        }
+    }
+}
+
+impl Write for Stream {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        match self {
+            Stream::Plain(s) => s.write(buf),
+            Stream::Tls(s) => s.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        match self {
+            Stream::Plain(s) => s.flush(),
+            Stream::Tls(s) => s.flush(),
+        }
+    }
+}
+
+struct Smtp {
+    conn: BufReader<Stream>,
+}
+
+impl Smtp {
+    /// Sends one command and checks the reply code. Errors name only the verb, so credentials
+    /// and message bodies stay out of logs.
+    fn command(&mut self, line: &str, accepted: &[u16]) -> Result<String, String> {
+        let verb = line.split([' ', ':', '\r']).next().unwrap_or(line);
+        let verb = if line.contains("\r\n.") { "DATA" } else { verb };
+        let stream = self.conn.get_mut();
+        stream.write_all(format!("{}\r\n", line).as_bytes()).and_then(|_| stream.flush()).map_err(|e| format!("SMTP {} failed: {}", verb, e))?;
+        self.expect(verb, accepted)
+    }
+
+    /// Reads a possibly multi-line reply (`250-...` continued, `250 ...` last).
+    fn expect(&mut self, step: &str, accepted: &[u16]) -> Result<String, String> {
+        let mut text = Vec::new();
+        loop {
+            let mut line = String::new();
+            let read = self.conn.read_line(&mut line).map_err(|e| format!("SMTP {} failed: {}", step, e))?;
+            if read == 0 {
+                return Err(format!("SMTP {} failed: connection closed", step));
+            }
+            let line = line.trim_end();
+            let code: u16 = line.get(..3).and_then(|c| c.parse().ok()).ok_or_else(|| format!("SMTP {} failed: unexpected reply '{}'", step, line))?;
+            text.push(line.get(4..).unwrap_or_default().to_string());
+            if line.as_bytes().get(3) != Some(&b'-') {
+                let text = text.join(" ");
+                return if accepted.contains(&code) { Ok(format!("{} {}", code, text)) } else { Err(format!("SMTP {} rejected: {} {}", step, code, text)) };
+            }
+        }
+    }
+}
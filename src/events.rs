@@ -0,0 +1,118 @@
+use colored::*;
+
+use crate::latency::TurnLatency;
+use crate::usage::{ReplyCost, TokenUsage};
+
+/// Something that happened during a chat session. The chat loop publishes these instead of
+/// printing, and the terminal renderer, transcript log and audit log react to them.
+#[derive(Debug, Clone)]
+pub enum Event {
+    /// The session header: the settings the session runs with, as `(label, value)` in the order shown.
+    SessionStarted { model: String, settings: Vec<(&'static str, String)> },
+    SessionEnded,
+    Notice(String),
+    /// What voice input was heard, before it is sent as the user's message.
+    VoiceTranscribed(String),
+    Warning(String),
+    ToolRequested { tool: String, input: String },
+    ToolCompleted { tool: String, input: String, output: String, success: bool, exit_code: Option<i32> },
+    ToolDenied { tool: String, user: String, role: String },
+    MessageSaved { role: String, content: String },
+    Reasoning(String),
+    AssistantReply(String),
+    /// Tokens the provider reported for the last reply, across every call of its turn, and
+    /// their cost when the model has a price.
+    TokenUsage(TokenUsage, Option<ReplyCost>),
+    /// Where the time of the turn that produced the last reply went.
+    Latency(TurnLatency),
+    /// Suggested next prompts, numbered from 1 in the order given.
+    FollowUps(Vec<String>),
+    /// A reply in fan-out mode, from the `provider/model` named.
+    ProviderReply { provider: String, text: String },
+    ApiError { context: String, error: String },
+}
+
+pub trait Subscriber {
+    fn handle(&mut self, session_id: &str, event: &Event);
+}
+
+/// Fans events for one session out to every subscriber, in subscription order.
+pub struct EventBus {
+    session_id: String,
+    subscribers: Vec<Box<dyn Subscriber>>,
+}
+
+impl EventBus {
+    pub fn new(session_id: &str) -> Self {
+        EventBus { session_id: session_id.to_string(), subscribers: Vec::new() }
+    }
+
+    pub fn subscribe(&mut self, subscriber: Box<dyn Subscriber>) {
+        self.subscribers.push(subscriber);
+    }
+
+    pub fn publish(&mut self, event: Event) {
+        for subscriber in &mut self.subscribers {
+            subscriber.handle(&self.session_id, &event);
+        }
+    }
+}
+
+/// Renders events to the terminal with the usual colours. Model reasoning is only shown when
+/// `show_reasoning` is set, and latency breakdowns when `show_latency` is.
+pub struct CliRenderer {
+    pub show_reasoning: bool,
+    pub show_latency: bool,
+}
+
+impl Subscriber for CliRenderer {
+    fn handle(&mut self, _session_id: &str, event: &Event) {
+        match event {
+            Event::SessionStarted { settings, .. } => {
+                println!("{}", "New chat session started. Type 'exit' to quit.".bold().yellow());
+                for (label, value) in settings {
+                    println!("{}: {}", label, value);
+                }
+                println!();
+            }
+            Event::SessionEnded => println!("{}", "Session ended.".bold().yellow()),
+            Event::Notice(text) => println!("{} {}", "System:".bold().magenta(), text),
+            Event::Warning(text) => println!("{} {}", "System:".bold().magenta(), text.red()),
+            Event::VoiceTranscribed(text) => println!("{} {}", "You (voice):".bold().blue(), text.blue()),
+            Event::ToolRequested { tool, input } => match tool.as_str() {
+                "run_command" => println!("{} Running command: {}", "System:".bold().magenta(), input.magenta()),
+                "web_search" => println!("{} Searching the web for: {}", "System:".bold().magenta(), input.magenta()),
+                "browse" => println!("{} Browsing: {}", "System:".bold().magenta(), input.magenta()),
+                "calculate" => println!("{} Calculating: {}", "System:".bold().magenta(), input.magenta()),
+                "schedule" => println!("{} Scheduling: {}", "System:".bold().magenta(), input.magenta()),
+                "send_email" => println!("{} Drafting email: {}", "System:".bold().magenta(), input.magenta()),
+                _ => println!("{} Calling {}: {}", "System:".bold().magenta(), tool, input.magenta()),
+            },
+            Event::ToolCompleted { tool, output, .. } => {
+                if tool == "run_command" {
+                    println!("{}\n{}", "Assistant:".bold().green(), output.green());
+                }
+            }
+            Event::ToolDenied { tool, role, .. } => println!("{} {}", "System:".bold().magenta(), format!("Tool {} is not permitted for role {}.", tool, role).red()),
+            Event::MessageSaved { .. } => {}
+            Event::Reasoning(text) if self.show_reasoning => println!("{}\n{}\n", "Reasoning:".bold().blue(), text.dimmed()),
+            Event::Reasoning(_) => {}
+            Event::AssistantReply(reply) => println!("{} {}\n", "Assistant:".bold().green(), reply.green()),
+            Event::TokenUsage(usage, cost) => {
+                let cost = cost.as_ref().map(|cost| format!(", cost {}", cost.describe())).unwrap_or_default();
+                println!("{}\n", format!("Tokens: {}{}", usage.describe(), cost).dimmed())
+            }
+            Event::Latency(latency) if self.show_latency => println!("{}\n", format!("Latency {}", latency.describe()).dimmed()),
+            Event::Latency(_) => {}
+            Event::FollowUps(prompts) => {
+                println!("{}", "Follow-ups (type a number to ask):".dimmed());
+                for (i, prompt) in prompts.iter().enumerate() {
+                    println!("  {}", format!("{}. {}", i + 1, prompt).dimmed());
+                }
+                println!();
+            }
+            Event::ProviderReply { provider, text } => println!("{} {}\n", format!("Assistant ({}):", provider).bold().green(), text.green()),
+            Event::ApiError { context, error } => println!("Assistant: {} ({})", context.red(), error.red()),
+        }
+    }
+}
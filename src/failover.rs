@@ -0,0 +1,80 @@
+use std::env;
+use std::error::Error;
+use std::fmt;
+
+use reqwest::StatusCode;
+use rusqlite::{params, Connection};
+
+/// A provider answered with an error status. Kept apart from other errors so that rate limits
+/// and server errors can be told from requests that would fail anywhere.
+#[derive(Debug)]
+pub struct ApiError {
+    pub status: StatusCode,
+    pub message: String,
+}
+
+impl fmt::Display for ApiError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "API Error: {} ({})", self.message, self.status)
+    }
+}
+
+impl Error for ApiError {}
+
+/// True for failures another provider may not share: 429, 5xx, timeouts and refused
+/// connections.
+pub fn should_fail_over(error: &(dyn Error + 'static)) -> bool {
+    if let Some(e) = error.downcast_ref::<ApiError>() {
+        return e.status == StatusCode::TOO_MANY_REQUESTS || e.status.is_server_error();
+    }
+    error.downcast_ref::<reqwest::Error>().is_some_and(|e| e.is_timeout() || e.is_connect())
+}
+
+/// How a reply came from a backup: the `provider/model` that answered and why the ones before
+/// it did not.
+#[derive(Debug)]
+pub struct Failover {
+    pub answered_by: String,
+    pub reason: String,
+}
+
+/// A provider named in a list such as `PROVIDER_FALLBACKS`.
+#[derive(Debug)]
+pub struct ProviderSpec {
+    /// Provider name as in its settings (`GROQ` for `GROQ_API_KEY`).
+    pub provider: String,
+    pub model: Option<String>,
+}
+
+/// Backup providers tried in order when the selected one fails, from `PROVIDER_FALLBACKS`.
+pub fn chain_from_env() -> Result<Vec<ProviderSpec>, String> {
+    parse_providers("PROVIDER_FALLBACKS", &env::var("PROVIDER_FALLBACKS").unwrap_or_default())
+}
+
+/// Parses comma-separated provider names, each optionally followed by `:model`
+/// (`groq:llama-3.1-8b-instant,openrouter,ollama:llama3.2:3b`). `setting` names the list in
+/// errors.
+pub fn parse_providers(setting: &str, spec: &str) -> Result<Vec<ProviderSpec>, String> {
+    spec.split(',')
+        .map(str::trim)
+        .filter(|entry| !entry.is_empty())
+        .map(|entry| {
+            let (provider, model) = match entry.split_once(':') {
+                Some((provider, model)) => (provider.trim(), Some(model.trim().to_string()).filter(|m| !m.is_empty())),
+                None => (entry, None),
+            };
+            if provider.is_empty() {
+                return Err(format!("{} entry '{}' has no provider", setting, entry));
+            }
+            Ok(ProviderSpec { provider: provider.to_uppercase(), model })
+        })
+        .collect()
+}
+
+/// Records that a stored reply came from a backup provider (`provider/model`).
+pub fn record(conn: &Connection, message_id: i64, answered_by: &str) {
+    conn.execute(
+        "UPDATE messages SET answered_by = ?2 WHERE id = ?1",
+        params![message_id, answered_by],
+    ).unwrap();
+}
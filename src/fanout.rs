@@ -0,0 +1,35 @@
+use std::env;
+use std::future::Future;
+use std::task::Poll;
+
+use crate::failover::{self, ProviderSpec};
+
+/// Providers that answer every prompt alongside the selected one, from `--fanout <list>` or
+/// `FANOUT_PROVIDERS`, in the `PROVIDER_FALLBACKS` format.
+pub fn providers_from_args(args: &[String]) -> Result<Vec<ProviderSpec>, String> {
+    match args.iter().position(|a| a == "--fanout") {
+        Some(i) => {
+            let spec = args.get(i + 1).ok_or("--fanout needs a provider list such as groq,ollama:llama3.2")?;
+            failover::parse_providers("--fanout", spec)
+        }
+        None => failover::parse_providers("FANOUT_PROVIDERS", &env::var("FANOUT_PROVIDERS").unwrap_or_default()),
+    }
+}
+
+/// Runs `futures` concurrently on the current task and returns their outputs in order.
+pub async fn join_all<F: Future>(futures: Vec<F>) -> Vec<F::Output> {
+    let mut futures: Vec<_> = futures.into_iter().map(Box::pin).collect();
+    let mut outputs: Vec<Option<F::Output>> = futures.iter().map(|_| None).collect();
+    std::future::poll_fn(|cx| {
+        for (future, output) in futures.iter_mut().zip(outputs.iter_mut()) {
+            if output.is_none()
+                && let Poll::Ready(value) = future.as_mut().poll(cx)
+            {
+                *output = Some(value);
+            }
+        }
+        if outputs.iter().all(Option::is_some) { Poll::Ready(()) } else { Poll::Pending }
+    })
+    .await;
+    outputs.into_iter().flatten().collect()
+}
@@ -0,0 +1,146 @@
+use std::env;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use base64::Engine;
+use base64::engine::general_purpose::{STANDARD, URL_SAFE_NO_PAD};
+use ring::rand::SystemRandom;
+use ring::signature::{RSA_PKCS1_SHA256, RsaKeyPair};
+use serde_json::json;
+
+use crate::auth;
+
+const SCOPE: &str = "https://www.googleapis.com/auth/cloud-platform";
+const USER_TOKEN_URL: &str = "https://oauth2.googleapis.com/token";
+
+/// Google credentials for Vertex AI: a service account key in `VERTEX_SERVICE_ACCOUNT_KEY`,
+/// otherwise application-default credentials (ADC) in the order the Google client libraries
+/// look: `GOOGLE_APPLICATION_CREDENTIALS`, the file written by
+/// `gcloud auth application-default login`, then the metadata server of the VM or container.
+#[derive(Debug)]
+pub enum Credentials {
+    ServiceAccount { client_email: String, private_key: String, token_uri: String },
+    AuthorizedUser { client_id: String, client_secret: String, refresh_token: String },
+    MetadataServer,
+}
+
+fn gcloud_credentials_file() -> Option<PathBuf> {
+    let config_dir = match env::var("CLOUDSDK_CONFIG") {
+        Ok(dir) => PathBuf::from(dir),
+        Err(_) if cfg!(windows) => PathBuf::from(env::var("APPDATA").ok()?).join("gcloud"),
+        Err(_) => PathBuf::from(env::var("HOME").ok()?).join(".config").join("gcloud"),
+    };
+    Some(config_dir.join("application_default_credentials.json")).filter(|path| path.exists())
+}
+
+impl Credentials {
+    pub fn discover() -> Result<Self, String> {
+        if let Ok(path) = env::var("VERTEX_SERVICE_ACCOUNT_KEY") {
+            return match Credentials::from_file(Path::new(&path))? {
+                credentials @ Credentials::ServiceAccount { .. } => Ok(credentials),
+                _ => Err(format!("VERTEX_SERVICE_ACCOUNT_KEY {} is not a service account key", path)),
+            };
+        }
+        match env::var("GOOGLE_APPLICATION_CREDENTIALS") {
+            Ok(path) => Credentials::from_file(Path::new(&path)),
+            Err(_) => match gcloud_credentials_file() {
+                Some(path) => Credentials::from_file(&path),
+                None => Ok(Credentials::MetadataServer),
+            },
+        }
+    }
+
+    fn from_file(path: &Path) -> Result<Self, String> {
+        let text = std::fs::read_to_string(path).map_err(|e| format!("could not read {}: {}", path.display(), e))?;
+        let file: serde_json::Value = serde_json::from_str(&text).map_err(|e| format!("invalid credentials in {}: {}", path.display(), e))?;
+        let field = |name: &str| {
+            file[name].as_str().map(str::to_string).ok_or_else(|| format!("{} has no {}", path.display(), name))
+        };
+        match file["type"].as_str() {
+            Some("service_account") => Ok(Credentials::ServiceAccount {
+                client_email: field("client_email")?,
+                private_key: field("private_key")?,
+                token_uri: field("token_uri").unwrap_or_else(|_| USER_TOKEN_URL.to_string()),
+            }),
+            Some("authorized_user") => Ok(Credentials::AuthorizedUser {
+                client_id: field("client_id")?,
+                client_secret: field("client_secret")?,
+                refresh_token: field("refresh_token")?,
+            }),
+            other => Err(format!("unsupported credential type {:?} in {}", other.unwrap_or("none"), path.display())),
+        }
+    }
+
+    pub fn describe(&self) -> String {
+        match self {
+            Credentials::ServiceAccount { client_email, .. } => format!("service account {}", client_email),
+            Credentials::AuthorizedUser { .. } => "gcloud user credentials".to_string(),
+            Credentials::MetadataServer => "metadata server".to_string(),
+        }
+    }
+}
+
+/// Signed JWT asserting the service account, exchanged for an access token.
+fn service_account_assertion(client_email: &str, private_key: &str, token_uri: &str) -> Result<String, String> {
+    let pem: String = private_key.lines().filter(|line| !line.starts_with("-----")).collect();
+    let der = STANDARD.decode(pem.trim()).map_err(|e| format!("invalid private key: {}", e))?;
+    let key = RsaKeyPair::from_pkcs8(&der).map_err(|e| format!("invalid private key: {}", e))?;
+
+    let issued_at = SystemTime::now().duration_since(UNIX_EPOCH).map_err(|e| e.to_string())?.as_secs();
+    let header = json!({"alg": "RS256", "typ": "JWT"});
+    let claims = json!({
+        "iss": client_email,
+        "scope": SCOPE,
+        "aud": token_uri,
+        "iat": issued_at,
+        "exp": issued_at + 3600,
+    });
+    let signing_input =
+        format!("{}.{}", URL_SAFE_NO_PAD.encode(header.to_string()), URL_SAFE_NO_PAD.encode(claims.to_string()));
+    let mut signature = vec![0; key.public().modulus_len()];
+    key.sign(&RSA_PKCS1_SHA256, &SystemRandom::new(), signing_input.as_bytes(), &mut signature)
+        .map_err(|_| "could not sign the token request".to_string())?;
+    Ok(format!("{}.{}", signing_input, URL_SAFE_NO_PAD.encode(signature)))
+}
+
+async fn fetch_token(client: &reqwest::Client, credentials: &Credentials) -> Result<(String, Duration), String> {
+    let request = match credentials {
+        Credentials::ServiceAccount { client_email, private_key, token_uri } => {
+            let assertion = service_account_assertion(client_email, private_key, token_uri)?;
+            client.post(token_uri).form(&[("grant_type", "urn:ietf:params:oauth:grant-type:jwt-bearer"), ("assertion", &assertion)])
+        }
+        Credentials::AuthorizedUser { client_id, client_secret, refresh_token } => client.post(USER_TOKEN_URL).form(&[
+            ("grant_type", "refresh_token"),
+            ("client_id", client_id),
+            ("client_secret", client_secret),
+            ("refresh_token", refresh_token),
+        ]),
+        Credentials::MetadataServer => {
+            let host = env::var("GCE_METADATA_HOST").unwrap_or_else(|_| "metadata.google.internal".to_string());
+            client
+                .get(format!("http://{}/computeMetadata/v1/instance/service-accounts/default/token", host))
+                .header("Metadata-Flavor", "Google")
+        }
+    };
+    let res = request.send().await.map_err(|e| format!("{} unreachable: {}", credentials.describe(), e))?;
+    let status = res.status();
+    let body: serde_json::Value = res.json().await.unwrap_or_default();
+    if !status.is_success() {
+        let reason = body["error_description"].as_str().or(body["error"].as_str()).unwrap_or("no details");
+        return Err(format!("token request with {} returned {} ({})", credentials.describe(), status, reason));
+    }
+    let token = body["access_token"].as_str().ok_or("token response has no access_token")?.to_string();
+    Ok((token, Duration::from_secs(body["expires_in"].as_u64().unwrap_or(3600))))
+}
+
+/// An access token for Google Cloud from the discovered credentials, cached under
+/// `provider` and refreshed shortly before it expires.
+pub async fn access_token(client: &reqwest::Client, provider: &str) -> Result<String, String> {
+    if let Some(token) = auth::cached_token(provider) {
+        return Ok(token);
+    }
+    let credentials = Credentials::discover()?;
+    let (token, lifetime) = fetch_token(client, &credentials).await?;
+    auth::store_token(provider, &token, lifetime);
+    Ok(token)
+}
@@ -0,0 +1,84 @@
+use std::env;
+use std::path::{Path, PathBuf};
+
+use ignore::WalkBuilder;
+
+/// Project-specific ignore file, read in addition to `.gitignore`.
+pub const IGNORE_FILE_NAME: &str = ".agentbenchignore";
+
+/// Byte limits applied when files are pulled into the prompt.
+#[derive(Debug, Clone, Copy)]
+pub struct IngestLimits {
+    pub max_file_bytes: u64,
+    pub max_total_bytes: u64,
+}
+
+impl IngestLimits {
+    pub fn from_env() -> Self {
+        let read = |name: &str, default: u64| {
+            env::var(name)
+                .ok()
+                .and_then(|v| v.trim().parse().ok())
+                .unwrap_or(default)
+        };
+        IngestLimits {
+            max_file_bytes: read("INGEST_MAX_FILE_BYTES", 20_000),
+            max_total_bytes: read("INGEST_MAX_TOTAL_BYTES", 60_000),
+        }
+    }
+}
+
+/// What a directory ingestion pulled in, and what it left out and why.
+#[derive(Debug, Default)]
+pub struct IngestReport {
+    pub included: Vec<(String, u64)>,
+    pub excluded: Vec<(String, String)>,
+    pub contents: String,
+}
+
+impl IngestReport {
+    pub fn total_bytes(&self) -> u64 {
+        self.included.iter().map(|(_, size)| size).sum()
+    }
+}
+
+/// Lists the files below `dir`, honouring `.gitignore`, `.agentbenchignore` and hidden-file rules.
+/// Paths are returned sorted and relative to `dir`.
+pub fn walk(dir: &Path) -> Vec<PathBuf> {
+    let mut files: Vec<PathBuf> = WalkBuilder::new(dir)
+        .add_custom_ignore_filename(IGNORE_FILE_NAME)
+        .require_git(false)
+        .build()
+        .filter_map(|entry| entry.ok())
+        .filter(|entry| entry.file_type().is_some_and(|t| t.is_file()))
+        .filter_map(|entry| entry.path().strip_prefix(dir).ok().map(Path::to_path_buf))
+        .collect();
+    files.sort();
+    files
+}
+
+/// Reads the files below `dir` into a single prompt-ready string within `limits`.
+pub fn ingest_directory(dir: &Path, limits: IngestLimits) -> IngestReport {
+    let mut report = IngestReport::default();
+    for relative in walk(dir) {
+        let name = relative.to_string_lossy().to_string();
+        let size = std::fs::metadata(dir.join(&relative)).map(|m| m.len()).unwrap_or(0);
+
+        if size > limits.max_file_bytes {
+            report.excluded.push((name, format!("{} bytes exceeds the per-file limit", size)));
+            continue;
+        }
+        if report.total_bytes() + size > limits.max_total_bytes {
+            report.excluded.push((name, "total size limit reached".to_string()));
+            continue;
+        }
+        let Ok(text) = std::fs::read_to_string(dir.join(&relative)) else {
+            report.excluded.push((name, "binary or not UTF-8".to_string()));
+            continue;
+        };
+
+        report.contents.push_str(&format!("File: {}\n```\n{}\n```\n\n", name, text.trim_end()));
+        report.included.push((name, size));
+    }
+    report
+}
@@ -0,0 +1,308 @@
+use std::fmt;
+
+use regex::Regex;
+use serde_json::Value;
+
+/// Keywords the validator checks.
+const VALIDATED: &[&str] = &[
+    "type",
+    "enum",
+    "const",
+    "minLength",
+    "maxLength",
+    "pattern",
+    "minimum",
+    "maximum",
+    "exclusiveMinimum",
+    "exclusiveMaximum",
+    "minItems",
+    "maxItems",
+    "items",
+    "required",
+    "properties",
+    "additionalProperties",
+    "allOf",
+    "anyOf",
+    "oneOf",
+    "not",
+];
+
+/// Keywords that only describe a schema and never affect validation.
+const ANNOTATIONS: &[&str] = &["$schema", "$id", "$comment", "title", "description", "default", "examples", "deprecated", "readOnly", "writeOnly"];
+
+/// A value's mismatch with a schema, at a JSON path such as `$.items[2].name`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SchemaError {
+    pub path: String,
+    pub message: String,
+}
+
+impl fmt::Display for SchemaError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.path, self.message)
+    }
+}
+
+/// Checks that `schema` uses only keywords this validator understands, with well-formed values,
+/// so nothing in it (`$ref`, `format`, `patternProperties`...) is silently skipped.
+pub fn check_supported(schema: &Value) -> Result<(), SchemaError> {
+    check_schema(schema, "$")
+}
+
+fn check_schema(schema: &Value, path: &str) -> Result<(), SchemaError> {
+    let error = |message: String| Err(SchemaError { path: path.to_string(), message });
+    let object = match schema {
+        Value::Bool(_) => return Ok(()),
+        Value::Object(object) => object,
+        _ => return error("a schema must be an object or a boolean".to_string()),
+    };
+    for (keyword, value) in object {
+        if ANNOTATIONS.contains(&keyword.as_str()) {
+            continue;
+        }
+        if !VALIDATED.contains(&keyword.as_str()) {
+            return error(format!("unsupported keyword '{}'", keyword));
+        }
+        let nested = format!("{}.{}", path, keyword);
+        match (keyword.as_str(), value) {
+            ("pattern", Value::String(pattern)) => {
+                if let Err(e) = Regex::new(pattern) {
+                    return error(format!("invalid pattern ({})", e));
+                }
+            }
+            ("properties", Value::Object(properties)) => {
+                for (name, property) in properties {
+                    check_schema(property, &format!("{}.{}", nested, name))?;
+                }
+            }
+            ("items" | "additionalProperties" | "not", subschema) => check_schema(subschema, &nested)?,
+            ("allOf" | "anyOf" | "oneOf", Value::Array(branches)) if !branches.is_empty() => {
+                for (i, branch) in branches.iter().enumerate() {
+                    check_schema(branch, &format!("{}[{}]", nested, i))?;
+                }
+            }
+            ("type", Value::String(_) | Value::Array(_)) | ("enum" | "required", Value::Array(_)) | ("const", _) => {}
+            ("minLength" | "maxLength" | "minItems" | "maxItems", value) if value.is_u64() => {}
+            ("minimum" | "maximum" | "exclusiveMinimum" | "exclusiveMaximum", Value::Number(_)) => {}
+            (keyword, value) => return error(format!("invalid value {} for '{}'", value, keyword)),
+        }
+    }
+    Ok(())
+}
+
+fn type_name(value: &Value) -> &'static str {
+    match value {
+        Value::Null => "null",
+        Value::Bool(_) => "boolean",
+        Value::Number(n) if n.is_i64() || n.is_u64() => "integer",
+        Value::Number(_) => "number",
+        Value::String(_) => "string",
+        Value::Array(_) => "array",
+        Value::Object(_) => "object",
+    }
+}
+
+fn has_type(value: &Value, expected: &str) -> bool {
+    let actual = type_name(value);
+    actual == expected || (expected == "number" && actual == "integer")
+        || (expected == "integer" && value.as_f64().is_some_and(|n| n.fract() == 0.0))
+}
+
+/// Every mismatch between `value` and `schema`, which [`check_supported`] accepted.
+pub fn validate(value: &Value, schema: &Value) -> Vec<SchemaError> {
+    let mut errors = Vec::new();
+    validate_at(value, schema, "$", &mut errors);
+    errors
+}
+
+fn validate_at(value: &Value, schema: &Value, path: &str, errors: &mut Vec<SchemaError>) {
+    let mut fail = |message: String| errors.push(SchemaError { path: path.to_string(), message });
+    let Some(schema) = schema.as_object() else {
+        if schema == &Value::Bool(false) {
+            fail("not allowed".to_string());
+        }
+        return;
+    };
+
+    if let Some(expected) = schema.get("type") {
+        let types: Vec<&str> = match expected {
+            Value::String(t) => vec![t.as_str()],
+            Value::Array(ts) => ts.iter().filter_map(Value::as_str).collect(),
+            _ => Vec::new(),
+        };
+        if !types.is_empty() && !types.iter().any(|t| has_type(value, t)) {
+            fail(format!("expected {}, got {}", types.join(" or "), type_name(value)));
+            return;
+        }
+    }
+    if let Some(options) = schema.get("enum").and_then(Value::as_array)
+        && !options.contains(value)
+    {
+        fail(format!("must be one of {}", Value::Array(options.clone())));
+    }
+    if let Some(constant) = schema.get("const")
+        && constant != value
+    {
+        fail(format!("must be {}", constant));
+    }
+
+    match value {
+        Value::String(text) => {
+            let length = text.chars().count() as u64;
+            match schema.get("minLength").and_then(Value::as_u64) {
+                Some(1) if length == 0 => fail("must not be empty".to_string()),
+                Some(min) if length < min => fail(format!("shorter than {} characters", min)),
+                _ => {}
+            }
+            if let Some(max) = schema.get("maxLength").and_then(Value::as_u64)
+                && length > max
+            {
+                fail(format!("longer than {} characters", max));
+            }
+            if let Some(pattern) = schema.get("pattern").and_then(Value::as_str)
+                && Regex::new(pattern).is_ok_and(|re| !re.is_match(text))
+            {
+                fail(format!("does not match pattern {}", pattern));
+            }
+        }
+        Value::Number(number) => {
+            let n = number.as_f64().unwrap_or_default();
+            let bound = |keyword: &str| schema.get(keyword).and_then(Value::as_f64);
+            if let Some(min) = bound("minimum")
+                && n < min
+            {
+                fail(format!("less than {}", min));
+            }
+            if let Some(max) = bound("maximum")
+                && n > max
+            {
+                fail(format!("greater than {}", max));
+            }
+            if let Some(min) = bound("exclusiveMinimum")
+                && n <= min
+            {
+                fail(format!("not greater than {}", min));
+            }
+            if let Some(max) = bound("exclusiveMaximum")
+                && n >= max
+            {
+                fail(format!("not less than {}", max));
+            }
+        }
+        Value::Array(items) => {
+            if let Some(min) = schema.get("minItems").and_then(Value::as_u64)
+                && (items.len() as u64) < min
+            {
+                fail(format!("fewer than {} items", min));
+            }
+            if let Some(max) = schema.get("maxItems").and_then(Value::as_u64)
+                && (items.len() as u64) > max
+            {
+                fail(format!("more than {} items", max));
+            }
+            if let Some(item_schema) = schema.get("items") {
+                for (i, item) in items.iter().enumerate() {
+                    validate_at(item, item_schema, &format!("{}[{}]", path, i), errors);
+                }
+            }
+        }
+        Value::Object(fields) => {
+            for required in schema.get("required").and_then(Value::as_array).into_iter().flatten().filter_map(Value::as_str) {
+                if !fields.contains_key(required) {
+                    errors.push(SchemaError { path: format!("{}.{}", path, required), message: "missing required property".to_string() });
+                }
+            }
+            let properties = schema.get("properties").and_then(Value::as_object);
+            for (name, field) in fields {
+                let field_path = format!("{}.{}", path, name);
+                match (properties.and_then(|p| p.get(name)), schema.get("additionalProperties")) {
+                    (Some(field_schema), _) => validate_at(field, field_schema, &field_path, errors),
+                    (None, Some(Value::Bool(false))) => errors.push(SchemaError { path: field_path, message: "property not allowed".to_string() }),
+                    (None, Some(extra)) => validate_at(field, extra, &field_path, errors),
+                    (None, None) => {}
+                }
+            }
+        }
+        _ => {}
+    }
+
+    if let Some(all) = schema.get("allOf").and_then(Value::as_array) {
+        for branch in all {
+            validate_at(value, branch, path, errors);
+        }
+    }
+    let matches = |branch: &Value| validate(value, branch).is_empty();
+    for (keyword, exactly_one) in [("anyOf", false), ("oneOf", true)] {
+        if let Some(branches) = schema.get(keyword).and_then(Value::as_array) {
+            let matching = branches.iter().filter(|branch| matches(branch)).count();
+            if matching == 0 || (exactly_one && matching > 1) {
+                errors.push(SchemaError {
+                    path: path.to_string(),
+                    message: format!("must match {} of the {} schemas", if exactly_one { "exactly one" } else { "at least one" }, keyword),
+                });
+            }
+        }
+    }
+    if let Some(excluded) = schema.get("not")
+        && matches(excluded)
+    {
+        errors.push(SchemaError { path: path.to_string(), message: "must not match the 'not' schema".to_string() });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::{check_supported, validate};
+
+    fn errors(value: serde_json::Value, schema: serde_json::Value) -> Vec<String> {
+        check_supported(&schema).unwrap();
+        validate(&value, &schema).iter().map(ToString::to_string).collect()
+    }
+
+    #[test]
+    fn reports_each_mismatch_at_its_path() {
+        let schema = json!({
+            "type": "object",
+            "properties": {
+                "name": {"type": "string", "minLength": 1},
+                "age": {"type": "integer", "minimum": 0, "exclusiveMaximum": 150},
+                "tags": {"type": "array", "items": {"enum": ["a", "b"]}, "maxItems": 2},
+            },
+            "required": ["name", "age"],
+            "additionalProperties": false,
+        });
+        assert!(errors(json!({"name": "Ada", "age": 36, "tags": ["a"]}), schema.clone()).is_empty());
+        assert_eq!(
+            errors(json!({"name": "", "age": 150, "tags": ["a", "c", "b"], "extra": 1}), schema.clone()),
+            [
+                "$.age: not less than 150",
+                "$.extra: property not allowed",
+                "$.name: must not be empty",
+                "$.tags: more than 2 items",
+                "$.tags[1]: must be one of [\"a\",\"b\"]",
+            ]
+        );
+        assert_eq!(errors(json!({"age": 1.5}), schema), ["$.name: missing required property", "$.age: expected integer, got number"]);
+    }
+
+    #[test]
+    fn combinators() {
+        let schema = json!({"oneOf": [{"type": "integer"}, {"type": "number", "minimum": 10}], "not": {"const": 3}});
+        assert!(errors(json!(2), schema.clone()).is_empty());
+        assert_eq!(errors(json!(12), schema.clone()), ["$: must match exactly one of the oneOf schemas"]);
+        assert_eq!(errors(json!(3), schema), ["$: must not match the 'not' schema"]);
+        assert!(errors(json!("x"), json!({"anyOf": [{"type": "string"}, {"type": "null"}]})).is_empty());
+    }
+
+    #[test]
+    fn rejects_what_it_cannot_check() {
+        let rejected = |schema: serde_json::Value| check_supported(&schema).unwrap_err().to_string();
+        assert_eq!(rejected(json!({"$ref": "#/$defs/a", "$defs": {"a": {}}})), "$: unsupported keyword '$defs'");
+        assert_eq!(rejected(json!({"properties": {"email": {"type": "string", "format": "email"}}})), "$.properties.email: unsupported keyword 'format'");
+        assert_eq!(rejected(json!({"items": {"pattern": "("}})).split(" (").next().unwrap(), "$.items: invalid pattern");
+        assert_eq!(rejected(json!({"minLength": -1})), "$: invalid value -1 for 'minLength'");
+        assert!(check_supported(&json!({"title": "T", "description": "d", "type": ["string", "null"]})).is_ok());
+    }
+}
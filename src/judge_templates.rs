@@ -0,0 +1,277 @@
+use std::env;
+use std::path::Path;
+
+use sha2::{Digest, Sha256};
+
+/// What a judge template compares, which decides the placeholders it is given and the verdict
+/// it asks for.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Kind {
+    /// Two responses to the same task; the verdict is `Winner: A`, `Winner: B` or `Winner: tie`.
+    Pairwise,
+    /// One response scored against `{rubric}`; the verdict is `Score: <1-10>`.
+    Rubric,
+    /// One response scored against a `{reference}` answer; the verdict is `Score: <1-10>`.
+    Reference,
+}
+
+impl Kind {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Kind::Pairwise => "pairwise",
+            Kind::Rubric => "rubric",
+            Kind::Reference => "reference",
+        }
+    }
+
+    /// Kind of a template file, from the placeholders it uses.
+    fn infer(text: &str) -> Self {
+        if text.contains("{response_b}") {
+            Kind::Pairwise
+        } else if text.contains("{reference}") {
+            Kind::Reference
+        } else {
+            Kind::Rubric
+        }
+    }
+}
+
+/// A judge prompt with the version it is recorded under: the first 12 hex digits of the SHA-256
+/// of its text, so a grade can always be traced to the exact wording that produced it.
+#[derive(Debug, Clone)]
+pub struct Template {
+    pub name: String,
+    pub kind: Kind,
+    /// `default`, a provider variant such as `anthropic`, or `file` for `JUDGE_TEMPLATE_DIR`.
+    pub variant: String,
+    pub text: String,
+    pub version: String,
+}
+
+const PAIRWISE: &str = "You are an impartial judge comparing two responses to the same task.
+
+Task:
+{task}
+
+Response A:
+{response_a}
+
+Response B:
+{response_b}
+
+Judge which response completes the task better: correctness first, then completeness, then \
+clarity. Ignore length and the order the responses are shown in. Explain your reasoning in a few \
+sentences, then end with exactly one line: `Winner: A`, `Winner: B` or `Winner: tie`.";
+
+const PAIRWISE_ANTHROPIC: &str = "You are an impartial judge comparing two responses to the same task.
+
+<task>
+{task}
+</task>
+
+<response_a>
+{response_a}
+</response_a>
+
+<response_b>
+{response_b}
+</response_b>
+
+Judge which response completes the task better: correctness first, then completeness, then \
+clarity. Ignore length and the order the responses are shown in. Give your reasoning inside \
+<reasoning> tags, then end with exactly one line: `Winner: A`, `Winner: B` or `Winner: tie`.";
+
+const RUBRIC: &str = "You are a strict grader. Score the response to the task against the rubric.
+
+Task:
+{task}
+
+Rubric:
+{rubric}
+
+Response:
+{response}
+
+Check each rubric point in turn and note whether the response meets it. Then end with exactly \
+one line `Score: N`, where N is a whole number from 1 (fails the rubric) to 10 (meets every point).";
+
+const RUBRIC_ANTHROPIC: &str = "You are a strict grader. Score the response to the task against the rubric.
+
+<task>
+{task}
+</task>
+
+<rubric>
+{rubric}
+</rubric>
+
+<response>
+{response}
+</response>
+
+Check each rubric point in turn inside <reasoning> tags, noting whether the response meets it. \
+Then end with exactly one line `Score: N`, where N is a whole number from 1 (fails the rubric) to \
+10 (meets every point).";
+
+const REFERENCE: &str = "You are a strict grader. Compare the response with the reference answer.
+
+Task:
+{task}
+
+Reference answer:
+{reference}
+
+Response:
+{response}
+
+The response does not need the reference's wording, only its substance. Note any facts that are \
+missing, wrong or contradicted. Then end with exactly one line `Score: N`, where N is a whole \
+number from 1 (wrong) to 10 (equivalent to the reference).";
+
+const REFERENCE_ANTHROPIC: &str = "You are a strict grader. Compare the response with the reference answer.
+
+<task>
+{task}
+</task>
+
+<reference>
+{reference}
+</reference>
+
+<response>
+{response}
+</response>
+
+The response does not need the reference's wording, only its substance. Inside <reasoning> tags, \
+note any facts that are missing, wrong or contradicted. Then end with exactly one line \
+`Score: N`, where N is a whole number from 1 (wrong) to 10 (equivalent to the reference).";
+
+impl Template {
+    /// The prompt with each `{name}` placeholder replaced by its value, in one pass over the
+    /// template so that braces inside a value are never taken for placeholders.
+    pub fn render(&self, values: &[(&str, &str)]) -> String {
+        let mut rendered = String::with_capacity(self.text.len());
+        let mut rest = self.text.as_str();
+        while let Some(start) = rest.find('{') {
+            rendered.push_str(&rest[..start]);
+            rest = &rest[start..];
+            let value = rest.find('}').and_then(|end| {
+                let name = &rest[1..end];
+                values.iter().find(|(n, _)| *n == name).map(|(_, value)| (*value, end + 1))
+            });
+            match value {
+                Some((value, len)) => {
+                    rendered.push_str(value);
+                    rest = &rest[len..];
+                }
+                None => {
+                    rendered.push('{');
+                    rest = &rest[1..];
+                }
+            }
+        }
+        rendered.push_str(rest);
+        rendered
+    }
+}
+
+/// The score from a `Score: N` line, the last one if the judge wrote several, when N is 1 to 10.
+pub fn parse_score(reply: &str) -> Option<u8> {
+    reply
+        .lines()
+        .rev()
+        .find_map(|line| {
+            let line = line.trim().trim_matches(|c| c == '`' || c == '*');
+            let rest = line.get(..6).filter(|p| p.eq_ignore_ascii_case("score:")).map(|_| &line[6..])?;
+            let digits: String = rest.trim().chars().take_while(char::is_ascii_digit).collect();
+            digits.parse().ok()
+        })
+        .filter(|score| (1..=10).contains(score))
+}
+
+/// Built-in templates: name, kind, variant, text.
+const BUILTIN: &[(&str, Kind, &str, &str)] = &[
+    ("pairwise", Kind::Pairwise, "default", PAIRWISE),
+    ("pairwise", Kind::Pairwise, "anthropic", PAIRWISE_ANTHROPIC),
+    ("rubric", Kind::Rubric, "default", RUBRIC),
+    ("rubric", Kind::Rubric, "anthropic", RUBRIC_ANTHROPIC),
+    ("reference", Kind::Reference, "default", REFERENCE),
+    ("reference", Kind::Reference, "anthropic", REFERENCE_ANTHROPIC),
+];
+
+/// Provider variant suited to a judge model: Claude models follow XML-tagged sections more
+/// reliably than headings.
+fn variant_for(model_name: &str) -> &'static str {
+    if model_name.starts_with("claude-") || model_name.contains("anthropic.") { "anthropic" } else { "default" }
+}
+
+fn version(text: &str) -> String {
+    Sha256::digest(text.as_bytes()).iter().take(6).map(|b| format!("{:02x}", b)).collect()
+}
+
+fn template(name: &str, kind: Kind, variant: &str, text: String) -> Template {
+    Template { name: name.to_string(), kind, variant: variant.to_string(), version: version(&text), text }
+}
+
+/// Template directory (`JUDGE_TEMPLATE_DIR`, default `judge_templates`). `<name>.txt` there
+/// adds a template or replaces the built-in one of that name for every judge model.
+fn template_dir() -> String {
+    env::var("JUDGE_TEMPLATE_DIR").unwrap_or_else(|_| "judge_templates".to_string())
+}
+
+/// The template called `name` for a judge running `model_name`.
+pub fn for_model(name: &str, model_name: &str) -> Result<Template, String> {
+    let path = Path::new(&template_dir()).join(format!("{}.txt", name));
+    if let Ok(text) = std::fs::read_to_string(&path) {
+        return Ok(template(name, Kind::infer(&text), "file", text));
+    }
+    let variant = variant_for(model_name);
+    BUILTIN
+        .iter()
+        .filter(|(builtin, ..)| *builtin == name)
+        .find(|(.., candidate, _)| *candidate == variant)
+        .or_else(|| BUILTIN.iter().find(|(builtin, ..)| *builtin == name))
+        .map(|(name, kind, variant, text)| template(name, *kind, variant, text.to_string()))
+        .ok_or_else(|| format!("unknown judge template '{}' (use {} or add {})", name, names().join(", "), path.display()))
+}
+
+/// Every template name, built-in ones first.
+pub fn names() -> Vec<String> {
+    let mut names: Vec<String> = Vec::new();
+    for (name, ..) in BUILTIN {
+        if !names.iter().any(|n| n == name) {
+            names.push(name.to_string());
+        }
+    }
+    let mut files: Vec<String> = std::fs::read_dir(template_dir())
+        .into_iter()
+        .flatten()
+        .filter_map(Result::ok)
+        .filter_map(|entry| entry.file_name().to_str()?.strip_suffix(".txt").map(str::to_string))
+        .filter(|name| !names.contains(name))
+        .collect();
+    files.sort();
+    names.extend(files);
+    names
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{parse_score, template, Kind};
+
+    #[test]
+    fn render_never_rescans_values() {
+        let reference = template("t", Kind::Reference, "default", "Task: {task}\nReference: {reference}\nResponse: {response} {other}".to_string());
+        assert_eq!(
+            reference.render(&[("task", "Say {reference}"), ("reference", "42"), ("response", "{task}")]),
+            "Task: Say {reference}\nReference: 42\nResponse: {task} {other}"
+        );
+    }
+
+    #[test]
+    fn score_from_the_last_score_line() {
+        for (reply, score) in [("Score: 7", Some(7)), ("**Score: 3**\nScore: 9/10", Some(9)), ("score: 11", None), ("no score", None)] {
+            assert_eq!(parse_score(reply), score, "{}", reply);
+        }
+    }
+}
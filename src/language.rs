@@ -0,0 +1,22 @@
+use regex::Regex;
+use whatlang::Lang;
+
+/// Minimum number of letters of prose before a reply's language is judged.
+const MIN_LETTERS: usize = 20;
+
+/// Parses a language given by English name (`German`) or ISO 639-3 code (`deu`).
+pub fn parse(name: &str) -> Option<Lang> {
+    let name = name.trim();
+    Lang::from_code(name.to_lowercase()).or_else(|| Lang::all().iter().copied().find(|l| l.eng_name().eq_ignore_ascii_case(name)))
+}
+
+/// Returns the detected language when `text` is reliably written in something other than
+/// `expected`. Code blocks, inline code and URLs are ignored, and short replies are not judged.
+pub fn wrong_language(text: &str, expected: Lang) -> Option<Lang> {
+    let noise = Regex::new(r"(?s)```.*?(?:```|\z)|`[^`]*`|https?://\S+").unwrap();
+    let prose = noise.replace_all(text, " ");
+    if prose.chars().filter(|c| c.is_alphabetic()).count() < MIN_LETTERS {
+        return None;
+    }
+    whatlang::detect(&prose).filter(|info| info.is_reliable() && info.lang() != expected).map(|info| info.lang())
+}
@@ -0,0 +1,136 @@
+use std::env;
+use std::time::{Duration, Instant};
+
+use reqwest::header::HeaderMap;
+use serde_json::{Value, json};
+
+/// Headers in which providers report their own processing time, in milliseconds: OpenAI and
+/// Azure send `openai-processing-ms`; gateways behind Envoy (Mistral, among others) send
+/// `x-envoy-upstream-service-time`.
+const PROCESSING_HEADERS: &[&str] = &["openai-processing-ms", "x-envoy-upstream-service-time"];
+
+/// Whether to show the latency breakdown after each reply (`SHOW_LATENCY`).
+pub fn show_latency() -> bool {
+    matches!(env::var("SHOW_LATENCY").unwrap_or_default().trim(), "1" | "true" | "yes")
+}
+
+/// Where the time of one provider call went.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct CallTiming {
+    /// Before the request was sent: checks and fetching the credential, plus any providers that
+    /// failed before a fallback answered.
+    pub waiting: Duration,
+    /// From sending the request to receiving the response headers.
+    pub to_headers: Duration,
+    /// The provider's own processing time, when it reports one.
+    pub processing: Option<Duration>,
+    /// Reading the response body.
+    pub download: Duration,
+}
+
+impl CallTiming {
+    /// Processing time from the first of `PROCESSING_HEADERS` the response carries.
+    pub fn processing_from(headers: &HeaderMap) -> Option<Duration> {
+        PROCESSING_HEADERS
+            .iter()
+            .find_map(|name| headers.get(*name)?.to_str().ok()?.trim().parse::<f64>().ok())
+            .map(|ms| Duration::from_secs_f64(ms / 1000.0))
+    }
+
+    /// Time from sending the request to having the whole reply.
+    pub fn generation(&self) -> Duration {
+        self.to_headers + self.download
+    }
+}
+
+/// Where the time of one chat turn went, across every provider call and tool it made.
+#[derive(Debug, Clone)]
+pub struct TurnLatency {
+    started: Instant,
+    total: Duration,
+    waiting: Duration,
+    network: Duration,
+    processing: Duration,
+    /// Time to headers of calls without a processing header, which cannot be split.
+    unsplit: Duration,
+    download: Duration,
+    tools: Duration,
+    /// Estimated output tokens per second of the final call.
+    tokens_per_sec: Option<f64>,
+}
+
+impl TurnLatency {
+    pub fn start() -> Self {
+        TurnLatency {
+            started: Instant::now(),
+            total: Duration::ZERO,
+            waiting: Duration::ZERO,
+            network: Duration::ZERO,
+            processing: Duration::ZERO,
+            unsplit: Duration::ZERO,
+            download: Duration::ZERO,
+            tools: Duration::ZERO,
+            tokens_per_sec: None,
+        }
+    }
+
+    pub fn add_call(&mut self, call: &CallTiming) {
+        self.waiting += call.waiting;
+        match call.processing {
+            Some(processing) => {
+                let processing = processing.min(call.to_headers);
+                self.processing += processing;
+                self.network += call.to_headers - processing;
+            }
+            None => self.unsplit += call.to_headers,
+        }
+        self.download += call.download;
+    }
+
+    pub fn add_tools(&mut self, duration: Duration) {
+        self.tools += duration;
+    }
+
+    /// Closes the turn once the final reply, of about `output_tokens` tokens, has arrived
+    /// through `last_call`.
+    pub fn finish(&mut self, last_call: &CallTiming, output_tokens: usize) {
+        self.total = self.started.elapsed();
+        let generation = last_call.generation().as_secs_f64();
+        self.tokens_per_sec = Some(output_tokens as f64 / generation).filter(|_| generation > 0.0 && output_tokens > 0);
+    }
+
+    /// `2.41s: wait 0.02s, network 0.31s, processing 1.98s, download 0.01s, tools 0.09s, ~52 tok/s`.
+    pub fn describe(&self) -> String {
+        let mut parts = vec![format!("wait {:.2}s", self.waiting.as_secs_f64())];
+        if !self.network.is_zero() || !self.processing.is_zero() {
+            parts.push(format!("network {:.2}s", self.network.as_secs_f64()));
+            parts.push(format!("processing {:.2}s", self.processing.as_secs_f64()));
+        }
+        if !self.unsplit.is_zero() {
+            parts.push(format!("network+processing {:.2}s", self.unsplit.as_secs_f64()));
+        }
+        parts.push(format!("download {:.2}s", self.download.as_secs_f64()));
+        if !self.tools.is_zero() {
+            parts.push(format!("tools {:.2}s", self.tools.as_secs_f64()));
+        }
+        if let Some(rate) = self.tokens_per_sec {
+            parts.push(format!("~{:.0} tok/s", rate));
+        }
+        format!("{:.2}s: {}", self.total.as_secs_f64(), parts.join(", "))
+    }
+
+    /// The breakdown in milliseconds, for the transcript log.
+    pub fn to_json(&self) -> Value {
+        let ms = |d: Duration| d.as_millis() as u64;
+        json!({
+            "total_ms": ms(self.total),
+            "wait_ms": ms(self.waiting),
+            "network_ms": ms(self.network),
+            "processing_ms": ms(self.processing),
+            "unsplit_ms": ms(self.unsplit),
+            "download_ms": ms(self.download),
+            "tools_ms": ms(self.tools),
+            "tokens_per_sec": self.tokens_per_sec,
+        })
+    }
+}
@@ -1,37 +1,297 @@
 use std::env;
 use dotenv::dotenv;
-use reqwest::header::{AUTHORIZATION, CONTENT_TYPE};
 use serde_json::json;
-use rusqlite::{params, Connection};
+use rusqlite::{params, Connection, OpenFlags};
 use uuid::Uuid;
 use std::io::{self, Write};
 use colored::*;
 use std::fs::File;
-use std::time::Duration;
-use chrono::Datelike;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+mod attachments;
+mod audit;
+mod auth;
+mod bench;
+mod browser;
+mod calculate;
+mod capabilities;
+mod capture;
+mod context;
+mod email;
+mod events;
+mod failover;
+mod fanout;
+mod google_auth;
+mod ingest;
+mod json_schema;
+mod judge_templates;
+mod language;
+mod latency;
+mod outcome;
+mod permissions;
+mod postprocess;
+mod pricing;
+mod project;
+mod prompts;
+mod providers;
+mod reasoning;
+mod recall;
+mod retention;
+mod sampling;
+mod schedule;
+mod scrub;
+mod session_meta;
+mod sigv4;
+mod snippets;
+mod storage;
+mod structured;
+mod suggestions;
+mod summarize;
+mod tokenizer;
+mod toml;
+mod tool_args;
+mod tool_format;
+mod transcript_log;
+mod usage;
+mod validate;
+mod voice;
+mod workspace;
+
+use context::TruncationStrategy;
+use events::{Event, EventBus};
+use tool_args::ToolCall;
 
 #[derive(Debug, Clone, Copy)]
 enum ApiProvider {
     OpenAI,
     Sambanova,
     Gemini,
+    Anthropic,
+    Ollama,
+    AzureOpenAI,
+    OpenRouter,
+    Custom,
+    Groq,
+    Bedrock,
+    Mistral,
+    Vertex,
+    DeepSeek,
+    Cohere,
+    HuggingFace,
 }
 
-#[derive(Debug)]
+impl ApiProvider {
+    const ALL: [ApiProvider; 15] = [
+        ApiProvider::OpenAI,
+        ApiProvider::Sambanova,
+        ApiProvider::Gemini,
+        ApiProvider::Anthropic,
+        ApiProvider::Ollama,
+        ApiProvider::AzureOpenAI,
+        ApiProvider::OpenRouter,
+        ApiProvider::Custom,
+        ApiProvider::Groq,
+        ApiProvider::Bedrock,
+        ApiProvider::Mistral,
+        ApiProvider::Vertex,
+        ApiProvider::DeepSeek,
+        ApiProvider::Cohere,
+        ApiProvider::HuggingFace,
+    ];
+
+    /// The provider whose settings start with `name` (`GROQ`, `AZURE_OPENAI`).
+    fn from_env_name(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|p| p.env_name() == name)
+    }
+
+    /// Prefix of the provider's settings (`<NAME>_API_KEY`, `AUTH_<NAME>`).
+    fn env_name(&self) -> &'static str {
+        match self {
+            ApiProvider::OpenAI => "OPENAI",
+            ApiProvider::Sambanova => "SAMBANOVA",
+            ApiProvider::Gemini => "GEMINI",
+            ApiProvider::Anthropic => "ANTHROPIC",
+            ApiProvider::Ollama => "OLLAMA",
+            ApiProvider::AzureOpenAI => "AZURE_OPENAI",
+            ApiProvider::OpenRouter => "OPENROUTER",
+            ApiProvider::Custom => "CUSTOM",
+            ApiProvider::Groq => "GROQ",
+            ApiProvider::Bedrock => "BEDROCK",
+            ApiProvider::Mistral => "MISTRAL",
+            ApiProvider::Vertex => "VERTEX",
+            ApiProvider::DeepSeek => "DEEPSEEK",
+            ApiProvider::Cohere => "COHERE",
+            ApiProvider::HuggingFace => "HUGGINGFACE",
+        }
+    }
+
+    /// Name shown in menus and messages.
+    fn label(&self) -> &'static str {
+        match self {
+            ApiProvider::OpenAI => "OpenAI",
+            ApiProvider::Sambanova => "Sambanova",
+            ApiProvider::Gemini => "Google Gemini",
+            ApiProvider::Anthropic => "Anthropic",
+            ApiProvider::Ollama => "Ollama",
+            ApiProvider::AzureOpenAI => "Azure OpenAI",
+            ApiProvider::OpenRouter => "OpenRouter",
+            ApiProvider::Custom => "custom endpoint",
+            ApiProvider::Groq => "Groq",
+            ApiProvider::Bedrock => "AWS Bedrock",
+            ApiProvider::Mistral => "Mistral",
+            ApiProvider::Vertex => "Vertex AI",
+            ApiProvider::DeepSeek => "DeepSeek",
+            ApiProvider::Cohere => "Cohere",
+            ApiProvider::HuggingFace => "Hugging Face",
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
 struct ApiConfig {
     provider: ApiProvider,
     api_key: String,
     base_url: String,
     model_name: String,
+    reasoning: reasoning::ReasoningConfig,
+    sampling: sampling::SamplingConfig,
+    auth: auth::Method,
+    /// Tools declared as native functions, for providers that take them; empty declares none.
+    tools: Vec<&'static str>,
+    /// Schema answers must match, for providers with a native JSON mode.
+    response_schema: Option<structured::OutputSchema>,
+    /// Providers tried in order when this one is rate limited, failing or unreachable.
+    fallbacks: Vec<ApiConfig>,
+}
+
+impl ApiConfig {
+    fn new(provider: ApiProvider, api_key: String, base_url: String, model_name: String) -> Self {
+        ApiConfig {
+            provider,
+            api_key,
+            base_url,
+            model_name,
+            reasoning: Default::default(),
+            sampling: Default::default(),
+            auth: Default::default(),
+            tools: Vec::new(),
+            response_schema: None,
+            fallbacks: Vec::new(),
+        }
+    }
+
+    /// Uses `sampling` here and in every fallback.
+    fn set_sampling(&mut self, sampling: sampling::SamplingConfig) {
+        for fallback in &mut self.fallbacks {
+            fallback.set_sampling(sampling.clone());
+        }
+        self.sampling = sampling;
+    }
+
+    /// A copy for one chat turn, declaring `tools` and asking for `response_schema`, here and in
+    /// every fallback.
+    fn for_turn(&self, tools: &[&'static str], response_schema: Option<&structured::OutputSchema>) -> ApiConfig {
+        let mut config = self.clone();
+        config.tools = tools.to_vec();
+        config.response_schema = response_schema.cloned();
+        config.fallbacks = self.fallbacks.iter().map(|fallback| fallback.for_turn(tools, response_schema)).collect();
+        config
+    }
+
+    /// Switches to `model`, rewriting endpoints that name the model in their URL.
+    fn set_model(&mut self, model: String) {
+        match self.provider {
+            ApiProvider::Gemini | ApiProvider::Vertex => {
+                if let Some((root, _)) = self.base_url.split_once("/models/") {
+                    self.base_url = format!("{}/models/{}:generateContent", root, model);
+                }
+            }
+            ApiProvider::Bedrock => self.base_url = bedrock_url(&model),
+            ApiProvider::AzureOpenAI => {
+                if let Some((root, rest)) = self.base_url.split_once("/deployments/")
+                    && let Some((_, endpoint)) = rest.split_once('/')
+                {
+                    self.base_url = format!("{}/deployments/{}/{}", root, model, endpoint);
+                }
+            }
+            _ => {}
+        }
+        self.model_name = model;
+    }
+
+    /// `provider/model`, as recorded for replies from a fallback.
+    fn describe(&self) -> String {
+        format!("{}/{}", self.provider.env_name().to_lowercase(), self.model_name)
+    }
 }
 
+/// Per-run options for new chat sessions, taken from the command line.
 #[derive(Debug)]
+struct SessionOptions {
+    voice_mode: bool,
+    retention: retention::Retention,
+    /// Providers that answer every prompt alongside the selected one.
+    fanout: Vec<ApiConfig>,
+    /// Metadata attached to every new session (`--meta key=value`).
+    meta: Vec<(String, serde_json::Value)>,
+    /// Schema every answer is validated against (`--json-schema <file>`).
+    output_schema: Option<structured::OutputSchema>,
+    /// Model that suggests follow-up prompts after each answer (`--suggest`).
+    suggestions: Option<ApiConfig>,
+}
+
+#[derive(Debug, Clone)]
 struct Message {
     role: String,
     content: String,
 }
 
+/// Adds `column` to `table` on databases created before the column existed.
+fn add_column_if_missing(conn: &Connection, table: &str, column: &str, definition: &str) {
+    let mut stmt = conn.prepare(&format!("PRAGMA table_info({})", table)).unwrap();
+    let exists = stmt
+        .query_map([], |row| row.get::<_, String>(1))
+        .unwrap()
+        .any(|name| name.map(|n| n == column).unwrap_or(false));
+    if !exists {
+        conn.execute(&format!("ALTER TABLE {} ADD COLUMN {} {}", table, column, definition), []).unwrap();
+    }
+}
+
+/// Opens the active workspace's database.
+fn open_db() -> Connection {
+    let conn = open_db_at(&workspace::active().path);
+    let expired = retention::sweep_expired(&conn);
+    if expired > 0 {
+        println!("Deleted {} expired session(s).", expired);
+    }
+    conn
+}
+
+fn open_db_at(path: &str) -> Connection {
+    let conn = Connection::open(path).unwrap();
+    init_db(&conn);
+    conn
+}
+
+/// Opens another workspace's database without migrating it, for reading only. Fails when the file
+/// cannot be opened or predates the message columns `search_messages` reads.
+fn open_db_read_only(path: &str) -> Result<Connection, String> {
+    let conn = Connection::open_with_flags(path, OpenFlags::SQLITE_OPEN_READ_ONLY).map_err(|e| e.to_string())?;
+    storage::register_functions(&conn);
+    let columns: Vec<String> = conn
+        .prepare("PRAGMA table_info(messages)")
+        .and_then(|mut stmt| stmt.query_map([], |row| row.get(1))?.collect())
+        .map_err(|e| e.to_string())?;
+    match ["deleted_at", "encoding"].into_iter().find(|c| !columns.iter().any(|name| name == c)) {
+        Some(missing) => Err(format!("messages has no {} column", missing)),
+        None => Ok(conn),
+    }
+}
+
 fn init_db(conn: &Connection) {
+    storage::register_functions(conn);
     conn.execute(
         "CREATE TABLE IF NOT EXISTS sessions (
             id TEXT PRIMARY KEY,
@@ -39,6 +299,13 @@ fn init_db(conn: &Connection) {
         )",
         [],
     ).unwrap();
+    add_column_if_missing(conn, "sessions", "expires_at", "TIMESTAMP");
+    add_column_if_missing(conn, "sessions", "model", "TEXT");
+    add_column_if_missing(conn, "sessions", "provider", "TEXT");
+    add_column_if_missing(conn, "sessions", "prompt_variant", "TEXT");
+    add_column_if_missing(conn, "sessions", "response_language", "TEXT");
+    add_column_if_missing(conn, "sessions", "reasoning", "TEXT");
+    add_column_if_missing(conn, "sessions", "sampling", "TEXT");
     conn.execute(
         "CREATE TABLE IF NOT EXISTS messages (
             id INTEGER PRIMARY KEY AUTOINCREMENT,
@@ -50,13 +317,231 @@ fn init_db(conn: &Connection) {
         )",
         [],
     ).unwrap();
+    add_column_if_missing(conn, "messages", "deleted_at", "TIMESTAMP");
+    add_column_if_missing(conn, "messages", "encoding", "TEXT");
+    add_column_if_missing(conn, "messages", "outcome", "TEXT");
+    add_column_if_missing(conn, "messages", "reasoning_chars", "INTEGER");
+    add_column_if_missing(conn, "messages", "answered_by", "TEXT");
+    add_column_if_missing(conn, "messages", "attachments", "TEXT");
+    add_column_if_missing(conn, "messages", "prompt_tokens", "INTEGER");
+    add_column_if_missing(conn, "messages", "completion_tokens", "INTEGER");
+    add_column_if_missing(conn, "messages", "cost", "REAL");
+    add_column_if_missing(conn, "messages", "seed", "INTEGER");
+    add_column_if_missing(conn, "messages", "logprobs", "TEXT");
+    add_column_if_missing(conn, "messages", "raw_content", "TEXT");
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS maintenance_runs (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            ran_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
+            integrity TEXT,
+            backup_path TEXT,
+            size_before INTEGER,
+            size_after INTEGER
+        )",
+        [],
+    ).unwrap();
+    add_column_if_missing(conn, "maintenance_runs", "backup_error", "TEXT");
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS checkpoints (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            session_id TEXT,
+            name TEXT,
+            message_id INTEGER,
+            created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
+            UNIQUE(session_id, name),
+            FOREIGN KEY(session_id) REFERENCES sessions(id)
+        )",
+        [],
+    ).unwrap();
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS scheduled_jobs (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            session_id TEXT,
+            kind TEXT,
+            payload TEXT,
+            run_at TIMESTAMP,
+            every_secs INTEGER,
+            status TEXT,
+            last_run_at TIMESTAMP,
+            last_result TEXT,
+            created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
+            FOREIGN KEY(session_id) REFERENCES sessions(id)
+        )",
+        [],
+    ).unwrap();
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS tool_runs (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            session_id TEXT,
+            tool TEXT,
+            duration_ms INTEGER,
+            success BOOLEAN,
+            cost REAL,
+            created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
+            FOREIGN KEY(session_id) REFERENCES sessions(id)
+        )",
+        [],
+    ).unwrap();
+    add_column_if_missing(conn, "tool_runs", "run_id", "TEXT");
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS session_meta (
+            session_id TEXT,
+            key TEXT,
+            value TEXT,
+            updated_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
+            PRIMARY KEY(session_id, key),
+            FOREIGN KEY(session_id) REFERENCES sessions(id)
+        )",
+        [],
+    ).unwrap();
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS runs (
+            id TEXT PRIMARY KEY,
+            suite TEXT,
+            suite_path TEXT,
+            started_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
+            finished_at TIMESTAMP
+        )",
+        [],
+    ).unwrap();
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS results (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            run_id TEXT,
+            task_id TEXT,
+            provider TEXT,
+            model TEXT,
+            output TEXT,
+            error TEXT,
+            latency_ms INTEGER,
+            prompt_tokens INTEGER,
+            completion_tokens INTEGER,
+            created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
+            FOREIGN KEY(run_id) REFERENCES runs(id)
+        )",
+        [],
+    ).unwrap();
+    add_column_if_missing(conn, "runs", "providers", "TEXT");
+    add_column_if_missing(conn, "runs", "judge", "TEXT");
+    add_column_if_missing(conn, "runs", "reasoning", "TEXT");
+    add_column_if_missing(conn, "runs", "sampling", "TEXT");
+    add_column_if_missing(conn, "runs", "concurrency", "INTEGER");
+    add_column_if_missing(conn, "runs", "tags", "TEXT");
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS tasks (
+            run_id TEXT,
+            task_id TEXT,
+            prompt_hash TEXT,
+            system TEXT,
+            prompts TEXT,
+            expected TEXT,
+            rubric TEXT,
+            grader TEXT,
+            checker TEXT,
+            tags TEXT,
+            repetitions INTEGER,
+            tools TEXT,
+            PRIMARY KEY(run_id, task_id),
+            FOREIGN KEY(run_id) REFERENCES runs(id)
+        )",
+        [],
+    ).unwrap();
+    add_column_if_missing(conn, "results", "prompt_hash", "TEXT");
+    add_column_if_missing(conn, "results", "score", "REAL");
+    add_column_if_missing(conn, "results", "repetition", "INTEGER");
+    add_column_if_missing(conn, "results", "passed", "INTEGER");
+    add_column_if_missing(conn, "results", "tool_calls", "INTEGER");
+    add_column_if_missing(conn, "results", "generation_ms", "INTEGER");
+    add_column_if_missing(conn, "results", "tokens_per_sec", "REAL");
+    add_column_if_missing(conn, "results", "judge_score", "INTEGER");
+    add_column_if_missing(conn, "results", "judge", "TEXT");
+    add_column_if_missing(conn, "results", "judge_template", "TEXT");
+    add_column_if_missing(conn, "results", "judge_version", "TEXT");
+    add_column_if_missing(conn, "results", "judge_reply", "TEXT");
+    add_column_if_missing(conn, "results", "tool_cost", "REAL");
+    add_column_if_missing(conn, "results", "judge_prompt_tokens", "INTEGER");
+    add_column_if_missing(conn, "results", "judge_completion_tokens", "INTEGER");
+    add_column_if_missing(conn, "results", "judge_cost", "REAL");
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS run_summaries (
+            run_id TEXT,
+            provider TEXT,
+            model TEXT,
+            results INTEGER,
+            errors INTEGER,
+            graded INTEGER,
+            passed INTEGER,
+            mean_ms REAL,
+            stddev_ms REAL,
+            p50_ms REAL,
+            p90_ms REAL,
+            p99_ms REAL,
+            prompt_tokens INTEGER,
+            completion_tokens INTEGER,
+            PRIMARY KEY(run_id, provider, model),
+            FOREIGN KEY(run_id) REFERENCES runs(id)
+        )",
+        [],
+    ).unwrap();
+    add_column_if_missing(conn, "run_summaries", "tokens_per_sec", "REAL");
+    add_column_if_missing(conn, "run_summaries", "median_tokens_per_sec", "REAL");
+    add_column_if_missing(conn, "run_summaries", "tool_runs", "INTEGER");
+    add_column_if_missing(conn, "run_summaries", "tool_cost", "REAL");
+    add_column_if_missing(conn, "run_summaries", "judge_tokens", "INTEGER");
+    add_column_if_missing(conn, "run_summaries", "judge_cost", "REAL");
+    add_column_if_missing(conn, "run_summaries", "cost", "REAL");
+    add_column_if_missing(conn, "run_summaries", "currency", "TEXT");
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS judge_prompts (
+            version TEXT PRIMARY KEY,
+            name TEXT,
+            variant TEXT,
+            text TEXT
+        )",
+        [],
+    ).unwrap();
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS artifacts (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            result_id INTEGER,
+            path TEXT,
+            hash TEXT,
+            size INTEGER,
+            created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
+            FOREIGN KEY(result_id) REFERENCES results(id)
+        )",
+        [],
+    ).unwrap();
+}
+
+/// Stores a message. Inline images are stored as `[image: <source>]` references, with their
+/// sources listed in `attachments`.
+fn save_message(conn: &Connection, session_id: &str, role: &str, content: &str) -> i64 {
+    let sources = attachments::sources(content);
+    let attachments = Some(sources).filter(|s| !s.is_empty()).map(|s| json!(s).to_string());
+    let (content, encoding) = storage::encode_content(&attachments::without_images(content));
+    conn.execute(
+        "INSERT INTO messages (session_id, role, content, encoding, attachments) VALUES (?1, ?2, ?3, ?4, ?5)",
+        params![session_id, role, content, encoding, attachments],
+    ).unwrap();
+    conn.last_insert_rowid()
 }
 
-fn save_message(conn: &Connection, session_id: &str, role: &str, content: &str) {
+/// Content left in place of a deleted message, so the conversation keeps its shape.
+const MESSAGE_TOMBSTONE: &str = "[message deleted]";
+
+/// Replaces a message's content with a tombstone and returns its session id, or `None` if
+/// there is no such message.
+fn delete_message(conn: &Connection, message_id: i64) -> Option<String> {
+    let session_id: String = conn
+        .query_row("SELECT session_id FROM messages WHERE id = ?1", params![message_id], |row| row.get(0))
+        .ok()?;
     conn.execute(
-        "INSERT INTO messages (session_id, role, content) VALUES (?1, ?2, ?3)",
-        params![session_id, role, content],
+        "UPDATE messages SET content = ?2, encoding = NULL, raw_content = NULL, logprobs = NULL, seed = NULL,
+         attachments = NULL, deleted_at = CURRENT_TIMESTAMP WHERE id = ?1",
+        params![message_id, MESSAGE_TOMBSTONE],
     ).unwrap();
+    Some(session_id)
 }
 
 fn save_session(conn: &Connection, session_id: &str) {
@@ -66,20 +551,62 @@ fn save_session(conn: &Connection, session_id: &str) {
     ).unwrap();
 }
 
-fn list_sessions(conn: &Connection) {
-    let mut stmt = conn.prepare("SELECT id, created_at FROM sessions ORDER BY created_at DESC").unwrap();
+/// Lists sessions, newest first, keeping only those whose metadata matches every filter.
+fn list_sessions(conn: &Connection, filters: &[(String, serde_json::Value)]) {
+    let mut stmt = conn.prepare("SELECT id, created_at, model, prompt_variant FROM sessions ORDER BY created_at DESC").unwrap();
     let rows = stmt.query_map([], |row| {
-        Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?))
+        Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?, row.get::<_, Option<String>>(2)?, row.get::<_, Option<String>>(3)?))
     }).unwrap();
     println!("{}", "Previous Sessions:".bold().yellow());
-    for (i, row) in rows.enumerate() {
-        let (id, created_at) = row.unwrap();
-        println!("{}: {} ({})", i + 1, id, created_at);
+    let rows = rows.filter_map(Result::ok).filter(|(id, ..)| session_meta::matches(conn, id, filters));
+    for (i, (id, created_at, model, variant)) in rows.enumerate() {
+        match (model, variant) {
+            (Some(model), Some(variant)) => println!("{}: {} ({}) {} [{}]", i + 1, id, created_at, model, variant),
+            _ => println!("{}: {} ({})", i + 1, id, created_at),
+        }
+        let meta = session_meta::get(conn, &id);
+        if !meta.is_empty() {
+            println!("   {}", session_meta::describe(&meta).dimmed());
+        }
     }
 }
 
+/// A stored message matching a search, with a short excerpt around the match.
+struct SearchHit {
+    session_id: String,
+    message_id: i64,
+    role: String,
+    excerpt: String,
+}
+
+fn search_messages(conn: &Connection, query: &str) -> Vec<SearchHit> {
+    let mut stmt = conn
+        .prepare(
+            "SELECT session_id, id, role, message_text(content, encoding) AS text FROM messages
+             WHERE deleted_at IS NULL AND instr(lower(text), lower(?1)) > 0
+             ORDER BY id DESC LIMIT 20",
+        )
+        .unwrap();
+    stmt.query_map(params![query], |row| {
+        let text: String = row.get(3)?;
+        let lower = text.to_lowercase();
+        let at = lower.find(&query.to_lowercase()).unwrap_or(0);
+        let start = text.char_indices().map(|(i, _)| i).take_while(|&i| i + 40 <= at).last().unwrap_or(0);
+        let excerpt: String = text[start..].chars().take(100).collect();
+        Ok(SearchHit {
+            session_id: row.get(0)?,
+            message_id: row.get(1)?,
+            role: row.get(2)?,
+            excerpt: excerpt.replace('\n', " "),
+        })
+    })
+    .unwrap()
+    .filter_map(Result::ok)
+    .collect()
+}
+
 fn load_history(conn: &Connection, session_id: &str) -> Vec<Message> {
-    let mut stmt = conn.prepare("SELECT role, content FROM messages WHERE session_id = ?1 ORDER BY id ASC").unwrap();
+    let mut stmt = conn.prepare("SELECT role, message_text(content, encoding) FROM messages WHERE session_id = ?1 ORDER BY id ASC").unwrap();
     let rows = stmt
         .query_map(params![session_id], |row| {
             Ok(Message {
@@ -91,37 +618,104 @@ fn load_history(conn: &Connection, session_id: &str) -> Vec<Message> {
     rows.map(|m| m.unwrap()).collect()
 }
 
+fn save_checkpoint(conn: &Connection, session_id: &str, name: &str) {
+    conn.execute(
+        "INSERT OR REPLACE INTO checkpoints (session_id, name, message_id)
+         SELECT ?1, ?2, COALESCE(MAX(id), 0) FROM messages WHERE session_id = ?1",
+        params![session_id, name],
+    ).unwrap();
+}
+
+/// Rolls the session back to the named checkpoint, deleting every later message and
+/// checkpoint, and returns the remaining history. Returns `None` if there is no such checkpoint.
+fn restore_checkpoint(conn: &Connection, session_id: &str, name: &str) -> Option<Vec<Message>> {
+    let message_id: i64 = conn
+        .query_row(
+            "SELECT message_id FROM checkpoints WHERE session_id = ?1 AND name = ?2",
+            params![session_id, name],
+            |row| row.get(0),
+        )
+        .ok()?;
+    conn.execute(
+        "DELETE FROM messages WHERE session_id = ?1 AND id > ?2",
+        params![session_id, message_id],
+    ).unwrap();
+    conn.execute(
+        "DELETE FROM checkpoints WHERE session_id = ?1 AND message_id > ?2",
+        params![session_id, message_id],
+    ).unwrap();
+    let history = load_history(conn, session_id);
+    Some(history.into_iter().map(|m| Message { content: attachments::reattach(&m.content), ..m }).collect())
+}
+
 fn view_session(conn: &Connection) {
     print!("Enter session ID to view: ");
     io::stdout().flush().unwrap();
     let mut session_id = String::new();
     io::stdin().read_line(&mut session_id).unwrap();
     let session_id = session_id.trim();
-    let history = load_history(conn, session_id);
+    let mut stmt = conn.prepare("SELECT id, role, message_text(content, encoding), answered_by FROM messages WHERE session_id = ?1 ORDER BY id ASC").unwrap();
+    let rows = stmt
+        .query_map(params![session_id], |row| {
+            Ok((row.get::<_, i64>(0)?, row.get::<_, String>(1)?, row.get::<_, String>(2)?, row.get::<_, Option<String>>(3)?))
+        })
+        .unwrap();
     println!("\n{}\n", "Session History:".bold().yellow());
-    for msg in history {
-        match msg.role.as_str() {
-            "user" => println!("{} {}", "You:".bold().blue(), msg.content.blue()),
-            "assistant" => println!("{} {}", "Assistant:".bold().green(), msg.content.green()),
-            "system" => println!("{} {}", "System:".bold().magenta(), msg.content.magenta()),
-            _ => println!("{}: {}", msg.role, msg.content),
+    for row in rows {
+        let (id, role, content, answered_by) = row.unwrap();
+        let id = format!("#{}", id).dimmed();
+        match role.as_str() {
+            "user" => println!("{} {} {}", id, "You:".bold().blue(), content.blue()),
+            "assistant" => match answered_by {
+                Some(provider) => println!("{} {} {}", id, format!("Assistant ({}):", provider).bold().green(), content.green()),
+                None => println!("{} {} {}", id, "Assistant:".bold().green(), content.green()),
+            },
+            "system" => println!("{} {} {}", id, "System:".bold().magenta(), content.magenta()),
+            _ => println!("{} {}: {}", id, role, content),
         }
     }
 }
 
-fn export_session(conn: &Connection) {
-    print!("Enter session ID to export: ");
-    io::stdout().flush().unwrap();
-    let mut session_id = String::new();
-    io::stdin().read_line(&mut session_id).unwrap();
-    let session_id = session_id.trim();
+/// Writes the session transcript to `session_<id>.txt`, passing it through `scrub` if given,
+/// and returns the file name.
+fn write_export(conn: &Connection, session_id: &str, scrub: Option<&scrub::ScrubProfile>) -> String {
     let history = load_history(conn, session_id);
     let filename = format!("session_{}.txt", session_id);
     let mut file = File::create(&filename).unwrap();
     for msg in &history {
         let line = format!("{}: {}\n", msg.role, msg.content);
+        let line = match scrub {
+            Some(profile) => profile.apply(&line),
+            None => line,
+        };
         file.write_all(line.as_bytes()).unwrap();
     }
+    filename
+}
+
+fn export_session(conn: &Connection) {
+    print!("Enter session ID to export: ");
+    io::stdout().flush().unwrap();
+    let mut session_id = String::new();
+    io::stdin().read_line(&mut session_id).unwrap();
+    let session_id = session_id.trim();
+
+    print!("Scrub profile (press Enter for none): ");
+    io::stdout().flush().unwrap();
+    let mut profile_name = String::new();
+    io::stdin().read_line(&mut profile_name).unwrap();
+    let profile = match profile_name.trim() {
+        "" => None,
+        name => match scrub::ScrubProfile::load(name) {
+            Ok(profile) => Some(profile),
+            Err(e) => {
+                println!("{} ({})", "Export cancelled".red(), e.red());
+                return;
+            }
+        },
+    };
+
+    let filename = write_export(conn, session_id, profile.as_ref());
     println!("Session exported to {}", filename.bold().yellow());
 }
 
@@ -140,251 +734,2415 @@ async fn web_search(query: &str) -> Result<String, reqwest::Error> {
     response.text().await
 }
 
+/// A model reply with the provider's finish reason, when it reports one.
+struct LlmReply {
+    text: String,
+    finish_reason: Option<String>,
+    /// Reasoning the provider returned apart from the answer (`reasoning_content`, thinking
+    /// blocks), never sent back in later turns.
+    reasoning: Option<String>,
+    /// Set when a backup provider answered instead of the configured one.
+    failover: Option<failover::Failover>,
+    timing: latency::CallTiming,
+    /// Tokens the provider reported, when the response has a usage block.
+    usage: Option<usage::TokenUsage>,
+    /// Per-token log probabilities, when `logprobs` was requested and the provider returned them.
+    logprobs: Option<serde_json::Value>,
+    /// The sampling seed, when the provider that answered was sent one.
+    seed: Option<i64>,
+}
+
+/// Key or token sent to `provider`: a Google access token for Vertex with static auth, otherwise
+/// the configured auth plugin or static key.
+async fn provider_credential(client: &reqwest::Client, config: &ApiConfig, provider: &dyn providers::Provider) -> Result<String, String> {
+    match provider.credential() {
+        providers::Credential::GoogleAccessToken if config.auth.is_static() => google_auth::access_token(client, config.provider.env_name()).await,
+        _ => auth::credential(client, config.provider.env_name(), &config.auth, &config.api_key).await,
+    }
+}
+
+/// Model ids the provider reports, sorted, for providers with a model listing endpoint.
+async fn list_models(client: &reqwest::Client, config: &ApiConfig) -> Result<Vec<String>, String> {
+    let provider = providers::get(config.provider.env_name()).ok_or("no provider registered")?;
+    let credential = provider_credential(client, config, provider).await?;
+    let request = provider.models_request(client, config, &credential).ok_or("the provider has no model listing")?;
+    let res = request.send().await.map_err(|e| e.to_string())?;
+    if !res.status().is_success() {
+        let status = res.status();
+        let body = res.text().await.unwrap_or_default();
+        return Err(format!("{} ({})", provider.parse_error(&body).unwrap_or(body), status));
+    }
+    let body: serde_json::Value = res.json().await.map_err(|e| e.to_string())?;
+    let mut models = provider.parse_models(&body);
+    models.sort();
+    models.dedup();
+    Ok(models)
+}
+
 async fn call_llm(client: &reqwest::Client, config: &ApiConfig, history: &[Message]) -> Result<String, Box<dyn std::error::Error>> {
-    let res = match config.provider {
-        ApiProvider::OpenAI | ApiProvider::Sambanova => {
-            let messages_json: Vec<_> = history.iter().map(|m| json!({"role": m.role, "content": m.content})).collect();
-            let body = json!({
-                "model": config.model_name,
-                "messages": messages_json,
-                "temperature": 0.1,
-                "top_p": 0.1
-            });
-            client
-                .post(&config.base_url)
-                .header(AUTHORIZATION, format!("Bearer {}", config.api_key))
-                .header(CONTENT_TYPE, "application/json")
-                .json(&body)
-                .send()
-                .await?
-        }
-        ApiProvider::Gemini => {
-            // Gemini uses 'model' for assistant and 'user' for user.
-            // It also expects contents to not have adjacent same roles.
-            let mut gemini_contents = Vec::new();
-            if let Some(first_message) = history.first() {
-                 if first_message.role == "system" {
-                    gemini_contents.push(json!({
-                        "role": "user",
-                        "parts": [{"text": first_message.content}]
-                    }));
-                    gemini_contents.push(json!({
-                        "role": "model",
-                        "parts": [{"text": "Understood."}]
-                    }));
-                }
-            }
-
-            for msg in history.iter().skip(1) {
-                let role = if msg.role == "assistant" { "model" } else { "user" };
-                gemini_contents.push(json!({
-                    "role": role,
-                    "parts": [{"text": msg.content}]
-                }));
-            }
+    Ok(call_llm_detailed(client, config, history).await?.text)
+}
 
-            let body = json!({
-                "contents": gemini_contents
-            });
-            let url = format!("{}?key={}", config.base_url, config.api_key);
-            client
-                .post(&url)
-                .header(CONTENT_TYPE, "application/json")
-                .json(&body)
-                .send()
-                .await?
-        }
+/// Sends `history` to the configured provider and, when it is rate limited, failing or
+/// unreachable, to each of its fallbacks in turn.
+async fn call_llm_detailed(client: &reqwest::Client, config: &ApiConfig, history: &[Message]) -> Result<LlmReply, Box<dyn std::error::Error>> {
+    let started = Instant::now();
+    // Only the message is kept: the boxed error is not `Send`, and benchmark runs call this from
+    // spawned tasks.
+    let error = match call_provider(client, config, history).await {
+        Err(e) if !config.fallbacks.is_empty() && failover::should_fail_over(e.as_ref()) => e.to_string(),
+        result => return result,
     };
+    let mut failures = vec![format!("{}: {}", config.describe(), error)];
+    for fallback in &config.fallbacks {
+        // A backup that is misconfigured or rejects the request is skipped like one that is down.
+        match call_provider(client, fallback, history).await {
+            Ok(mut reply) => {
+                reply.failover = Some(failover::Failover { answered_by: fallback.describe(), reason: failures.join("; ") });
+                // Time spent on the providers that failed counts as waiting.
+                reply.timing.waiting = started.elapsed().saturating_sub(reply.timing.to_headers + reply.timing.download);
+                return Ok(reply);
+            }
+            Err(e) => failures.push(format!("{}: {}", fallback.describe(), e)),
+        }
+    }
+    Err(format!("All providers failed ({})", failures.join("; ")).into())
+}
+
+async fn call_provider(client: &reqwest::Client, config: &ApiConfig, history: &[Message]) -> Result<LlmReply, Box<dyn std::error::Error>> {
+    let started = Instant::now();
+    validate::check_request(&config.model_name, history).map_err(|e| format!("Request not sent: {}", e))?;
+    let provider = providers::get(config.provider.env_name()).ok_or_else(|| format!("Request not sent: no provider registered for {}", config.provider.env_name()))?;
+    let api_key = provider_credential(client, config, provider).await.map_err(|e| format!("Request not sent: {}", e))?;
+
+    let request = provider.build_request(client, config, history, &api_key)?;
+    let waiting = started.elapsed();
+    let res = request.send().await?;
+    let to_headers = started.elapsed() - waiting;
 
     if !res.status().is_success() {
         let status = res.status();
+        if status == reqwest::StatusCode::UNAUTHORIZED {
+            auth::invalidate(config.provider.env_name());
+        }
         let error_text = res.text().await.unwrap_or_else(|_| "Could not read error body".to_string());
-        return Err(format!("API Error: {} ({})", error_text, status).into());
+        let error_text = provider.parse_error(&error_text).unwrap_or(error_text);
+        return Err(failover::ApiError { status, message: error_text }.into());
     }
 
+    let processing = latency::CallTiming::processing_from(res.headers());
     let resp_json: serde_json::Value = res.json().await.unwrap_or_else(|_| json!({}));
+    let mut reply = provider.parse_response(&resp_json);
+    reply.usage = provider.parse_usage(&resp_json);
+    reply.logprobs = provider.parse_logprobs(&resp_json);
+    reply.seed = config.sampling.seed.filter(|_| provider.sends_seed());
+    reply.timing = latency::CallTiming { waiting, to_headers, processing, download: started.elapsed() - waiting - to_headers };
+    Ok(reply)
+}
+
+/// What `usage` cost on the model of a `provider/model` label, with the session's running total,
+/// or `None` when the model has no price.
+fn reply_cost(conn: &Connection, session_id: &str, catalog: &pricing::Catalog, answered_by: &str, usage: &usage::TokenUsage) -> Option<usage::ReplyCost> {
+    let model = answered_by.split_once('/').map_or(answered_by, |(_, model)| model);
+    let reply = catalog.cost(model, usage)?;
+    Some(usage::ReplyCost { reply, session: usage::session_cost(conn, session_id) + reply, currency: catalog.currency.clone() })
+}
 
-    let assistant_reply = match config.provider {
-        ApiProvider::OpenAI | ApiProvider::Sambanova => {
-            resp_json["choices"][0]["message"]["content"].as_str().unwrap_or("[No response]").to_string()
+/// Sends the latest prompt to every provider at once, each with its own history, then shows and
+/// stores every reply tagged with the provider that gave it.
+async fn fan_out_turn(
+    conn: &Connection,
+    session_id: &str,
+    client: &reqwest::Client,
+    configs: &[&ApiConfig],
+    mut histories: Vec<&mut Vec<Message>>,
+    expected_language: Option<whatlang::Lang>,
+    bus: &mut EventBus,
+) {
+    let requests = configs.iter().zip(&histories).map(|(config, history)| call_llm_detailed(client, config, history));
+    let replies = fanout::join_all(requests.collect()).await;
+    let catalog = pricing::Catalog::load();
+    for ((config, history), reply) in configs.iter().zip(histories.iter_mut()).zip(replies) {
+        let reply = match reply {
+            Ok(reply) => reply,
+            Err(e) => {
+                bus.publish(Event::ApiError { context: format!("API Error from {}", config.describe()), error: e.to_string() });
+                continue;
+            }
+        };
+        let provider = note_failover(bus, reply.failover).unwrap_or_else(|| config.describe());
+        let text = postprocess::Pipeline::for_model(&config.model_name).unwrap_or_default().apply(&reply.text);
+        if let Some(reasoning) = &reply.reasoning {
+            bus.publish(Event::Reasoning(reasoning.clone()));
         }
-        ApiProvider::Gemini => {
-            resp_json["candidates"][0]["content"]["parts"][0]["text"].as_str().unwrap_or("[No response]").to_string()
+        bus.publish(Event::ProviderReply { provider: provider.clone(), text: text.clone() });
+        let cost = reply.usage.and_then(|usage| reply_cost(conn, session_id, &catalog, &provider, &usage));
+        if let Some(usage) = reply.usage {
+            bus.publish(Event::TokenUsage(usage, cost.clone()));
         }
-    };
+        history.push(Message { role: "assistant".to_string(), content: text.clone() });
+        let message_id = save_message(conn, session_id, "assistant", &text);
+        outcome::record(conn, message_id, outcome::classify(&text, reply.finish_reason.as_deref(), false, expected_language));
+        if let Some(reasoning) = &reply.reasoning {
+            reasoning::record_message(conn, message_id, reasoning);
+        }
+        if let Some(usage) = &reply.usage {
+            usage::record_tokens(conn, message_id, usage, cost.map(|cost| cost.reply));
+        }
+        sampling::record_reply(conn, message_id, reply.seed, reply.logprobs.as_ref());
+        failover::record(conn, message_id, &provider);
+        bus.publish(Event::MessageSaved { role: "assistant".to_string(), content: text });
+    }
+}
+
+/// Whether to declare tools as native functions: the provider takes them, the model is not
+/// known to lack tool support, and `TOOL_CALLING` is not `text`.
+fn native_tool_calling(config: &ApiConfig) -> bool {
+    let text_only = env::var("TOOL_CALLING").is_ok_and(|mode| mode.trim().eq_ignore_ascii_case("text"));
+    !text_only
+        && capabilities::for_model(&config.model_name).tools != Some(false)
+        && providers::get(config.provider.env_name()).is_some_and(|provider| provider.declares_tools())
+}
+
+/// Tells the user a backup provider answered, returning which one.
+fn note_failover(bus: &mut EventBus, failover: Option<failover::Failover>) -> Option<String> {
+    let failover = failover?;
+    bus.publish(Event::Notice(format!("Answered by fallback {} ({}).", failover.answered_by, failover.reason)));
+    Some(failover.answered_by)
+}
+
+async fn fit_context(client: &reqwest::Client, config: &ApiConfig, history: &mut Vec<Message>, strategy: TruncationStrategy, bus: &mut EventBus) {
+    let budget = context::ContextBudget::from_env(&config.model_name);
+    let removed = context::truncate_history(history, strategy, &budget);
+    if removed.is_empty() {
+        return;
+    }
+
+    if strategy == TruncationStrategy::SummarizeOldest {
+        let transcript: String = removed.iter().map(|m| format!("{}: {}\n", m.role, m.content)).collect();
+        let request = vec![
+            Message { role: "system".to_string(), content: "Summarize the following conversation excerpt. Keep facts, decisions and command results; be concise.".to_string() },
+            Message { role: "user".to_string(), content: transcript },
+        ];
+        match call_llm(client, config, &request).await {
+            Ok(summary) => history.insert(1, Message { role: "system".to_string(), content: format!("Summary of earlier conversation:\n{}", summary) }),
+            Err(e) => bus.publish(Event::Warning(format!("Could not summarize trimmed history ({})", e))),
+        }
+    }
 
-    Ok(assistant_reply)
+    bus.publish(Event::Notice(format!("Trimmed {} message(s) from context ({}).", removed.len(), strategy.name())));
 }
 
-async fn start_chat_session(conn: &Connection, config: &ApiConfig) {
+async fn start_chat_session(conn: &Connection, config: &ApiConfig, options: &SessionOptions) {
     let session_id = Uuid::new_v4().to_string();
-    save_session(&conn, &session_id);
+    save_session(conn, &session_id);
+    retention::set_session_expiry(conn, &session_id, options.retention);
+    for (key, value) in &options.meta {
+        session_meta::set(conn, &session_id, key, value);
+    }
+    reasoning::record(conn, &session_id, &config.reasoning);
+    if !config.sampling.is_default() {
+        sampling::record(conn, &session_id, &config.sampling);
+    }
+    // `/set` changes sampling for this session only.
+    let mut session_config = config.clone();
 
-    print!("Enable web search for this session? (y/n): ");
-    io::stdout().flush().unwrap();
-    let mut web_search_choice = String::new();
-    io::stdin().read_line(&mut web_search_choice).unwrap();
-    let web_search_enabled = web_search_choice.trim().eq_ignore_ascii_case("y");
+    // Tool calls are not run in fan-out mode, so tools are not offered.
+    let web_search_enabled = options.fanout.is_empty() && {
+        print!("Enable web search for this session? (y/n): ");
+        io::stdout().flush().unwrap();
+        let mut web_search_choice = String::new();
+        io::stdin().read_line(&mut web_search_choice).unwrap();
+        web_search_choice.trim().eq_ignore_ascii_case("y")
+    };
 
-    println!("{}\n", "New chat session started. Type 'exit' to quit.".bold().yellow());
-    
-    let system_prompt = if web_search_enabled {
-        let current_year = chrono::Local::now().year();
-        format!(
-            "You are a helpful AI assistant powered by the {} model.
-You have the ability to run any Linux shell command.
-Your response MUST be ONLY the tool command. Do not add any explanation.
-Do NOT use interactive commands (like 'nano', 'vim'). Use non-interactive commands like `cat` to read files.
-
-Tool format:
-- Run a shell command: `[RUN_COMMAND <command to run>]`
-- Search the web: `[SEARCH: your query]`. Current year: {}",
-            config.model_name, current_year
-        )
+    let strategy = TruncationStrategy::from_env();
+    let tool_policy = permissions::ToolPolicy::from_env();
+
+    let mut bus = EventBus::new(&session_id);
+    bus.subscribe(Box::new(events::CliRenderer { show_reasoning: reasoning::show_reasoning(), show_latency: latency::show_latency() }));
+    bus.subscribe(Box::new(transcript_log::TranscriptLogger));
+    bus.subscribe(Box::new(audit::AuditLogger));
+    bus.subscribe(Box::new(usage::ToolUsageRecorder::new(open_db_at(&workspace::active().path))));
+
+    // The session header: settings shown under the banner, then notices and warnings about them,
+    // published together once everything below is resolved.
+    let mut startup_events = Vec::new();
+    let strategy = strategy.unwrap_or_else(|e| {
+        startup_events.push(Event::Warning(format!("{}; using drop-oldest.", e)));
+        TruncationStrategy::DropOldest
+    });
+    let mut settings = vec![(
+        "Context strategy",
+        format!("{} ({})", strategy.name(), context::ContextBudget::from_env(&config.model_name).describe()),
+    )];
+    let capabilities = capabilities::for_model(&config.model_name);
+    if capabilities != capabilities::Capabilities::default() {
+        settings.push(("Capabilities", capabilities.describe()));
+    }
+    if tool_policy.is_restricted() {
+        settings.push(("Tools allowed", format!("{} ({}): {}", tool_policy.user, tool_policy.role, tool_policy.describe())));
+    }
+    if options.retention != retention::Retention::Keep {
+        settings.push(("Retention", options.retention.describe()));
+    }
+    if !config.reasoning.is_default() {
+        settings.push(("Reasoning", config.reasoning.describe()));
+    }
+    if !config.sampling.is_default() {
+        settings.push(("Sampling", config.sampling.describe()));
+    }
+    if options.voice_mode {
+        settings.push(("Voice mode", "press Enter on an empty line to record.".to_string()));
+    }
+    if !options.fanout.is_empty() {
+        let providers: Vec<String> = std::iter::once(config).chain(&options.fanout).map(ApiConfig::describe).collect();
+        settings.push(("Fan-out", format!("{} (tools off)", providers.join(", "))));
+    }
+    if let Some(suggester) = &options.suggestions {
+        settings.push(("Follow-up suggestions", suggester.describe()));
+    }
+    if pricing::Catalog::load().price_for(&config.model_name).is_none() {
+        startup_events.push(Event::Notice(format!("No price known for {}; replies are not costed (set PRICE_{}=<input>,<output>).", config.model_name, prompts::model_key(&config.model_name))));
+    }
+    let postprocess = match postprocess::Pipeline::for_model(&config.model_name) {
+        Ok(pipeline) => pipeline,
+        Err(e) => {
+            startup_events.push(Event::Warning(format!("Post-processing disabled: {}", e)));
+            postprocess::Pipeline::default()
+        }
+    };
+    if !postprocess.is_empty() {
+        settings.push(("Post-processing", postprocess.describe()));
+    }
+    // Email is one of the tools the web search answer turns on, so without it the model is never
+    // told the tool exists.
+    let smtp = match email::SmtpConfig::from_env() {
+        Ok(Some(_)) if !web_search_enabled => {
+            let reason = if options.fanout.is_empty() { "web search (and with it tool use) is off" } else { "tools are not offered in fan-out mode" };
+            startup_events.push(Event::Notice(format!("Email tool off for this session: {}.", reason)));
+            None
+        }
+        Ok(smtp) => smtp,
+        Err(e) => {
+            startup_events.push(Event::Warning(format!("Email tool disabled: {}", e)));
+            None
+        }
+    };
+    if let Some(smtp) = &smtp {
+        settings.push(("Email", format!("as {} via {}:{} to {}", smtp.from, smtp.host, smtp.port, smtp.describe_allowed())));
+    }
+    // Tool calls are declared natively where the provider supports it; replies may still use
+    // the text markers the system prompt describes.
+    let native_tools = if web_search_enabled && native_tool_calling(config) {
+        tool_args::offered(browser::enabled(), smtp.is_some())
     } else {
-        format!("You are an AI assistant powered by the {} model.", config.model_name)
+        Vec::new()
     };
+    if !native_tools.is_empty() {
+        settings.push(("Tool calling", format!("native ({})", native_tools.join(", "))));
+    }
+
+    let prompts::SystemPrompt { text: mut system_prompt, variant } = prompts::system_prompt(&config.model_name, web_search_enabled);
+    prompts::record_session_prompt(conn, &session_id, &config.provider.env_name().to_lowercase(), &config.model_name, &variant);
+    if variant != "base" {
+        settings.push(("System prompt", variant.clone()));
+    }
+    if web_search_enabled && browser::enabled() {
+        system_prompt.push_str(&format!("\n{}", prompts::browse_instruction()));
+    }
+    if let Some(smtp) = &smtp {
+        system_prompt.push_str(&format!("\n{}", prompts::email_instruction(&smtp.describe_allowed())));
+    }
+    if let Some(schema) = &options.output_schema {
+        system_prompt.push_str(&format!("\n\n{}", schema.instruction()));
+        settings.push(("Structured output", format!("{} ({} retries)", schema.name, structured::repair_attempts())));
+    }
+
+    let mut expected_language = None;
+    if let Some(language) = prompts::response_language() {
+        system_prompt.push_str(&format!("\n\n{}", prompts::language_instruction(&language)));
+        prompts::record_response_language(conn, &session_id, &language);
+        expected_language = language::parse(&language);
+        if expected_language.is_none() {
+            startup_events.push(Event::Warning(format!("Reply language not checked: '{}' is not a recognised language name or ISO 639-3 code", language)));
+        }
+        settings.push(("Response language", language));
+    }
+
+    if project::project_context_enabled() {
+        match project::project_brief() {
+            Some(brief) => {
+                system_prompt.push_str(&format!("\n\nProject context:\n{}", brief));
+                settings.push(("Project context", format!("attached ({} characters)", brief.chars().count())));
+            }
+            None => settings.push(("Project context", "not inside a git repository".to_string())),
+        }
+    }
 
     let mut history = vec![
         Message { role: "system".to_string(), content: system_prompt }
     ];
+    let exemplars = if web_search_enabled {
+        match prompts::tool_exemplars(&config.model_name) {
+            Ok(exemplars) => exemplars,
+            Err(e) => {
+                startup_events.push(Event::Warning(format!("Tool exemplars not loaded: {}", e)));
+                Vec::new()
+            }
+        }
+    } else {
+        Vec::new()
+    };
+    if !exemplars.is_empty() {
+        settings.push(("Tool exemplars", format!("{} message(s)", exemplars.len())));
+        history.extend(exemplars.iter().cloned());
+    }
+    bus.publish(Event::SessionStarted { model: config.model_name.clone(), settings });
+    for event in startup_events {
+        bus.publish(event);
+    }
+    // Each fan-out provider keeps its own side of the conversation.
+    let mut fanout_histories: Vec<Vec<Message>> = options.fanout.iter().map(|_| history.clone()).collect();
+    let mut speak_replies = voice::tts_enabled_by_default();
+    let snippets = snippets::all();
+    let catalog = pricing::Catalog::load();
+    // Follow-ups offered after the last answer, picked by typing their number.
+    let mut follow_ups: Vec<String> = Vec::new();
 
     loop {
         print!("{} ", "You:".bold().blue());
         io::stdout().flush().unwrap();
-        let mut user_input = String::new();
-        io::stdin().read_line(&mut user_input).unwrap();
-        let user_input = user_input.trim();
+        let mut line = String::new();
+        io::stdin().read_line(&mut line).unwrap();
+        let mut user_input = line.trim().to_string();
+        if let Some(prompt) = suggestions::pick(&user_input, &follow_ups) {
+            bus.publish(Event::Notice(format!("Asking: {}", prompt)));
+            user_input = prompt;
+        }
+
+        if user_input == "/snippets" {
+            if snippets.is_empty() {
+                bus.publish(Event::Notice("No snippets configured. Add SNIPPET_<NAME>=text to .env.".to_string()));
+            }
+            for (name, text) in &snippets {
+                bus.publish(Event::Notice(format!("!{}: {}", name, text.trim())));
+            }
+            continue;
+        }
+        let (expanded, used) = snippets::expand(&user_input, &snippets);
+        if !used.is_empty() {
+            bus.publish(Event::Notice(format!("Expanded {}", used.iter().map(|name| format!("!{}", name)).collect::<Vec<_>>().join(", "))));
+            user_input = expanded;
+        }
+
+        if user_input == "/set" || user_input.starts_with("/set ") {
+            let rest = user_input["/set".len()..].trim();
+            let mut sampling = session_config.sampling.clone();
+            let result = match rest.split_once(' ') {
+                _ if rest.is_empty() => Ok(()),
+                Some((name, value)) => sampling.set(name, value),
+                None => Err(format!("Usage: /set <{}> <value|default>", sampling::SETTINGS.join("|"))),
+            };
+            match result {
+                Ok(()) => {
+                    if sampling != session_config.sampling {
+                        sampling::record(conn, &session_id, &sampling);
+                        session_config.set_sampling(sampling);
+                    }
+                    let current = session_config.sampling.describe();
+                    bus.publish(Event::Notice(format!("Sampling: {}", if current.is_empty() { "provider defaults" } else { &current })));
+                }
+                Err(e) => bus.publish(Event::Warning(e)),
+            }
+            continue;
+        }
+        let config = &session_config;
+
+        if user_input == "/voice" || (options.voice_mode && user_input.is_empty()) {
+            bus.publish(Event::Notice("Recording...".to_string()));
+            match voice::record_and_transcribe().await {
+                Ok(transcript) => {
+                    bus.publish(Event::VoiceTranscribed(transcript.clone()));
+                    user_input = transcript;
+                }
+                Err(e) => {
+                    bus.publish(Event::Warning(format!("Voice input failed ({})", e)));
+                    continue;
+                }
+            }
+        }
+
+        if user_input == "/paste" || user_input.starts_with("/paste ") {
+            let prompt = user_input["/paste".len()..].trim().to_string();
+            match capture::read_clipboard() {
+                Ok(text) if !text.trim().is_empty() => {
+                    let total = text.chars().count();
+                    let max_chars = capture::paste_max_chars();
+                    let text = match capture::cut_at_chars(&text, max_chars) {
+                        Some(cut) => {
+                            bus.publish(Event::Notice(format!(
+                                "The clipboard holds {} characters; attached the first {} (PASTE_MAX_CHARS).",
+                                total, max_chars
+                            )));
+                            format!("{}\n[truncated at {} of {} characters]", cut, max_chars, total)
+                        }
+                        None => {
+                            bus.publish(Event::Notice(format!("Attached {} characters from the clipboard.", total)));
+                            text
+                        }
+                    };
+                    user_input = capture::as_context_message("Clipboard contents", &text, &prompt);
+                }
+                Ok(_) => {
+                    bus.publish(Event::Warning("The clipboard is empty.".to_string()));
+                    continue;
+                }
+                Err(e) => {
+                    bus.publish(Event::Warning(format!("Could not read the clipboard ({})", e)));
+                    continue;
+                }
+            }
+        }
+
+        if let Some(rest) = user_input.strip_prefix("/tmux ") {
+            let (target, prompt) = capture::split_argument(rest);
+            match capture::capture_tmux_pane(target) {
+                Ok(text) => {
+                    bus.publish(Event::Notice(format!("Attached {} characters from tmux pane {}.", text.chars().count(), target)));
+                    user_input = capture::as_context_message(&format!("Contents of tmux pane {}", target), &text, prompt);
+                }
+                Err(e) => {
+                    bus.publish(Event::Warning(format!("Could not capture tmux pane ({})", e)));
+                    continue;
+                }
+            }
+        }
+
+        if user_input == "/screenshot" || user_input.starts_with("/screenshot ") || user_input.starts_with("/ocr ") {
+            let (label, text, prompt) = match user_input.strip_prefix("/ocr ") {
+                Some(rest) => {
+                    let (image, prompt) = capture::split_argument(rest);
+                    (format!("Text read from {}", image), capture::image_text(std::path::Path::new(image)), prompt.to_string())
+                }
+                None => ("Text read from a screenshot".to_string(), capture::screenshot_text(), user_input["/screenshot".len()..].trim().to_string()),
+            };
+            match text {
+                Ok(text) if !text.trim().is_empty() => {
+                    bus.publish(Event::Notice(format!("Attached {} characters of OCR text.", text.chars().count())));
+                    user_input = capture::as_context_message(&label, &text, &prompt);
+                }
+                Ok(_) => {
+                    bus.publish(Event::Warning("OCR found no text in the image.".to_string()));
+                    continue;
+                }
+                Err(e) => {
+                    bus.publish(Event::Warning(format!("Could not read text from the image ({})", e)));
+                    continue;
+                }
+            }
+        }
+
+        if let Some(rest) = user_input.strip_prefix("/image ") {
+            let (source, prompt) = capture::split_argument(rest);
+            let client = reqwest::Client::builder()
+                .timeout(Duration::from_secs(90))
+                .build()
+                .unwrap();
+            match attachments::load(&client, source).await {
+                Ok(image) => {
+                    bus.publish(Event::Notice(format!("Attached image {}.", source)));
+                    user_input = format!("{}\n\n{}", prompt, image).trim_start().to_string();
+                }
+                Err(e) => {
+                    bus.publish(Event::Warning(format!("Could not attach the image ({})", e)));
+                    continue;
+                }
+            }
+        }
+
+        if let Some(rest) = user_input.strip_prefix("/ingest ") {
+            let (dir, prompt) = capture::split_argument(rest);
+            let report = ingest::ingest_directory(std::path::Path::new(dir), ingest::IngestLimits::from_env());
+            bus.publish(Event::Notice(format!("Ingested {} file(s), {} bytes from {}.", report.included.len(), report.total_bytes(), dir)));
+            for (path, reason) in &report.excluded {
+                bus.publish(Event::Notice(format!("Skipped {} ({})", path, reason)));
+            }
+            if report.included.is_empty() {
+                continue;
+            }
+            user_input = capture::as_context_message(&format!("Files from {}", dir), &report.contents, prompt);
+        }
 
         if user_input.is_empty() {
             continue;
         }
         
         if user_input.eq_ignore_ascii_case("exit") || user_input.eq_ignore_ascii_case("quit") {
-            println!("{}", "Session ended.".bold().yellow());
+            bus.publish(Event::SessionEnded);
+            if options.retention == retention::Retention::Ephemeral {
+                retention::delete_session(conn, &session_id);
+            }
             break;
         }
 
+        if user_input == "/speak" {
+            speak_replies = !speak_replies;
+            bus.publish(Event::Notice(format!("Spoken replies {}.", if speak_replies { "on" } else { "off" })));
+            continue;
+        }
+
+        if let Some(rest) = user_input.strip_prefix("/checkpoint ") {
+            save_checkpoint(conn, &session_id, rest.trim());
+            bus.publish(Event::Notice(format!("Checkpoint '{}' saved.", rest.trim())));
+            continue;
+        }
+
+        if let Some(rest) = user_input.strip_prefix("/restore ") {
+            match restore_checkpoint(conn, &session_id, rest.trim()) {
+                Some(restored) => {
+                    history.truncate(1);
+                    history.extend(exemplars.iter().cloned());
+                    history.extend(restored);
+                    bus.publish(Event::Notice(format!("Restored checkpoint '{}'.", rest.trim())));
+                }
+                None => bus.publish(Event::Warning(format!("No checkpoint named '{}'.", rest.trim()))),
+            }
+            continue;
+        }
+
+        if let Some((command, rest)) = user_input.split_once(' ').filter(|(c, _)| *c == "/remind" || *c == "/schedule") {
+            match schedule::split_when(rest).and_then(|(when, text)| Ok((schedule::parse_when(&when)?, text))) {
+                Ok((when, text)) => {
+                    let action = if command == "/remind" { schedule::Action::Remind(text) } else { schedule::Action::Command(text) };
+                    let id = schedule::create(conn, &session_id, &when, &action);
+                    bus.publish(Event::Notice(format!(
+                        "Scheduled job {}: {} at {}. Due jobs run while `agent_bench daemon` is running.",
+                        id,
+                        action.describe(),
+                        when.describe()
+                    )));
+                }
+                Err(e) => bus.publish(Event::Warning(format!("Could not schedule ({}).", e))),
+            }
+            continue;
+        }
+
+        if user_input == "/meta" || user_input.starts_with("/meta ") {
+            let rest = user_input["/meta".len()..].trim();
+            if rest.is_empty() {
+                let meta = session_meta::get(conn, &session_id);
+                bus.publish(Event::Notice(if meta.is_empty() {
+                    "No metadata on this session; add some with /meta key=value.".to_string()
+                } else {
+                    format!("Metadata: {}", session_meta::describe(&meta))
+                }));
+            } else if let Some(key) = rest.strip_prefix("unset ") {
+                if session_meta::unset(conn, &session_id, key.trim()) {
+                    bus.publish(Event::Notice(format!("Removed {}.", key.trim())));
+                } else {
+                    bus.publish(Event::Warning(format!("{} is not set.", key.trim())));
+                }
+            } else {
+                match session_meta::parse_entry(rest) {
+                    Ok((key, value)) => {
+                        session_meta::set(conn, &session_id, &key, &value);
+                        bus.publish(Event::Notice(format!("Set {}.", session_meta::describe(&[(key, value)]))));
+                    }
+                    Err(e) => bus.publish(Event::Warning(e)),
+                }
+            }
+            continue;
+        }
+
+        if user_input == "/jobs" {
+            let jobs = schedule::list(conn, Some(&session_id), false);
+            if jobs.is_empty() {
+                bus.publish(Event::Notice("No scheduled jobs in this session.".to_string()));
+            }
+            for job in jobs {
+                bus.publish(Event::Notice(format!("Job {}: {} at {}", job.id, job.action.describe(), job.describe_schedule())));
+            }
+            continue;
+        }
+
+        if let Some(rest) = user_input.strip_prefix("/translate ") {
+            let language = rest.trim();
+            // Skip the system prompt and any exemplars so only real answers are translated.
+            let Some(last_answer) = history.iter().skip(1 + exemplars.len()).rev().find(|m| m.role == "assistant") else {
+                bus.publish(Event::Warning("There is no answer to translate yet.".to_string()));
+                continue;
+            };
+            let client = reqwest::Client::builder()
+                .timeout(Duration::from_secs(90))
+                .build()
+                .unwrap();
+            match call_llm(&client, config, &prompts::translation_request(&last_answer.content, language)).await {
+                Ok(translation) => {
+                    bus.publish(Event::AssistantReply(translation.clone()));
+                    history.push(Message { role: "assistant".to_string(), content: translation.clone() });
+                    save_message(conn, &session_id, "assistant", &translation);
+                    bus.publish(Event::MessageSaved { role: "assistant".to_string(), content: translation });
+                }
+                Err(e) => bus.publish(Event::ApiError { context: "API Error during translation".to_string(), error: e.to_string() }),
+            }
+            continue;
+        }
+
+        if recall::duplicate_check_enabled() && let Some(past) = recall::find_similar_question(conn, &attachments::without_images(&user_input)) {
+            bus.publish(Event::Notice(format!(
+                "You asked this before ({:.0}% similar, session {} at {}): {}",
+                past.similarity * 100.0, past.session_id, past.asked_at, past.question
+            )));
+            bus.publish(Event::AssistantReply(past.answer.clone()));
+            print!("Press Enter to reuse this answer, or type 'a' to ask anyway: ");
+            io::stdout().flush().unwrap();
+            let mut choice = String::new();
+            io::stdin().read_line(&mut choice).unwrap();
+            if !choice.trim().eq_ignore_ascii_case("a") {
+                for (role, content) in [("user", user_input.clone()), ("assistant", past.answer)] {
+                    history.push(Message { role: role.to_string(), content: content.clone() });
+                    save_message(conn, &session_id, role, &content);
+                    bus.publish(Event::MessageSaved { role: role.to_string(), content });
+                }
+                continue;
+            }
+        }
+
+        follow_ups.clear();
         history.push(Message { role: "user".to_string(), content: user_input.to_string() });
-        save_message(&conn, &session_id, "user", user_input);
+        save_message(conn, &session_id, "user", &user_input);
+        bus.publish(Event::MessageSaved { role: "user".to_string(), content: attachments::without_images(&user_input).into_owned() });
 
         let client = reqwest::Client::builder()
             .timeout(Duration::from_secs(90))
             .build()
             .unwrap();
 
-        match call_llm(&client, config, &history).await {
-            Ok(mut assistant_reply) => {
-                let trimmed_reply = assistant_reply.trim().trim_matches(|c| c == '\'' || c == '\"' || c == '`');
-
-                let mut tool_used = false;
+        fit_context(&client, config, &mut history, strategy, &mut bus).await;
 
-                if trimmed_reply.to_uppercase().starts_with("[RUN_COMMAND") {
-                    tool_used = true;
-                    let command_str = if let Some(pos) = trimmed_reply.find(' ') {
-                        trimmed_reply[pos..].trim_start().trim_end_matches(']')
-                    } else {
-                        ""
-                    };
+        if !options.fanout.is_empty() {
+            for (lane, lane_config) in fanout_histories.iter_mut().zip(&options.fanout) {
+                lane.push(Message { role: "user".to_string(), content: user_input.clone() });
+                fit_context(&client, lane_config, lane, strategy, &mut bus).await;
+            }
+            let lanes: Vec<ApiConfig> = options.fanout.iter().map(|lane| ApiConfig { sampling: config.sampling.clone(), ..lane.for_turn(&[], options.output_schema.as_ref()) }).collect();
+            let primary = config.for_turn(&[], options.output_schema.as_ref());
+            let configs: Vec<&ApiConfig> = std::iter::once(&primary).chain(&lanes).collect();
+            let histories = std::iter::once(&mut history).chain(fanout_histories.iter_mut());
+            fan_out_turn(conn, &session_id, &client, &configs, histories.collect(), expected_language, &mut bus).await;
+            continue;
+        }
 
-                    if command_str.is_empty() {
-                        println!("{} {}", "System:".bold().magenta(), "No command provided for [RUN_COMMAND].".red());
-                        continue;
+        // Native JSON modes would rule out text tool calls, and Gemini rejects them alongside
+        // function declarations, so the schema constrains only the answer after tools.
+        let tool_config = config.for_turn(&native_tools, None);
+        let answer_config = config.for_turn(&[], options.output_schema.as_ref());
+        let mut turn_latency = latency::TurnLatency::start();
+        match call_llm_detailed(&client, &tool_config, &history).await {
+            Ok(LlmReply { text, mut finish_reason, mut reasoning, failover, timing, usage, mut logprobs, mut seed }) => {
+                turn_latency.add_call(&timing);
+                let mut turn_usage = usage;
+                let mut last_timing = timing;
+                let mut answered_by = note_failover(&mut bus, failover);
+                let mut assistant_reply = postprocess.apply(&text);
+                let mut raw_reply = text;
+                // Malformed tool calls are sent back for correction without touching the history.
+                let mut attempts = 0;
+                let mut format_error = tool_format::check_tool_call(&assistant_reply, web_search_enabled).err();
+                while let Some(error) = format_error.clone() {
+                    if attempts == tool_format::repair_attempts() {
+                        break;
                     }
+                    attempts += 1;
+                    bus.publish(Event::Notice(format!(
+                        "Malformed tool call ({}); asking the model to retry ({}/{}).",
+                        error, attempts, tool_format::repair_attempts()
+                    )));
+                    let mut request = history.clone();
+                    request.push(Message { role: "assistant".to_string(), content: assistant_reply.clone() });
+                    request.push(Message { role: "system".to_string(), content: tool_format::correction(&error, web_search_enabled) });
+                    match call_llm_detailed(&client, &tool_config, &request).await {
+                        Ok(reply) => {
+                            turn_latency.add_call(&reply.timing);
+                            turn_usage = usage::sum(turn_usage, reply.usage);
+                            last_timing = reply.timing;
+                            assistant_reply = postprocess.apply(&reply.text);
+                            raw_reply = reply.text;
+                            finish_reason = reply.finish_reason;
+                            reasoning = reply.reasoning;
+                            logprobs = reply.logprobs;
+                            seed = reply.seed;
+                            answered_by = note_failover(&mut bus, reply.failover);
+                            format_error = tool_format::check_tool_call(&assistant_reply, web_search_enabled).err();
+                        }
+                        Err(e) => {
+                            bus.publish(Event::ApiError { context: "API Error during tool call repair".to_string(), error: e.to_string() });
+                            break;
+                        }
+                    }
+                }
 
-                    println!("{} Running command: {}", "System:".bold().magenta(), command_str.magenta());
+                let mut tool_used = false;
+                let tools_started = Instant::now();
+
+                // A call still malformed after repair is not run. A bad format is shown as text; bad
+                // arguments go back to the model as JSON so it can answer without the tool.
+                let tool_call = match &format_error {
+                    None => tool_args::parse(&assistant_reply, web_search_enabled).ok().flatten(),
+                    Some(tool_format::ToolCallError::Format(error)) => {
+                        bus.publish(Event::Warning(format!("Tool call still malformed ({}); showing the reply as text.", error)));
+                        None
+                    }
+                    Some(tool_format::ToolCallError::Arguments(error)) => {
+                        bus.publish(Event::Warning(format!("Invalid tool call ({}); asking the model to answer without it.", error)));
+                        tool_used = true;
+                        history.push(Message { role: "assistant".to_string(), content: assistant_reply.clone() });
+                        history.push(Message { role: "system".to_string(), content: tool_format::rejection(error) });
+                        None
+                    }
+                };
 
-                    let output = std::process::Command::new("sh")
-                        .arg("-c")
-                        .arg(command_str)
-                        .output()
-                        .expect("failed to execute process");
+                if let Some(tool) = tool_call.as_ref().map(ToolCall::name).filter(|t| !tool_policy.allows(t)) {
+                    tool_used = true;
+                    bus.publish(Event::ToolDenied {
+                        tool: tool.to_string(),
+                        user: tool_policy.user.clone(),
+                        role: tool_policy.role.clone(),
+                    });
+                    history.push(Message { role: "assistant".to_string(), content: assistant_reply.clone() });
+                    history.push(Message { role: "system".to_string(), content: format!("The {} tool is not permitted for this user. Answer without it.", tool) });
+                } else if let Some(ToolCall::RunCommand(args)) = &tool_call {
+                    tool_used = true;
+                    bus.publish(Event::ToolRequested { tool: "run_command".to_string(), input: args.command.clone() });
 
-                    let result = if output.status.success() {
-                        String::from_utf8_lossy(&output.stdout).to_string()
-                    } else {
-                        String::from_utf8_lossy(&output.stderr).to_string()
+                    let mut command = std::process::Command::new("sh");
+                    command.arg("-c").arg(&args.command);
+                    if let Some(workdir) = &args.workdir {
+                        command.current_dir(workdir);
+                    }
+                    let (result, success, exit_code) = match command.output() {
+                        Ok(output) if output.status.success() => (String::from_utf8_lossy(&output.stdout).to_string(), true, output.status.code()),
+                        Ok(output) => (String::from_utf8_lossy(&output.stderr).to_string(), false, output.status.code()),
+                        Err(e) => (format!("Failed to run the command: {}", e), false, None),
                     };
-                    
-                    println!("{}\n{}", "Assistant:".bold().green(), result.green());
+                    bus.publish(Event::ToolCompleted {
+                        tool: "run_command".to_string(),
+                        input: args.command.clone(),
+                        output: result.clone(),
+                        success,
+                        exit_code,
+                    });
+
                     history.push(Message { role: "assistant".to_string(), content: assistant_reply.clone() });
                     history.push(Message { role: "system".to_string(), content: format!("Command output:\n{}", result) });
-                } else if web_search_enabled && trimmed_reply.to_uppercase().starts_with("[SEARCH:") {
+                } else if let Some(ToolCall::WebSearch(args)) = &tool_call {
                     tool_used = true;
-                    let query_part = trimmed_reply.splitn(2, ':').nth(1).unwrap_or("").trim_end_matches(']');
-                    println!("{} Searching the web for: {}", "System:".bold().magenta(), query_part.magenta());
-                    
-                    let search_results = web_search(query_part).await.unwrap_or_else(|e| format!("Failed to perform web search: {}", e));
+                    let query_part = args.query.as_str();
+                    bus.publish(Event::ToolRequested { tool: "web_search".to_string(), input: query_part.to_string() });
+
+                    let search = web_search(query_part).await;
+                    let search_results = match &search {
+                        Ok(body) => body.clone(),
+                        Err(e) => format!("Failed to perform web search: {}", e),
+                    };
+                    bus.publish(Event::ToolCompleted {
+                        tool: "web_search".to_string(),
+                        input: query_part.to_string(),
+                        output: search_results.clone(),
+                        success: search.is_ok(),
+                        exit_code: None,
+                    });
                     let tool_result_prompt = format!("Web search results for '{}':\n{}", query_part, search_results);
                     history.push(Message { role: "assistant".to_string(), content: assistant_reply.clone() });
                     history.push(Message { role: "system".to_string(), content: tool_result_prompt });
+                } else if let Some(ToolCall::Browse(args)) = &tool_call {
+                    tool_used = true;
+                    let input = match &args.actions {
+                        Some(actions) => format!("{} ({})", args.url, actions),
+                        None => args.url.clone(),
+                    };
+                    bus.publish(Event::ToolRequested { tool: "browse".to_string(), input: input.clone() });
+                    let page = browser::browse(&args.url, args.actions.as_deref().unwrap_or_default());
+                    let result = match &page {
+                        Ok(text) => text.clone(),
+                        Err(e) => format!("Failed to browse: {}", e),
+                    };
+                    bus.publish(Event::ToolCompleted {
+                        tool: "browse".to_string(),
+                        input,
+                        output: result.clone(),
+                        success: page.is_ok(),
+                        exit_code: None,
+                    });
+                    history.push(Message { role: "assistant".to_string(), content: assistant_reply.clone() });
+                    history.push(Message { role: "system".to_string(), content: format!("Rendered page {}:\n{}", args.url, result) });
+                } else if let Some(ToolCall::Schedule(args)) = &tool_call {
+                    tool_used = true;
+                    let action = match (&args.command, &args.reminder) {
+                        (Some(command), _) => schedule::Action::Command(command.clone()),
+                        (None, reminder) => schedule::Action::Remind(reminder.clone().unwrap_or_default()),
+                    };
+                    bus.publish(Event::ToolRequested { tool: "schedule".to_string(), input: format!("{} {}", args.when, action.describe()) });
+                    // A scheduled command runs later without asking, so it needs the same grant as
+                    // running it now.
+                    let result = if matches!(action, schedule::Action::Command(_)) && !tool_policy.allows("run_command") {
+                        bus.publish(Event::ToolDenied {
+                            tool: "run_command".to_string(),
+                            user: tool_policy.user.clone(),
+                            role: tool_policy.role.clone(),
+                        });
+                        Err("The run_command tool is not permitted for this user, so commands cannot be scheduled.".to_string())
+                    } else {
+                        schedule::parse_when(&args.when).map(|when| {
+                            let id = schedule::create(conn, &session_id, &when, &action);
+                            format!("Scheduled job {}: {} at {}.", id, action.describe(), when.describe())
+                        })
+                    };
+                    let output = result.clone().unwrap_or_else(|e| e);
+                    bus.publish(Event::ToolCompleted {
+                        tool: "schedule".to_string(),
+                        input: args.when.clone(),
+                        output: output.clone(),
+                        success: result.is_ok(),
+                        exit_code: None,
+                    });
+                    history.push(Message { role: "assistant".to_string(), content: assistant_reply.clone() });
+                    history.push(Message { role: "system".to_string(), content: output });
+                } else if let Some(ToolCall::SendEmail(args)) = &tool_call {
+                    tool_used = true;
+                    bus.publish(Event::ToolRequested { tool: "send_email".to_string(), input: format!("to {}: {}", args.to, args.subject) });
+                    // Every email is shown in full and needs an explicit yes; nothing is sent
+                    // on the model's word alone.
+                    let result = match &smtp {
+                        None => Err("The send_email tool is not configured (SMTP_HOST is unset).".to_string()),
+                        Some(smtp) => smtp.recipients(&args.to).and_then(|recipients| {
+                            bus.publish(Event::Notice(format!(
+                                "Email draft:\nFrom: {}\nTo: {}\nSubject: {}\n\n{}",
+                                smtp.from,
+                                recipients.join(", "),
+                                args.subject,
+                                args.body
+                            )));
+                            print!("Send this email? (y/n): ");
+                            io::stdout().flush().unwrap();
+                            let mut answer = String::new();
+                            io::stdin().read_line(&mut answer).unwrap();
+                            if answer.trim().eq_ignore_ascii_case("y") {
+                                smtp.send(&recipients, &args.subject, &args.body).map(|reply| format!("Email sent to {} ({}).", recipients.join(", "), reply))
+                            } else {
+                                Err("The user declined to send this email. Do not retry unless asked.".to_string())
+                            }
+                        }),
+                    };
+                    let output = result.clone().unwrap_or_else(|e| format!("Email not sent: {}", e));
+                    bus.publish(Event::ToolCompleted {
+                        tool: "send_email".to_string(),
+                        input: format!("to {}: {}", args.to, args.subject),
+                        output: output.clone(),
+                        success: result.is_ok(),
+                        exit_code: None,
+                    });
+                    bus.publish(Event::Notice(output.clone()));
+                    history.push(Message { role: "assistant".to_string(), content: assistant_reply.clone() });
+                    history.push(Message { role: "system".to_string(), content: output });
+                } else if let Some(ToolCall::Calculate(args)) = &tool_call {
+                    tool_used = true;
+                    bus.publish(Event::ToolRequested { tool: "calculate".to_string(), input: args.expression.clone() });
+                    let calculation = calculate::evaluate(&args.expression);
+                    let result = match &calculation {
+                        Ok(value) => value.clone(),
+                        Err(e) => format!("Calculation failed: {}", e),
+                    };
+                    bus.publish(Event::ToolCompleted {
+                        tool: "calculate".to_string(),
+                        input: args.expression.clone(),
+                        output: result.clone(),
+                        success: calculation.is_ok(),
+                        exit_code: None,
+                    });
+                    history.push(Message { role: "assistant".to_string(), content: assistant_reply.clone() });
+                    history.push(Message { role: "system".to_string(), content: format!("Calculation result for '{}':\n{}", args.expression, result) });
                 }
 
                 if tool_used {
-                    match call_llm(&client, config, &history).await {
+                    turn_latency.add_tools(tools_started.elapsed());
+                    fit_context(&client, config, &mut history, strategy, &mut bus).await;
+                    match call_llm_detailed(&client, &answer_config, &history).await {
                         Ok(final_reply) => {
-                            assistant_reply = final_reply;
+                            turn_latency.add_call(&final_reply.timing);
+                            turn_usage = usage::sum(turn_usage, final_reply.usage);
+                            last_timing = final_reply.timing;
+                            assistant_reply = postprocess.apply(&final_reply.text);
+                            raw_reply = final_reply.text;
+                            finish_reason = final_reply.finish_reason;
+                            reasoning = final_reply.reasoning;
+                            logprobs = final_reply.logprobs;
+                            seed = final_reply.seed;
+                            answered_by = note_failover(&mut bus, final_reply.failover);
                         }
                         Err(e) => {
-                             println!("Assistant: {} ({})", "API Error after tool use".red(), e.to_string().red());
+                            bus.publish(Event::ApiError { context: "API Error after tool use".to_string(), error: e.to_string() });
                             continue;
                         }
                     }
                 }
 
-                println!("{} {}\n", "Assistant:".bold().green(), assistant_reply.green());
+                // Answers that do not match the output schema are sent back with the errors.
+                let mut schema_error = None;
+                if let Some(schema) = &options.output_schema {
+                    let mut attempts = 0;
+                    schema_error = schema.check(&assistant_reply).err();
+                    while let Some(error) = schema_error.clone() {
+                        if attempts == structured::repair_attempts() {
+                            bus.publish(Event::Warning(format!("Reply still does not match the schema ({}).", error)));
+                            break;
+                        }
+                        attempts += 1;
+                        bus.publish(Event::Notice(format!(
+                            "Reply does not match the schema ({}); asking the model to retry ({}/{}).",
+                            error, attempts, structured::repair_attempts()
+                        )));
+                        let mut request = history.clone();
+                        request.push(Message { role: "assistant".to_string(), content: assistant_reply.clone() });
+                        request.push(Message { role: "system".to_string(), content: structured::correction(&error) });
+                        match call_llm_detailed(&client, &answer_config, &request).await {
+                            Ok(reply) => {
+                                turn_latency.add_call(&reply.timing);
+                                turn_usage = usage::sum(turn_usage, reply.usage);
+                                last_timing = reply.timing;
+                                assistant_reply = postprocess.apply(&reply.text);
+                                raw_reply = reply.text;
+                                finish_reason = reply.finish_reason;
+                                reasoning = reply.reasoning;
+                                logprobs = reply.logprobs;
+                                seed = reply.seed;
+                                answered_by = note_failover(&mut bus, reply.failover);
+                                schema_error = schema.check(&assistant_reply).err();
+                            }
+                            Err(e) => {
+                                bus.publish(Event::ApiError { context: "API Error during schema repair".to_string(), error: e.to_string() });
+                                break;
+                            }
+                        }
+                    }
+                }
+
+                if let Some(reasoning) = &reasoning {
+                    bus.publish(Event::Reasoning(reasoning.clone()));
+                }
+                bus.publish(Event::AssistantReply(assistant_reply.clone()));
+                let cost = turn_usage.and_then(|usage| {
+                    reply_cost(conn, &session_id, &catalog, answered_by.as_deref().unwrap_or(&config.describe()), &usage)
+                });
+                if let Some(usage) = turn_usage {
+                    bus.publish(Event::TokenUsage(usage, cost.clone()));
+                }
+                turn_latency.finish(&last_timing, tokenizer::for_model(&config.model_name).count(&assistant_reply));
+                bus.publish(Event::Latency(turn_latency));
+                if speak_replies && let Err(e) = voice::speak(&assistant_reply).await {
+                    bus.publish(Event::Warning(format!("Text-to-speech failed ({})", e)));
+                }
                 history.push(Message { role: "assistant".to_string(), content: assistant_reply.clone() });
-                save_message(&conn, &session_id, "assistant", &assistant_reply);
+                let message_id = save_message(conn, &session_id, "assistant", &assistant_reply);
+                outcome::record(conn, message_id, outcome::classify(&assistant_reply, finish_reason.as_deref(), format_error.is_some() || schema_error.is_some(), expected_language));
+                postprocess::record_raw(conn, message_id, &raw_reply, &assistant_reply);
+                if let Some(reasoning) = &reasoning {
+                    reasoning::record_message(conn, message_id, reasoning);
+                }
+                if let Some(answered_by) = &answered_by {
+                    failover::record(conn, message_id, answered_by);
+                }
+                if let Some(usage) = &turn_usage {
+                    usage::record_tokens(conn, message_id, usage, cost.map(|cost| cost.reply));
+                }
+                sampling::record_reply(conn, message_id, seed, logprobs.as_ref());
+                bus.publish(Event::MessageSaved { role: "assistant".to_string(), content: assistant_reply.clone() });
+                if let Some(suggester) = &options.suggestions {
+                    match call_llm(&client, suggester, &suggestions::request(&history)).await {
+                        Ok(reply) => {
+                            follow_ups = suggestions::parse(&reply);
+                            if !follow_ups.is_empty() {
+                                bus.publish(Event::FollowUps(follow_ups.clone()));
+                            }
+                        }
+                        Err(e) => bus.publish(Event::Warning(format!("Could not suggest follow-ups ({})", e))),
+                    }
+                }
             },
             Err(e) => {
-                println!("Assistant: {} ({})", "API Error".red(), e.to_string().red());
+                bus.publish(Event::ApiError { context: "API Error".to_string(), error: e.to_string() });
                 continue;
             }
         }
     }
 }
 
-#[tokio::main]
-async fn main() {
-    dotenv().ok();
+/// Claude model used by the Anthropic provider (`ANTHROPIC_MODEL`).
+fn anthropic_model() -> String {
+    env::var("ANTHROPIC_MODEL").unwrap_or_else(|_| "claude-sonnet-4-20250514".to_string())
+}
+
+/// Azure OpenAI chat completions URL for `AZURE_OPENAI_ENDPOINT`, `AZURE_OPENAI_DEPLOYMENT` and
+/// `AZURE_OPENAI_API_VERSION`.
+fn azure_openai_url() -> Result<String, String> {
+    Ok(format!(
+        "{}/openai/deployments/{}/chat/completions?api-version={}",
+        required_env("AZURE_OPENAI_ENDPOINT", "Azure OpenAI")?.trim_end_matches('/'),
+        azure_openai_deployment()?,
+        env::var("AZURE_OPENAI_API_VERSION").unwrap_or_else(|_| "2024-10-21".to_string()),
+    ))
+}
+
+fn azure_openai_deployment() -> Result<String, String> {
+    required_env("AZURE_OPENAI_DEPLOYMENT", "Azure OpenAI")
+}
+
+/// A setting the chosen provider cannot work without.
+fn required_env(var: &str, label: &str) -> Result<String, String> {
+    env::var(var).map_err(|_| format!("{} not set in .env for {}", var, label))
+}
+
+/// AWS region for Bedrock (`AWS_REGION`, then `AWS_DEFAULT_REGION`).
+fn bedrock_region() -> String {
+    env::var("AWS_REGION").or_else(|_| env::var("AWS_DEFAULT_REGION")).unwrap_or_else(|_| "us-east-1".to_string())
+}
+
+/// Bedrock model or inference profile id (`BEDROCK_MODEL_ID`).
+fn bedrock_model_id() -> String {
+    env::var("BEDROCK_MODEL_ID").unwrap_or_else(|_| "anthropic.claude-3-haiku-20240307-v1:0".to_string())
+}
+
+/// Bedrock Converse URL for `model` in `bedrock_region()`.
+fn bedrock_url(model: &str) -> String {
+    format!("https://bedrock-runtime.{}.amazonaws.com/model/{}/converse", bedrock_region(), urlencoding::encode(model))
+}
+
+/// The provider's `<NAME>_API_KEY`, required unless `AUTH_<NAME>` selects an auth plugin.
+fn static_api_key(provider: ApiProvider) -> Result<String, String> {
+    let var = format!("{}_API_KEY", provider.env_name());
+    match env::var(&var) {
+        Ok(key) => Ok(key),
+        Err(_) if auth::uses_plugin(provider.env_name()) => Ok(String::new()),
+        Err(_) => Err(format!("{} not set in .env for {}", var, provider.label())),
+    }
+}
+
+/// Vertex AI `generateContent` URL for `VERTEX_MODEL` in `VERTEX_PROJECT` (or
+/// `GOOGLE_CLOUD_PROJECT`) and `VERTEX_LOCATION`.
+fn vertex_url() -> Result<String, String> {
+    let project = env::var("VERTEX_PROJECT")
+        .or_else(|_| env::var("GOOGLE_CLOUD_PROJECT"))
+        .map_err(|_| "VERTEX_PROJECT not set in .env for Vertex AI".to_string())?;
+    let location = env::var("VERTEX_LOCATION").unwrap_or_else(|_| "us-central1".to_string());
+    let host = if location == "global" { "aiplatform.googleapis.com".to_string() } else { format!("{}-aiplatform.googleapis.com", location) };
+    Ok(format!(
+        "https://{}/v1/projects/{}/locations/{}/publishers/google/models/{}:generateContent",
+        host,
+        project,
+        location,
+        vertex_model()
+    ))
+}
+
+fn vertex_model() -> String {
+    env::var("VERTEX_MODEL").unwrap_or_else(|_| "gemini-2.0-flash".to_string())
+}
+
+/// `GEMINI_BACKEND=vertex` sends the Gemini option through Vertex AI, for organisations that
+/// block the generativelanguage.googleapis.com API-key flow.
+fn gemini_via_vertex() -> bool {
+    env::var("GEMINI_BACKEND").is_ok_and(|backend| backend.trim().eq_ignore_ascii_case("vertex"))
+}
+
+fn vertex_config() -> Result<ApiConfig, String> {
+    // Access tokens come from the service account or application-default credentials at send
+    // time.
+    Ok(ApiConfig::new(ApiProvider::Vertex, String::new(), vertex_url()?, vertex_model()))
+}
+
+/// Hugging Face chat completions URL: the dedicated Inference Endpoint in
+/// `HUGGINGFACE_ENDPOINT_URL`, otherwise the serverless Inference Providers router.
+fn huggingface_url() -> String {
+    match env::var("HUGGINGFACE_ENDPOINT_URL") {
+        Ok(endpoint) => format!("{}/v1/chat/completions", endpoint.trim_end_matches('/').trim_end_matches("/v1")),
+        Err(_) => "https://router.huggingface.co/v1/chat/completions".to_string(),
+    }
+}
+
+/// Hugging Face model (`HUGGINGFACE_MODEL`). Dedicated endpoints serve a single model, which TGI
+/// answers to as `tgi`.
+fn huggingface_model() -> String {
+    env::var("HUGGINGFACE_MODEL").unwrap_or_else(|_| {
+        if env::var("HUGGINGFACE_ENDPOINT_URL").is_ok() { "tgi" } else { "meta-llama/Llama-3.1-8B-Instruct" }.to_string()
+    })
+}
+
+/// Cohere model (`COHERE_MODEL`).
+fn cohere_model() -> String {
+    env::var("COHERE_MODEL").unwrap_or_else(|_| "command-r-plus".to_string())
+}
+
+/// Models offered when a provider's model list cannot be fetched; the first is the default
+/// when it is also listed.
+fn fallback_models(provider: ApiProvider) -> Vec<String> {
+    let models: &[&str] = match provider {
+        ApiProvider::OpenAI => &["gpt-4-turbo"],
+        ApiProvider::Sambanova => &["Meta-Llama-3.2-1B-Instruct"],
+        ApiProvider::Gemini => &["gemini-2.0-flash"],
+        ApiProvider::Anthropic => &["claude-sonnet-4-20250514"],
+        ApiProvider::Groq => &["llama-3.3-70b-versatile", "llama-3.1-8b-instant", "mixtral-8x7b-32768", "gemma2-9b-it"],
+        ApiProvider::Mistral => &["mistral-large-latest", "codestral-latest"],
+        ApiProvider::DeepSeek => &["deepseek-chat", "deepseek-reasoner"],
+        ApiProvider::Cohere => &["command-r-plus"],
+        ApiProvider::HuggingFace => &["meta-llama/Llama-3.1-8B-Instruct"],
+        _ => &[],
+    };
+    models.iter().map(|m| m.to_string()).collect()
+}
 
+/// Gemini API `generateContent` URL for `model`.
+fn gemini_url(model: &str) -> String {
+    format!("https://generativelanguage.googleapis.com/v1beta/models/{}:generateContent", model)
+}
+
+/// Model pinned with `<NAME>_MODEL`, which skips the model list.
+fn pinned_model(provider: ApiProvider) -> Option<String> {
+    env::var(format!("{}_MODEL", provider.env_name())).ok().map(|m| m.trim().to_string()).filter(|m| !m.is_empty())
+}
+
+/// Longest model list shown at once; longer lists are narrowed by name first.
+const MODEL_LIST_LIMIT: usize = 30;
+
+/// Picks a model from the provider's live model list, or from `fallback_models` when the list
+/// cannot be fetched.
+async fn choose_model(config: &ApiConfig) -> Option<String> {
+    let label = config.provider.label();
+    let fallback = fallback_models(config.provider);
+    let models = match list_models(&reqwest::Client::new(), config).await {
+        Ok(models) if !models.is_empty() => models,
+        Ok(_) => {
+            println!("{}", format!("{} reports no models.", label).red());
+            fallback.clone()
+        }
+        Err(e) => {
+            println!("{} ({})", format!("Could not list {} models", label).red(), e);
+            fallback.clone()
+        }
+    };
+    let default = fallback.first().filter(|d| models.contains(d)).map(String::as_str);
+    pick_model(label, &models, default)
+}
+
+/// Numbered model menu. Enter keeps the default and a full model id is taken as given.
+fn pick_model(label: &str, models: &[String], default: Option<&str>) -> Option<String> {
+    let read_line = || {
+        io::stdout().flush().unwrap();
+        let mut line = String::new();
+        io::stdin().read_line(&mut line).unwrap();
+        line.trim().to_string()
+    };
+    if models.is_empty() {
+        print!("Enter a model id for {}: ", label);
+        return Some(read_line()).filter(|m| !m.is_empty());
+    }
+
+    let mut shown: Vec<&String> = models.iter().collect();
+    if shown.len() > MODEL_LIST_LIMIT {
+        print!("{} models available from {}; type part of a name to narrow the list: ", models.len(), label);
+        let filter = read_line().to_lowercase();
+        shown.retain(|m| m.to_lowercase().contains(&filter));
+        if shown.is_empty() {
+            println!("{}", format!("No {} model matches '{}'.", label, filter).red());
+            return None;
+        }
+    }
+    println!("{}", format!("Select a model from {}:", label).bold().yellow());
+    for (i, model) in shown.iter().take(MODEL_LIST_LIMIT).enumerate() {
+        if default == Some(model.as_str()) {
+            println!("{}. {} (default)", i + 1, model);
+        } else {
+            println!("{}. {}", i + 1, model);
+        }
+    }
+    if shown.len() > MODEL_LIST_LIMIT {
+        println!("... and {} more; enter a full model id to use one of them.", shown.len() - MODEL_LIST_LIMIT);
+    }
+    match default {
+        Some(default) => print!("Enter your choice (Enter for {}): ", default),
+        None => print!("Enter your choice: "),
+    }
+    let choice = read_line();
+    if choice.is_empty() {
+        return default.map(str::to_string);
+    }
+    match choice.parse::<usize>() {
+        Ok(i) => shown.get(i.wrapping_sub(1)).filter(|_| i <= MODEL_LIST_LIMIT).map(|m| m.to_string()),
+        Err(_) => Some(choice),
+    }
+}
+
+/// Chat completions URL of a custom OpenAI-compatible server. `CUSTOM_BASE_URL` may be the API
+/// root (`http://localhost:8000/v1`) or the full endpoint.
+fn custom_url() -> Result<String, String> {
+    let base = required_env("CUSTOM_BASE_URL", "the custom provider")?;
+    let base = base.trim_end_matches('/');
+    Ok(if base.ends_with("/chat/completions") { base.to_string() } else { format!("{}/chat/completions", base) })
+}
+
+/// Ollama server address (`OLLAMA_HOST`).
+fn ollama_host() -> String {
+    env::var("OLLAMA_HOST").unwrap_or_else(|_| "http://localhost:11434".to_string()).trim_end_matches('/').to_string()
+}
+
+async fn select_provider() -> Option<ApiConfig> {
     println!("{}", "Select an API Provider:".bold().yellow());
     println!("1. OpenAI (gpt-4-turbo)");
     println!("2. Sambanova (Meta-Llama-3.2-1B-Instruct)");
-    println!("3. Google Gemini (gemini-2.0-flash)");
+    if gemini_via_vertex() {
+        println!("3. Google Gemini ({} via Vertex AI)", vertex_model());
+    } else {
+        println!("3. Google Gemini (gemini-2.0-flash)");
+    }
+    println!("4. Anthropic ({})", anthropic_model());
+    println!("5. Ollama (local models)");
+    println!("6. Azure OpenAI ({})", env::var("AZURE_OPENAI_DEPLOYMENT").unwrap_or_else(|_| "deployment from AZURE_OPENAI_DEPLOYMENT".to_string()));
+    println!("7. OpenRouter (any model)");
+    println!("8. Custom OpenAI-compatible endpoint ({})", env::var("CUSTOM_BASE_URL").unwrap_or_else(|_| "CUSTOM_BASE_URL".to_string()));
+    println!("9. Groq (llama-3.x, mixtral)");
+    println!("10. AWS Bedrock ({})", bedrock_model_id());
+    println!("11. Mistral (mistral-large, codestral)");
+    println!("12. Google Vertex AI ({})", vertex_model());
+    println!("13. DeepSeek (deepseek-chat, deepseek-reasoner)");
+    println!("14. Cohere ({})", cohere_model());
+    println!("15. Hugging Face ({})", huggingface_model());
     print!("Enter your choice: ");
     io::stdout().flush().unwrap();
 
     let mut choice = String::new();
     io::stdin().read_line(&mut choice).unwrap();
 
-    let config = match choice.trim() {
-        "1" => ApiConfig {
-            provider: ApiProvider::OpenAI,
-            api_key: env::var("OPENAI_API_KEY").expect("OPENAI_API_KEY not set in .env for OpenAI"),
-            base_url: "https://api.openai.com/v1/chat/completions".to_string(),
-            model_name: "gpt-4-turbo".to_string(),
+    let provider = match choice.trim() {
+        "1" => ApiProvider::OpenAI,
+        "2" => ApiProvider::Sambanova,
+        "3" => ApiProvider::Gemini,
+        "4" => ApiProvider::Anthropic,
+        "5" => ApiProvider::Ollama,
+        "6" => ApiProvider::AzureOpenAI,
+        "7" => ApiProvider::OpenRouter,
+        "8" => ApiProvider::Custom,
+        "9" => ApiProvider::Groq,
+        "10" => ApiProvider::Bedrock,
+        "11" => ApiProvider::Mistral,
+        "12" => ApiProvider::Vertex,
+        "13" => ApiProvider::DeepSeek,
+        "14" => ApiProvider::Cohere,
+        "15" => ApiProvider::HuggingFace,
+        _ => return None,
+    };
+    let mut config = match provider_config(provider) {
+        Ok(config) => config,
+        Err(e) => {
+            println!("{}", e.red());
+            return None;
+        }
+    };
+    if config.model_name.is_empty() {
+        let model = choose_model(&config).await?;
+        config.set_model(model);
+    }
+    Some(config)
+}
+
+/// Settings for `provider` from the environment, with the model left empty when it is to be
+/// picked from the provider's list.
+fn provider_config(provider: ApiProvider) -> Result<ApiConfig, String> {
+    let pinned = pinned_model(provider).unwrap_or_default();
+    Ok(match provider {
+        ApiProvider::OpenAI => ApiConfig::new(provider, static_api_key(provider)?, "https://api.openai.com/v1/chat/completions".to_string(), pinned),
+        ApiProvider::Sambanova => ApiConfig::new(provider, static_api_key(provider)?, "https://api.sambanova.ai/v1/chat/completions".to_string(), pinned),
+        ApiProvider::Gemini if gemini_via_vertex() => vertex_config()?,
+        // Rebuilt by `set_model` for a model picked from the list.
+        ApiProvider::Gemini => {
+            let base_url = gemini_url(if pinned.is_empty() { "gemini-2.0-flash" } else { &pinned });
+            ApiConfig::new(provider, static_api_key(provider)?, base_url, pinned)
+        }
+        ApiProvider::Anthropic => ApiConfig::new(provider, static_api_key(provider)?, "https://api.anthropic.com/v1/messages".to_string(), pinned),
+        ApiProvider::Ollama => ApiConfig::new(provider, String::new(), format!("{}/api/chat", ollama_host()), pinned),
+        ApiProvider::AzureOpenAI => ApiConfig::new(provider, static_api_key(provider)?, azure_openai_url()?, azure_openai_deployment()?),
+        ApiProvider::OpenRouter => ApiConfig::new(provider, static_api_key(provider)?, "https://openrouter.ai/api/v1/chat/completions".to_string(), pinned),
+        ApiProvider::Custom => ApiConfig::new(provider, env::var("CUSTOM_API_KEY").unwrap_or_default(), custom_url()?, env::var("CUSTOM_MODEL_NAME").unwrap_or_default()),
+        ApiProvider::Groq => ApiConfig::new(provider, static_api_key(provider)?, "https://api.groq.com/openai/v1/chat/completions".to_string(), pinned),
+        // Requests are signed with the AWS credentials at send time.
+        ApiProvider::Bedrock => ApiConfig::new(provider, String::new(), bedrock_url(&bedrock_model_id()), bedrock_model_id()),
+        ApiProvider::Mistral => ApiConfig::new(provider, static_api_key(provider)?, "https://api.mistral.ai/v1/chat/completions".to_string(), pinned),
+        ApiProvider::Vertex => vertex_config()?,
+        ApiProvider::DeepSeek => ApiConfig::new(provider, static_api_key(provider)?, "https://api.deepseek.com/chat/completions".to_string(), pinned),
+        ApiProvider::Cohere => ApiConfig::new(provider, static_api_key(provider)?, "https://api.cohere.com/v1/chat".to_string(), pinned),
+        ApiProvider::HuggingFace => {
+            // HF_TOKEN is the name the Hugging Face tools use.
+            let api_key = match env::var("HUGGINGFACE_API_KEY").or_else(|_| env::var("HF_TOKEN")) {
+                Ok(key) => key,
+                Err(_) => static_api_key(provider)?,
+            };
+            // A dedicated endpoint serves one model, so there is nothing to pick.
+            let model = pinned_model(provider).or_else(|| env::var("HUGGINGFACE_ENDPOINT_URL").is_ok().then(huggingface_model)).unwrap_or_default();
+            ApiConfig::new(provider, api_key, huggingface_url(), model)
+        }
+    })
+}
+
+/// Settings for the providers listed in `setting`, all with the given reasoning and sampling
+/// settings. One listed without a model uses its `<NAME>_MODEL`, then the first of its fallback
+/// models.
+fn provider_configs(
+    setting: &str,
+    specs: Vec<failover::ProviderSpec>,
+    reasoning: reasoning::ReasoningConfig,
+    sampling: &sampling::SamplingConfig,
+) -> Result<Vec<ApiConfig>, String> {
+    specs
+        .into_iter()
+        .map(|spec| {
+            let provider = ApiProvider::from_env_name(&spec.provider)
+                .ok_or_else(|| format!("Unknown provider '{}' in {}", spec.provider.to_lowercase(), setting))?;
+            let mut config = provider_config(provider)?;
+            match spec.model {
+                Some(model) => config.set_model(model),
+                None if config.model_name.is_empty() => {
+                    let model = fallback_models(provider).into_iter().next().ok_or_else(|| {
+                        format!("{} needs a model for {} ({}:<model>)", setting, provider.label(), spec.provider.to_lowercase())
+                    })?;
+                    config.set_model(model);
+                }
+                None => {}
+            }
+            config.reasoning = reasoning;
+            config.sampling = sampling.clone();
+            config.auth = auth::Method::from_env(provider.env_name())?;
+            Ok(config)
+        })
+        .collect()
+}
+
+/// Handles `agent_bench sessions <command> ...`.
+async fn run_sessions_command(args: &[String]) {
+    match args.first().map(String::as_str) {
+        Some("summarize") => {
+            let session_id = args.get(1);
+            let kind = args
+                .iter()
+                .position(|a| a == "--as")
+                .and_then(|i| args.get(i + 1))
+                .and_then(|k| summarize::ArtifactKind::parse(k));
+            let (Some(session_id), Some(kind)) = (session_id, kind) else {
+                println!("{}", "Usage: agent_bench sessions summarize <id> --as issue|pr|runbook".red());
+                return;
+            };
+
+            let Some(config) = select_provider().await else {
+                println!("{}", "Invalid choice. Exiting.".red());
+                return;
+            };
+            let conn = open_db();
+
+            match summarize::summarize_session(&conn, &config, session_id, kind).await {
+                Ok(filename) => println!("Session summarized as {} in {}", kind.name(), filename.bold().yellow()),
+                Err(e) => println!("{} ({})", "Summarize failed".red(), e.to_string().red()),
+            }
+        }
+        Some("export") => {
+            let Some(session_id) = args.get(1) else {
+                println!("{}", "Usage: agent_bench sessions export <id> [--scrub <profile>]".red());
+                return;
+            };
+            let profile = match args.iter().position(|a| a == "--scrub").map(|i| args.get(i + 1)) {
+                None => None,
+                Some(Some(name)) => match scrub::ScrubProfile::load(name) {
+                    Ok(profile) => Some(profile),
+                    Err(e) => {
+                        println!("{} ({})", "Export cancelled".red(), e.red());
+                        return;
+                    }
+                },
+                Some(None) => {
+                    println!("{}", "Usage: agent_bench sessions export <id> [--scrub <profile>]".red());
+                    return;
+                }
+            };
+
+            let conn = open_db();
+            let filename = write_export(&conn, session_id, profile.as_ref());
+            match &profile {
+                Some(profile) => println!("Session exported to {} (scrubbed with '{}')", filename.bold().yellow(), profile.name),
+                None => println!("Session exported to {}", filename.bold().yellow()),
+            }
+        }
+        Some("outcomes") => {
+            let conn = open_db();
+            let report = outcome::report(&conn);
+            if report.is_empty() {
+                println!("No classified assistant replies yet.");
+                return;
+            }
+            println!("{}", "Reply outcomes by model:".bold().yellow());
+            for model in &report {
+                println!(
+                    "{} ({} replies, {} infrastructure failures, {:.1}% refusals of answered)",
+                    model.model.bold(),
+                    model.total,
+                    model.infrastructure_failures(),
+                    model.answered_rate(outcome::Outcome::Refusal) * 100.0
+                );
+                for (outcome, count) in &model.counts {
+                    println!("  {:<15} {:>6}  {:>5.1}%", outcome.name(), count, model.rate(*outcome) * 100.0);
+                }
+            }
+        }
+        Some("usage") => {
+            let conn = open_db();
+            let run_id = match (args.get(1).map(String::as_str), args.get(2)) {
+                (Some("--run"), Some(id)) => match bench::compare::resolve_run(&conn, id) {
+                    Ok(run_id) => Some(run_id),
+                    Err(e) => {
+                        println!("{}", e.red());
+                        return;
+                    }
+                },
+                (Some("--run"), None) => {
+                    println!("{}", "Usage: agent_bench sessions usage --run <run-id>".red());
+                    return;
+                }
+                _ => None,
+            };
+            let (scope, label) = match (&run_id, args.get(1)) {
+                (Some(run_id), _) => (usage::ToolScope::Run(run_id), format!(" for run {}", run_id)),
+                (None, Some(session_id)) => (usage::ToolScope::Session(session_id), format!(" for session {}", session_id)),
+                (None, None) => (usage::ToolScope::All, String::new()),
+            };
+            let report = usage::report(&conn, scope);
+            if report.is_empty() {
+                println!("No tool runs recorded{}.", label);
+                return;
+            }
+            let currency = pricing::Catalog::load().currency;
+            println!("{}", "Tool usage (LLM calls not included):".bold().yellow());
+            println!("  {:<15} {:>6} {:>8} {:>10} {:>10} {:>12}", "tool", "runs", "failed", "total s", "avg ms", "cost");
+            for tool in &report {
+                println!(
+                    "  {:<15} {:>6} {:>8} {:>10.1} {:>10} {:>12}",
+                    tool.tool,
+                    tool.runs,
+                    tool.failures,
+                    tool.total_ms as f64 / 1000.0,
+                    tool.average_ms(),
+                    format!("{:.4} {}", tool.cost, currency)
+                );
+            }
+            let total: f64 = report.iter().map(|t| t.cost).sum();
+            println!("  Total tool cost: {:.4} {}", total, currency);
+        }
+        Some("list") => {
+            match session_meta::from_args(args) {
+                Ok(filters) => list_sessions(&open_db(), &filters),
+                Err(e) => println!("{}", e.red()),
+            }
+        }
+        Some("meta") => {
+            let Some(session_id) = args.get(1) else {
+                println!("{}", "Usage: agent_bench sessions meta <id> [key=value ...] [--unset <key>]".red());
+                return;
+            };
+            let conn = open_db();
+            if conn.query_row("SELECT 1 FROM sessions WHERE id = ?1", params![session_id], |_| Ok(())).is_err() {
+                println!("{}", format!("No session with id {}", session_id).red());
+                return;
+            }
+            let mut rest = args[2..].iter();
+            while let Some(arg) = rest.next() {
+                if arg == "--unset" {
+                    match rest.next() {
+                        Some(key) if session_meta::unset(&conn, session_id, key) => println!("Removed {}", key),
+                        Some(key) => println!("{}", format!("{} is not set on session {}", key, session_id).red()),
+                        None => println!("{}", "--unset needs a key".red()),
+                    }
+                    continue;
+                }
+                match session_meta::parse_entry(arg) {
+                    Ok((key, value)) => session_meta::set(&conn, session_id, &key, &value),
+                    Err(e) => {
+                        println!("{}", e.red());
+                        return;
+                    }
+                }
+            }
+            for (key, value) in session_meta::get(&conn, session_id) {
+                println!("{} {}", key.bold(), session_meta::display(&value));
+            }
+        }
+        Some("search") => {
+            let all_workspaces = args.iter().any(|a| a == "--all-workspaces");
+            let query = args[1..].iter().filter(|a| *a != "--all-workspaces").cloned().collect::<Vec<_>>().join(" ");
+            if query.trim().is_empty() {
+                println!("{}", "Usage: agent_bench sessions search <text> [--all-workspaces]".red());
+                return;
+            }
+
+            // Other workspaces are only read when asked for explicitly, and never migrated.
+            let databases: Vec<(String, String)> = if all_workspaces {
+                workspace::Registry::load().workspaces.into_iter().filter(|(_, path)| std::path::Path::new(path).exists()).collect()
+            } else {
+                let active = workspace::active();
+                vec![(active.label.clone(), active.path.clone())]
+            };
+            let mut found = 0;
+            for (label, path) in databases {
+                let conn = if all_workspaces {
+                    match open_db_read_only(&path) {
+                        Ok(conn) => conn,
+                        Err(e) => {
+                            println!("{}", format!("Skipping workspace '{}' ({}): {}", label, path, e).yellow());
+                            continue;
+                        }
+                    }
+                } else {
+                    open_db_at(&path)
+                };
+                for hit in search_messages(&conn, &query) {
+                    found += 1;
+                    println!(
+                        "{} {} {} {}: {}",
+                        format!("[{}]", label).dimmed(),
+                        hit.session_id,
+                        format!("#{}", hit.message_id).dimmed(),
+                        hit.role.bold(),
+                        hit.excerpt
+                    );
+                }
+            }
+            if found == 0 {
+                println!("No messages match '{}'.", query);
+            }
+        }
+        _ => {
+            println!("{}", "Usage:".red());
+            println!("  agent_bench sessions list [--meta key=value ...]");
+            println!("  agent_bench sessions meta <id> [key=value ...] [--unset <key>]");
+            println!("  agent_bench sessions summarize <id> --as issue|pr|runbook");
+            println!("  agent_bench sessions export <id> [--scrub <profile>]");
+            println!("  agent_bench sessions outcomes");
+            println!("  agent_bench sessions usage [<id> | --run <run-id>]");
+            println!("  agent_bench sessions search <text> [--all-workspaces]");
+        }
+    }
+}
+
+/// Handles `agent_bench messages <command> ...`.
+fn run_messages_command(args: &[String]) {
+    match (args.first().map(String::as_str), args.get(1).and_then(|id| id.trim_start_matches('#').parse::<i64>().ok())) {
+        (Some("delete"), Some(message_id)) => {
+            let conn = open_db();
+            match delete_message(&conn, message_id) {
+                Some(session_id) => {
+                    transcript_log::log_event(&session_id, "message_deleted", json!({"message_id": message_id}));
+                    println!("Message #{} deleted from session {}", message_id, session_id);
+                }
+                None => println!("{}", format!("No message with id {}", message_id).red()),
+            }
+        }
+        _ => println!("{}", "Usage: agent_bench messages delete <id>".red()),
+    }
+}
+
+/// Handles `agent_bench workspaces list` and `agent_bench workspaces default <name>`.
+fn run_workspaces_command(args: &[String]) {
+    let mut registry = workspace::Registry::load();
+    match (args.first().map(String::as_str), args.get(1)) {
+        (Some("list"), _) => {
+            println!("{}", "Workspaces:".bold().yellow());
+            for (name, path) in &registry.workspaces {
+                let marker = if name == registry.default_name() { " (default)" } else { "" };
+                println!("{}{} {}", name.bold(), marker, path.dimmed());
+            }
+        }
+        (Some("default"), Some(name)) => {
+            if !registry.workspaces.contains_key(name) {
+                println!("{}", format!("Unknown workspace '{}'; open it once with --workspace {}", name, name).red());
+                return;
+            }
+            registry.default = Some(name.clone());
+            match registry.save() {
+                Ok(()) => println!("Default workspace set to {}", name.bold()),
+                Err(e) => println!("{} ({})", "Could not save the workspace registry".red(), e),
+            }
+        }
+        _ => {
+            println!("{}", "Usage:".red());
+            println!("  agent_bench workspaces list");
+            println!("  agent_bench workspaces default <name>");
+        }
+    }
+}
+
+/// Handles `agent_bench pricing <command> ...`.
+async fn run_pricing_command(args: &[String]) {
+    match (args.first().map(String::as_str), args.get(1)) {
+        (Some("show"), Some(model)) => match pricing::Catalog::load().price_for(model) {
+            Some((price, source)) => println!(
+                "{}: {} input / {} output per 1M tokens{}",
+                model.bold(),
+                price.input_per_mtok,
+                price.output_per_mtok,
+                if source == pricing::Source::Override { " (override)" } else { "" }
+            ),
+            None => println!("{}", format!("No price known for {}; set PRICE_{}=<input>,<output>", model, prompts::model_key(model)).red()),
+        },
+        (Some("show"), None) => {
+            let catalog = pricing::Catalog::load();
+            println!("{}", format!("Pricing catalog {} ({} per 1M tokens, input / output):", catalog.version, catalog.currency).bold().yellow());
+            for (model, price) in &catalog.prices {
+                println!("{} {} / {}", model.bold(), price.input_per_mtok, price.output_per_mtok);
+            }
+        }
+        (Some("update"), url) => match pricing::update(url.map(String::as_str)).await {
+            Ok(catalog) => println!(
+                "Saved pricing catalog {} ({} models) to {}",
+                catalog.version.bold(),
+                catalog.prices.len(),
+                pricing::catalog_file()
+            ),
+            Err(e) => println!("{} ({})", "Could not update the pricing catalog".red(), e),
         },
-        "2" => ApiConfig {
-            provider: ApiProvider::Sambanova,
-            api_key: env::var("SAMBANOVA_API_KEY").expect("SAMBANOVA_API_KEY not set in .env for Sambanova"),
-            base_url: "https://api.sambanova.ai/v1/chat/completions".to_string(),
-            model_name: "Meta-Llama-3.2-1B-Instruct".to_string(),
+        _ => {
+            println!("{}", "Usage:".red());
+            println!("  agent_bench pricing show [model]");
+            println!("  agent_bench pricing update [url]");
+        }
+    }
+}
+
+/// Handles `agent_bench cost [provider|model|day] [--session <id>]`.
+fn run_cost_command(args: &[String]) {
+    let session = args.iter().position(|a| a == "--session").map(|i| args.get(i + 1).map(String::as_str));
+    let grouping = match args.first().filter(|a| !a.starts_with("--")) {
+        None => Some(usage::SpendGrouping::Model),
+        Some(group) => usage::SpendGrouping::parse(group),
+    };
+    let (Some(grouping), None | Some(Some(_))) = (grouping, session) else {
+        println!("{}", "Usage:".red());
+        println!("  agent_bench cost [provider|model|day] [--session <id>]");
+        return;
+    };
+    let session_id = session.flatten();
+    let conn = open_db();
+    let report = usage::spend_report(&conn, grouping, session_id);
+    if report.is_empty() {
+        println!("No token usage recorded{}.", session_id.map(|id| format!(" for session {}", id)).unwrap_or_default());
+        return;
+    }
+    let currency = pricing::Catalog::load().currency;
+    println!("{}", format!("LLM spend by {} (tools not included):", grouping.name()).bold().yellow());
+    println!("  {:<40} {:>8} {:>12} {:>12} {:>14}", grouping.name(), "replies", "prompt", "completion", "cost");
+    for spend in &report {
+        let unpriced = if spend.unpriced > 0 { format!(" ({} unpriced)", spend.unpriced).dimmed().to_string() } else { String::new() };
+        println!(
+            "  {:<40} {:>8} {:>12} {:>12} {:>14}{}",
+            spend.key,
+            spend.replies,
+            spend.prompt_tokens,
+            spend.completion_tokens,
+            format!("{:.4} {}", spend.cost, currency),
+            unpriced
+        );
+    }
+    let total: f64 = report.iter().map(|s| s.cost).sum();
+    println!("  Total LLM cost: {:.4} {}", total, currency);
+    if report.iter().any(|s| s.unpriced > 0) {
+        println!("{}", "Unpriced replies have no price for their model; set PRICE_<MODEL_KEY>=<input>,<output> or update the catalog.".dimmed());
+    }
+}
+
+/// Handles `agent_bench run <suite.toml> [--providers <list>] [--tags <list>] [--judge <provider>]`:
+/// every task of the suite (or those with one of the tags) against every provider, recorded
+/// under a new run.
+async fn run_bench_command(args: &[String]) {
+    let Some(path) = args.first().filter(|a| !a.starts_with("--")) else {
+        println!("{}", "Usage:".red());
+        println!("  agent_bench run <suite.toml> [--providers <provider[:model],...>] [--tags <tag,...>] [--judge <provider[:model]>]");
+        println!("                             [--format json|csv|md [--output <file>]]");
+        return;
+    };
+    let export = match leaderboard_export(args) {
+        Ok(export) => export,
+        Err(e) => {
+            println!("{}", e.red());
+            return;
+        }
+    };
+    let configs = bench::suite::Suite::load(path).and_then(|mut suite| {
+        if let Some(i) = args.iter().position(|a| a == "--tags") {
+            let tags = args.get(i + 1).ok_or("--tags needs a tag list such as easy,geography")?;
+            suite.retain_tagged(&tags.split(',').map(|tag| tag.trim().to_string()).filter(|tag| !tag.is_empty()).collect::<Vec<_>>());
+            if suite.tasks.is_empty() {
+                return Err(format!("{}: no tasks tagged {}", path, tags));
+            }
+        }
+        let providers = match args.iter().position(|a| a == "--providers") {
+            Some(i) => args.get(i + 1).cloned().ok_or("--providers needs a provider list such as groq,ollama:llama3.2")?,
+            None => suite.providers.join(","),
+        };
+        let specs = failover::parse_providers("providers", &providers)?;
+        if specs.is_empty() {
+            return Err(format!("{}: no providers (set providers in the suite or pass --providers)", path));
+        }
+        let reasoning = reasoning::ReasoningConfig::from_args(args)?;
+        let sampling = sampling::SamplingConfig::from_args(args)?;
+        let configs = provider_configs("providers", specs, reasoning, &sampling)?;
+        // The judge samples at temperature 0 whatever the benchmarked settings are, so its scores
+        // are comparable across runs.
+        let judge = match args.iter().position(|a| a == "--judge") {
+            Some(i) => Some(args.get(i + 1).cloned().ok_or("--judge needs a provider such as anthropic:claude-sonnet-4-20250514")?),
+            None => suite.judge.clone().or_else(|| env::var("JUDGE_PROVIDER").ok()).filter(|judge| !judge.trim().is_empty()),
+        };
+        let judge = match judge.filter(|_| suite.needs_judge()) {
+            Some(judge) => {
+                let mut specs = failover::parse_providers("judge", &judge)?;
+                if specs.len() != 1 {
+                    return Err("the judge must be a single provider".to_string());
+                }
+                let sampling = sampling::SamplingConfig { temperature: Some(0.0), ..Default::default() };
+                provider_configs("judge", vec![specs.remove(0)], Default::default(), &sampling)?.pop()
+            }
+            None if suite.needs_judge() => return Err(format!("{}: tasks use the judge grader but no judge is set (judge in the suite, --judge or JUDGE_PROVIDER)", path)),
+            None => None,
+        };
+        Ok((suite, configs, judge, bench::concurrency(args)?, reasoning, sampling))
+    });
+    let (suite, configs, judge, concurrency, reasoning, sampling) = match configs {
+        Ok(loaded) => loaded,
+        Err(e) => {
+            println!("{}", e.red());
+            return;
+        }
+    };
+
+    let conn = open_db();
+    let run_id = bench::start_run(
+        &conn,
+        &bench::RunSettings {
+            suite: &suite,
+            path,
+            providers: configs.iter().map(ApiConfig::describe).collect(),
+            judge: judge.as_ref().map(ApiConfig::describe),
+            reasoning: &reasoning,
+            sampling: &sampling,
+            concurrency,
+            tags: args.iter().position(|a| a == "--tags").and_then(|i| args.get(i + 1)).map(String::as_str),
+        },
+    );
+    println!(
+        "{}",
+        format!("Run {}: suite {} ({} tasks) on {} provider(s)", run_id, suite.name, suite.tasks.len(), configs.len()).bold().yellow()
+    );
+    if let Some(judge) = &judge {
+        println!("Judge: {}", judge.describe());
+    }
+    if concurrency > 1 {
+        println!("Concurrency: {} tasks at once", concurrency);
+    }
+    let client = reqwest::Client::builder().timeout(Duration::from_secs(90)).build().unwrap();
+
+    // Tasks are started in suite order, a new one whenever one finishes, and stored and printed as
+    // they finish, which is in suite order when only one runs at a time.
+    let policy = permissions::ToolPolicy::from_env();
+    let denied: Vec<&str> = bench::suite::TOOLS
+        .iter()
+        .copied()
+        .filter(|tool| !policy.allows(tool) && suite.tasks.iter().any(|task| task.tools.iter().any(|t| t == tool)))
+        .collect();
+    if !denied.is_empty() {
+        println!("{}", format!("Not permitted for {} ({}), so not run: {}", policy.user, policy.role, denied.join(", ")).yellow());
+    }
+    let (suite, configs, judge, policy) = (Arc::new(suite), Arc::new(configs), Arc::new(judge), Arc::new(policy));
+    let mut queue = (0..configs.len())
+        .flat_map(|provider| suite.tasks.iter().enumerate().map(move |(index, task)| (provider, index, task.repetitions)))
+        .flat_map(|(provider, index, repetitions)| (1..=repetitions).map(move |repetition| (provider, index, repetition)))
+        .enumerate()
+        .peekable();
+    let mut jobs = tokio::task::JoinSet::new();
+    let mut results = Vec::new();
+    // Tasks that run commands or capture artifacts share the working directory, so only one of
+    // them runs at a time, and its artifacts are captured before the next one starts.
+    let mut workdir_busy = false;
+    loop {
+        while jobs.len() < concurrency
+            && let Some(&(order, (provider, index, repetition))) = queue.peek()
+        {
+            let uses_workdir = suite.tasks[index].uses_workdir();
+            if uses_workdir && workdir_busy {
+                break;
+            }
+            queue.next();
+            workdir_busy |= uses_workdir;
+            let (client, suite, configs, judge, policy) = (client.clone(), suite.clone(), configs.clone(), judge.clone(), policy.clone());
+            jobs.spawn(async move {
+                let started = std::time::SystemTime::now();
+                let result = bench::run_task(&client, &configs[provider], &suite.tasks[index], repetition, judge.as_ref().as_ref(), &policy).await;
+                (order, index, started, result)
+            });
+        }
+        let Some(job) = jobs.join_next().await else { break };
+        let (order, index, started, result) = job.expect("benchmark task panicked");
+        let task = &suite.tasks[index];
+        if task.uses_workdir() {
+            workdir_busy = false;
+        }
+        let result_id = bench::record(&conn, &run_id, &result);
+        let label = if task.repetitions > 1 { format!("{} #{}", task.id, result.repetition) } else { task.id.clone() };
+        let mut details = format!("{} ms", result.latency.as_millis());
+        if let Some(usage) = result.usage {
+            details.push_str(&format!(", {} tokens", usage.prompt_tokens + usage.completion_tokens));
+        }
+        if let Some(rate) = result.tokens_per_sec() {
+            details.push_str(&format!(", {:.0} tok/s", rate));
+        }
+        if result.tool_calls > 0 {
+            details.push_str(&format!(", {} tool call(s)", result.tool_calls));
+        }
+        if let Some(judgement) = &result.judgement {
+            details.push_str(&format!(", score {}/10", judgement.score));
+        }
+        let verdict = match &result.verdict {
+            Some(Ok(true)) => format!(" {}", "pass".green()),
+            Some(Ok(false)) => format!(" {}", "fail".red()),
+            Some(Err(e)) => format!(" {}", e.red()),
+            None => String::new(),
+        };
+        match &result.error {
+            None => println!("  {} {} on {} ({}){} [result {}]", "ok".green(), label, result.provider, details, verdict, result_id),
+            Some(e) => println!("  {} {} on {}: {} [result {}]", "error".red(), label, result.provider, e, result_id),
+        }
+        if !task.artifacts.is_empty() {
+            let (captured, skipped) = bench::artifacts::capture(&conn, result_id, &task.artifacts, started);
+            for artifact in captured {
+                println!("    {} {} ({} bytes)", "artifact".dimmed(), artifact.path, artifact.size);
+            }
+            for problem in skipped {
+                println!("    {}", format!("artifact {} not captured", problem).dimmed());
+            }
+        }
+        results.push((order, result));
+    }
+    bench::finish_run(&conn, &run_id);
+
+    results.sort_by_key(|(order, _)| *order);
+    let results: Vec<bench::TaskResult> = results.into_iter().map(|(_, result)| result).collect();
+    let summaries = bench::summarize(&results);
+    let catalog = pricing::Catalog::load();
+    println!("{}", "Summary:".bold().yellow());
+    println!(
+        "  {:<40} {:>7} {:>7} {:>16} {:>10} {:>12} {:>8} {:>10}",
+        "provider", "results", "errors", "accuracy", "prompt", "completion", "tok/s", "median"
+    );
+    for summary in &summaries {
+        bench::record_summary(&conn, &run_id, summary, &catalog);
+        let accuracy = match summary.accuracy() {
+            Some(accuracy) => format!("{:.1}% ({}/{})", accuracy * 100.0, summary.passed, summary.graded),
+            None => "-".to_string(),
+        };
+        let rate = |rate: Option<f64>| rate.map_or_else(|| "-".to_string(), |rate| format!("{:.1}", rate));
+        println!(
+            "  {:<40} {:>7} {:>7} {:>16} {:>10} {:>12} {:>8} {:>10}",
+            summary.provider,
+            summary.results,
+            summary.errors,
+            accuracy,
+            summary.prompt_tokens,
+            summary.completion_tokens,
+            rate(summary.tokens_per_sec()),
+            rate(summary.median_tokens_per_sec())
+        );
+    }
+    if summaries.iter().any(|s| s.tool_runs > 0 || s.judge_tokens > 0) {
+        let currency = &catalog.currency;
+        println!("{}", "Tools and judging:".bold().yellow());
+        println!("  {:<40} {:>10} {:>14} {:>12} {:>14}", "provider", "tool runs", "tool cost", "judge tokens", "judge cost");
+        for summary in &summaries {
+            println!(
+                "  {:<40} {:>10} {:>14} {:>12} {:>14}",
+                summary.provider,
+                summary.tool_runs,
+                format!("{:.4} {}", summary.tool_cost, currency),
+                summary.judge_tokens,
+                summary.judge_cost.map_or_else(|| "-".to_string(), |cost| format!("{:.4} {}", cost, currency))
+            );
+        }
+    }
+    println!("{}", "Latency (ms, answered tasks):".bold().yellow());
+    println!("  {:<40} {:>7} {:>9} {:>9} {:>9} {:>9} {:>9}", "provider", "samples", "mean", "stddev", "p50", "p90", "p99");
+    for summary in &summaries {
+        let ms = |d: Duration| format!("{:.0}", d.as_secs_f64() * 1000.0);
+        match summary.latency() {
+            Some(stats) => println!(
+                "  {:<40} {:>7} {:>9} {:>9} {:>9} {:>9} {:>9}",
+                summary.provider,
+                stats.samples,
+                ms(stats.mean),
+                ms(stats.stddev),
+                ms(stats.p50),
+                ms(stats.p90),
+                ms(stats.p99)
+            ),
+            None => println!("  {:<40} {:>7}", summary.provider, 0),
+        }
+    }
+    if summaries.len() > 1 || export.is_some() {
+        show_leaderboard(&conn, &run_id, export);
+    }
+}
+
+/// `--format` and `--output` for exporting a leaderboard.
+fn leaderboard_export(args: &[String]) -> Result<Option<(bench::leaderboard::Format, Option<String>)>, String> {
+    let output = match args.iter().position(|a| a == "--output") {
+        Some(i) => Some(args.get(i + 1).cloned().ok_or("--output needs a file name")?),
+        None => None,
+    };
+    match (bench::leaderboard::Format::from_args(args)?, output) {
+        (None, Some(_)) => Err("--output needs --format json, csv or md".to_string()),
+        (format, output) => Ok(format.map(|format| (format, output))),
+    }
+}
+
+/// Prints the run's models ranked, then exports the ranking when a format is given: to the
+/// output file, or else to stdout.
+fn show_leaderboard(conn: &Connection, run_id: &str, export: Option<(bench::leaderboard::Format, Option<String>)>) {
+    let entries = bench::leaderboard::load(conn, run_id);
+    println!("{}", "Leaderboard:".bold().yellow());
+    println!("  {:>4} {:<40} {:>16} {:>9} {:>14} {:>8}", "rank", "provider", "accuracy", "mean ms", "cost", "tok/s");
+    for entry in &entries {
+        let value = |value: Option<f64>, decimals: usize| value.map_or_else(|| "-".to_string(), |v| format!("{:.*}", decimals, v));
+        let accuracy = entry.accuracy.map_or_else(|| "-".to_string(), |accuracy| format!("{:.1}%", accuracy * 100.0));
+        let cost = entry.cost.map_or_else(|| "-".to_string(), |cost| format!("{:.6} {}", cost, entry.currency));
+        println!(
+            "  {:>4} {:<40} {:>16} {:>9} {:>14} {:>8}",
+            entry.rank,
+            entry.provider,
+            accuracy,
+            value(entry.mean_latency_ms, 0),
+            cost,
+            value(entry.tokens_per_sec, 1)
+        );
+    }
+    let Some((format, output)) = export else { return };
+    let rendered = bench::leaderboard::render(&entries, format);
+    match output {
+        Some(path) => match std::fs::write(&path, rendered) {
+            Ok(()) => println!("Leaderboard written to {}", path.bold().yellow()),
+            Err(e) => println!("{}", format!("Could not write {} ({})", path, e).red()),
+        },
+        None => print!("{}", rendered),
+    }
+}
+
+/// Handles `agent_bench bench runs`, `agent_bench bench leaderboard <run_id>` and
+/// `agent_bench bench artifacts <result_id> [--restore <dir>]`.
+fn run_bench_tools_command(args: &[String]) {
+    let conn = open_db();
+    if let (Some("leaderboard"), Some(run_id)) = (args.first().map(String::as_str), args.get(1).filter(|a| !a.starts_with("--"))) {
+        match bench::compare::resolve_run(&conn, run_id).and_then(|run_id| Ok((run_id, leaderboard_export(args)?))) {
+            Ok((run_id, export)) => show_leaderboard(&conn, &run_id, export),
+            Err(e) => println!("{}", e.red()),
+        }
+        return;
+    }
+    let result_id = match (args.first().map(String::as_str), args.get(1).map(|id| id.parse::<i64>())) {
+        (Some("runs"), None) => return list_bench_runs(&conn),
+        (Some("artifacts"), Some(Ok(id))) => id,
+        _ => {
+            println!("{}", "Usage:".red());
+            println!("  agent_bench bench runs");
+            println!("  agent_bench bench leaderboard <run_id> [--format json|csv|md [--output <file>]]");
+            println!("  agent_bench bench artifacts <result_id> [--restore <dir>]");
+            return;
+        }
+    };
+    let artifacts = bench::artifacts::list(&conn, result_id);
+    if artifacts.is_empty() {
+        println!("No artifacts for result {}.", result_id);
+        return;
+    }
+    if let Some(i) = args.iter().position(|a| a == "--restore") {
+        let Some(dir) = args.get(i + 1) else {
+            println!("{}", "--restore needs a directory".red());
+            return;
+        };
+        match bench::artifacts::restore(&artifacts, std::path::Path::new(dir)) {
+            Ok(written) => {
+                for path in written {
+                    println!("Restored {}", path.display());
+                }
+            }
+            Err(e) => println!("{}", format!("Could not restore artifacts: {}", e).red()),
+        }
+        return;
+    }
+    println!("{}", format!("Artifacts of result {}:", result_id).bold().yellow());
+    for artifact in artifacts {
+        println!("  {} ({} bytes, sha256 {})", artifact.path.bold(), artifact.size, &artifact.hash[..12]);
+        println!("    {}", bench::artifacts::stored_path(&artifact.hash).display().to_string().dimmed());
+    }
+}
+
+fn list_bench_runs(conn: &Connection) {
+    let runs = bench::list_runs(conn);
+    if runs.is_empty() {
+        println!("No benchmark runs.");
+        return;
+    }
+    println!("{}", "Benchmark runs:".bold().yellow());
+    for run in runs {
+        let mut counts = format!("{} results, {} errors", run.results, run.errors);
+        if run.graded > 0 {
+            counts.push_str(&format!(", {}/{} passed", run.passed, run.graded));
+        }
+        if !run.finished {
+            counts.push_str(", unfinished");
+        }
+        println!("{} {} {} ({})", run.id.bold(), run.suite, run.started_at.dimmed(), counts);
+        if let Some(providers) = run.providers.filter(|p| !p.is_empty()) {
+            println!("  {}", providers.dimmed());
+        }
+    }
+}
+
+/// Handles `agent_bench compare <run_a> <run_b>`, exiting with 1 when run b regressed from run a
+/// and 2 when the runs cannot be compared, so it can gate CI.
+fn run_compare_command(args: &[String]) {
+    let fail = |message: String| -> ! {
+        println!("{}", message.red());
+        std::process::exit(2);
+    };
+    let (Some(a), Some(b)) = (args.first().filter(|a| !a.starts_with("--")), args.get(1).filter(|b| !b.starts_with("--"))) else {
+        println!("{}", "Usage:".red());
+        println!("  agent_bench compare <run_a> <run_b> [--max-score-drop 0.05] [--max-latency-increase 20]");
+        std::process::exit(2);
+    };
+    let thresholds = bench::compare::Thresholds::from_args(args).unwrap_or_else(|e| fail(e));
+    let conn = open_db();
+    let a = bench::compare::resolve_run(&conn, a).unwrap_or_else(|e| fail(e));
+    let b = bench::compare::resolve_run(&conn, b).unwrap_or_else(|e| fail(e));
+    let comparison = bench::compare::compare(&conn, &a, &b, &thresholds);
+    if comparison.tasks.is_empty() {
+        fail(format!("Runs {} and {} have no tasks in common with the same prompts.", a, b));
+    }
+
+    println!("{}", format!("Comparing run {} with baseline {}", b, a).bold().yellow());
+    let score = |score: Option<f64>| score.map_or("-".to_string(), |s| format!("{:.2}", s));
+    println!("{}", "Tasks:".bold());
+    let mut unchanged = 0;
+    for task in &comparison.tasks {
+        let ((errors_a, results_a), (errors_b, results_b)) = task.errors;
+        if task.score.0 == task.score.1 && errors_a * results_b == errors_b * results_a {
+            unchanged += 1;
+            continue;
+        }
+        let line = format!(
+            "  {:<24} {:<32} score {:>4} -> {:<4} errors {}/{} -> {}/{}",
+            task.task_id, task.provider, score(task.score.0), score(task.score.1), errors_a, results_a, errors_b, results_b
+        );
+        match &task.regression {
+            Some(reason) => println!("{} {}", line, format!("REGRESSION: {}", reason).red().bold()),
+            None => println!("{}", line),
+        }
+    }
+    if unchanged > 0 {
+        println!("  {}", format!("{} unchanged", unchanged).dimmed());
+    }
+    if !comparison.latencies.is_empty() {
+        println!("{}", "Mean latency:".bold());
+        for latency in &comparison.latencies {
+            let line = format!("  {:<32} {:>8.0} ms -> {:>8.0} ms", latency.provider, latency.mean_ms.0, latency.mean_ms.1);
+            let change = latency.increase_percent();
+            match (latency.regression, change.is_finite()) {
+                (true, _) => println!("{} {}", line, format!("{:+.1}% REGRESSION", change).red().bold()),
+                (false, true) => println!("{} {:+.1}%", line, change),
+                (false, false) => println!("{}", line),
+            }
+        }
+    }
+    for (label, pairs) in [
+        ("Not compared, prompts changed:", &comparison.changed_prompts),
+        ("Only in the baseline:", &comparison.only_first),
+        ("Only in the new run:", &comparison.only_second),
+    ] {
+        if !pairs.is_empty() {
+            println!("{} {}", label.dimmed(), pairs.join(", ").dimmed());
+        }
+    }
+
+    match comparison.regressions() {
+        0 => println!("{}", "No regressions.".green()),
+        n => {
+            println!("{}", format!("{} regression(s).", n).red().bold());
+            std::process::exit(1);
+        }
+    }
+}
+
+/// Handles `agent_bench calc <expression>`, the evaluator behind the `calculate` tool.
+fn run_calc_command(args: &[String]) {
+    if args.is_empty() {
+        println!("{}", "Usage:".red());
+        println!("  agent_bench calc <expression>");
+        return;
+    }
+    match calculate::evaluate(&args.join(" ")) {
+        Ok(value) => println!("{}", value),
+        Err(e) => println!("{}", e.red()),
+    }
+}
+
+/// Handles `agent_bench judge templates [name] [--model <model>]`.
+fn run_judge_command(args: &[String]) {
+    let model = args.iter().position(|a| a == "--model").and_then(|i| args.get(i + 1)).map(String::as_str).unwrap_or("");
+    match (args.first().map(String::as_str), args.get(1).filter(|a| !a.starts_with("--"))) {
+        (Some("templates"), None) => {
+            println!("{}", "Judge templates:".bold().yellow());
+            for name in judge_templates::names() {
+                match judge_templates::for_model(&name, model) {
+                    Ok(template) => println!("{} {} {}", name.bold(), template.kind.name(), format!("{} {}", template.variant, template.version).dimmed()),
+                    Err(e) => println!("{}", e.red()),
+                }
+            }
+        }
+        (Some("templates"), Some(name)) => match judge_templates::for_model(name, model) {
+            Ok(template) => {
+                println!("{}", format!("{} ({}, {} variant, version {}):", template.name, template.kind.name(), template.variant, template.version).bold().yellow());
+                println!("{}", template.text);
+            }
+            Err(e) => println!("{}", e.red()),
         },
-        "3" => ApiConfig {
-            provider: ApiProvider::Gemini,
-            api_key: env::var("GEMINI_API_KEY").expect("GEMINI_API_KEY not set in .env for Google Gemini"),
-            base_url: "https://generativelanguage.googleapis.com/v1beta/models/gemini-2.0-flash:generateContent".to_string(),
-            model_name: "gemini-2.0-flash".to_string(),
+        _ => {
+            println!("{}", "Usage:".red());
+            println!("  agent_bench judge templates [name] [--model <model>]");
+        }
+    }
+}
+
+/// Handles `agent_bench jobs <command> ...`.
+fn run_jobs_command(args: &[String]) {
+    let conn = open_db();
+    match (args.first().map(String::as_str), args.get(1)) {
+        (Some("list"), flag) => {
+            let jobs = schedule::list(&conn, None, flag.is_some_and(|f| f == "--all"));
+            if jobs.is_empty() {
+                println!("No scheduled jobs.");
+            }
+            for job in jobs {
+                println!(
+                    "{} {} [{}] {} at {} (session {})",
+                    "Job".bold(),
+                    job.id.to_string().bold(),
+                    job.status,
+                    job.action.describe(),
+                    job.describe_schedule(),
+                    job.session_id
+                );
+                if let Some(result) = &job.last_result {
+                    println!("  Last result: {}", result.lines().next().unwrap_or_default());
+                }
+            }
+        }
+        (Some("cancel"), Some(id)) => match id.parse() {
+            Ok(id) if schedule::cancel(&conn, id) => println!("Cancelled job {}.", id),
+            _ => println!("{}", format!("No pending job {}.", id).red()),
         },
         _ => {
-            println!("{}", "Invalid choice. Exiting.".red());
+            println!("{}", "Usage:".red());
+            println!("  agent_bench jobs list [--all]");
+            println!("  agent_bench jobs cancel <id>");
+        }
+    }
+}
+
+/// Runs due scheduled jobs every `SCHEDULER_POLL_SECS` (default 30), or once with `--once`
+/// (e.g. from cron).
+fn run_daemon(args: &[String]) {
+    let once = args.iter().any(|arg| arg == "--once");
+    let poll = env::var("SCHEDULER_POLL_SECS").ok().and_then(|v| v.trim().parse().ok()).unwrap_or(30);
+    let conn = open_db();
+    if !once {
+        println!("{}", format!("Running scheduled jobs every {}s. Press Ctrl+C to stop.", poll).bold().yellow());
+    }
+    loop {
+        for (job, result) in schedule::run_due(&conn) {
+            println!("{} {} (session {}): {}", "Job".bold(), job.id.to_string().bold(), job.session_id, result.trim_end());
+        }
+        if once {
+            break;
+        }
+        std::thread::sleep(Duration::from_secs(poll));
+    }
+}
+
+/// Handles `agent_bench db <command>`.
+fn run_db_command(args: &[String]) {
+    match args.first().map(String::as_str) {
+        Some("compact") => {
+            let conn = open_db();
+            let (compressed, before, after) = storage::compact(&conn);
+            println!(
+                "Compressed {} message(s); database {} -> {} bytes ({} bytes reclaimed)",
+                compressed, before, after, (before - after).max(0)
+            );
+        }
+        Some("maintain") => {
+            let conn = open_db();
+            let report = storage::maintain(&conn);
+            if report.integrity == "ok" {
+                println!("Integrity check: {}", "ok".green());
+            } else {
+                println!("Integrity check: {}", report.integrity.red());
+            }
+            match (&report.backup_path, &report.backup_error) {
+                (Some(path), _) => println!("Backup: {} ({} old backup(s) removed)", path, report.backups_removed),
+                (None, Some(e)) => println!("Backup: {}", format!("failed ({})", e).red()),
+                (None, None) => println!("Backup: {}", "skipped because the integrity check failed".red()),
+            }
+            println!("Reindexed and vacuumed: {} -> {} bytes", report.size_before, report.size_after);
+        }
+        _ => {
+            println!("{}", "Usage:".red());
+            println!("  agent_bench db compact");
+            println!("  agent_bench db maintain");
+        }
+    }
+}
+
+#[tokio::main]
+async fn main() {
+    dotenv().ok();
+
+    let args = match workspace::select_from_args(env::args().skip(1).collect()) {
+        Ok(args) => args,
+        Err(e) => {
+            println!("{}", e.red());
             return;
         }
     };
+    match args.first().map(String::as_str) {
+        Some("sessions") => return run_sessions_command(&args[1..]).await,
+        Some("workspaces") => return run_workspaces_command(&args[1..]),
+        Some("messages") => return run_messages_command(&args[1..]),
+        Some("db") => return run_db_command(&args[1..]),
+        Some("pricing") => return run_pricing_command(&args[1..]).await,
+        Some("cost") => return run_cost_command(&args[1..]),
+        Some("run") => return run_bench_command(&args[1..]).await,
+        Some("bench") => return run_bench_tools_command(&args[1..]),
+        Some("compare") => return run_compare_command(&args[1..]),
+        Some("calc") => return run_calc_command(&args[1..]),
+        Some("judge") => return run_judge_command(&args[1..]),
+        Some("jobs") => return run_jobs_command(&args[1..]),
+        Some("daemon") => return run_daemon(&args[1..]),
+        _ => {}
+    }
 
-    let conn = Connection::open("chat_sessions.db").unwrap();
-    init_db(&conn);
+    let parsed = retention::Retention::from_args(&args)
+        .and_then(|retention| Ok((retention, session_meta::from_args(&args)?, structured::OutputSchema::from_args(&args)?)));
+    let mut options = match parsed {
+        Ok((retention, meta, output_schema)) => SessionOptions {
+            voice_mode: args.iter().any(|arg| arg == "--voice"),
+            retention,
+            fanout: Vec::new(),
+            meta,
+            output_schema,
+            suggestions: None,
+        },
+        Err(e) => {
+            println!("{}", e.red());
+            return;
+        }
+    };
+
+    let reasoning = match reasoning::ReasoningConfig::from_args(&args) {
+        Ok(reasoning) => reasoning,
+        Err(e) => {
+            println!("{}", e.red());
+            return;
+        }
+    };
+
+    let sampling = match sampling::SamplingConfig::from_args(&args) {
+        Ok(sampling) => sampling,
+        Err(e) => {
+            println!("{}", e.red());
+            return;
+        }
+    };
+
+    let Some(mut config) = select_provider().await else {
+        println!("{}", "Invalid choice. Exiting.".red());
+        return;
+    };
+    config.reasoning = reasoning;
+    config.sampling = sampling;
+    config.auth = match auth::Method::from_env(config.provider.env_name()) {
+        Ok(method) => method,
+        Err(e) => {
+            println!("{}", e.red());
+            return;
+        }
+    };
+    config.fallbacks = match failover::chain_from_env().and_then(|specs| provider_configs("PROVIDER_FALLBACKS", specs, config.reasoning, &config.sampling)) {
+        Ok(fallbacks) => fallbacks,
+        Err(e) => {
+            println!("{}", e.red());
+            return;
+        }
+    };
+    options.fanout = match fanout::providers_from_args(&args).and_then(|specs| provider_configs("--fanout", specs, config.reasoning, &config.sampling)) {
+        Ok(fanout) => fanout,
+        Err(e) => {
+            println!("{}", e.red());
+            return;
+        }
+    };
+    if suggestions::enabled(&args) {
+        options.suggestions = match suggestions::provider_from_env() {
+            Ok(Some(spec)) => match provider_configs("SUGGEST_PROVIDER", vec![spec], config.reasoning, &config.sampling) {
+                Ok(mut configs) => configs.pop(),
+                Err(e) => {
+                    println!("{}", e.red());
+                    return;
+                }
+            },
+            Ok(None) => Some(config.clone()),
+            Err(e) => {
+                println!("{}", e.red());
+                return;
+            }
+        };
+    }
+
+    let conn = open_db();
+    if workspace::active().label != workspace::DEFAULT_WORKSPACE {
+        println!("Workspace: {}", workspace::active().label.bold());
+    }
+    if !config.fallbacks.is_empty() {
+        let chain: Vec<String> = config.fallbacks.iter().map(ApiConfig::describe).collect();
+        println!("Fallbacks: {}", chain.join(", "));
+    }
+    if !config.auth.is_static() {
+        println!("Auth: {}", config.auth.name());
+    } else if let ApiProvider::Vertex = config.provider {
+        match google_auth::Credentials::discover() {
+            Ok(credentials) => println!("Google credentials: {}", credentials.describe()),
+            Err(e) => {
+                println!("{}", e.red());
+                return;
+            }
+        }
+    }
 
     loop {
         println!("\n{}", "Main Menu".bold().yellow());
@@ -400,8 +3158,8 @@ async fn main() {
         io::stdin().read_line(&mut menu_choice).unwrap();
 
         match menu_choice.trim() {
-            "1" => start_chat_session(&conn, &config).await,
-            "2" => list_sessions(&conn),
+            "1" => start_chat_session(&conn, &config, &options).await,
+            "2" => list_sessions(&conn, &[]),
             "3" => view_session(&conn),
             "4" => export_session(&conn),
             "5" => {
@@ -412,3 +3170,31 @@ async fn main() {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use rusqlite::{params, Connection};
+
+    use super::{delete_message, init_db, save_message, save_session, MESSAGE_TOMBSTONE};
+
+    #[test]
+    fn deleting_a_message_clears_what_it_said() {
+        let conn = Connection::open_in_memory().unwrap();
+        init_db(&conn);
+        save_session(&conn, "s");
+        let id = save_message(&conn, "s", "assistant", "see ![chart](chart.png)");
+        conn.execute(
+            "UPDATE messages SET raw_content = 'raw', logprobs = '[{\"token\":\"see\"}]', seed = 7 WHERE id = ?1",
+            params![id],
+        ).unwrap();
+        assert_eq!(delete_message(&conn, id).as_deref(), Some("s"));
+        let row: (String, Option<String>, Option<String>, Option<i64>, Option<String>) = conn
+            .query_row(
+                "SELECT content, raw_content, logprobs, seed, attachments FROM messages WHERE id = ?1",
+                params![id],
+                |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?, row.get(4)?)),
+            )
+            .unwrap();
+        assert_eq!(row, (MESSAGE_TOMBSTONE.to_string(), None, None, None, None));
+    }
+}
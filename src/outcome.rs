@@ -0,0 +1,180 @@
+use rusqlite::{params, Connection};
+use whatlang::Lang;
+
+use crate::language;
+
+/// Classification of an assistant reply, stored in `messages.outcome`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Outcome {
+    Normal,
+    Refusal,
+    FormatError,
+    Empty,
+    SafetyBlocked,
+    Truncated,
+    WrongLanguage,
+}
+
+/// Order in which outcomes are listed in reports.
+pub const ALL: [Outcome; 7] = [
+    Outcome::Normal,
+    Outcome::Refusal,
+    Outcome::FormatError,
+    Outcome::Empty,
+    Outcome::SafetyBlocked,
+    Outcome::Truncated,
+    Outcome::WrongLanguage,
+];
+
+/// Provider finish reasons (OpenAI and Cohere `finish_reason`, Gemini `finishReason`, Anthropic
+/// `stop_reason`, Bedrock `stopReason`) meaning the reply was withheld or cut by a content
+/// filter.
+const SAFETY_REASONS: &[&str] = &[
+    "content_filter",
+    "safety",
+    "recitation",
+    "blocklist",
+    "prohibited_content",
+    "spii",
+    "refusal",
+    "content_filtered",
+    "guardrail_intervened",
+    "error_toxic",
+];
+const LENGTH_REASONS: &[&str] = &["length", "max_tokens", "model_length"];
+
+/// Openings that mark a reply as a refusal. Only the start of the reply is checked, so answers
+/// that mention a limitation further down still count as normal.
+const REFUSAL_OPENINGS: &[&str] = &[
+    "i'm sorry, but i can",
+    "i am sorry, but i can",
+    "sorry, but i can",
+    "i can't help",
+    "i cannot help",
+    "i can't assist",
+    "i cannot assist",
+    "i can't provide",
+    "i cannot provide",
+    "i'm not able to",
+    "i am not able to",
+    "i'm unable to",
+    "i am unable to",
+    "i won't",
+    "i will not",
+    "as an ai",
+];
+
+impl Outcome {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Outcome::Normal => "normal",
+            Outcome::Refusal => "refusal",
+            Outcome::FormatError => "format_error",
+            Outcome::Empty => "empty",
+            Outcome::SafetyBlocked => "safety_blocked",
+            Outcome::Truncated => "truncated",
+            Outcome::WrongLanguage => "wrong_language",
+        }
+    }
+
+    /// Replies that say nothing about the model's ability (placeholders, empty bodies, answers
+    /// in the wrong language) are reported apart from scored answers.
+    pub fn is_infrastructure_failure(&self) -> bool {
+        matches!(self, Outcome::Empty | Outcome::WrongLanguage)
+    }
+
+    fn parse(name: &str) -> Option<Self> {
+        ALL.into_iter().find(|o| o.name() == name)
+    }
+}
+
+/// Classifies a reply from provider metadata first, then from its text. `format_error` is set
+/// when the reply was a tool call that still failed to parse after repair or an answer that
+/// still does not match the output schema, and
+/// `expected_language` when the session pins a response language.
+pub fn classify(reply: &str, finish_reason: Option<&str>, format_error: bool, expected_language: Option<Lang>) -> Outcome {
+    let reason = finish_reason.map(str::to_lowercase);
+    let text = reply.trim();
+    if reason.as_deref().is_some_and(|r| SAFETY_REASONS.contains(&r)) {
+        return Outcome::SafetyBlocked;
+    }
+    if reason.as_deref().is_some_and(|r| LENGTH_REASONS.contains(&r)) {
+        return Outcome::Truncated;
+    }
+    if text.is_empty() || text == "[No response]" {
+        return Outcome::Empty;
+    }
+    if format_error {
+        return Outcome::FormatError;
+    }
+    if expected_language.is_some_and(|expected| language::wrong_language(text, expected).is_some()) {
+        return Outcome::WrongLanguage;
+    }
+    let opening: String = text.chars().take(80).collect::<String>().to_lowercase().replace('’', "'");
+    if REFUSAL_OPENINGS.iter().any(|o| opening.starts_with(o)) {
+        return Outcome::Refusal;
+    }
+    Outcome::Normal
+}
+
+pub fn record(conn: &Connection, message_id: i64, outcome: Outcome) {
+    conn.execute(
+        "UPDATE messages SET outcome = ?2 WHERE id = ?1",
+        params![message_id, outcome.name()],
+    ).unwrap();
+}
+
+/// Outcome counts for one model's assistant replies.
+#[derive(Debug)]
+pub struct ModelOutcomes {
+    pub model: String,
+    pub counts: Vec<(Outcome, i64)>,
+    pub total: i64,
+}
+
+impl ModelOutcomes {
+    pub fn rate(&self, outcome: Outcome) -> f64 {
+        let count = self.counts.iter().find(|(o, _)| *o == outcome).map_or(0, |(_, c)| *c);
+        if self.total == 0 { 0.0 } else { count as f64 / self.total as f64 }
+    }
+
+    pub fn infrastructure_failures(&self) -> i64 {
+        self.counts.iter().filter(|(o, _)| o.is_infrastructure_failure()).map(|(_, c)| c).sum()
+    }
+
+    /// Share of real answers (infrastructure failures excluded) with the given outcome.
+    pub fn answered_rate(&self, outcome: Outcome) -> f64 {
+        let answered = self.total - self.infrastructure_failures();
+        let count = self.counts.iter().find(|(o, _)| *o == outcome).map_or(0, |(_, c)| *c);
+        if answered == 0 { 0.0 } else { count as f64 / answered as f64 }
+    }
+}
+
+/// Aggregates classified assistant replies per model, skipping deleted messages.
+pub fn report(conn: &Connection) -> Vec<ModelOutcomes> {
+    let mut stmt = conn
+        .prepare(
+            "SELECT COALESCE(s.model, 'unknown'), m.outcome, COUNT(*)
+             FROM messages m JOIN sessions s ON s.id = m.session_id
+             WHERE m.role = 'assistant' AND m.outcome IS NOT NULL AND m.deleted_at IS NULL
+             GROUP BY 1, 2 ORDER BY 1",
+        )
+        .unwrap();
+    let rows: Vec<(String, String, i64)> = stmt
+        .query_map([], |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)))
+        .unwrap()
+        .filter_map(Result::ok)
+        .collect();
+
+    let mut report: Vec<ModelOutcomes> = Vec::new();
+    for (model, name, count) in rows {
+        let Some(outcome) = Outcome::parse(&name) else { continue };
+        if report.last().is_none_or(|r| r.model != model) {
+            report.push(ModelOutcomes { model: model.clone(), counts: Vec::new(), total: 0 });
+        }
+        let entry = report.last_mut().unwrap();
+        entry.counts.push((outcome, count));
+        entry.total += count;
+    }
+    report
+}
@@ -0,0 +1,60 @@
+use std::env;
+
+/// Which tools the current user may invoke, resolved from `USER_ROLES` and `ROLE_TOOLS`.
+///
+/// `USER_ROLES=alice:admin,bob:analyst` maps users to roles (unlisted users get the `default`
+/// role) and `ROLE_TOOLS=admin:*,analyst:web_search` lists the tools each role may use,
+/// joined with `+`. When `ROLE_TOOLS` is unset every tool is allowed.
+#[derive(Debug, Clone)]
+pub struct ToolPolicy {
+    pub user: String,
+    pub role: String,
+    allowed: Option<Vec<String>>,
+}
+
+fn parse_pairs(spec: &str) -> Vec<(String, String)> {
+    spec.split(',')
+        .filter_map(|entry| entry.split_once(':'))
+        .map(|(key, value)| (key.trim().to_string(), value.trim().to_string()))
+        .collect()
+}
+
+impl ToolPolicy {
+    pub fn from_env() -> Self {
+        let user = env::var("AGENT_BENCH_USER")
+            .or_else(|_| env::var("USER"))
+            .unwrap_or_else(|_| "unknown".to_string());
+        let role = parse_pairs(&env::var("USER_ROLES").unwrap_or_default())
+            .into_iter()
+            .find(|(name, _)| *name == user)
+            .map(|(_, role)| role)
+            .unwrap_or_else(|| "default".to_string());
+        let allowed = env::var("ROLE_TOOLS").ok().map(|spec| {
+            parse_pairs(&spec)
+                .into_iter()
+                .filter(|(name, _)| *name == role)
+                .flat_map(|(_, tools)| tools.split('+').map(|t| t.trim().to_string()).collect::<Vec<_>>())
+                .collect()
+        });
+        ToolPolicy { user, role, allowed }
+    }
+
+    pub fn is_restricted(&self) -> bool {
+        self.allowed.is_some()
+    }
+
+    pub fn allows(&self, tool: &str) -> bool {
+        match &self.allowed {
+            None => true,
+            Some(tools) => tools.iter().any(|t| t == "*" || t == tool),
+        }
+    }
+
+    pub fn describe(&self) -> String {
+        match &self.allowed {
+            Some(tools) if !tools.is_empty() => tools.join(", "),
+            Some(_) => "none".to_string(),
+            None => "all".to_string(),
+        }
+    }
+}
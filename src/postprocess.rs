@@ -0,0 +1,128 @@
+use std::env;
+
+use regex::Regex;
+use rusqlite::{params, Connection};
+
+use crate::prompts::model_key;
+
+/// One step of a reply post-processing pipeline. Extraction steps leave the text unchanged
+/// when they find nothing, so plain answers and tool calls pass through.
+#[derive(Debug)]
+pub enum Step {
+    StripThink,
+    ExtractBoxed,
+    LastCodeBlock,
+    Regex(Regex),
+}
+
+impl Step {
+    fn name(&self) -> &'static str {
+        match self {
+            Step::StripThink => "strip-think",
+            Step::ExtractBoxed => "extract-boxed",
+            Step::LastCodeBlock => "last-code-block",
+            Step::Regex(_) => "regex",
+        }
+    }
+
+    fn apply(&self, text: &str) -> String {
+        match self {
+            Step::StripThink => {
+                // An unclosed block (a reply cut off mid-thought) is removed to the end.
+                let blocks = Regex::new(
+                    r"(?s)<think>.*?(?:</think>|\z)|<thinking>.*?(?:</thinking>|\z)|<reasoning>.*?(?:</reasoning>|\z)",
+                )
+                .unwrap();
+                blocks.replace_all(text, "").trim().to_string()
+            }
+            Step::ExtractBoxed => last_boxed(text).unwrap_or_else(|| text.to_string()),
+            Step::LastCodeBlock => {
+                let blocks = Regex::new(r"(?s)```[^\n`]*\n(.*?)```").unwrap();
+                match blocks.captures_iter(text).last() {
+                    Some(block) => block[1].trim_end().to_string(),
+                    None => text.to_string(),
+                }
+            }
+            Step::Regex(regex) => match regex.captures_iter(text).last() {
+                Some(found) => found.get(1).or(found.get(0)).map_or("", |m| m.as_str()).trim().to_string(),
+                None => text.to_string(),
+            },
+        }
+    }
+}
+
+/// Contents of the last `\boxed{...}`, allowing nested braces.
+fn last_boxed(text: &str) -> Option<String> {
+    let start = text.rfind("\\boxed{")? + "\\boxed{".len();
+    let mut depth = 1;
+    for (i, c) in text[start..].char_indices() {
+        match c {
+            '{' => depth += 1,
+            '}' => {
+                depth -= 1;
+                if depth == 0 {
+                    return Some(text[start..start + i].trim().to_string());
+                }
+            }
+            _ => {}
+        }
+    }
+    None
+}
+
+/// Ordered post-processing applied to replies before they are shown, stored and classified.
+#[derive(Debug, Default)]
+pub struct Pipeline {
+    steps: Vec<Step>,
+}
+
+impl Pipeline {
+    /// Parses a comma-separated step list. `regex` takes its pattern from `regex_pattern`,
+    /// keeping the first capture group (or the whole match) of the last match.
+    pub fn parse(spec: &str, regex_pattern: Option<&str>) -> Result<Self, String> {
+        let mut steps = Vec::new();
+        for name in spec.split(',').map(str::trim).filter(|s| !s.is_empty()) {
+            steps.push(match name {
+                "strip-think" => Step::StripThink,
+                "extract-boxed" => Step::ExtractBoxed,
+                "last-code-block" => Step::LastCodeBlock,
+                "regex" => {
+                    let pattern = regex_pattern.ok_or("the regex step needs a pattern")?;
+                    Step::Regex(Regex::new(pattern).map_err(|e| format!("invalid pattern: {}", e))?)
+                }
+                other => return Err(format!("unknown post-processing step '{}'", other)),
+            });
+        }
+        Ok(Pipeline { steps })
+    }
+
+    /// Pipeline for `model_name` from `POSTPROCESS_<MODEL_KEY>`, with the `regex` step's pattern
+    /// in `POSTPROCESS_REGEX_<MODEL_KEY>`.
+    pub fn for_model(model_name: &str) -> Result<Self, String> {
+        let key = model_key(model_name);
+        match env::var(format!("POSTPROCESS_{}", key)) {
+            Ok(spec) => Pipeline::parse(&spec, env::var(format!("POSTPROCESS_REGEX_{}", key)).ok().as_deref()),
+            Err(_) => Ok(Pipeline::default()),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.steps.is_empty()
+    }
+
+    pub fn describe(&self) -> String {
+        self.steps.iter().map(Step::name).collect::<Vec<_>>().join(", ")
+    }
+
+    pub fn apply(&self, text: &str) -> String {
+        self.steps.iter().fold(text.to_string(), |text, step| step.apply(&text))
+    }
+}
+
+/// Keeps a reply's text as the model sent it in `messages.raw_content`, when the pipeline
+/// changed it.
+pub fn record_raw(conn: &Connection, message_id: i64, raw: &str, processed: &str) {
+    if raw != processed {
+        conn.execute("UPDATE messages SET raw_content = ?2 WHERE id = ?1", params![message_id, raw]).unwrap();
+    }
+}
@@ -0,0 +1,94 @@
+use std::collections::BTreeMap;
+use std::env;
+
+use serde::{Deserialize, Serialize};
+
+use crate::prompts::model_key;
+use crate::usage::TokenUsage;
+
+/// Catalog shipped with the binary, used until `pricing update` downloads a newer one.
+const BUNDLED_CATALOG: &str = include_str!("pricing.json");
+
+/// Price of a model in the catalog's currency per million tokens.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct Price {
+    pub input_per_mtok: f64,
+    pub output_per_mtok: f64,
+}
+
+/// Versioned per-model prices. Keys match model names by prefix, longest first, so dated
+/// snapshots (`claude-sonnet-4-20250514`) share their family's price.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Catalog {
+    pub version: String,
+    pub currency: String,
+    pub prices: BTreeMap<String, Price>,
+}
+
+/// Where a model's price came from.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Source {
+    Override,
+    Catalog,
+}
+
+/// Downloaded catalog (`PRICING_CATALOG_FILE`, default `pricing_catalog.json`).
+pub fn catalog_file() -> String {
+    env::var("PRICING_CATALOG_FILE").unwrap_or_else(|_| "pricing_catalog.json".to_string())
+}
+
+fn parse(text: &str) -> Result<Catalog, String> {
+    let catalog: Catalog = serde_json::from_str(text).map_err(|e| format!("invalid pricing catalog: {}", e))?;
+    if catalog.version.trim().is_empty() {
+        return Err("invalid pricing catalog: missing version".to_string());
+    }
+    Ok(catalog)
+}
+
+impl Catalog {
+    /// The downloaded catalog when present and valid, otherwise the bundled one.
+    pub fn load() -> Self {
+        std::fs::read_to_string(catalog_file())
+            .ok()
+            .and_then(|text| parse(&text).ok())
+            .unwrap_or_else(|| parse(BUNDLED_CATALOG).expect("bundled pricing catalog is valid"))
+    }
+
+    /// Price for `model_name`: `PRICE_<MODEL_KEY>=<input>,<output>` (per million tokens) takes
+    /// precedence over the catalog.
+    pub fn price_for(&self, model_name: &str) -> Option<(Price, Source)> {
+        if let Ok(value) = env::var(format!("PRICE_{}", model_key(model_name)))
+            && let Some((input, output)) = value.split_once(',')
+            && let (Ok(input_per_mtok), Ok(output_per_mtok)) = (input.trim().parse(), output.trim().parse())
+        {
+            return Some((Price { input_per_mtok, output_per_mtok }, Source::Override));
+        }
+        self.prices
+            .iter()
+            .filter(|(prefix, _)| model_name.starts_with(prefix.as_str()))
+            .max_by_key(|(prefix, _)| prefix.len())
+            .map(|(_, price)| (*price, Source::Catalog))
+    }
+
+    /// What `usage` cost on `model_name`, or `None` when the model has no price.
+    pub fn cost(&self, model_name: &str, usage: &TokenUsage) -> Option<f64> {
+        let (price, _) = self.price_for(model_name)?;
+        Some((usage.prompt_tokens as f64 * price.input_per_mtok + usage.completion_tokens as f64 * price.output_per_mtok) / 1_000_000.0)
+    }
+}
+
+/// Fetches a catalog from `url` (or `PRICING_CATALOG_URL`) and saves it locally once it parses.
+pub async fn update(url: Option<&str>) -> Result<Catalog, String> {
+    let url = match url {
+        Some(url) => url.to_string(),
+        None => env::var("PRICING_CATALOG_URL").map_err(|_| "no URL given and PRICING_CATALOG_URL not set".to_string())?,
+    };
+    let res = reqwest::get(&url).await.map_err(|e| e.to_string())?;
+    if !res.status().is_success() {
+        return Err(format!("{} returned {}", url, res.status()));
+    }
+    let text = res.text().await.map_err(|e| e.to_string())?;
+    let catalog = parse(&text)?;
+    std::fs::write(catalog_file(), text).map_err(|e| e.to_string())?;
+    Ok(catalog)
+}
@@ -0,0 +1,96 @@
+use std::collections::BTreeSet;
+use std::env;
+use std::path::Path;
+use std::process::Command;
+
+use crate::ingest;
+
+const MAX_TREE_ENTRIES: usize = 200;
+const MAX_STATUS_ENTRIES: usize = 50;
+
+fn git(args: &[&str]) -> Option<String> {
+    let output = Command::new("git").args(args).output().ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    Some(String::from_utf8_lossy(&output.stdout).to_string())
+}
+
+/// Whether the project brief is injected into new sessions (`PROJECT_CONTEXT`).
+pub fn project_context_enabled() -> bool {
+    matches!(env::var("PROJECT_CONTEXT").unwrap_or_default().trim(), "1" | "true" | "yes")
+}
+
+/// Builds a compact description of the git repository containing the working directory:
+/// current branch, dirty files (the first 50), a README excerpt and the file tree (minus ignored files) down
+/// to `PROJECT_TREE_DEPTH` levels. Returns `None` outside a git repository.
+pub fn project_brief() -> Option<String> {
+    let root = git(&["rev-parse", "--show-toplevel"])?.trim().to_string();
+    let depth = env::var("PROJECT_TREE_DEPTH")
+        .ok()
+        .and_then(|v| v.trim().parse().ok())
+        .unwrap_or(2);
+    let readme_chars = env::var("PROJECT_README_CHARS")
+        .ok()
+        .and_then(|v| v.trim().parse().ok())
+        .unwrap_or(1500);
+
+    let mut brief = format!("Project root: {}\n", root);
+
+    if let Some(branch) = git(&["rev-parse", "--abbrev-ref", "HEAD"]) {
+        brief.push_str(&format!("Current branch: {}\n", branch.trim()));
+    }
+
+    let dirty = git(&["status", "--porcelain"]).unwrap_or_default();
+    if dirty.trim().is_empty() {
+        brief.push_str("Working tree: clean\n");
+    } else {
+        brief.push_str(&format!("Uncommitted changes:\n{}", status_list(&dirty)));
+    }
+
+    let readme = ["README.md", "README", "README.txt"]
+        .iter()
+        .find_map(|name| std::fs::read_to_string(Path::new(&root).join(name)).ok());
+    if let Some(readme) = readme {
+        let excerpt: String = readme.chars().take(readme_chars).collect();
+        brief.push_str(&format!("\nREADME excerpt:\n{}\n", excerpt.trim_end()));
+    }
+
+    let files: Vec<String> = ingest::walk(Path::new(&root))
+        .iter()
+        .map(|p| p.to_string_lossy().to_string())
+        .collect();
+    brief.push_str(&format!("\nFile tree (depth {}):\n{}", depth, file_tree(&files, depth)));
+
+    Some(brief)
+}
+
+/// The first `MAX_STATUS_ENTRIES` lines of `git status --porcelain` output, then a count of the rest.
+fn status_list(porcelain: &str) -> String {
+    let lines: Vec<&str> = porcelain.lines().filter(|line| !line.trim().is_empty()).collect();
+    let mut list: String = lines.iter().take(MAX_STATUS_ENTRIES).map(|line| format!("{}\n", line)).collect();
+    if lines.len() > MAX_STATUS_ENTRIES {
+        list.push_str(&format!("... ({} more entries)\n", lines.len() - MAX_STATUS_ENTRIES));
+    }
+    list
+}
+
+/// Collapses a list of paths to their first `depth` components, marking directories with `/`.
+fn file_tree(paths: &[String], depth: usize) -> String {
+    let depth = depth.max(1);
+    let mut entries = BTreeSet::new();
+    for path in paths {
+        let parts: Vec<&str> = path.split('/').collect();
+        if parts.len() > depth {
+            entries.insert(format!("{}/", parts[..depth].join("/")));
+        } else {
+            entries.insert(path.clone());
+        }
+    }
+    let total = entries.len();
+    let mut tree: String = entries.into_iter().take(MAX_TREE_ENTRIES).map(|e| format!("{}\n", e)).collect();
+    if total > MAX_TREE_ENTRIES {
+        tree.push_str(&format!("... ({} more entries)\n", total - MAX_TREE_ENTRIES));
+    }
+    tree
+}
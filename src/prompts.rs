@@ -0,0 +1,182 @@
+use std::env;
+
+use chrono::Datelike;
+use rusqlite::{params, Connection};
+
+use crate::Message;
+
+/// The system prompt for a session and a label for where it came from, stored with the session.
+#[derive(Debug)]
+pub struct SystemPrompt {
+    pub text: String,
+    pub variant: String,
+}
+
+/// Environment-variable key for a model name: `Meta-Llama-3.2-1B-Instruct` becomes
+/// `META_LLAMA_3_2_1B_INSTRUCT`.
+pub fn model_key(model_name: &str) -> String {
+    model_name
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c.to_ascii_uppercase() } else { '_' })
+        .collect()
+}
+
+fn base_persona(model_name: &str, tools_enabled: bool) -> String {
+    if tools_enabled {
+        let current_year = chrono::Local::now().year();
+        format!(
+            "You are a helpful AI assistant powered by the {} model.
+You have the ability to run any Linux shell command.
+Your response MUST be ONLY the tool command. Do not add any explanation.
+Do NOT use interactive commands (like 'nano', 'vim'). Use non-interactive commands like `cat` to read files.
+
+Tool format:
+- Run a shell command: `[RUN_COMMAND <command to run>]`
+- Search the web: `[SEARCH: your query]`. Current year: {}
+- Calculate exactly (units and dates too): `[CALCULATE: 2 h + 30 min to min]`, `[CALCULATE: 2025-12-25 - today]`
+- Schedule for later: `[SCHEDULE: in 2h check the deploy]` for a reminder, or `[SCHEDULE: {{\"when\": \"17:00\", \"command\": \"cargo test\"}}]` to run a command
+- Arguments may also be a JSON object, e.g. `[RUN_COMMAND {{\"command\": \"ls -la\", \"workdir\": \"src\"}}]`",
+            model_name, current_year
+        )
+    } else {
+        format!("You are an AI assistant powered by the {} model.", model_name)
+    }
+}
+
+/// Builds the system prompt for `model_name`. `SYSTEM_PROMPT_OVERRIDE_<MODEL_KEY>` replaces the
+/// base persona outright, while `SYSTEM_PROMPT_APPEND_<MODEL_KEY>` is added after it. In both,
+/// `{model}` is replaced with the model name.
+pub fn system_prompt(model_name: &str, tools_enabled: bool) -> SystemPrompt {
+    let key = model_key(model_name);
+    if let Ok(text) = env::var(format!("SYSTEM_PROMPT_OVERRIDE_{}", key)) {
+        return SystemPrompt {
+            text: text.replace("{model}", model_name),
+            variant: format!("override:{}", key),
+        };
+    }
+
+    let base = base_persona(model_name, tools_enabled);
+    match env::var(format!("SYSTEM_PROMPT_APPEND_{}", key)) {
+        Ok(extra) => SystemPrompt {
+            text: format!("{}\n\n{}", base, extra.replace("{model}", model_name)),
+            variant: format!("append:{}", key),
+        },
+        Err(_) => SystemPrompt { text: base, variant: "base".to_string() },
+    }
+}
+
+/// Records which provider, model and prompt variant a session was started with.
+pub fn record_session_prompt(conn: &Connection, session_id: &str, provider: &str, model_name: &str, variant: &str) {
+    conn.execute(
+        "UPDATE sessions SET provider = ?2, model = ?3, prompt_variant = ?4 WHERE id = ?1",
+        params![session_id, provider, model_name, variant],
+    ).unwrap();
+}
+
+/// Built-in tool-use exchanges, in the same shape the chat loop produces for real tool calls.
+const BUILTIN_EXEMPLARS: &[(&str, &str)] = &[
+    ("user", "What files are in the current directory?"),
+    ("assistant", "[RUN_COMMAND ls -la]"),
+    ("system", "Command output:\ntotal 8\n-rw-r--r-- 1 user user 120 Cargo.toml\ndrwxr-xr-x 2 user user 4096 src"),
+    ("assistant", "The directory contains `Cargo.toml` and a `src` directory."),
+    ("user", "Who won the 2024 Nobel Prize in Physics?"),
+    ("assistant", "[SEARCH: 2024 Nobel Prize in Physics winner]"),
+    (
+        "system",
+        "Web search results for '2024 Nobel Prize in Physics winner':\nThe Nobel Prize in Physics 2024 was awarded jointly to John J. Hopfield and Geoffrey E. Hinton \"for foundational discoveries and inventions that enable machine learning with artificial neural networks\".",
+    ),
+    ("assistant", "John Hopfield and Geoffrey Hinton, for foundational work that enables machine learning with artificial neural networks."),
+    ("user", "How many minutes is a 42.195 km marathon at 12 km/h?"),
+    ("assistant", "[CALCULATE: 42.195 km / (12 km/h) to min]"),
+    ("system", "Calculation result for '42.195 km / (12 km/h) to min':\n210.975 min"),
+    ("assistant", "About 211 minutes (210.975)."),
+];
+
+/// Parses an exemplar file. Each message starts with `user:`, `assistant:` or `system:` at the
+/// beginning of a line; following lines up to the next marker belong to the same message.
+fn parse_exemplars(text: &str) -> Result<Vec<Message>, String> {
+    let mut messages: Vec<Message> = Vec::new();
+    for line in text.lines() {
+        let marker = ["user:", "assistant:", "system:"].iter().find(|m| line.starts_with(*m));
+        match (marker, messages.last_mut()) {
+            (Some(marker), _) => messages.push(Message {
+                role: marker.trim_end_matches(':').to_string(),
+                content: line[marker.len()..].trim().to_string(),
+            }),
+            (None, Some(last)) => {
+                last.content.push('\n');
+                last.content.push_str(line);
+            }
+            (None, None) if line.trim().is_empty() => {}
+            (None, None) => return Err("exemplar file must start with user:, assistant: or system:".to_string()),
+        }
+    }
+    for message in &mut messages {
+        message.content = message.content.trim().to_string();
+    }
+    Ok(messages)
+}
+
+/// Canned tool-use exchanges to place after the system prompt, for models that ignore the tool
+/// format. `FEW_SHOT_<MODEL_KEY>` is either `builtin` or the path of an exemplar file.
+pub fn tool_exemplars(model_name: &str) -> Result<Vec<Message>, String> {
+    let source = match env::var(format!("FEW_SHOT_{}", model_key(model_name))) {
+        Ok(source) if !source.trim().is_empty() => source,
+        _ => return Ok(Vec::new()),
+    };
+    if source.trim().eq_ignore_ascii_case("builtin") {
+        return Ok(BUILTIN_EXEMPLARS
+            .iter()
+            .map(|(role, content)| Message { role: role.to_string(), content: content.to_string() })
+            .collect());
+    }
+    let text = std::fs::read_to_string(source.trim()).map_err(|e| format!("could not read {}: {}", source.trim(), e))?;
+    parse_exemplars(&text)
+}
+
+/// System prompt addition describing the `browse` tool, for builds with a headless browser.
+pub fn browse_instruction() -> String {
+    "- Render a page that needs JavaScript: `[BROWSE: https://example.com]`, or with steps: `[BROWSE: {\"url\": \"https://example.com\", \"actions\": \"click #load-more; scroll 2\"}]`".to_string()
+}
+
+/// System prompt addition describing the `send_email` tool and who it may write to.
+pub fn email_instruction(allowed: &str) -> String {
+    format!(
+        "- Send an email (the user confirms each one): `[SEND_EMAIL {{\"to\": \"ops@example.com\", \"subject\": \"Weekly report\", \"body\": \"...\"}}]`. Allowed recipients: {}",
+        allowed
+    )
+}
+
+/// Preferred response language pinned for new sessions (`RESPONSE_LANGUAGE`).
+pub fn response_language() -> Option<String> {
+    env::var("RESPONSE_LANGUAGE").ok().map(|v| v.trim().to_string()).filter(|v| !v.is_empty())
+}
+
+/// System prompt addition pinning the response language. Tool calls keep their fixed format.
+pub fn language_instruction(language: &str) -> String {
+    format!(
+        "Always write your answers in {}, whatever language the user writes in. Tool calls keep their exact format.",
+        language
+    )
+}
+
+pub fn record_response_language(conn: &Connection, session_id: &str, language: &str) {
+    conn.execute(
+        "UPDATE sessions SET response_language = ?2 WHERE id = ?1",
+        params![session_id, language],
+    ).unwrap();
+}
+
+/// Request asking the model to translate `text` into `language`.
+pub fn translation_request(text: &str, language: &str) -> Vec<Message> {
+    vec![
+        Message {
+            role: "system".to_string(),
+            content: format!(
+                "Translate the user's text into {}. Keep code, commands, file names and formatting unchanged. Reply with the translation only.",
+                language
+            ),
+        },
+        Message { role: "user".to_string(), content: text.to_string() },
+    ]
+}
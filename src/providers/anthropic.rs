@@ -0,0 +1,93 @@
+use std::env;
+
+use reqwest::header::CONTENT_TYPE;
+use reqwest::{Client, RequestBuilder};
+use serde_json::{Value, json};
+
+use super::{Provider, alternating_turns, reply, set_if, sibling_url, token_usage};
+use crate::sampling::DEFAULT_TEMPERATURE;
+use crate::usage::TokenUsage;
+use crate::{ApiConfig, LlmReply, Message, attachments};
+
+/// Anthropic's Messages API.
+pub struct Anthropic;
+
+impl Provider for Anthropic {
+    fn build_request(&self, client: &Client, config: &ApiConfig, history: &[Message], credential: &str) -> Result<RequestBuilder, String> {
+        let (system, messages) = alternating_turns(history);
+        let messages_json: Vec<_> = messages
+            .iter()
+            .map(|(role, content)| {
+                let (text, images) = attachments::split(content);
+                if images.is_empty() {
+                    return json!({"role": role, "content": text});
+                }
+                // Images go before the text, as Anthropic recommends.
+                let mut blocks: Vec<Value> = images
+                    .into_iter()
+                    .map(|(media_type, data)| json!({"type": "image", "source": {"type": "base64", "media_type": media_type, "data": data}}))
+                    .collect();
+                if !text.is_empty() {
+                    blocks.push(json!({"type": "text", "text": text}));
+                }
+                json!({"role": role, "content": blocks})
+            })
+            .collect();
+
+        let sampling = &config.sampling;
+        let max_tokens: u32 = sampling
+            .max_tokens
+            .or_else(|| env::var("ANTHROPIC_MAX_TOKENS").ok().and_then(|v| v.trim().parse().ok()))
+            .unwrap_or(4096);
+        let mut body = json!({
+            "model": config.model_name,
+            "max_tokens": max_tokens,
+            "messages": messages_json,
+        });
+        if let Some(system) = system {
+            body["system"] = json!(system);
+        }
+        set_if(&mut body, "stop_sequences", Some(&sampling.stop).filter(|stop| !stop.is_empty()));
+        match config.reasoning.budget_tokens().filter(|&budget| budget > 0) {
+            // Extended thinking needs at least 1024 tokens, counts against max_tokens and
+            // does not accept sampling overrides.
+            Some(budget) => {
+                let budget = budget.max(1024);
+                body["thinking"] = json!({"type": "enabled", "budget_tokens": budget});
+                body["max_tokens"] = json!(budget + max_tokens);
+            }
+            None => {
+                body["temperature"] = json!(sampling.temperature.unwrap_or(DEFAULT_TEMPERATURE));
+                set_if(&mut body, "top_p", sampling.top_p);
+            }
+        }
+        Ok(client
+            .post(&config.base_url)
+            .header("x-api-key", credential)
+            .header("anthropic-version", "2023-06-01")
+            .header(CONTENT_TYPE, "application/json")
+            .json(&body))
+    }
+
+    fn models_request(&self, client: &Client, config: &ApiConfig, credential: &str) -> Option<RequestBuilder> {
+        let url = sibling_url(&config.base_url, "/messages", "/models?limit=1000")?;
+        Some(client.get(url).header("x-api-key", credential).header("anthropic-version", "2023-06-01"))
+    }
+
+    fn sends_seed(&self) -> bool {
+        false
+    }
+
+    fn parse_response(&self, body: &Value) -> LlmReply {
+        // Thinking blocks come before the answer; only text blocks are the reply.
+        let blocks = body["content"].as_array().cloned().unwrap_or_default();
+        let text: Vec<&str> = blocks.iter().filter(|b| b["type"] == "text").filter_map(|b| b["text"].as_str()).collect();
+        let thinking: Vec<&str> = blocks.iter().filter(|b| b["type"] == "thinking").filter_map(|b| b["thinking"].as_str()).collect();
+        reply(Some(text.join("")).filter(|t| !t.is_empty()), body["stop_reason"].as_str(), Some(thinking.join("\n")))
+    }
+
+    /// Prompt caching reports cache reads and writes apart from `input_tokens`.
+    fn parse_usage(&self, body: &Value) -> Option<TokenUsage> {
+        token_usage(&body["usage"], &["input_tokens", "cache_creation_input_tokens", "cache_read_input_tokens"], &["output_tokens"])
+    }
+}
@@ -0,0 +1,84 @@
+use std::env;
+
+use reqwest::header::CONTENT_TYPE;
+use reqwest::{Client, RequestBuilder};
+use serde_json::{Value, json};
+
+use super::{Provider, alternating_turns, reply, set_if, token_usage};
+use crate::sampling::DEFAULT_TEMPERATURE;
+use crate::usage::TokenUsage;
+use crate::{ApiConfig, LlmReply, Message, attachments, bedrock_region, sigv4};
+
+/// The Bedrock Converse API: content is a list of blocks and the request is signed with SigV4.
+pub struct Bedrock;
+
+impl Provider for Bedrock {
+    fn build_request(&self, client: &Client, config: &ApiConfig, history: &[Message], _credential: &str) -> Result<RequestBuilder, String> {
+        let (system, messages) = alternating_turns(history);
+        let messages_json: Vec<_> = messages
+            .iter()
+            .map(|(role, content)| {
+                let (text, images) = attachments::split(content);
+                let mut blocks: Vec<Value> = images
+                    .into_iter()
+                    .map(|(media_type, data)| json!({"image": {"format": media_type.trim_start_matches("image/"), "source": {"bytes": data}}}))
+                    .collect();
+                if !text.is_empty() || blocks.is_empty() {
+                    blocks.push(json!({"text": text}));
+                }
+                json!({"role": role, "content": blocks})
+            })
+            .collect();
+        let sampling = &config.sampling;
+        let max_tokens: u32 = sampling
+            .max_tokens
+            .or_else(|| env::var("BEDROCK_MAX_TOKENS").ok().and_then(|v| v.trim().parse().ok()))
+            .unwrap_or(4096);
+        let mut body = json!({
+            "messages": messages_json,
+            "inferenceConfig": {"maxTokens": max_tokens},
+        });
+        set_if(&mut body["inferenceConfig"], "stopSequences", Some(&sampling.stop).filter(|stop| !stop.is_empty()));
+        if let Some(system) = system {
+            body["system"] = json!([{"text": system}]);
+        }
+        // Only Anthropic models on Bedrock take a thinking budget, passed through as a
+        // model-specific field; like the Anthropic API they then refuse sampling overrides.
+        match config.reasoning.budget_tokens().filter(|&budget| budget > 0 && config.model_name.contains("anthropic.")) {
+            Some(budget) => {
+                let budget = budget.max(1024);
+                body["additionalModelRequestFields"] = json!({"thinking": {"type": "enabled", "budget_tokens": budget}});
+                body["inferenceConfig"]["maxTokens"] = json!(budget + max_tokens);
+            }
+            None => {
+                body["inferenceConfig"]["temperature"] = json!(sampling.temperature.unwrap_or(DEFAULT_TEMPERATURE));
+                set_if(&mut body["inferenceConfig"], "topP", sampling.top_p);
+            }
+        }
+
+        let credentials = sigv4::Credentials::from_env()?;
+        let url = reqwest::Url::parse(&config.base_url).map_err(|e| e.to_string())?;
+        let payload = serde_json::to_vec(&body).map_err(|e| e.to_string())?;
+        let mut request = client.post(url.clone()).header(CONTENT_TYPE, "application/json");
+        for (name, value) in sigv4::sign("POST", &url, &payload, &bedrock_region(), "bedrock", &credentials, chrono::Utc::now()) {
+            request = request.header(name, value);
+        }
+        Ok(request.body(payload))
+    }
+
+    fn sends_seed(&self) -> bool {
+        false
+    }
+
+    fn parse_response(&self, body: &Value) -> LlmReply {
+        // Reasoning comes back in `reasoningContent` blocks; only text blocks are the reply.
+        let blocks = body["output"]["message"]["content"].as_array().cloned().unwrap_or_default();
+        let text: Vec<&str> = blocks.iter().filter_map(|b| b["text"].as_str()).collect();
+        let thinking: Vec<&str> = blocks.iter().filter_map(|b| b["reasoningContent"]["reasoningText"]["text"].as_str()).collect();
+        reply(Some(text.join("")).filter(|t| !t.is_empty()), body["stopReason"].as_str(), Some(thinking.join("\n")))
+    }
+
+    fn parse_usage(&self, body: &Value) -> Option<TokenUsage> {
+        token_usage(&body["usage"], &["inputTokens", "cacheReadInputTokens", "cacheWriteInputTokens"], &["outputTokens"])
+    }
+}
@@ -0,0 +1,74 @@
+use reqwest::header::{AUTHORIZATION, CONTENT_TYPE};
+use reqwest::{Client, RequestBuilder};
+use serde_json::{Value, json};
+
+use super::{Provider, model_names, reply, set_if, sibling_url, token_usage};
+use crate::sampling::{DEFAULT_TEMPERATURE, DEFAULT_TOP_P};
+use crate::usage::TokenUsage;
+use crate::{ApiConfig, LlmReply, Message, attachments};
+
+/// Cohere's Chat API.
+pub struct Cohere;
+
+impl Provider for Cohere {
+    fn build_request(&self, client: &Client, config: &ApiConfig, history: &[Message], credential: &str) -> Result<RequestBuilder, String> {
+        // Cohere's Chat API takes the system prompt as `preamble`, the latest turn as
+        // `message` and everything between as `chat_history`. It takes no images, so they are
+        // sent as references.
+        let system = history.first().filter(|m| m.role == "system").map(|m| m.content.clone());
+        let turns = &history[usize::from(system.is_some())..];
+        let (latest, earlier) = turns.split_last().ok_or("Request not sent: no message to send")?;
+        let chat_history: Vec<_> = earlier
+            .iter()
+            .map(|m| {
+                let role = match m.role.as_str() {
+                    "assistant" => "CHATBOT",
+                    "system" => "SYSTEM",
+                    _ => "USER",
+                };
+                json!({"role": role, "message": attachments::without_images(&m.content)})
+            })
+            .collect();
+        let sampling = &config.sampling;
+        let mut body = json!({
+            "model": config.model_name,
+            "message": attachments::without_images(&latest.content),
+            "chat_history": chat_history,
+            "temperature": sampling.temperature.unwrap_or(DEFAULT_TEMPERATURE),
+            "p": sampling.top_p.unwrap_or(DEFAULT_TOP_P),
+        });
+        set_if(&mut body, "max_tokens", sampling.max_tokens);
+        set_if(&mut body, "presence_penalty", sampling.presence_penalty);
+        set_if(&mut body, "frequency_penalty", sampling.frequency_penalty);
+        set_if(&mut body, "stop_sequences", Some(&sampling.stop).filter(|stop| !stop.is_empty()));
+        set_if(&mut body, "seed", sampling.seed);
+        if let Some(system) = system {
+            body["preamble"] = json!(system);
+        }
+        Ok(client
+            .post(&config.base_url)
+            .header(AUTHORIZATION, format!("Bearer {}", credential))
+            .header(CONTENT_TYPE, "application/json")
+            .json(&body))
+    }
+
+    /// Models usable with the Chat API.
+    fn models_request(&self, client: &Client, config: &ApiConfig, credential: &str) -> Option<RequestBuilder> {
+        let url = sibling_url(&config.base_url, "/chat", "/models?endpoint=chat&page_size=1000")?;
+        Some(client.get(url).header(AUTHORIZATION, format!("Bearer {}", credential)))
+    }
+
+    fn parse_models(&self, body: &Value) -> Vec<String> {
+        model_names(&body["models"], "name")
+    }
+
+    fn parse_response(&self, body: &Value) -> LlmReply {
+        reply(body["text"].as_str().map(str::to_string), body["finish_reason"].as_str(), None)
+    }
+
+    /// Token counts are in `meta.tokens`, or only as `meta.billed_units` on some models.
+    fn parse_usage(&self, body: &Value) -> Option<TokenUsage> {
+        let fields = |usage: &Value| token_usage(usage, &["input_tokens"], &["output_tokens"]);
+        fields(&body["meta"]["tokens"]).or_else(|| fields(&body["meta"]["billed_units"]))
+    }
+}
@@ -0,0 +1,170 @@
+use reqwest::header::{AUTHORIZATION, CONTENT_TYPE};
+use reqwest::{Client, RequestBuilder};
+use serde_json::{Value, json};
+
+use super::{Credential, Provider, declaration, reply, set_if, text_or_tool_call, token_usage};
+use crate::usage::TokenUsage;
+use crate::{ApiConfig, LlmReply, Message, attachments};
+
+/// Gemini's `generateContent`, on the Gemini API or on Vertex AI.
+pub struct Gemini {
+    vertex: bool,
+}
+
+pub static GEMINI: Gemini = Gemini { vertex: false };
+pub static VERTEX: Gemini = Gemini { vertex: true };
+
+impl Provider for Gemini {
+    fn credential(&self) -> Credential {
+        if self.vertex { Credential::GoogleAccessToken } else { Credential::ApiKey }
+    }
+
+    fn build_request(&self, client: &Client, config: &ApiConfig, history: &[Message], credential: &str) -> Result<RequestBuilder, String> {
+        // Gemini uses 'model' for assistant and 'user' for user.
+        // It also expects contents to not have adjacent same roles.
+        let mut gemini_contents = Vec::new();
+        if let Some(first_message) = history.first().filter(|m| m.role == "system") {
+            gemini_contents.push(json!({
+                "role": "user",
+                "parts": [{"text": first_message.content}]
+            }));
+            gemini_contents.push(json!({
+                "role": "model",
+                "parts": [{"text": "Understood."}]
+            }));
+        }
+
+        for msg in history.iter().skip(1) {
+            let role = if msg.role == "assistant" { "model" } else { "user" };
+            let (text, images) = attachments::split(&msg.content);
+            // An image sent on its own gets no empty text part, which Gemini rejects.
+            let mut parts: Vec<Value> = images
+                .iter()
+                .map(|(media_type, data)| json!({"inline_data": {"mime_type": media_type, "data": data}}))
+                .collect();
+            if !text.is_empty() || images.is_empty() {
+                parts.push(json!({"text": text}));
+            }
+            gemini_contents.push(json!({
+                "role": role,
+                "parts": parts
+            }));
+        }
+
+        // Only explicit sampling settings are sent; Gemini keeps its own defaults otherwise.
+        let sampling = &config.sampling;
+        let mut generation = json!({});
+        set_if(&mut generation, "temperature", sampling.temperature);
+        set_if(&mut generation, "topP", sampling.top_p);
+        set_if(&mut generation, "maxOutputTokens", sampling.max_tokens);
+        set_if(&mut generation, "presencePenalty", sampling.presence_penalty);
+        set_if(&mut generation, "frequencyPenalty", sampling.frequency_penalty);
+        set_if(&mut generation, "stopSequences", Some(&sampling.stop).filter(|stop| !stop.is_empty()));
+        set_if(&mut generation, "seed", sampling.seed);
+        if let Some(alternatives) = sampling.logprobs {
+            generation["responseLogprobs"] = json!(true);
+            set_if(&mut generation, "logprobs", Some(alternatives).filter(|&n| n > 0));
+        }
+        if let Some(schema) = &config.response_schema {
+            generation["responseMimeType"] = json!("application/json");
+            generation["responseJsonSchema"] = schema.schema.clone();
+        }
+        set_if(&mut generation, "thinkingConfig", config.reasoning.budget_tokens().map(|budget| json!({ "thinkingBudget": budget })));
+        let mut body = json!({
+            "contents": gemini_contents
+        });
+        if !config.tools.is_empty() {
+            let declarations: Vec<Value> = config.tools.iter().map(|tool| without_unsupported_keywords(declaration(tool))).collect();
+            body["tools"] = json!([{ "functionDeclarations": declarations }]);
+        }
+        if generation.as_object().is_some_and(|g| !g.is_empty()) {
+            body["generationConfig"] = generation;
+        }
+        // Vertex and OAuth2 or command tokens go in a bearer header instead of the key
+        // parameter.
+        let request = if config.auth.is_static() && !self.vertex {
+            client.post(format!("{}?key={}", config.base_url, credential))
+        } else {
+            client.post(&config.base_url).header(AUTHORIZATION, format!("Bearer {}", credential))
+        };
+        Ok(request.header(CONTENT_TYPE, "application/json").json(&body))
+    }
+
+    /// Vertex publisher models are not listed; the Gemini API lists every model for the key.
+    fn models_request(&self, client: &Client, config: &ApiConfig, credential: &str) -> Option<RequestBuilder> {
+        let (root, _) = config.base_url.split_once("/models/").filter(|_| !self.vertex)?;
+        let url = format!("{}/models?pageSize=1000", root);
+        Some(if config.auth.is_static() {
+            client.get(format!("{}&key={}", url, credential))
+        } else {
+            client.get(url).header(AUTHORIZATION, format!("Bearer {}", credential))
+        })
+    }
+
+    /// Models that can chat, as `gemini-2.0-flash` rather than `models/gemini-2.0-flash`.
+    fn parse_models(&self, body: &Value) -> Vec<String> {
+        body["models"]
+            .as_array()
+            .into_iter()
+            .flatten()
+            .filter(|m| m["supportedGenerationMethods"].as_array().is_some_and(|methods| methods.iter().any(|g| g == "generateContent")))
+            .filter_map(|m| m["name"].as_str())
+            .map(|name| name.trim_start_matches("models/").to_string())
+            .collect()
+    }
+
+    fn declares_tools(&self) -> bool {
+        true
+    }
+
+    fn parse_response(&self, body: &Value) -> LlmReply {
+        let parts = body["candidates"][0]["content"]["parts"].as_array().map(Vec::as_slice).unwrap_or_default();
+        let text = parts.iter().find_map(|part| part["text"].as_str());
+        let tool_call = parts.iter().map(|part| &part["functionCall"]).find(|call| call.is_object());
+        reply(
+            text_or_tool_call(text, tool_call.and_then(|call| Some((call["name"].as_str()?, &call["args"])))),
+            // A blocked prompt comes back with no candidates, only a block reason.
+            body["candidates"][0]["finishReason"]
+                .as_str()
+                .or(body["promptFeedback"]["blockReason"].as_str().map(|_| "SAFETY")),
+            None,
+        )
+    }
+
+    /// `logprobsResult` lists the chosen tokens and, at the same positions, the top candidates.
+    fn parse_logprobs(&self, body: &Value) -> Option<Value> {
+        let result = &body["candidates"][0]["logprobsResult"];
+        let chosen = result["chosenCandidates"].as_array()?;
+        let entry = |candidate: &Value| json!({"token": candidate["token"], "logprob": candidate["logProbability"]});
+        Some(
+            chosen
+                .iter()
+                .enumerate()
+                .map(|(i, candidate)| {
+                    let mut token = entry(candidate);
+                    let top = result["topCandidates"][i]["candidates"].as_array().map(|top| top.iter().map(entry).collect::<Vec<_>>());
+                    token["top_logprobs"] = json!(top.unwrap_or_default());
+                    token
+                })
+                .collect(),
+        )
+    }
+
+    /// Thinking tokens are billed as output but counted apart from the candidates.
+    fn parse_usage(&self, body: &Value) -> Option<TokenUsage> {
+        token_usage(&body["usageMetadata"], &["promptTokenCount"], &["candidatesTokenCount", "thoughtsTokenCount"])
+    }
+}
+
+/// A declaration without the JSON Schema keywords Gemini's schema subset rejects.
+fn without_unsupported_keywords(mut declaration: Value) -> Value {
+    if let Some(parameters) = declaration["parameters"].as_object_mut() {
+        parameters.remove("additionalProperties");
+        for property in parameters.get_mut("properties").and_then(Value::as_object_mut).into_iter().flat_map(|p| p.values_mut()) {
+            if let Some(property) = property.as_object_mut() {
+                property.remove("minLength");
+            }
+        }
+    }
+    declaration
+}
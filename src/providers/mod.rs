@@ -0,0 +1,199 @@
+mod anthropic;
+mod bedrock;
+mod cohere;
+mod gemini;
+mod ollama;
+mod openai;
+
+use reqwest::{Client, RequestBuilder};
+use serde_json::{Value, json};
+
+use crate::usage::TokenUsage;
+use crate::{ApiConfig, LlmReply, Message, tool_args};
+
+/// Which credential a provider is sent.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Credential {
+    /// `<PROVIDER>_API_KEY`, or the token of the configured auth plugin.
+    ApiKey,
+    /// A Google Cloud access token from service account or application-default credentials,
+    /// unless an auth plugin is configured.
+    GoogleAccessToken,
+}
+
+/// A chat backend: how a conversation becomes an HTTP request and how the reply is read back.
+/// `call_llm_detailed` resolves the credential, sends the request and handles HTTP errors, so a
+/// new backend only implements this trait and adds itself to `REGISTRY`.
+pub trait Provider: Sync {
+    fn credential(&self) -> Credential {
+        Credential::ApiKey
+    }
+
+    /// The request for `history`, authenticated with `credential`.
+    fn build_request(&self, client: &Client, config: &ApiConfig, history: &[Message], credential: &str) -> Result<RequestBuilder, String>;
+
+    /// Reply text, finish reason and any separately returned reasoning from a successful
+    /// response body.
+    fn parse_response(&self, body: &Value) -> LlmReply;
+
+    /// Tokens the response reports having used, by default from the OpenAI `usage` block.
+    fn parse_usage(&self, body: &Value) -> Option<TokenUsage> {
+        token_usage(&body["usage"], &["prompt_tokens"], &["completion_tokens"])
+    }
+
+    /// Per-token log probabilities of the reply, when they were requested, in the OpenAI shape:
+    /// `[{"token", "logprob", "top_logprobs": [{"token", "logprob"}]}]`.
+    fn parse_logprobs(&self, body: &Value) -> Option<Value> {
+        Some(body["choices"][0]["logprobs"]["content"].clone()).filter(Value::is_array)
+    }
+
+    /// Whether requests carry the sampling seed. Anthropic and Bedrock have no seed parameter.
+    fn sends_seed(&self) -> bool {
+        true
+    }
+
+    /// Whether requests declare `ApiConfig::tools` as native functions and replies come back
+    /// with structured tool calls. Other providers rely on the text markers alone.
+    fn declares_tools(&self) -> bool {
+        false
+    }
+
+    /// Readable message from an error body, for providers with a known error shape.
+    fn parse_error(&self, _body: &str) -> Option<String> {
+        None
+    }
+
+    /// The request listing the models this provider serves, when it has such an endpoint.
+    fn models_request(&self, _client: &Client, _config: &ApiConfig, _credential: &str) -> Option<RequestBuilder> {
+        None
+    }
+
+    /// Model ids from a model listing, by default the OpenAI shape `{"data": [{"id": ...}]}`.
+    fn parse_models(&self, body: &Value) -> Vec<String> {
+        model_names(&body["data"], "id")
+    }
+}
+
+/// Providers by `ApiProvider::env_name`.
+static REGISTRY: &[(&str, &dyn Provider)] = &[
+    ("OPENAI", &openai::OPENAI),
+    ("SAMBANOVA", &openai::OPENAI),
+    ("GROQ", &openai::OPENAI),
+    ("AZURE_OPENAI", &openai::AZURE_OPENAI),
+    ("OPENROUTER", &openai::OPENROUTER),
+    ("CUSTOM", &openai::CUSTOM),
+    ("MISTRAL", &openai::MISTRAL),
+    ("DEEPSEEK", &openai::DEEPSEEK),
+    ("HUGGINGFACE", &openai::HUGGINGFACE),
+    ("GEMINI", &gemini::GEMINI),
+    ("VERTEX", &gemini::VERTEX),
+    ("OLLAMA", &ollama::Ollama),
+    ("BEDROCK", &bedrock::Bedrock),
+    ("COHERE", &cohere::Cohere),
+    ("ANTHROPIC", &anthropic::Anthropic),
+];
+
+pub fn get(name: &str) -> Option<&'static dyn Provider> {
+    REGISTRY.iter().find(|(registered, _)| *registered == name).map(|(_, provider)| *provider)
+}
+
+/// The `field` of each entry of a model listing array.
+fn model_names(list: &Value, field: &str) -> Vec<String> {
+    list.as_array().into_iter().flatten().filter_map(|m| m[field].as_str().map(str::to_string)).collect()
+}
+
+/// `base_url` with its final `suffix` (the chat endpoint) replaced by `replacement`.
+fn sibling_url(base_url: &str, suffix: &str, replacement: &str) -> Option<String> {
+    base_url.strip_suffix(suffix).map(|root| format!("{}{}", root, replacement))
+}
+
+/// Sets `body[key]` to `value` when there is one, leaving the provider default otherwise.
+fn set_if<T: serde::Serialize>(body: &mut Value, key: &str, value: Option<T>) {
+    if let Some(value) = value {
+        body[key] = json!(value);
+    }
+}
+
+/// Name, description and argument schema of a tool, as function declarations take them.
+fn declaration(tool: &str) -> Value {
+    json!({"name": tool, "description": tool_args::description(tool), "parameters": tool_args::schema(tool)})
+}
+
+/// The reply text, or the bracket form of the first native tool call when there is one.
+fn text_or_tool_call(text: Option<&str>, tool_call: Option<(&str, &Value)>) -> Option<String> {
+    tool_call.and_then(|(tool, arguments)| tool_args::marker(tool, arguments)).or_else(|| text.map(str::to_string))
+}
+
+/// Usage from a response's usage block, adding up the named fields for each side (cached or
+/// reasoning tokens that a provider counts separately). `None` when none of them are present.
+fn token_usage(usage: &Value, prompt: &[&str], completion: &[&str]) -> Option<TokenUsage> {
+    let total = |fields: &[&str]| fields.iter().filter_map(|field| usage[*field].as_u64()).reduce(|a, b| a + b);
+    match (total(prompt), total(completion)) {
+        (None, None) => None,
+        (prompt_tokens, completion_tokens) => {
+            Some(TokenUsage { prompt_tokens: prompt_tokens.unwrap_or(0), completion_tokens: completion_tokens.unwrap_or(0) })
+        }
+    }
+}
+
+/// A reply with `[No response]` standing in for missing text and empty reasoning dropped.
+fn reply(text: Option<String>, finish_reason: Option<&str>, reasoning: Option<String>) -> LlmReply {
+    LlmReply {
+        text: text.unwrap_or_else(|| "[No response]".to_string()),
+        finish_reason: finish_reason.map(str::to_string),
+        reasoning: reasoning.filter(|r| !r.trim().is_empty()),
+        failover: None,
+        timing: Default::default(),
+        usage: None,
+        logprobs: None,
+        seed: None,
+    }
+}
+
+/// Splits off a leading system prompt and merges the rest into alternating user and assistant
+/// turns, for APIs that take the system prompt as a separate field. Tool results and other
+/// mid-conversation system messages go in as user turns. Assistant turns before the first user
+/// turn are dropped, as these APIs need the conversation to start with the user.
+fn alternating_turns(history: &[Message]) -> (Option<String>, Vec<(&'static str, String)>) {
+    let system = history.first().filter(|m| m.role == "system").map(|m| m.content.clone());
+    let mut turns: Vec<(&'static str, String)> = Vec::new();
+    for msg in history.iter().skip(usize::from(system.is_some())) {
+        let role = if msg.role == "assistant" { "assistant" } else { "user" };
+        if role == "assistant" && turns.is_empty() {
+            continue;
+        }
+        match turns.last_mut() {
+            Some((last_role, content)) if *last_role == role => {
+                content.push_str("\n\n");
+                content.push_str(&msg.content);
+            }
+            _ => turns.push((role, msg.content.clone())),
+        }
+    }
+    (system, turns)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::alternating_turns;
+    use crate::Message;
+
+    fn message(role: &str, content: &str) -> Message {
+        Message { role: role.to_string(), content: content.to_string() }
+    }
+
+    #[test]
+    fn turns_start_with_the_user_and_alternate() {
+        let history = [
+            message("system", "Be brief."),
+            message("assistant", "Hello!"),
+            message("user", "Hi"),
+            message("system", "Command output:\nok"),
+            message("assistant", "Done."),
+            message("assistant", "Anything else?"),
+        ];
+        let (system, turns) = alternating_turns(&history);
+        assert_eq!(system.as_deref(), Some("Be brief."));
+        assert_eq!(turns, [("user", "Hi\n\nCommand output:\nok".to_string()), ("assistant", "Done.\n\nAnything else?".to_string())]);
+    }
+}
@@ -0,0 +1,71 @@
+use reqwest::header::CONTENT_TYPE;
+use reqwest::{Client, RequestBuilder};
+use serde_json::{Value, json};
+
+use super::{Provider, model_names, reply, set_if, sibling_url, token_usage};
+use crate::sampling::{DEFAULT_TEMPERATURE, DEFAULT_TOP_P};
+use crate::usage::TokenUsage;
+use crate::{ApiConfig, LlmReply, Message, attachments};
+
+/// Ollama's native `/api/chat`, which takes no key.
+pub struct Ollama;
+
+impl Provider for Ollama {
+    fn build_request(&self, client: &Client, config: &ApiConfig, history: &[Message], _credential: &str) -> Result<RequestBuilder, String> {
+        let messages_json: Vec<_> = history
+            .iter()
+            .map(|m| {
+                let (text, images) = attachments::split(&m.content);
+                let mut message = json!({"role": m.role, "content": text});
+                if !images.is_empty() {
+                    message["images"] = images.into_iter().map(|(_, data)| data).collect();
+                }
+                message
+            })
+            .collect();
+        let sampling = &config.sampling;
+        let mut options = json!({
+            "temperature": sampling.temperature.unwrap_or(DEFAULT_TEMPERATURE),
+            "top_p": sampling.top_p.unwrap_or(DEFAULT_TOP_P)
+        });
+        set_if(&mut options, "num_predict", sampling.max_tokens);
+        set_if(&mut options, "presence_penalty", sampling.presence_penalty);
+        set_if(&mut options, "frequency_penalty", sampling.frequency_penalty);
+        set_if(&mut options, "stop", Some(&sampling.stop).filter(|stop| !stop.is_empty()));
+        set_if(&mut options, "seed", sampling.seed);
+        let mut body = json!({
+            "model": config.model_name,
+            "messages": messages_json,
+            "stream": false,
+            "options": options
+        });
+        if let Some(schema) = &config.response_schema {
+            body["format"] = schema.schema.clone();
+        }
+        if config.reasoning.budget_tokens().is_some_and(|budget| budget > 0) {
+            body["think"] = json!(true);
+        }
+        Ok(client.post(&config.base_url).header(CONTENT_TYPE, "application/json").json(&body))
+    }
+
+    /// The models installed on the server.
+    fn models_request(&self, client: &Client, config: &ApiConfig, _credential: &str) -> Option<RequestBuilder> {
+        sibling_url(&config.base_url, "/api/chat", "/api/tags").map(|url| client.get(url))
+    }
+
+    fn parse_models(&self, body: &Value) -> Vec<String> {
+        model_names(&body["models"], "name")
+    }
+
+    fn parse_response(&self, body: &Value) -> LlmReply {
+        reply(
+            body["message"]["content"].as_str().map(str::to_string),
+            body["done_reason"].as_str(),
+            body["message"]["thinking"].as_str().map(str::to_string),
+        )
+    }
+
+    fn parse_usage(&self, body: &Value) -> Option<TokenUsage> {
+        token_usage(body, &["prompt_eval_count"], &["eval_count"])
+    }
+}
@@ -0,0 +1,248 @@
+use reqwest::header::{AUTHORIZATION, CONTENT_TYPE};
+use reqwest::{Client, RequestBuilder};
+use serde_json::{Value, json};
+
+use super::{Provider, declaration, reply, set_if, sibling_url, text_or_tool_call};
+use crate::sampling::{DEFAULT_TEMPERATURE, DEFAULT_TOP_P};
+use crate::{ApiConfig, LlmReply, Message, attachments};
+
+/// How a backend takes the configured reasoning level.
+enum ReasoningField {
+    /// `reasoning_effort`, as OpenAI defines it.
+    Effort,
+    /// OpenRouter's own `reasoning` object, translated for each upstream provider.
+    OpenRouter,
+    /// Not sent at all.
+    Omitted,
+}
+
+/// How the key is sent.
+enum KeyHeader {
+    Bearer,
+    /// Azure's `api-key` header for static keys; plugin tokens still go as bearer tokens.
+    AzureApiKey,
+    /// A bearer token only when a key is set, for local servers that take none.
+    OptionalBearer,
+}
+
+/// Any backend speaking the OpenAI chat completions format, with its small differences.
+pub struct OpenAiCompatible {
+    reasoning: ReasoningField,
+    key_header: KeyHeader,
+    extra_headers: &'static [(&'static str, &'static str)],
+    error_message: Option<fn(&str) -> Option<String>>,
+    lists_models: bool,
+    /// The field the sampling seed goes in.
+    seed_field: &'static str,
+    /// Whether token log probabilities can be requested.
+    logprobs: bool,
+}
+
+pub static OPENAI: OpenAiCompatible = OpenAiCompatible {
+    reasoning: ReasoningField::Effort,
+    key_header: KeyHeader::Bearer,
+    extra_headers: &[],
+    error_message: None,
+    lists_models: true,
+    seed_field: "seed",
+    logprobs: true,
+};
+
+/// Azure selects the model by deployment in the URL, so there is no model to list.
+pub static AZURE_OPENAI: OpenAiCompatible = OpenAiCompatible {
+    reasoning: ReasoningField::Effort,
+    key_header: KeyHeader::AzureApiKey,
+    extra_headers: &[],
+    error_message: None,
+    lists_models: false,
+    seed_field: "seed",
+    logprobs: true,
+};
+
+pub static OPENROUTER: OpenAiCompatible = OpenAiCompatible {
+    reasoning: ReasoningField::OpenRouter,
+    key_header: KeyHeader::Bearer,
+    extra_headers: &[],
+    error_message: None,
+    lists_models: true,
+    seed_field: "seed",
+    logprobs: true,
+};
+
+pub static CUSTOM: OpenAiCompatible = OpenAiCompatible {
+    reasoning: ReasoningField::Effort,
+    key_header: KeyHeader::OptionalBearer,
+    extra_headers: &[],
+    error_message: None,
+    lists_models: true,
+    seed_field: "seed",
+    logprobs: true,
+};
+
+/// Mistral rejects unknown fields, so no reasoning level or logprobs are sent, and takes the seed
+/// as `random_seed`.
+pub static MISTRAL: OpenAiCompatible = OpenAiCompatible {
+    reasoning: ReasoningField::Omitted,
+    key_header: KeyHeader::Bearer,
+    extra_headers: &[],
+    error_message: Some(mistral_error),
+    lists_models: true,
+    seed_field: "random_seed",
+    logprobs: false,
+};
+
+/// deepseek-reasoner always reasons, so no reasoning level is sent.
+pub static DEEPSEEK: OpenAiCompatible = OpenAiCompatible {
+    reasoning: ReasoningField::Omitted,
+    key_header: KeyHeader::Bearer,
+    extra_headers: &[],
+    error_message: None,
+    lists_models: true,
+    seed_field: "seed",
+    logprobs: true,
+};
+
+/// Hugging Face backends differ in what they accept, so no reasoning level is sent. Serverless
+/// models that are not loaded yet make the request wait instead of returning 503.
+pub static HUGGINGFACE: OpenAiCompatible = OpenAiCompatible {
+    reasoning: ReasoningField::Omitted,
+    key_header: KeyHeader::Bearer,
+    extra_headers: &[("x-wait-for-model", "true")],
+    error_message: Some(huggingface_error),
+    lists_models: true,
+    seed_field: "seed",
+    logprobs: true,
+};
+
+impl Provider for OpenAiCompatible {
+    fn build_request(&self, client: &Client, config: &ApiConfig, history: &[Message], credential: &str) -> Result<RequestBuilder, String> {
+        let messages_json: Vec<_> = history.iter().map(|m| json!({"role": m.role, "content": content(&m.content)})).collect();
+        let sampling = &config.sampling;
+        let mut body = json!({
+            "model": config.model_name,
+            "messages": messages_json,
+            "temperature": sampling.temperature.unwrap_or(DEFAULT_TEMPERATURE),
+            "top_p": sampling.top_p.unwrap_or(DEFAULT_TOP_P)
+        });
+        set_if(&mut body, "max_tokens", sampling.max_tokens);
+        set_if(&mut body, "presence_penalty", sampling.presence_penalty);
+        set_if(&mut body, "frequency_penalty", sampling.frequency_penalty);
+        set_if(&mut body, "stop", Some(&sampling.stop).filter(|stop| !stop.is_empty()));
+        set_if(&mut body, self.seed_field, sampling.seed);
+        if let Some(alternatives) = sampling.logprobs.filter(|_| self.logprobs) {
+            body["logprobs"] = json!(true);
+            set_if(&mut body, "top_logprobs", Some(alternatives).filter(|&n| n > 0));
+        }
+        if let Some(schema) = &config.response_schema {
+            body["response_format"] = json!({"type": "json_schema", "json_schema": {"name": schema.name, "schema": schema.schema}});
+        }
+        if !config.tools.is_empty() {
+            body["tools"] = config.tools.iter().map(|tool| json!({"type": "function", "function": declaration(tool)})).collect();
+        }
+        match self.reasoning {
+            ReasoningField::OpenRouter => {
+                if let Some(budget) = config.reasoning.thinking_budget {
+                    body["reasoning"] = json!({"max_tokens": budget});
+                } else if let Some(effort) = config.reasoning.effort {
+                    body["reasoning"] = json!({"effort": effort.name()});
+                }
+            }
+            ReasoningField::Effort => {
+                if let Some(effort) = config.reasoning.effort {
+                    body["reasoning_effort"] = json!(effort.name());
+                }
+            }
+            ReasoningField::Omitted => {}
+        }
+        Ok(self.authorized(client.post(&config.base_url), config, credential).header(CONTENT_TYPE, "application/json").json(&body))
+    }
+
+    fn parse_response(&self, body: &Value) -> LlmReply {
+        // DeepSeek and vLLM return `reasoning_content`; OpenRouter and Groq use `reasoning`.
+        let message = &body["choices"][0]["message"];
+        let tool_call = &message["tool_calls"][0]["function"];
+        reply(
+            text_or_tool_call(message["content"].as_str(), tool_call["name"].as_str().map(|name| (name, &tool_call["arguments"]))),
+            body["choices"][0]["finish_reason"].as_str(),
+            message["reasoning_content"].as_str().or(message["reasoning"].as_str()).map(str::to_string),
+        )
+    }
+
+    fn declares_tools(&self) -> bool {
+        true
+    }
+
+    fn parse_error(&self, body: &str) -> Option<String> {
+        self.error_message.and_then(|parse| parse(body))
+    }
+
+    fn models_request(&self, client: &Client, config: &ApiConfig, credential: &str) -> Option<RequestBuilder> {
+        let url = sibling_url(&config.base_url, "/chat/completions", "/models").filter(|_| self.lists_models)?;
+        Some(self.authorized(client.get(url), config, credential))
+    }
+}
+
+impl OpenAiCompatible {
+    fn authorized(&self, request: RequestBuilder, config: &ApiConfig, credential: &str) -> RequestBuilder {
+        let mut request = match self.key_header {
+            KeyHeader::AzureApiKey if config.auth.is_static() => request.header("api-key", credential),
+            KeyHeader::OptionalBearer if credential.is_empty() => request,
+            _ => request.header(AUTHORIZATION, format!("Bearer {}", credential)),
+        };
+        for (name, value) in self.extra_headers {
+            request = request.header(*name, *value);
+        }
+        request
+    }
+}
+
+/// Message content: the text, or text and `image_url` parts when it has images.
+fn content(text: &str) -> Value {
+    let (text, images) = attachments::split(text);
+    if images.is_empty() {
+        return json!(text);
+    }
+    let mut parts: Vec<Value> = images
+        .into_iter()
+        .map(|(media_type, data)| json!({"type": "image_url", "image_url": {"url": format!("data:{};base64,{}", media_type, data)}}))
+        .collect();
+    if !text.is_empty() {
+        parts.insert(0, json!({"type": "text", "text": text}));
+    }
+    Value::Array(parts)
+}
+
+/// Readable message from a Mistral error body: `{"message": ...}`, where the message may itself be
+/// an object, or `{"detail": [{"loc": [...], "msg": ...}]}` for requests that fail validation.
+fn mistral_error(body: &str) -> Option<String> {
+    let value: Value = serde_json::from_str(body).ok()?;
+    if let Some(details) = value["detail"].as_array() {
+        let problems: Vec<String> = details
+            .iter()
+            .map(|d| {
+                let location: Vec<String> = d["loc"]
+                    .as_array()
+                    .map(|loc| loc.iter().map(|part| part.as_str().map_or_else(|| part.to_string(), str::to_string)).collect())
+                    .unwrap_or_default();
+                format!("{}: {}", location.join("."), d["msg"].as_str().unwrap_or("invalid value"))
+            })
+            .collect();
+        return Some(problems.join("; "));
+    }
+    match &value["message"] {
+        Value::String(message) => Some(message.clone()),
+        Value::Null => value["detail"].as_str().map(str::to_string),
+        message => Some(message["detail"].as_str().map_or_else(|| message.to_string(), str::to_string)),
+    }
+}
+
+/// Readable message from a Hugging Face error body: `{"error": "..."}`, with `estimated_time` while
+/// a model is loading, or `{"error": {"message": ...}}` from the router.
+fn huggingface_error(body: &str) -> Option<String> {
+    let value: Value = serde_json::from_str(body).ok()?;
+    let message = value["error"].as_str().or(value["error"]["message"].as_str())?;
+    Some(match value["estimated_time"].as_f64() {
+        Some(seconds) => format!("{} (ready in about {:.0}s)", message, seconds),
+        None => message.to_string(),
+    })
+}
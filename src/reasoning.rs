@@ -0,0 +1,121 @@
+use std::env;
+
+use rusqlite::{params, Connection};
+
+/// Reasoning effort level, sent as OpenAI's `reasoning_effort`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Effort {
+    Minimal,
+    Low,
+    Medium,
+    High,
+}
+
+impl Effort {
+    fn parse(value: &str) -> Option<Self> {
+        match value.trim().to_lowercase().as_str() {
+            "minimal" => Some(Effort::Minimal),
+            "low" => Some(Effort::Low),
+            "medium" => Some(Effort::Medium),
+            "high" => Some(Effort::High),
+            _ => None,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Effort::Minimal => "minimal",
+            Effort::Low => "low",
+            Effort::Medium => "medium",
+            Effort::High => "high",
+        }
+    }
+
+    /// Thinking budget in tokens used for providers that take a budget rather than a level.
+    pub fn thinking_budget(&self) -> u32 {
+        match self {
+            Effort::Minimal => 0,
+            Effort::Low => 1024,
+            Effort::Medium => 8192,
+            Effort::High => 24_576,
+        }
+    }
+}
+
+/// Reasoning controls for a run, from `--reasoning-effort` / `--thinking-budget` or
+/// `REASONING_EFFORT` / `THINKING_BUDGET_TOKENS`. Unset values leave the provider default.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct ReasoningConfig {
+    pub effort: Option<Effort>,
+    pub thinking_budget: Option<u32>,
+}
+
+impl ReasoningConfig {
+    pub fn from_args(args: &[String]) -> Result<Self, String> {
+        let value = |flag: &str, var: &str| -> Result<Option<String>, String> {
+            match args.iter().position(|a| a == flag) {
+                Some(i) => args.get(i + 1).cloned().map(Some).ok_or_else(|| format!("{} needs a value", flag)),
+                None => Ok(env::var(var).ok().filter(|v| !v.trim().is_empty())),
+            }
+        };
+
+        let effort = match value("--reasoning-effort", "REASONING_EFFORT")? {
+            Some(level) => Some(
+                Effort::parse(&level)
+                    .ok_or_else(|| format!("invalid reasoning effort '{}' (use minimal, low, medium or high)", level))?,
+            ),
+            None => None,
+        };
+        let thinking_budget = match value("--thinking-budget", "THINKING_BUDGET_TOKENS")? {
+            Some(tokens) => Some(
+                tokens.trim().parse().map_err(|_| format!("invalid thinking budget '{}' (use a number of tokens)", tokens))?,
+            ),
+            None => None,
+        };
+        Ok(ReasoningConfig { effort, thinking_budget })
+    }
+
+    pub fn is_default(&self) -> bool {
+        *self == ReasoningConfig::default()
+    }
+
+    /// The explicit budget, or one derived from the effort level.
+    pub fn budget_tokens(&self) -> Option<u32> {
+        self.thinking_budget.or(self.effort.map(|e| e.thinking_budget()))
+    }
+
+    pub fn describe(&self) -> String {
+        let mut parts = Vec::new();
+        if let Some(effort) = self.effort {
+            parts.push(format!("effort={}", effort.name()));
+        }
+        if let Some(budget) = self.thinking_budget {
+            parts.push(format!("thinking_budget={}", budget));
+        }
+        parts.join(", ")
+    }
+}
+
+/// Records a session's reasoning settings alongside its model and prompt variant.
+pub fn record(conn: &Connection, session_id: &str, reasoning: &ReasoningConfig) {
+    if !reasoning.is_default() {
+        conn.execute(
+            "UPDATE sessions SET reasoning = ?2 WHERE id = ?1",
+            params![session_id, reasoning.describe()],
+        ).unwrap();
+    }
+}
+
+/// Whether reasoning returned apart from the answer is shown in the terminal (`SHOW_REASONING`).
+pub fn show_reasoning() -> bool {
+    matches!(env::var("SHOW_REASONING").unwrap_or_default().trim(), "1" | "true" | "yes")
+}
+
+/// Records the length of the reasoning behind a stored reply, for comparing how much models
+/// think before answering.
+pub fn record_message(conn: &Connection, message_id: i64, reasoning: &str) {
+    conn.execute(
+        "UPDATE messages SET reasoning_chars = ?2 WHERE id = ?1",
+        params![message_id, reasoning.chars().count() as i64],
+    ).unwrap();
+}
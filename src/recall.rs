@@ -0,0 +1,80 @@
+use std::collections::HashSet;
+use std::env;
+
+use rusqlite::Connection;
+
+/// How many past questions are compared against a new one.
+const MAX_CANDIDATES: i64 = 2000;
+
+/// A previously asked question similar to the current one, with the answer it received.
+#[derive(Debug)]
+pub struct PastAnswer {
+    pub session_id: String,
+    pub asked_at: String,
+    pub question: String,
+    pub answer: String,
+    pub similarity: f64,
+}
+
+/// Whether new questions are checked against earlier ones (`DUPLICATE_CHECK`).
+pub fn duplicate_check_enabled() -> bool {
+    matches!(env::var("DUPLICATE_CHECK").unwrap_or_default().trim(), "1" | "true" | "yes")
+}
+
+fn threshold() -> f64 {
+    env::var("DUPLICATE_THRESHOLD")
+        .ok()
+        .and_then(|v| v.trim().parse().ok())
+        .unwrap_or(0.8)
+}
+
+fn words(text: &str) -> HashSet<String> {
+    text.split(|c: char| !c.is_alphanumeric())
+        .filter(|w| !w.is_empty())
+        .map(|w| w.to_lowercase())
+        .collect()
+}
+
+/// Jaccard similarity of the word sets of `a` and `b`.
+fn similarity(a: &HashSet<String>, b: &HashSet<String>) -> f64 {
+    if a.is_empty() || b.is_empty() {
+        return 0.0;
+    }
+    a.intersection(b).count() as f64 / a.union(b).count() as f64
+}
+
+/// Finds the most similar earlier question that got an answer, if it clears `DUPLICATE_THRESHOLD`.
+pub fn find_similar_question(conn: &Connection, question: &str) -> Option<PastAnswer> {
+    let query_words = words(question);
+    let threshold = threshold();
+    let mut stmt = conn
+        .prepare(
+            "SELECT q.session_id, q.created_at, message_text(q.content, q.encoding),
+                    (SELECT CASE WHEN a.deleted_at IS NULL THEN message_text(a.content, a.encoding) END FROM messages a
+                     WHERE a.session_id = q.session_id AND a.id > q.id AND a.role = 'assistant'
+                     ORDER BY a.id LIMIT 1)
+             FROM messages q
+             WHERE q.role = 'user' AND q.deleted_at IS NULL
+             ORDER BY q.id DESC
+             LIMIT ?1",
+        )
+        .unwrap();
+    let rows = stmt
+        .query_map([MAX_CANDIDATES], |row| {
+            Ok((
+                row.get::<_, String>(0)?,
+                row.get::<_, String>(1)?,
+                row.get::<_, String>(2)?,
+                row.get::<_, Option<String>>(3)?,
+            ))
+        })
+        .unwrap();
+
+    rows.filter_map(Result::ok)
+        .filter_map(|(session_id, asked_at, past_question, answer)| {
+            let score = similarity(&query_words, &words(&past_question));
+            answer.map(|answer| PastAnswer { session_id, asked_at, question: past_question, answer, similarity: score })
+        })
+        .filter(|past| past.similarity >= threshold)
+        .max_by(|a, b| a.similarity.total_cmp(&b.similarity))
+}
@@ -0,0 +1,102 @@
+use std::env;
+
+use chrono::{Duration, Utc};
+use rusqlite::{params, Connection};
+
+/// How long a new session is kept before the expiry sweep deletes it.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Retention {
+    Keep,
+    Ephemeral,
+    For(Duration),
+}
+
+/// Parses durations such as `90m`, `12h`, `7d` or `2w`; `None` when malformed or too long to
+/// represent.
+pub fn parse_duration(value: &str) -> Option<Duration> {
+    let value = value.trim();
+    let unit = value.chars().last()?;
+    let amount: i64 = value[..value.len() - unit.len_utf8()].parse().ok()?;
+    match unit {
+        'm' => Duration::try_minutes(amount),
+        'h' => Duration::try_hours(amount),
+        'd' => Duration::try_days(amount),
+        'w' => Duration::try_weeks(amount),
+        _ => None,
+    }
+}
+
+impl Retention {
+    /// Reads `--ephemeral` / `--retain <duration>` from the command line, falling back to
+    /// `SESSION_RETAIN`.
+    pub fn from_args(args: &[String]) -> Result<Self, String> {
+        if args.iter().any(|a| a == "--ephemeral") {
+            return Ok(Retention::Ephemeral);
+        }
+        let value = match args.iter().position(|a| a == "--retain") {
+            Some(i) => args.get(i + 1).cloned().ok_or("--retain needs a duration such as 7d")?,
+            None => match env::var("SESSION_RETAIN") {
+                Ok(value) if !value.trim().is_empty() => value,
+                _ => return Ok(Retention::Keep),
+            },
+        };
+        parse_duration(&value)
+            .filter(|duration| Utc::now().checked_add_signed(*duration).is_some())
+            .map(Retention::For)
+            .ok_or_else(|| format!("invalid retention '{}' (use e.g. 12h, 7d, 2w)", value))
+    }
+
+    /// Expiry time in SQLite's `CURRENT_TIMESTAMP` format (UTC), if the session expires.
+    pub fn expires_at(&self) -> Option<String> {
+        let expires = match self {
+            Retention::Keep => return None,
+            Retention::Ephemeral => Utc::now(),
+            Retention::For(duration) => Utc::now().checked_add_signed(*duration)?,
+        };
+        Some(expires.format("%Y-%m-%d %H:%M:%S").to_string())
+    }
+
+    pub fn describe(&self) -> String {
+        match self {
+            Retention::Keep => "kept until deleted".to_string(),
+            Retention::Ephemeral => "ephemeral (deleted when the session ends)".to_string(),
+            Retention::For(_) => format!("expires at {} UTC", self.expires_at().unwrap_or_default()),
+        }
+    }
+}
+
+pub fn set_session_expiry(conn: &Connection, session_id: &str, retention: Retention) {
+    if let Some(expires_at) = retention.expires_at() {
+        conn.execute(
+            "UPDATE sessions SET expires_at = ?2 WHERE id = ?1",
+            params![session_id, expires_at],
+        ).unwrap();
+    }
+}
+
+/// Deletes a session together with its messages, checkpoints, tool runs, scheduled jobs and
+/// metadata.
+pub fn delete_session(conn: &Connection, session_id: &str) {
+    conn.execute("DELETE FROM messages WHERE session_id = ?1", params![session_id]).unwrap();
+    conn.execute("DELETE FROM scheduled_jobs WHERE session_id = ?1", params![session_id]).unwrap();
+    conn.execute("DELETE FROM checkpoints WHERE session_id = ?1", params![session_id]).unwrap();
+    conn.execute("DELETE FROM tool_runs WHERE session_id = ?1", params![session_id]).unwrap();
+    conn.execute("DELETE FROM session_meta WHERE session_id = ?1", params![session_id]).unwrap();
+    conn.execute("DELETE FROM sessions WHERE id = ?1", params![session_id]).unwrap();
+}
+
+/// Deletes every session whose expiry time has passed and returns how many were removed.
+pub fn sweep_expired(conn: &Connection) -> usize {
+    let mut stmt = conn
+        .prepare("SELECT id FROM sessions WHERE expires_at IS NOT NULL AND expires_at <= CURRENT_TIMESTAMP")
+        .unwrap();
+    let expired: Vec<String> = stmt
+        .query_map([], |row| row.get(0))
+        .unwrap()
+        .filter_map(Result::ok)
+        .collect();
+    for session_id in &expired {
+        delete_session(conn, session_id);
+    }
+    expired.len()
+}
@@ -0,0 +1,140 @@
+use std::env;
+
+use rusqlite::{params, Connection};
+use serde_json::Value;
+
+/// Temperature and top_p sent by providers that always sent them, unless overridden.
+pub const DEFAULT_TEMPERATURE: f64 = 0.1;
+pub const DEFAULT_TOP_P: f64 = 0.1;
+
+/// Names accepted by `/set` and as `--<name>` flags (with dashes).
+pub const SETTINGS: &[&str] = &["temperature", "top_p", "max_tokens", "presence_penalty", "frequency_penalty", "stop", "seed", "logprobs"];
+
+/// Most alternatives per token that `logprobs` can ask for (OpenAI's and Gemini's limit).
+const MAX_TOP_LOGPROBS: u32 = 20;
+
+/// Sampling controls for a session, from `--temperature`, `--top-p`, `--max-tokens`,
+/// `--presence-penalty`, `--frequency-penalty`, `--stop`, `--seed` and `--logprobs` (or
+/// `TEMPERATURE`, `TOP_P`, `MAX_TOKENS`, `PRESENCE_PENALTY`, `FREQUENCY_PENALTY`,
+/// `STOP_SEQUENCES`, `SEED`, `LOGPROBS`), changed in chat with `/set`. Unset values leave each
+/// provider's usual default; providers ignore what their API does not take.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct SamplingConfig {
+    pub temperature: Option<f64>,
+    pub top_p: Option<f64>,
+    pub max_tokens: Option<u32>,
+    pub presence_penalty: Option<f64>,
+    pub frequency_penalty: Option<f64>,
+    pub stop: Vec<String>,
+    /// Seed for providers that sample deterministically from one.
+    pub seed: Option<i64>,
+    /// Log probabilities are returned for each token, with this many alternatives (0 for none).
+    pub logprobs: Option<u32>,
+}
+
+impl SamplingConfig {
+    pub fn from_args(args: &[String]) -> Result<Self, String> {
+        let mut sampling = SamplingConfig::default();
+        for name in SETTINGS {
+            let flag = format!("--{}", name.replace('_', "-"));
+            let var = if *name == "stop" { "STOP_SEQUENCES".to_string() } else { name.to_uppercase() };
+            let value = match args.iter().position(|a| *a == flag) {
+                Some(i) => Some(args.get(i + 1).cloned().ok_or_else(|| format!("{} needs a value", flag))?),
+                None => env::var(&var).ok().filter(|v| !v.trim().is_empty()),
+            };
+            if let Some(value) = value {
+                sampling.set(name, &value)?;
+            }
+        }
+        Ok(sampling)
+    }
+
+    /// Sets one value by name; `default` clears it. Stop sequences are comma-separated, with
+    /// `\n` for a newline.
+    pub fn set(&mut self, name: &str, value: &str) -> Result<(), String> {
+        let value = value.trim();
+        let reset = value.eq_ignore_ascii_case("default");
+        let number = |range: std::ops::RangeInclusive<f64>| -> Result<Option<f64>, String> {
+            if reset {
+                return Ok(None);
+            }
+            value
+                .parse::<f64>()
+                .ok()
+                .filter(|v| range.contains(v))
+                .map(Some)
+                .ok_or_else(|| format!("invalid {} '{}' (use a number from {} to {})", name, value, range.start(), range.end()))
+        };
+        match name {
+            "temperature" => self.temperature = number(0.0..=2.0)?,
+            "top_p" => self.top_p = number(0.0..=1.0)?,
+            "presence_penalty" => self.presence_penalty = number(-2.0..=2.0)?,
+            "frequency_penalty" => self.frequency_penalty = number(-2.0..=2.0)?,
+            "max_tokens" if reset => self.max_tokens = None,
+            "max_tokens" => {
+                self.max_tokens = Some(value.parse().ok().filter(|&t| t > 0).ok_or_else(|| format!("invalid max_tokens '{}' (use a number of tokens)", value))?)
+            }
+            "stop" if reset => self.stop.clear(),
+            "stop" => self.stop = value.split(',').map(|s| s.replace("\\n", "\n")).filter(|s| !s.is_empty()).collect(),
+            "seed" if reset => self.seed = None,
+            "seed" => self.seed = Some(value.parse().map_err(|_| format!("invalid seed '{}' (use an integer)", value))?),
+            "logprobs" if reset => self.logprobs = None,
+            "logprobs" => {
+                self.logprobs = Some(value.parse().ok().filter(|&n| n <= MAX_TOP_LOGPROBS).ok_or_else(|| {
+                    format!("invalid logprobs '{}' (use the number of alternatives per token, 0 to {})", value, MAX_TOP_LOGPROBS)
+                })?)
+            }
+            _ => return Err(format!("unknown setting '{}' (use {})", name, SETTINGS.join(", "))),
+        }
+        Ok(())
+    }
+
+    pub fn is_default(&self) -> bool {
+        *self == SamplingConfig::default()
+    }
+
+    pub fn describe(&self) -> String {
+        let mut parts = Vec::new();
+        let numbers = [("temperature", self.temperature), ("top_p", self.top_p), ("presence_penalty", self.presence_penalty), ("frequency_penalty", self.frequency_penalty)];
+        for (name, value) in numbers {
+            if let Some(value) = value {
+                parts.push(format!("{}={}", name, value));
+            }
+        }
+        if let Some(tokens) = self.max_tokens {
+            parts.push(format!("max_tokens={}", tokens));
+        }
+        if !self.stop.is_empty() {
+            parts.push(format!("stop={:?}", self.stop));
+        }
+        if let Some(seed) = self.seed {
+            parts.push(format!("seed={}", seed));
+        }
+        if let Some(alternatives) = self.logprobs {
+            parts.push(format!("logprobs={}", alternatives));
+        }
+        parts.join(", ")
+    }
+}
+
+/// Records a session's sampling settings alongside its reasoning settings; called again after
+/// each `/set`.
+pub fn record(conn: &Connection, session_id: &str, sampling: &SamplingConfig) {
+    conn.execute(
+        "UPDATE sessions SET sampling = ?2 WHERE id = ?1",
+        params![session_id, Some(sampling.describe()).filter(|s| !s.is_empty())],
+    ).unwrap();
+}
+
+/// Records the seed a reply was sampled with, `None` when the answering provider was not sent
+/// one, and the log probabilities returned with it, in
+/// `messages.seed` and `messages.logprobs`, so runs can be reproduced and compared.
+pub fn record_reply(conn: &Connection, message_id: i64, seed: Option<i64>, logprobs: Option<&Value>) {
+    if seed.is_none() && logprobs.is_none() {
+        return;
+    }
+    conn.execute(
+        "UPDATE messages SET seed = ?2, logprobs = ?3 WHERE id = ?1",
+        params![message_id, seed, logprobs.map(Value::to_string)],
+    ).unwrap();
+}
@@ -0,0 +1,396 @@
+use std::env;
+use std::io::Read;
+use std::os::unix::process::CommandExt;
+use std::process::{Command, Stdio};
+use std::thread;
+use std::time::{Duration as StdDuration, Instant};
+
+use chrono::{DateTime, Duration, Local, NaiveDate, NaiveDateTime, NaiveTime, TimeZone, Utc};
+use rusqlite::{params, Connection};
+
+use crate::retention::parse_duration;
+use crate::save_message;
+
+/// How long a scheduled command may run before it is killed, unless `SCHEDULE_COMMAND_TIMEOUT_SECS`
+/// says otherwise.
+const DEFAULT_COMMAND_TIMEOUT_SECS: u64 = 300;
+
+/// Format of `run_at` and `last_run_at`, matching SQLite's `CURRENT_TIMESTAMP` (UTC).
+const TIMESTAMP: &str = "%Y-%m-%d %H:%M:%S";
+
+/// What a job does when it comes due: post a reminder, or run a shell command and post its
+/// output.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Action {
+    Remind(String),
+    Command(String),
+}
+
+impl Action {
+    fn kind(&self) -> &'static str {
+        match self {
+            Action::Remind(_) => "remind",
+            Action::Command(_) => "command",
+        }
+    }
+
+    fn payload(&self) -> &str {
+        match self {
+            Action::Remind(text) | Action::Command(text) => text,
+        }
+    }
+
+    pub fn describe(&self) -> String {
+        match self {
+            Action::Remind(text) => format!("reminder '{}'", text),
+            Action::Command(command) => format!("command `{}`", command),
+        }
+    }
+}
+
+/// When a job runs: once at `run_at`, or from `run_at` on at a fixed interval.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct When {
+    pub run_at: DateTime<Utc>,
+    pub every: Option<Duration>,
+}
+
+fn local_to_utc(datetime: NaiveDateTime) -> Result<DateTime<Utc>, String> {
+    Local
+        .from_local_datetime(&datetime)
+        .earliest()
+        .map(|local| local.with_timezone(&Utc))
+        .ok_or_else(|| format!("{} does not exist in the local time zone", datetime))
+}
+
+/// Parses a schedule: `in 10m`, `every 1h`, `17:00` or `at 17:00` (today, or tomorrow once that
+/// time has passed), or `2025-07-01 09:30`. Clock times are local; durations use `m`, `h`, `d`
+/// or `w`.
+pub fn parse_when(spec: &str) -> Result<When, String> {
+    let spec = spec.trim();
+    let spec = spec.strip_prefix("at ").map(str::trim).unwrap_or(spec);
+    let duration = |value: &str| {
+        let duration =
+            parse_duration(value).filter(|d| *d > Duration::zero()).ok_or_else(|| format!("invalid duration '{}' (use e.g. 10m, 2h, 1d)", value))?;
+        let run_at = Utc::now().checked_add_signed(duration).ok_or_else(|| format!("duration '{}' is too long", value))?;
+        Ok::<_, String>((duration, run_at))
+    };
+    if let Some(value) = spec.strip_prefix("in ") {
+        return Ok(When { run_at: duration(value)?.1, every: None });
+    }
+    if let Some(value) = spec.strip_prefix("every ") {
+        let (every, run_at) = duration(value)?;
+        return Ok(When { run_at, every: Some(every) });
+    }
+    if let Ok(time) = NaiveTime::parse_from_str(spec, "%H:%M") {
+        let now = Local::now();
+        let today = local_to_utc(now.date_naive().and_time(time))?;
+        let run_at = if today > now.with_timezone(&Utc) { today } else { local_to_utc((now.date_naive() + Duration::days(1)).and_time(time))? };
+        return Ok(When { run_at, every: None });
+    }
+    if let Ok(datetime) = NaiveDateTime::parse_from_str(spec, "%Y-%m-%d %H:%M") {
+        return Ok(When { run_at: local_to_utc(datetime)?, every: None });
+    }
+    if let Ok(date) = NaiveDate::parse_from_str(spec, "%Y-%m-%d") {
+        return Ok(When { run_at: local_to_utc(date.and_time(NaiveTime::MIN))?, every: None });
+    }
+    Err(format!("invalid time '{}' (use e.g. in 10m, every 1h, 17:00 or 2025-07-01 09:30)", spec))
+}
+
+fn format_interval(secs: i64) -> String {
+    match secs {
+        s if s % 604_800 == 0 => format!("{}w", s / 604_800),
+        s if s % 86_400 == 0 => format!("{}d", s / 86_400),
+        s if s % 3_600 == 0 => format!("{}h", s / 3_600),
+        s => format!("{}m", s / 60),
+    }
+}
+
+/// `run_at` (stored in UTC) in local time, with the interval of repeating jobs.
+pub fn describe_schedule(run_at: &DateTime<Utc>, every_secs: Option<i64>) -> String {
+    let at = run_at.with_timezone(&Local).format("%Y-%m-%d %H:%M");
+    match every_secs {
+        Some(secs) => format!("{}, then every {}", at, format_interval(secs)),
+        None => at.to_string(),
+    }
+}
+
+impl When {
+    pub fn describe(&self) -> String {
+        describe_schedule(&self.run_at, self.every.map(|d| d.num_seconds()))
+    }
+}
+
+impl Job {
+    pub fn describe_schedule(&self) -> String {
+        match NaiveDateTime::parse_from_str(&self.run_at, TIMESTAMP) {
+            Ok(run_at) => describe_schedule(&run_at.and_utc(), self.every_secs),
+            Err(_) => self.run_at.clone(),
+        }
+    }
+}
+
+/// Splits `<when> <text>` as typed after `/remind` or `/schedule`, e.g. `in 10m stretch` or
+/// `2025-07-01 09:30 cargo test`, into the schedule and the text.
+pub fn split_when(input: &str) -> Result<(String, String), String> {
+    let words: Vec<&str> = input.split_whitespace().collect();
+    for taken in [2, 1] {
+        if words.len() > taken && parse_when(&words[..taken].join(" ")).is_ok() {
+            return Ok((words[..taken].join(" "), words[taken..].join(" ")));
+        }
+    }
+    Err(format!("expected a time followed by what to do, e.g. in 10m stretch (got '{}')", input.trim()))
+}
+
+/// A row of `scheduled_jobs`.
+#[derive(Debug)]
+pub struct Job {
+    pub id: i64,
+    pub session_id: String,
+    pub action: Action,
+    pub run_at: String,
+    pub every_secs: Option<i64>,
+    pub status: String,
+    pub last_result: Option<String>,
+}
+
+/// Stores a job for `session_id` and returns its id.
+pub fn create(conn: &Connection, session_id: &str, when: &When, action: &Action) -> i64 {
+    conn.execute(
+        "INSERT INTO scheduled_jobs (session_id, kind, payload, run_at, every_secs, status) VALUES (?1, ?2, ?3, ?4, ?5, 'pending')",
+        params![session_id, action.kind(), action.payload(), when.run_at.format(TIMESTAMP).to_string(), when.every.map(|d| d.num_seconds())],
+    )
+    .unwrap();
+    conn.last_insert_rowid()
+}
+
+fn query(conn: &Connection, filter: &str, args: &[&dyn rusqlite::ToSql]) -> Vec<Job> {
+    let sql = format!(
+        "SELECT id, session_id, kind, payload, run_at, every_secs, status, last_result FROM scheduled_jobs {} ORDER BY run_at, id",
+        filter
+    );
+    let mut stmt = conn.prepare(&sql).unwrap();
+    stmt.query_map(args, |row| {
+        let kind: String = row.get(2)?;
+        let payload: String = row.get(3)?;
+        Ok(Job {
+            id: row.get(0)?,
+            session_id: row.get(1)?,
+            action: if kind == "command" { Action::Command(payload) } else { Action::Remind(payload) },
+            run_at: row.get(4)?,
+            every_secs: row.get(5)?,
+            status: row.get(6)?,
+            last_result: row.get(7)?,
+        })
+    })
+    .unwrap()
+    .map(|job| job.unwrap())
+    .collect()
+}
+
+/// Pending jobs, or every job with `all`, optionally only those of one session.
+pub fn list(conn: &Connection, session_id: Option<&str>, all: bool) -> Vec<Job> {
+    let status = if all { "1" } else { "status = 'pending'" };
+    match session_id {
+        Some(session_id) => query(conn, &format!("WHERE {} AND session_id = ?1", status), &[&session_id]),
+        None => query(conn, &format!("WHERE {}", status), &[]),
+    }
+}
+
+/// Cancels a pending job. Returns false if there is no such pending job.
+pub fn cancel(conn: &Connection, id: i64) -> bool {
+    conn.execute("UPDATE scheduled_jobs SET status = 'cancelled' WHERE id = ?1 AND status = 'pending'", params![id]).unwrap() > 0
+}
+
+/// How a job's action ended.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Outcome {
+    Succeeded,
+    Failed,
+    /// The command ran past the timeout and was killed; the job is not run again.
+    TimedOut,
+}
+
+/// `SCHEDULE_COMMAND_TIMEOUT_SECS`, default 300.
+fn command_timeout() -> StdDuration {
+    let secs = env::var("SCHEDULE_COMMAND_TIMEOUT_SECS")
+        .ok()
+        .and_then(|v| v.trim().parse().ok())
+        .filter(|secs| *secs > 0)
+        .unwrap_or(DEFAULT_COMMAND_TIMEOUT_SECS);
+    StdDuration::from_secs(secs)
+}
+
+/// A command that ran to completion.
+struct Finished {
+    /// Exit code, or `None` when it was killed by a signal.
+    code: Option<i32>,
+    stdout: Vec<u8>,
+    stderr: Vec<u8>,
+}
+
+/// Runs `command` with `sh -c`, killing it after `timeout`. Returns `None` when it timed out.
+fn run_with_timeout(command: &str, timeout: StdDuration) -> std::io::Result<Option<Finished>> {
+    // Its own process group, so a timeout also stops whatever the shell started.
+    let mut child = Command::new("sh")
+        .arg("-c")
+        .arg(command)
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .process_group(0)
+        .spawn()?;
+    // Read both pipes on their own threads so a chatty command cannot fill one and stall.
+    let read = |pipe: Option<Box<dyn Read + Send>>| {
+        thread::spawn(move || {
+            let mut buffer = Vec::new();
+            if let Some(mut pipe) = pipe {
+                let _ = pipe.read_to_end(&mut buffer);
+            }
+            buffer
+        })
+    };
+    let stdout = read(child.stdout.take().map(|p| Box::new(p) as Box<dyn Read + Send>));
+    let stderr = read(child.stderr.take().map(|p| Box::new(p) as Box<dyn Read + Send>));
+    let deadline = Instant::now() + timeout;
+    let status = loop {
+        if let Some(status) = child.try_wait()? {
+            break Some(status);
+        }
+        if Instant::now() >= deadline {
+            let _ = Command::new("kill").args(["-KILL", "--", &format!("-{}", child.id())]).status();
+            let _ = child.kill();
+            let _ = child.wait();
+            break None;
+        }
+        thread::sleep(StdDuration::from_millis(50));
+    };
+    let (stdout, stderr) = (stdout.join().unwrap_or_default(), stderr.join().unwrap_or_default());
+    Ok(status.map(|status| Finished { code: status.code(), stdout, stderr }))
+}
+
+/// Runs a job's action and returns the message to append to its session and how it ended.
+fn execute(job: &Job) -> (String, Outcome) {
+    match &job.action {
+        Action::Remind(text) => (format!("Scheduled reminder (job {}): {}", job.id, text), Outcome::Succeeded),
+        Action::Command(command) => {
+            let timeout = command_timeout();
+            match run_with_timeout(command, timeout) {
+                Ok(Some(Finished { code, stdout, stderr })) => {
+                    let success = code == Some(0);
+                    (
+                        format!(
+                            "Scheduled command `{}` (job {}) exited with {}:\n{}",
+                            command,
+                            job.id,
+                            code.map_or("a signal".to_string(), |code| code.to_string()),
+                            String::from_utf8_lossy(if success { &stdout } else { &stderr })
+                        ),
+                        if success { Outcome::Succeeded } else { Outcome::Failed },
+                    )
+                }
+                Ok(None) => (
+                    format!(
+                        "Scheduled command `{}` (job {}) timed out after {}s and was stopped (SCHEDULE_COMMAND_TIMEOUT_SECS).",
+                        command,
+                        job.id,
+                        timeout.as_secs()
+                    ),
+                    Outcome::TimedOut,
+                ),
+                Err(e) => (format!("Scheduled command `{}` (job {}) could not run: {}", command, job.id, e), Outcome::Failed),
+            }
+        }
+    }
+}
+
+/// Runs every pending job that is due, appends each result to the job's session as a system
+/// message, and reschedules repeating jobs at their next interval after now. Jobs whose session
+/// was deleted are cancelled instead. Returns the jobs run with their results.
+pub fn run_due(conn: &Connection) -> Vec<(Job, String)> {
+    let now = Utc::now();
+    let due = query(conn, "WHERE status = 'pending' AND run_at <= ?1", &[&now.format(TIMESTAMP).to_string()]);
+    due.into_iter()
+        .filter(|job| {
+            let exists = conn.query_row("SELECT 1 FROM sessions WHERE id = ?1", params![job.session_id], |_| Ok(())).is_ok();
+            if !exists {
+                conn.execute("UPDATE scheduled_jobs SET status = 'cancelled' WHERE id = ?1", params![job.id]).unwrap();
+            }
+            exists
+        })
+        .map(|job| {
+            let (result, outcome) = execute(&job);
+            save_message(conn, &job.session_id, "system", &result);
+            let next_run = job.every_secs.filter(|secs| *secs > 0).and_then(|secs| {
+                let mut next = NaiveDateTime::parse_from_str(&job.run_at, TIMESTAMP).map(|t| t.and_utc()).unwrap_or(now);
+                while next <= now {
+                    next = next.checked_add_signed(Duration::try_seconds(secs)?)?;
+                }
+                Some(next.format(TIMESTAMP).to_string())
+            });
+            let status = match (&next_run, outcome) {
+                (_, Outcome::TimedOut) | (None, Outcome::Failed) => "failed",
+                (Some(_), _) => "pending",
+                (None, Outcome::Succeeded) => "done",
+            };
+            conn.execute(
+                "UPDATE scheduled_jobs SET status = ?2, run_at = COALESCE(?3, run_at), last_run_at = ?4, last_result = ?5 WHERE id = ?1",
+                params![job.id, status, next_run, now.format(TIMESTAMP).to_string(), result],
+            )
+            .unwrap();
+            (job, result)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, Instant};
+
+    use rusqlite::{params, Connection};
+
+    use super::{create, list, parse_when, run_due, run_with_timeout, Action};
+    use crate::retention::delete_session;
+
+    fn database() -> Connection {
+        let conn = Connection::open_in_memory().unwrap();
+        crate::init_db(&conn);
+        conn
+    }
+
+    #[test]
+    fn deleting_a_session_removes_its_jobs() {
+        let conn = database();
+        conn.execute("INSERT INTO sessions (id) VALUES ('s')", []).unwrap();
+        create(&conn, "s", &parse_when("every 1m").unwrap(), &Action::Remind("stretch".to_string()));
+        delete_session(&conn, "s");
+        assert!(list(&conn, None, true).is_empty());
+    }
+
+    #[test]
+    fn jobs_of_a_deleted_session_are_cancelled_not_run() {
+        let conn = database();
+        let id = create(&conn, "gone", &parse_when("every 1m").unwrap(), &Action::Remind("stretch".to_string()));
+        conn.execute("UPDATE scheduled_jobs SET run_at = '2000-01-01 00:00:00' WHERE id = ?1", params![id]).unwrap();
+        assert!(run_due(&conn).is_empty());
+        assert_eq!(list(&conn, None, true)[0].status, "cancelled");
+        let messages: i64 = conn.query_row("SELECT COUNT(*) FROM messages", [], |row| row.get(0)).unwrap();
+        assert_eq!(messages, 0);
+    }
+
+    #[test]
+    fn commands_are_stopped_at_the_timeout() {
+        let started = Instant::now();
+        assert!(run_with_timeout("sleep 5", Duration::from_millis(200)).unwrap().is_none());
+        assert!(started.elapsed() < Duration::from_secs(2));
+        let finished = run_with_timeout("echo hi", Duration::from_secs(5)).unwrap().unwrap();
+        assert_eq!((finished.code, finished.stdout), (Some(0), b"hi\n".to_vec()));
+    }
+
+    #[test]
+    fn rejects_durations_out_of_range() {
+        assert!(parse_when("in 10m").is_ok());
+        assert!(parse_when("every 2h").unwrap().every.is_some());
+        assert_eq!(parse_when("in 99999999999999w").unwrap_err(), "invalid duration '99999999999999w' (use e.g. 10m, 2h, 1d)");
+        assert_eq!(parse_when("every 99999999w").unwrap_err(), "duration '99999999w' is too long");
+    }
+}
@@ -0,0 +1,68 @@
+use std::env;
+
+use regex::Regex;
+
+/// Built-in rule sets a profile can reference, applied in this order.
+const BUILTIN_RULES: &[(&str, &str, &str)] = &[
+    ("emails", r"[A-Za-z0-9._%+-]+@[A-Za-z0-9.-]+\.[A-Za-z]{2,}", "[EMAIL]"),
+    ("ips", r"\b(?:\d{1,3}\.){3}\d{1,3}\b", "[IP]"),
+    ("ips", r"\b(?:[0-9A-Fa-f]{1,4}:){2,7}[0-9A-Fa-f]{1,4}\b", "[IP]"),
+    (
+        "hostnames",
+        r"(?i)\b(?:[a-z0-9-]+\.)+(?:com|net|org|io|dev|ai|app|cloud|co|edu|gov|internal|local|lan|corp|intra)\b",
+        "[HOST]",
+    ),
+];
+
+const DEFAULT_RULE_SETS: &str = "emails,ips,hostnames";
+
+/// A named set of redaction rules applied to exported transcripts. Stored messages are never
+/// modified.
+///
+/// `SCRUB_PROFILE_<NAME>` lists the built-in rule sets to use (`emails`, `ips`, `hostnames`) and
+/// `SCRUB_PATTERNS_<NAME>` points at a file of extra regexes, one per line, replaced with
+/// `[REDACTED]`. A profile named `default` uses all built-in sets unless configured otherwise.
+#[derive(Debug)]
+pub struct ScrubProfile {
+    pub name: String,
+    rules: Vec<(Regex, String)>,
+}
+
+impl ScrubProfile {
+    pub fn load(name: &str) -> Result<Self, String> {
+        let key = name.trim().to_uppercase().replace('-', "_");
+        let sets = match env::var(format!("SCRUB_PROFILE_{}", key)) {
+            Ok(sets) => sets,
+            Err(_) if key == "DEFAULT" => DEFAULT_RULE_SETS.to_string(),
+            Err(_) if env::var(format!("SCRUB_PATTERNS_{}", key)).is_ok() => String::new(),
+            Err(_) => return Err(format!("unknown scrub profile '{}'", name)),
+        };
+
+        let mut rules = Vec::new();
+        for set in sets.split(',').map(str::trim).filter(|s| !s.is_empty()) {
+            let matching: Vec<_> = BUILTIN_RULES.iter().filter(|(rule_set, _, _)| *rule_set == set).collect();
+            if matching.is_empty() {
+                return Err(format!("unknown rule set '{}' in scrub profile '{}'", set, name));
+            }
+            for (_, pattern, replacement) in matching {
+                rules.push((Regex::new(pattern).unwrap(), replacement.to_string()));
+            }
+        }
+
+        if let Ok(path) = env::var(format!("SCRUB_PATTERNS_{}", key)) {
+            let patterns = std::fs::read_to_string(&path).map_err(|e| format!("could not read {}: {}", path, e))?;
+            for line in patterns.lines().map(str::trim).filter(|l| !l.is_empty() && !l.starts_with('#')) {
+                let regex = Regex::new(line).map_err(|e| format!("invalid pattern in {}: {}", path, e))?;
+                rules.push((regex, "[REDACTED]".to_string()));
+            }
+        }
+
+        Ok(ScrubProfile { name: name.trim().to_string(), rules })
+    }
+
+    pub fn apply(&self, text: &str) -> String {
+        self.rules
+            .iter()
+            .fold(text.to_string(), |text, (regex, replacement)| regex.replace_all(&text, replacement.as_str()).into_owned())
+    }
+}
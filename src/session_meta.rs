@@ -0,0 +1,76 @@
+use rusqlite::{params, Connection};
+use serde_json::Value;
+
+/// A value as typed: JSON when it parses (`3`, `true`, `{"a": 1}`), otherwise a string, so
+/// `ticket=ABC-1` and `ticket="ABC-1"` store the same thing.
+pub fn parse_value(text: &str) -> Value {
+    let text = text.trim();
+    serde_json::from_str(text).unwrap_or_else(|_| Value::String(text.to_string()))
+}
+
+/// Parses `key=value`.
+pub fn parse_entry(entry: &str) -> Result<(String, Value), String> {
+    match entry.split_once('=') {
+        Some((key, value)) if !key.trim().is_empty() => Ok((key.trim().to_string(), parse_value(value))),
+        _ => Err(format!("invalid metadata '{}' (use key=value)", entry)),
+    }
+}
+
+/// Every `--meta key=value` on the command line: metadata for new sessions, or filters for
+/// `sessions list`.
+pub fn from_args(args: &[String]) -> Result<Vec<(String, Value)>, String> {
+    args.iter()
+        .enumerate()
+        .filter(|(_, a)| *a == "--meta")
+        .map(|(i, _)| args.get(i + 1).ok_or_else(|| "--meta needs key=value".to_string()).and_then(|entry| parse_entry(entry)))
+        .collect()
+}
+
+/// Sets `key` on a session, replacing any earlier value.
+pub fn set(conn: &Connection, session_id: &str, key: &str, value: &Value) {
+    conn.execute(
+        "INSERT INTO session_meta (session_id, key, value) VALUES (?1, ?2, ?3)
+         ON CONFLICT(session_id, key) DO UPDATE SET value = excluded.value, updated_at = CURRENT_TIMESTAMP",
+        params![session_id, key, value.to_string()],
+    ).unwrap();
+}
+
+/// Removes `key` from a session, returning whether it was set.
+pub fn unset(conn: &Connection, session_id: &str, key: &str) -> bool {
+    conn.execute("DELETE FROM session_meta WHERE session_id = ?1 AND key = ?2", params![session_id, key]).unwrap() > 0
+}
+
+/// A session's metadata, by key.
+pub fn get(conn: &Connection, session_id: &str) -> Vec<(String, Value)> {
+    let mut stmt = conn.prepare("SELECT key, value FROM session_meta WHERE session_id = ?1 ORDER BY key").unwrap();
+    stmt.query_map(params![session_id], |row| Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?)))
+        .unwrap()
+        .filter_map(Result::ok)
+        .map(|(key, value)| (key, parse_value(&value)))
+        .collect()
+}
+
+/// Whether a session has every `filters` key set to its value.
+pub fn matches(conn: &Connection, session_id: &str, filters: &[(String, Value)]) -> bool {
+    filters.iter().all(|(key, value)| {
+        conn.query_row(
+            "SELECT 1 FROM session_meta WHERE session_id = ?1 AND key = ?2 AND value = ?3",
+            params![session_id, key, value.to_string()],
+            |_| Ok(()),
+        )
+        .is_ok()
+    })
+}
+
+/// A value as it would be typed: strings unquoted, anything else as JSON.
+pub fn display(value: &Value) -> String {
+    match value {
+        Value::String(text) => text.clone(),
+        other => other.to_string(),
+    }
+}
+
+/// `key=value, key=value`.
+pub fn describe(meta: &[(String, Value)]) -> String {
+    meta.iter().map(|(key, value)| format!("{}={}", key, display(value))).collect::<Vec<_>>().join(", ")
+}
@@ -0,0 +1,36 @@
+use std::collections::BTreeMap;
+use std::env;
+use std::sync::LazyLock;
+
+use regex::{Captures, Regex};
+
+/// `!name` at the start of the input or after whitespace, so `hello!` and `!=` are left alone.
+static REFERENCE: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"(^|\s)!([A-Za-z0-9_-]+)").unwrap());
+
+/// Snippets configured as `SNIPPET_<NAME>=text`, by name: lowercase, with `_` written as `-`, so
+/// `SNIPPET_EDGE_CASES` is typed `!edge-cases`.
+pub fn all() -> BTreeMap<String, String> {
+    env::vars()
+        .filter_map(|(key, text)| {
+            let name = key.strip_prefix("SNIPPET_")?.to_lowercase().replace('_', "-");
+            Some((name, text)).filter(|(name, text)| !name.is_empty() && !text.trim().is_empty())
+        })
+        .collect()
+}
+
+/// The input with every known `!name` replaced by its text, and the names that were expanded.
+/// Unknown names stay as typed.
+pub fn expand(input: &str, snippets: &BTreeMap<String, String>) -> (String, Vec<String>) {
+    let mut used = Vec::new();
+    let expanded = REFERENCE.replace_all(input, |c: &Captures| {
+        let name = c[2].to_lowercase().replace('_', "-");
+        match snippets.get(&name) {
+            Some(text) => {
+                used.push(name);
+                format!("{}{}", &c[1], text.trim())
+            }
+            None => c[0].to_string(),
+        }
+    });
+    (expanded.into_owned(), used)
+}
@@ -0,0 +1,173 @@
+use std::env;
+use std::path::PathBuf;
+
+use rusqlite::functions::FunctionFlags;
+use rusqlite::types::{Value, ValueRef};
+use rusqlite::{params, Connection};
+
+/// `messages.encoding` value for zstd-compressed content.
+const ZSTD: &str = "zstd";
+const ZSTD_LEVEL: i32 = 3;
+
+/// Messages larger than this many bytes (`COMPRESS_THRESHOLD_BYTES`) are stored compressed.
+fn compress_threshold() -> usize {
+    env::var("COMPRESS_THRESHOLD_BYTES")
+        .ok()
+        .and_then(|v| v.trim().parse().ok())
+        .unwrap_or(64 * 1024)
+}
+
+/// Returns the value to store in `messages.content` and the matching `encoding`.
+pub fn encode_content(content: &str) -> (Value, Option<&'static str>) {
+    if content.len() > compress_threshold()
+        && let Ok(compressed) = zstd::encode_all(content.as_bytes(), ZSTD_LEVEL)
+        && compressed.len() < content.len()
+    {
+        return (Value::Blob(compressed), Some(ZSTD));
+    }
+    (Value::Text(content.to_string()), None)
+}
+
+/// Registers `message_text(content, encoding)`, which every query reading message content uses
+/// so that compressed rows come back as plain text.
+pub fn register_functions(conn: &Connection) {
+    conn.create_scalar_function(
+        "message_text",
+        2,
+        FunctionFlags::SQLITE_UTF8 | FunctionFlags::SQLITE_DETERMINISTIC,
+        |ctx| {
+            let encoding: Option<String> = ctx.get(1)?;
+            match (ctx.get_raw(0), encoding.as_deref()) {
+                (ValueRef::Blob(bytes), Some(ZSTD)) => zstd::decode_all(bytes)
+                    .map(|raw| String::from_utf8_lossy(&raw).into_owned())
+                    .map_err(|e| rusqlite::Error::UserFunctionError(e.into())),
+                (ValueRef::Null, _) => Ok(String::new()),
+                (raw, _) => Ok(String::from_utf8_lossy(raw.as_bytes().unwrap_or_default()).into_owned()),
+            }
+        },
+    )
+    .unwrap();
+}
+
+fn database_size(conn: &Connection) -> i64 {
+    let pages: i64 = conn.query_row("PRAGMA page_count", [], |row| row.get(0)).unwrap();
+    let page_size: i64 = conn.query_row("PRAGMA page_size", [], |row| row.get(0)).unwrap();
+    pages * page_size
+}
+
+/// Compresses large messages stored before compression was enabled (or under a higher
+/// threshold), then vacuums. Returns the number of recompressed messages and the database
+/// size in bytes before and after.
+pub fn compact(conn: &Connection) -> (usize, i64, i64) {
+    let before = database_size(conn);
+
+    let mut stmt = conn
+        .prepare("SELECT id, content FROM messages WHERE encoding IS NULL AND length(content) > ?1")
+        .unwrap();
+    let candidates: Vec<(i64, String)> = stmt
+        .query_map(params![compress_threshold() as i64], |row| Ok((row.get(0)?, row.get(1)?)))
+        .unwrap()
+        .filter_map(Result::ok)
+        .collect();
+
+    let mut compressed = 0;
+    for (id, content) in candidates {
+        if let (value, Some(encoding)) = encode_content(&content) {
+            conn.execute(
+                "UPDATE messages SET content = ?2, encoding = ?3 WHERE id = ?1",
+                params![id, value, encoding],
+            ).unwrap();
+            compressed += 1;
+        }
+    }
+
+    conn.execute_batch("VACUUM").unwrap();
+    (compressed, before, database_size(conn))
+}
+
+/// Outcome of one `db maintain` run, also recorded in the `maintenance_runs` table.
+#[derive(Debug)]
+pub struct MaintenanceReport {
+    pub integrity: String,
+    pub backup_path: Option<String>,
+    /// Why the backup could not be taken, when it was attempted and failed.
+    pub backup_error: Option<String>,
+    pub backups_removed: usize,
+    pub size_before: i64,
+    pub size_after: i64,
+}
+
+/// Copies the database into `BACKUP_DIR` (default `backups`) and deletes all but the newest
+/// `BACKUP_KEEP` (default 7) copies. Backups are named after the database file, so each
+/// workspace has its own rotation. Returns the new backup's path and how many were removed.
+fn rotate_backups(conn: &Connection) -> Result<(String, usize), String> {
+    let dir = PathBuf::from(env::var("BACKUP_DIR").unwrap_or_else(|_| "backups".to_string()));
+    let keep: usize = env::var("BACKUP_KEEP")
+        .ok()
+        .and_then(|v| v.trim().parse().ok())
+        .unwrap_or(7);
+    std::fs::create_dir_all(&dir).map_err(|e| e.to_string())?;
+
+    let stem = conn
+        .path()
+        .and_then(|p| std::path::Path::new(p).file_stem().map(|s| s.to_string_lossy().to_string()))
+        .unwrap_or_else(|| "chat_sessions".to_string());
+    let prefix = format!("{}-", stem);
+    let path = dir.join(format!("{}{}.db", prefix, chrono::Local::now().format("%Y%m%d-%H%M%S")));
+    let path_str = path.to_string_lossy().to_string();
+    conn.execute("VACUUM INTO ?1", params![path_str]).map_err(|e| e.to_string())?;
+
+    let mut backups: Vec<PathBuf> = std::fs::read_dir(&dir)
+        .map_err(|e| e.to_string())?
+        .filter_map(|entry| entry.ok().map(|e| e.path()))
+        .filter(|p| {
+            let name = p.file_name().unwrap_or_default().to_string_lossy();
+            // Timestamps are all digits, so `proj-` never matches `proj-x-` backups.
+            name.strip_prefix(&prefix).and_then(|rest| rest.strip_suffix(".db")).is_some_and(|ts| ts.chars().all(|c| c.is_ascii_digit() || c == '-'))
+        })
+        .collect();
+    // Timestamped names sort chronologically.
+    backups.sort();
+    let excess = backups.len().saturating_sub(keep.max(1));
+    let mut removed = 0;
+    for old in &backups[..excess] {
+        if std::fs::remove_file(old).is_ok() {
+            removed += 1;
+        }
+    }
+    Ok((path_str, removed))
+}
+
+/// Runs an integrity check, takes a rotated backup, rebuilds indexes and statistics, and
+/// vacuums. The backup is skipped if the integrity check fails, so a damaged database never
+/// pushes a good backup out of the rotation.
+pub fn maintain(conn: &Connection) -> MaintenanceReport {
+    let size_before = database_size(conn);
+
+    let mut stmt = conn.prepare("PRAGMA integrity_check").unwrap();
+    let problems: Vec<String> = stmt
+        .query_map([], |row| row.get(0))
+        .unwrap()
+        .filter_map(Result::ok)
+        .collect();
+    let integrity = problems.join("; ");
+
+    let (backup_path, backup_error, backups_removed) = if integrity == "ok" {
+        match rotate_backups(conn) {
+            Ok((path, removed)) => (Some(path), None, removed),
+            Err(e) => (None, Some(e), 0),
+        }
+    } else {
+        (None, None, 0)
+    };
+
+    conn.execute_batch("REINDEX; ANALYZE; VACUUM;").unwrap();
+    let size_after = database_size(conn);
+
+    conn.execute(
+        "INSERT INTO maintenance_runs (integrity, backup_path, backup_error, size_before, size_after) VALUES (?1, ?2, ?3, ?4, ?5)",
+        params![integrity, backup_path, backup_error, size_before, size_after],
+    ).unwrap();
+
+    MaintenanceReport { integrity, backup_path, backup_error, backups_removed, size_before, size_after }
+}
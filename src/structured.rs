@@ -0,0 +1,80 @@
+use std::env;
+use std::path::Path;
+
+use serde_json::Value;
+
+use crate::json_schema;
+
+/// A JSON Schema that every answer must match, from `--json-schema <file>` or `JSON_SCHEMA`.
+/// Providers with a JSON mode are asked for it natively; every reply is validated here and
+/// sent back with the errors when it does not match.
+#[derive(Debug, Clone)]
+pub struct OutputSchema {
+    /// The file's stem, used as the schema name where a provider asks for one (OpenAI allows
+    /// only letters, digits, `_` and `-`).
+    pub name: String,
+    pub schema: Value,
+}
+
+impl OutputSchema {
+    pub fn from_args(args: &[String]) -> Result<Option<Self>, String> {
+        let path = match args.iter().position(|a| a == "--json-schema") {
+            Some(i) => args.get(i + 1).cloned().ok_or("--json-schema needs a file")?,
+            None => match env::var("JSON_SCHEMA").ok().filter(|v| !v.trim().is_empty()) {
+                Some(path) => path,
+                None => return Ok(None),
+            },
+        };
+        let text = std::fs::read_to_string(&path).map_err(|e| format!("could not read JSON schema {} ({})", path, e))?;
+        let schema: Value = serde_json::from_str(&text).map_err(|e| format!("{} is not valid JSON ({})", path, e))?;
+        if !schema.is_object() {
+            return Err(format!("{} must contain a JSON Schema object", path));
+        }
+        json_schema::check_supported(&schema).map_err(|e| format!("{} cannot be used ({})", path, e))?;
+        let stem = Path::new(&path).file_stem().and_then(|s| s.to_str()).unwrap_or("response");
+        let name = stem.chars().map(|c| if c.is_ascii_alphanumeric() || c == '-' { c } else { '_' }).collect();
+        Ok(Some(OutputSchema { name, schema }))
+    }
+
+    /// Added to the system prompt, for providers without a native JSON mode.
+    pub fn instruction(&self) -> String {
+        format!(
+            "Answer with only a JSON value matching this JSON Schema, with no prose or code fences around it:\n{}",
+            self.schema
+        )
+    }
+
+    /// The reply as JSON if it matches the schema, or every mismatch found.
+    pub fn check(&self, reply: &str) -> Result<Value, String> {
+        let value = parse(reply)?;
+        let errors = json_schema::validate(&value, &self.schema);
+        if errors.is_empty() { Ok(value) } else { Err(errors.iter().map(ToString::to_string).collect::<Vec<_>>().join("; ")) }
+    }
+}
+
+/// How many times a reply that does not match the schema is sent back (`STRUCTURED_OUTPUT_ATTEMPTS`).
+pub fn repair_attempts() -> usize {
+    env::var("STRUCTURED_OUTPUT_ATTEMPTS")
+        .ok()
+        .and_then(|v| v.trim().parse().ok())
+        .unwrap_or(2)
+}
+
+/// The message sent back with a reply that failed validation.
+pub fn correction(error: &str) -> String {
+    format!(
+        "Your last reply did not match the required JSON Schema: {}. Reply with only the corrected JSON.",
+        error
+    )
+}
+
+/// Parses a reply as JSON, ignoring a surrounding ```json fence.
+fn parse(reply: &str) -> Result<Value, String> {
+    let text = reply.trim();
+    let text = text
+        .strip_prefix("```")
+        .and_then(|rest| rest.strip_suffix("```"))
+        .map(|inner| inner.trim_start_matches("json").trim())
+        .unwrap_or(text);
+    serde_json::from_str(text).map_err(|e| format!("the reply is not valid JSON ({})", e))
+}
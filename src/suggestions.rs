@@ -0,0 +1,94 @@
+use std::env;
+use std::sync::LazyLock;
+
+use regex::Regex;
+
+use crate::failover::{self, ProviderSpec};
+use crate::{Message, attachments};
+
+/// A numbered-list prefix (`1.`, `2)`) or a bullet (`-`, `*`, `•`) and the space after it.
+static LIST_MARKER: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"^(?:\d+[.)]|[-*•])\s+").unwrap());
+
+/// At most this many follow-ups are offered after an answer.
+const MAX_SUGGESTIONS: usize = 3;
+
+/// Recent messages shown to the suggesting model, and how much of each.
+const CONTEXT_MESSAGES: usize = 4;
+const CONTEXT_CHARS: usize = 2000;
+
+/// Whether follow-up prompts are suggested after each answer (`--suggest` or `SUGGEST_FOLLOW_UPS`).
+pub fn enabled(args: &[String]) -> bool {
+    args.iter().any(|a| a == "--suggest") || matches!(env::var("SUGGEST_FOLLOW_UPS").unwrap_or_default().trim(), "1" | "true" | "yes")
+}
+
+/// The model that writes suggestions (`SUGGEST_PROVIDER`, one entry in the `PROVIDER_FALLBACKS`
+/// format such as `groq:llama-3.1-8b-instant`), or `None` to use the session's model.
+pub fn provider_from_env() -> Result<Option<ProviderSpec>, String> {
+    let mut specs = failover::parse_providers("SUGGEST_PROVIDER", &env::var("SUGGEST_PROVIDER").unwrap_or_default())?;
+    if specs.len() > 1 {
+        return Err("SUGGEST_PROVIDER takes a single provider".to_string());
+    }
+    Ok(specs.pop())
+}
+
+/// The request asking for follow-ups to the latest answer in `history`.
+pub fn request(history: &[Message]) -> Vec<Message> {
+    let recent: Vec<&Message> = history.iter().filter(|m| m.role == "user" || m.role == "assistant").collect();
+    let transcript: String = recent[recent.len().saturating_sub(CONTEXT_MESSAGES)..]
+        .iter()
+        .map(|m| format!("{}: {}\n\n", m.role, attachments::without_images(&m.content).chars().take(CONTEXT_CHARS).collect::<String>()))
+        .collect();
+    vec![
+        Message {
+            role: "system".to_string(),
+            content: format!(
+                "Suggest {} short follow-up prompts the user is likely to send next, written as the user would type them. Reply with one prompt per line and nothing else.",
+                MAX_SUGGESTIONS
+            ),
+        },
+        Message { role: "user".to_string(), content: format!("Conversation:\n\n{}", transcript) },
+    ]
+}
+
+/// The suggested prompts from a reply, without numbering (`1.` or `1)` and a space), bullets or
+/// quotes. Numbers that start the prompt itself, like a year, are kept.
+pub fn parse(reply: &str) -> Vec<String> {
+    reply
+        .lines()
+        .map(|line| {
+            let line = line.trim();
+            let line = LIST_MARKER.find(line).map_or(line, |marker| &line[marker.end()..]);
+            line.trim().trim_matches('"').trim().to_string()
+        })
+        .filter(|line| !line.is_empty() && !line.ends_with(':'))
+        .take(MAX_SUGGESTIONS)
+        .collect()
+}
+
+/// The suggestion chosen by typing its number, if `input` is one.
+pub fn pick(input: &str, suggestions: &[String]) -> Option<String> {
+    let number: usize = input.trim().parse().ok()?;
+    suggestions.get(number.checked_sub(1)?).cloned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::parse;
+
+    #[test]
+    fn strips_only_list_markers() {
+        let cases = [
+            ("1. What about Rust?", "What about Rust?"),
+            ("2) Compare them", "Compare them"),
+            ("- \"Show an example\"", "Show an example"),
+            ("• Explain lifetimes", "Explain lifetimes"),
+            ("2024 trends in AI?", "2024 trends in AI?"),
+            ("3.5 GPa in psi?", "3.5 GPa in psi?"),
+            ("10. 3D printing costs?", "3D printing costs?"),
+        ];
+        for (line, prompt) in cases {
+            assert_eq!(parse(line), [prompt], "{}", line);
+        }
+        assert_eq!(parse("Suggestions:\n\n1. A\n2. B\n3. C\n4. D"), ["A", "B", "C"]);
+    }
+}
@@ -0,0 +1,70 @@
+use std::time::Duration;
+
+use rusqlite::Connection;
+
+use crate::{call_llm, load_history, ApiConfig, Message};
+
+/// The kind of document a session transcript is turned into.
+#[derive(Debug, Clone, Copy)]
+pub enum ArtifactKind {
+    Issue,
+    Pr,
+    Runbook,
+}
+
+impl ArtifactKind {
+    pub fn parse(value: &str) -> Option<Self> {
+        match value.trim().to_lowercase().as_str() {
+            "issue" => Some(ArtifactKind::Issue),
+            "pr" => Some(ArtifactKind::Pr),
+            "runbook" => Some(ArtifactKind::Runbook),
+            _ => None,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            ArtifactKind::Issue => "issue",
+            ArtifactKind::Pr => "pr",
+            ArtifactKind::Runbook => "runbook",
+        }
+    }
+
+    fn instructions(&self) -> &'static str {
+        match self {
+            ArtifactKind::Issue => "Write a GitHub issue in Markdown with these sections: a one-line title as a level-1 heading, Context, Steps to Reproduce, Expected vs Actual Behavior, and Resolution (or current workaround).",
+            ArtifactKind::Pr => "Write a pull request description in Markdown with these sections: a one-line title as a level-1 heading, Context, Changes, Testing, and Notes for reviewers.",
+            ArtifactKind::Runbook => "Write an operational runbook in Markdown with these sections: a one-line title as a level-1 heading, Context (when to use it), Prerequisites, Steps (numbered, with exact commands), Verification, and Rollback.",
+        }
+    }
+}
+
+/// Turns the transcript of `session_id` into a document of the given kind using the configured
+/// model, writes it to `session_<id>_<kind>.md` and returns the file name.
+pub async fn summarize_session(conn: &Connection, config: &ApiConfig, session_id: &str, kind: ArtifactKind) -> Result<String, Box<dyn std::error::Error>> {
+    let history = load_history(conn, session_id);
+    if history.is_empty() {
+        return Err(format!("No messages found for session {}", session_id).into());
+    }
+
+    let transcript: String = history.iter().map(|m| format!("{}: {}\n\n", m.role, m.content)).collect();
+    let request = vec![
+        Message {
+            role: "system".to_string(),
+            content: format!(
+                "You turn problem-solving chat transcripts into structured documents. {} Only use facts from the transcript; do not invent details.",
+                kind.instructions()
+            ),
+        },
+        Message { role: "user".to_string(), content: format!("Transcript:\n\n{}", transcript) },
+    ];
+
+    let client = reqwest::Client::builder()
+        .timeout(Duration::from_secs(90))
+        .build()?;
+    let document = call_llm(&client, config, &request).await?;
+
+    let filename = format!("session_{}_{}.md", session_id, kind.name());
+    std::fs::write(&filename, document)?;
+    Ok(filename)
+}
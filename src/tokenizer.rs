@@ -0,0 +1,118 @@
+use std::collections::HashMap;
+use std::env;
+use std::sync::{Arc, Mutex, OnceLock};
+
+use colored::*;
+use tiktoken_rs::CoreBPE;
+
+use crate::prompts::model_key;
+
+/// Counts tokens the way a model's provider does, or estimates them when the tokenizer is unknown.
+pub enum Tokenizer {
+    Tiktoken { name: String, bpe: &'static CoreBPE },
+    #[cfg(feature = "hf-tokenizers")]
+    HuggingFace { path: String, tokenizer: Box<tokenizers::Tokenizer> },
+    Heuristic { chars_per_token: f64 },
+}
+
+impl Tokenizer {
+    pub fn count(&self, text: &str) -> usize {
+        match self {
+            Tokenizer::Tiktoken { bpe, .. } => bpe.encode_with_special_tokens(text).len(),
+            #[cfg(feature = "hf-tokenizers")]
+            Tokenizer::HuggingFace { tokenizer, .. } => tokenizer
+                .encode(text, false)
+                .map(|encoding| encoding.len())
+                .unwrap_or_else(|_| (text.chars().count() as f64 / 4.0).ceil() as usize),
+            Tokenizer::Heuristic { chars_per_token } => (text.chars().count() as f64 / chars_per_token).ceil() as usize,
+        }
+    }
+
+    pub fn name(&self) -> String {
+        match self {
+            Tokenizer::Tiktoken { name, .. } => name.clone(),
+            #[cfg(feature = "hf-tokenizers")]
+            Tokenizer::HuggingFace { path, .. } => format!("hf:{}", path),
+            Tokenizer::Heuristic { chars_per_token } => format!("heuristic:{}", chars_per_token),
+        }
+    }
+}
+
+const DEFAULT_CHARS_PER_TOKEN: f64 = 4.0;
+
+fn tiktoken(name: &str) -> Option<Tokenizer> {
+    let bpe = match name {
+        "o200k_base" => tiktoken_rs::o200k_base_singleton(),
+        "cl100k_base" => tiktoken_rs::cl100k_base_singleton(),
+        "p50k_base" => tiktoken_rs::p50k_base_singleton(),
+        "r50k_base" => tiktoken_rs::r50k_base_singleton(),
+        _ => return None,
+    };
+    Some(Tokenizer::Tiktoken { name: name.to_string(), bpe })
+}
+
+/// Parses a `TOKENIZER_<MODEL_KEY>` value: a tiktoken encoding (`cl100k_base`, `o200k_base`,
+/// `p50k_base`, `r50k_base`), `hf:<path to tokenizer.json>`, or `heuristic[:<chars per token>]`.
+fn from_spec(spec: &str) -> Result<Tokenizer, String> {
+    let spec = spec.trim();
+    if let Some(tokenizer) = tiktoken(spec) {
+        return Ok(tokenizer);
+    }
+    if spec == "heuristic" {
+        return Ok(Tokenizer::Heuristic { chars_per_token: DEFAULT_CHARS_PER_TOKEN });
+    }
+    if let Some(ratio) = spec.strip_prefix("heuristic:") {
+        return match ratio.trim().parse::<f64>() {
+            Ok(chars_per_token) if chars_per_token > 0.0 => Ok(Tokenizer::Heuristic { chars_per_token }),
+            _ => Err(format!("invalid characters per token '{}'", ratio)),
+        };
+    }
+    if let Some(path) = spec.strip_prefix("hf:") {
+        return hugging_face(path.trim());
+    }
+    Err(format!("unknown tokenizer '{}'", spec))
+}
+
+#[cfg(feature = "hf-tokenizers")]
+fn hugging_face(path: &str) -> Result<Tokenizer, String> {
+    let tokenizer = tokenizers::Tokenizer::from_file(path).map_err(|e| format!("could not load {}: {}", path, e))?;
+    Ok(Tokenizer::HuggingFace { path: path.to_string(), tokenizer: Box::new(tokenizer) })
+}
+
+#[cfg(not(feature = "hf-tokenizers"))]
+fn hugging_face(_path: &str) -> Result<Tokenizer, String> {
+    Err("Hugging Face tokenizers need a build with `--features hf-tokenizers`".to_string())
+}
+
+fn default_for(model_name: &str) -> Tokenizer {
+    use tiktoken_rs::tokenizer::{get_tokenizer, Tokenizer as Encoding};
+    let name = match get_tokenizer(model_name) {
+        Some(Encoding::O200kBase | Encoding::O200kHarmony) => "o200k_base",
+        Some(Encoding::Cl100kBase) => "cl100k_base",
+        Some(Encoding::P50kBase | Encoding::P50kEdit) => "p50k_base",
+        Some(Encoding::R50kBase | Encoding::Gpt2) => "r50k_base",
+        None => return Tokenizer::Heuristic { chars_per_token: DEFAULT_CHARS_PER_TOKEN },
+    };
+    tiktoken(name).unwrap()
+}
+
+/// Returns the tokenizer for `model_name`, loading it on first use. `TOKENIZER_<MODEL_KEY>`
+/// selects one explicitly; otherwise known OpenAI models use their tiktoken encoding and
+/// everything else falls back to a characters-per-token estimate.
+pub fn for_model(model_name: &str) -> Arc<Tokenizer> {
+    static REGISTRY: OnceLock<Mutex<HashMap<String, Arc<Tokenizer>>>> = OnceLock::new();
+    let mut registry = REGISTRY.get_or_init(Default::default).lock().unwrap();
+    registry
+        .entry(model_name.to_string())
+        .or_insert_with(|| {
+            let tokenizer = match env::var(format!("TOKENIZER_{}", model_key(model_name))) {
+                Ok(spec) => from_spec(&spec).unwrap_or_else(|e| {
+                    println!("{} {}; estimating tokens instead", "Tokenizer not loaded:".red(), e);
+                    Tokenizer::Heuristic { chars_per_token: DEFAULT_CHARS_PER_TOKEN }
+                }),
+                Err(_) => default_for(model_name),
+            };
+            Arc::new(tokenizer)
+        })
+        .clone()
+}
@@ -0,0 +1,488 @@
+use serde_json::{Map, Value};
+
+/// Parses the TOML used by suite files into JSON, ready to deserialize: tables, arrays of
+/// tables, dotted keys, inline tables and arrays, basic and literal strings (including
+/// multi-line ones), integers, floats and booleans. Dates and times are not supported.
+///
+/// Temporary: this stands in for the `toml` crate, which is not among the vendored dependencies
+/// and cannot be fetched by the offline build. Once it is, `Suite::load` should call
+/// `toml::from_str` directly and this module and its tests should go.
+pub fn parse(text: &str) -> Result<Value, String> {
+    let mut parser = Parser { chars: text.chars().collect(), pos: 0, line: 1 };
+    let mut root = Map::new();
+    // Path of the table that `key = value` lines go into, and whether it is the last element of
+    // an array of tables.
+    let mut current: Vec<String> = Vec::new();
+    let mut in_array = false;
+    // Tables defined so far and how. Paths under an array of tables refer to its last element.
+    let mut defined: Vec<(Vec<String>, Defined)> = Vec::new();
+
+    loop {
+        parser.skip_blank_lines();
+        let Some(c) = parser.peek() else { break };
+        let line = parser.line;
+        let located = |e: String| format!("line {}: {}", line, e);
+        if c == '[' {
+            parser.pos += 1;
+            in_array = parser.eat('[');
+            parser.skip_spaces();
+            current = parser.key().map_err(located)?;
+            parser.skip_spaces();
+            if !parser.eat(']') || (in_array && !parser.eat(']')) {
+                return Err(located("expected ']' after the table name".to_string()));
+            }
+            let (last, parents) = current.split_last().unwrap();
+            let parent = table_at(&mut root, parents, true).map_err(located)?;
+            if in_array {
+                defined.retain(|(path, _)| !path.starts_with(&current));
+                let tables = parent.entry(last.clone()).or_insert_with(|| Value::Array(Vec::new()));
+                tables
+                    .as_array_mut()
+                    .ok_or_else(|| located(format!("'{}' is not an array of tables", current.join("."))))?
+                    .push(Value::Object(Map::new()));
+            } else {
+                match parent.entry(last.clone()).or_insert_with(|| Value::Object(Map::new())) {
+                    Value::Object(_) => {}
+                    _ => return Err(located(format!("'{}' is already a value", current.join(".")))),
+                }
+                // A header may add sub-tables to any table but an inline one, and define each
+                // table once.
+                if let Some((path, _)) = defined.iter().find(|(path, how)| *path == current || (*how == Defined::Inline && current.starts_with(path))) {
+                    return Err(located(format!("table '{}' is defined twice", path.join("."))));
+                }
+                defined.push((current.clone(), Defined::Header));
+            }
+        } else {
+            let key = parser.key().map_err(located)?;
+            parser.skip_spaces();
+            if !parser.eat('=') {
+                return Err(located(format!("expected '=' after '{}'", key.join("."))));
+            }
+            parser.skip_spaces();
+            let value = parser.value().map_err(located)?;
+            // Dotted keys may extend tables made by other dotted keys in this table, but not ones
+            // made by a header or written inline.
+            for end in 1..key.len() {
+                let path = [current.as_slice(), &key[..end]].concat();
+                match defined.iter().find(|(defined, _)| *defined == path) {
+                    Some((_, Defined::Dotted)) => {}
+                    Some(_) => return Err(located(format!("table '{}' is defined twice", path.join(".")))),
+                    None => defined.push((path, Defined::Dotted)),
+                }
+            }
+            if value.is_object() {
+                defined.push(([current.as_slice(), &key].concat(), Defined::Inline));
+            }
+            let table = table_at(&mut root, &current, in_array).map_err(located)?;
+            insert(table, &key, value).map_err(located)?;
+        }
+        parser.end_of_line().map_err(|e| format!("line {}: {}", parser.line, e))?;
+    }
+    Ok(Value::Object(root))
+}
+
+/// How a table came to be defined.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Defined {
+    Header,
+    Dotted,
+    Inline,
+}
+
+/// The table at `path`, creating missing tables. With `last_element`, a path ending in an array
+/// of tables means its last element.
+fn table_at<'a>(root: &'a mut Map<String, Value>, path: &[String], last_element: bool) -> Result<&'a mut Map<String, Value>, String> {
+    let mut table = root;
+    for (i, part) in path.iter().enumerate() {
+        let mut entry = table.entry(part.clone()).or_insert_with(|| Value::Object(Map::new()));
+        if entry.is_array() && (last_element || i + 1 < path.len()) {
+            entry = entry.as_array_mut().and_then(|elements| elements.last_mut()).ok_or_else(|| format!("'{}' is empty", path[..=i].join(".")))?;
+        }
+        table = entry.as_object_mut().ok_or_else(|| format!("'{}' is not a table", path[..=i].join(".")))?;
+    }
+    Ok(table)
+}
+
+/// Sets a dotted key in `table`, refusing to overwrite a value.
+fn insert(table: &mut Map<String, Value>, key: &[String], value: Value) -> Result<(), String> {
+    let (last, parents) = key.split_last().unwrap();
+    let table = table_at(table, parents, false)?;
+    if table.contains_key(last) {
+        return Err(format!("'{}' is defined twice", key.join(".")));
+    }
+    table.insert(last.clone(), value);
+    Ok(())
+}
+
+struct Parser {
+    chars: Vec<char>,
+    pos: usize,
+    line: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.pos).copied()
+    }
+
+    fn starts_with(&self, text: &str) -> bool {
+        text.chars().enumerate().all(|(i, c)| self.chars.get(self.pos + i) == Some(&c))
+    }
+
+    fn eat(&mut self, c: char) -> bool {
+        if self.peek() == Some(c) {
+            self.pos += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn advance(&mut self) -> Option<char> {
+        let c = self.peek()?;
+        self.pos += 1;
+        if c == '\n' {
+            self.line += 1;
+        }
+        Some(c)
+    }
+
+    fn skip_spaces(&mut self) {
+        while matches!(self.peek(), Some(' ' | '\t')) {
+            self.pos += 1;
+        }
+    }
+
+    fn skip_comment(&mut self) {
+        if self.peek() == Some('#') {
+            while !matches!(self.peek(), None | Some('\n')) {
+                self.pos += 1;
+            }
+        }
+    }
+
+    /// Skips whitespace, newlines and comments, as between table entries and array elements.
+    fn skip_blank_lines(&mut self) {
+        loop {
+            self.skip_spaces();
+            self.skip_comment();
+            match self.peek() {
+                Some('\n') => {
+                    self.advance();
+                }
+                Some('\r') => self.pos += 1,
+                _ => break,
+            }
+        }
+    }
+
+    /// Only a comment may follow a value or table header on its line.
+    fn end_of_line(&mut self) -> Result<(), String> {
+        self.skip_spaces();
+        self.skip_comment();
+        self.eat('\r');
+        match self.advance() {
+            None | Some('\n') => Ok(()),
+            Some(c) => Err(format!("unexpected '{}' after the value", c)),
+        }
+    }
+
+    /// A key: bare or quoted parts joined by dots.
+    fn key(&mut self) -> Result<Vec<String>, String> {
+        let mut parts = Vec::new();
+        loop {
+            self.skip_spaces();
+            let part = match self.peek() {
+                Some('"') => self.basic_string()?,
+                Some('\'') => self.literal_string()?,
+                _ => {
+                    let start = self.pos;
+                    while self.peek().is_some_and(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-') {
+                        self.pos += 1;
+                    }
+                    if start == self.pos {
+                        return Err(match self.peek() {
+                            Some(c) => format!("unexpected '{}' where a key was expected", c),
+                            None => "missing key".to_string(),
+                        });
+                    }
+                    self.chars[start..self.pos].iter().collect()
+                }
+            };
+            parts.push(part);
+            self.skip_spaces();
+            if !self.eat('.') {
+                return Ok(parts);
+            }
+        }
+    }
+
+    fn value(&mut self) -> Result<Value, String> {
+        match self.peek() {
+            Some('"') => Ok(Value::String(if self.starts_with("\"\"\"") { self.multiline_basic_string()? } else { self.basic_string()? })),
+            Some('\'') => Ok(Value::String(if self.starts_with("'''") { self.multiline_literal_string()? } else { self.literal_string()? })),
+            Some('[') => self.array(),
+            Some('{') => self.inline_table(),
+            None | Some('\r' | '\n' | '#') => Err("missing value".to_string()),
+            Some(_) => self.scalar(),
+        }
+    }
+
+    fn escape(&mut self) -> Result<char, String> {
+        let c = self.advance().ok_or("unterminated escape")?;
+        Ok(match c {
+            'n' => '\n',
+            't' => '\t',
+            'r' => '\r',
+            'b' => '\u{8}',
+            'f' => '\u{c}',
+            '"' => '"',
+            '\\' => '\\',
+            'u' | 'U' => {
+                let digits = if c == 'u' { 4 } else { 8 };
+                let hex: String = (0..digits).filter_map(|_| self.advance()).collect();
+                u32::from_str_radix(&hex, 16).ok().and_then(char::from_u32).ok_or_else(|| format!("invalid escape \\{}{}", c, hex))?
+            }
+            other => return Err(format!("invalid escape \\{}", other)),
+        })
+    }
+
+    fn basic_string(&mut self) -> Result<String, String> {
+        self.pos += 1;
+        let mut text = String::new();
+        loop {
+            match self.advance() {
+                Some('"') => return Ok(text),
+                Some('\\') => text.push(self.escape()?),
+                Some('\n') | None => return Err("unterminated string".to_string()),
+                Some(c) => text.push(c),
+            }
+        }
+    }
+
+    fn literal_string(&mut self) -> Result<String, String> {
+        self.pos += 1;
+        let mut text = String::new();
+        loop {
+            match self.advance() {
+                Some('\'') => return Ok(text),
+                Some('\n') | None => return Err("unterminated string".to_string()),
+                Some(c) => text.push(c),
+            }
+        }
+    }
+
+    /// `"""..."""`, where a newline right after the opening quotes is dropped and a backslash at
+    /// the end of a line joins it to the next non-blank text.
+    fn multiline_basic_string(&mut self) -> Result<String, String> {
+        self.pos += 3;
+        self.eat('\r');
+        if self.peek() == Some('\n') {
+            self.advance();
+        }
+        let mut text = String::new();
+        loop {
+            if self.starts_with("\"\"\"") {
+                self.pos += 3;
+                return Ok(text);
+            }
+            match self.advance() {
+                Some('\\') if matches!(self.peek(), Some(' ' | '\t' | '\r' | '\n')) => {
+                    while matches!(self.peek(), Some(' ' | '\t' | '\r' | '\n')) {
+                        self.advance();
+                    }
+                }
+                Some('\\') => text.push(self.escape()?),
+                Some('\r') => {}
+                Some(c) => text.push(c),
+                None => return Err("unterminated multi-line string".to_string()),
+            }
+        }
+    }
+
+    fn multiline_literal_string(&mut self) -> Result<String, String> {
+        self.pos += 3;
+        self.eat('\r');
+        if self.peek() == Some('\n') {
+            self.advance();
+        }
+        let mut text = String::new();
+        loop {
+            if self.starts_with("'''") {
+                self.pos += 3;
+                return Ok(text);
+            }
+            match self.advance() {
+                Some('\r') => {}
+                Some(c) => text.push(c),
+                None => return Err("unterminated multi-line string".to_string()),
+            }
+        }
+    }
+
+    fn array(&mut self) -> Result<Value, String> {
+        self.pos += 1;
+        let mut elements = Vec::new();
+        loop {
+            self.skip_blank_lines();
+            if self.eat(']') {
+                return Ok(Value::Array(elements));
+            }
+            elements.push(self.value()?);
+            self.skip_blank_lines();
+            if !self.eat(',') {
+                self.skip_blank_lines();
+                return if self.eat(']') { Ok(Value::Array(elements)) } else { Err("expected ',' or ']' in array".to_string()) };
+            }
+        }
+    }
+
+    fn inline_table(&mut self) -> Result<Value, String> {
+        self.pos += 1;
+        let mut table = Map::new();
+        self.skip_spaces();
+        if self.eat('}') {
+            return Ok(Value::Object(table));
+        }
+        loop {
+            let key = self.key()?;
+            self.skip_spaces();
+            if !self.eat('=') {
+                return Err(format!("expected '=' after '{}'", key.join(".")));
+            }
+            self.skip_spaces();
+            let value = self.value()?;
+            insert(&mut table, &key, value)?;
+            self.skip_spaces();
+            if self.eat('}') {
+                return Ok(Value::Object(table));
+            }
+            if !self.eat(',') {
+                return Err("expected ',' or '}' in inline table".to_string());
+            }
+        }
+    }
+
+    /// Booleans, integers (decimal, hex, octal, binary) and floats.
+    fn scalar(&mut self) -> Result<Value, String> {
+        let start = self.pos;
+        while self.peek().is_some_and(|c| !matches!(c, ',' | ']' | '}' | '#' | ' ' | '\t' | '\r' | '\n')) {
+            self.pos += 1;
+        }
+        let word: String = self.chars[start..self.pos].iter().collect();
+        match word.as_str() {
+            "true" => return Ok(Value::Bool(true)),
+            "false" => return Ok(Value::Bool(false)),
+            "inf" | "+inf" | "-inf" | "nan" | "+nan" | "-nan" => return Err(format!("'{}' cannot be represented", word)),
+            _ => {}
+        }
+        let digits = word.replace('_', "");
+        let radix = [("0x", 16), ("0o", 8), ("0b", 2)].into_iter().find_map(|(prefix, radix)| digits.strip_prefix(prefix).map(|rest| (rest, radix)));
+        if let Some((rest, radix)) = radix {
+            return i64::from_str_radix(rest, radix).map(Value::from).map_err(|_| format!("invalid number '{}'", word));
+        }
+        if let Ok(integer) = digits.parse::<i64>() {
+            return Ok(Value::from(integer));
+        }
+        digits
+            .parse::<f64>()
+            .ok()
+            .filter(|_| digits.chars().any(|c| c.is_ascii_digit()))
+            .and_then(serde_json::Number::from_f64)
+            .map(Value::Number)
+            .ok_or_else(|| format!("invalid value '{}' (strings need quotes)", word))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::parse;
+
+    #[test]
+    fn parses_suite_syntax() {
+        let text = r#"
+# A suite.
+name = "demo"   # trailing comment
+providers = [
+    "groq:llama",  # first
+    'ollama:llama3.2',
+]
+
+[[tasks]]
+id = "sum"
+prompt = """
+What is \
+    17 + 25?"""
+checker = '''grep -q "$EXPECTED"'''
+tolerance = 0.5
+repetitions = 1_000
+limits = { hex = 0xff, octal = 0o17, binary = 0b101, exp = 1e3 }
+site."example.com".visits = 3
+
+[[tasks]]
+id = "escape"
+prompt = "tab\tquote\" \u00e9"
+tags = []
+nested = [[1, 2], ["a"]]
+flag = true
+"#;
+        assert_eq!(
+            parse(text).unwrap(),
+            json!({
+                "name": "demo",
+                "providers": ["groq:llama", "ollama:llama3.2"],
+                "tasks": [
+                    {
+                        "id": "sum",
+                        "prompt": "What is 17 + 25?",
+                        "checker": "grep -q \"$EXPECTED\"",
+                        "tolerance": 0.5,
+                        "repetitions": 1000,
+                        "limits": {"hex": 255, "octal": 15, "binary": 5, "exp": 1000.0},
+                        "site": {"example.com": {"visits": 3}},
+                    },
+                    {"id": "escape", "prompt": "tab\tquote\" é", "tags": [], "nested": [[1, 2], ["a"]], "flag": true},
+                ],
+            })
+        );
+    }
+
+    #[test]
+    fn tables_and_sub_tables() {
+        let text = "[a.b]\nx = 1\n[a]\ny = 2\n[fruit]\napple.color = 'red'\napple.taste.sweet = true\n[fruit.apple.texture]\nsmooth = true\n";
+        assert_eq!(
+            parse(text).unwrap(),
+            json!({"a": {"b": {"x": 1}, "y": 2}, "fruit": {"apple": {"color": "red", "taste": {"sweet": true}, "texture": {"smooth": true}}}})
+        );
+        // Each element of an array of tables has its own sub-tables.
+        let text = "[[t]]\n[t.opts]\nn = 1\n[[t]]\n[t.opts]\nn = 2\n";
+        assert_eq!(parse(text).unwrap(), json!({"t": [{"opts": {"n": 1}}, {"opts": {"n": 2}}]}));
+    }
+
+    #[test]
+    fn rejects_invalid_toml() {
+        let cases = [
+            ("[a]\nx = 1\n[a]\ny = 2\n", "line 3: table 'a' is defined twice"),
+            ("[fruit]\napple.color = 'red'\n[fruit.apple]\n", "line 3: table 'fruit.apple' is defined twice"),
+            ("[x.a]\n[x]\na.b = 1\n", "line 3: table 'x.a' is defined twice"),
+            ("a = { x = 1 }\na.y = 2\n", "line 2: table 'a' is defined twice"),
+            ("a = { x = 1 }\n[a.b]\n", "line 2: table 'a' is defined twice"),
+            ("x = 1\nx = 2\n", "line 2: 'x' is defined twice"),
+            ("x = 1\n[x]\n", "line 2: 'x' is already a value"),
+            ("[a]\n[[a]]\n", "line 2: 'a' is not an array of tables"),
+            ("x =\n", "line 1: missing value"),
+            ("x = hello\n", "line 1: invalid value 'hello' (strings need quotes)"),
+            ("x = \"open\n", "line 1: unterminated string"),
+            ("x = [1, 2\n", "line 1: expected ',' or ']' in array"),
+            ("x = 1 y = 2\n", "line 1: unexpected 'y' after the value"),
+            ("[a\n", "line 1: expected ']' after the table name"),
+            ("x = \"\\q\"\n", "line 1: invalid escape \\q"),
+            ("x = nan\n", "line 1: 'nan' cannot be represented"),
+        ];
+        for (text, expected) in cases {
+            assert_eq!(parse(text).unwrap_err(), expected, "{:?}", text);
+        }
+    }
+}
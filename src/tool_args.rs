@@ -0,0 +1,348 @@
+use std::fmt;
+
+use serde::Deserialize;
+use serde::de::DeserializeOwned;
+use serde_json::{Map, Value, json};
+
+use crate::{browser, json_schema, schedule};
+
+/// Arguments of `[RUN_COMMAND ...]`.
+#[derive(Debug, Deserialize)]
+pub struct RunCommandArgs {
+    pub command: String,
+    pub workdir: Option<String>,
+}
+
+/// Arguments of `[SEARCH: ...]`.
+#[derive(Debug, Deserialize)]
+pub struct SearchArgs {
+    pub query: String,
+}
+
+/// Arguments of `[BROWSE: ...]`: a page and optional `click`, `scroll` and `wait` steps.
+#[derive(Debug, Deserialize)]
+pub struct BrowseArgs {
+    pub url: String,
+    pub actions: Option<String>,
+}
+
+/// Arguments of `[CALCULATE: ...]`.
+#[derive(Debug, Deserialize)]
+pub struct CalculateArgs {
+    pub expression: String,
+}
+
+/// Arguments of `[SCHEDULE: ...]`: a time and either a reminder or a command.
+#[derive(Debug, Deserialize)]
+pub struct ScheduleArgs {
+    pub when: String,
+    pub reminder: Option<String>,
+    pub command: Option<String>,
+}
+
+/// Arguments of `[SEND_EMAIL {...}]`. `to` may list several addresses separated by commas.
+#[derive(Debug, Deserialize)]
+pub struct SendEmailArgs {
+    pub to: String,
+    pub subject: String,
+    pub body: String,
+}
+
+/// A tool call with validated, typed arguments.
+#[derive(Debug)]
+pub enum ToolCall {
+    RunCommand(RunCommandArgs),
+    WebSearch(SearchArgs),
+    Browse(BrowseArgs),
+    Calculate(CalculateArgs),
+    Schedule(ScheduleArgs),
+    SendEmail(SendEmailArgs),
+}
+
+impl ToolCall {
+    pub fn name(&self) -> &'static str {
+        match self {
+            ToolCall::RunCommand(_) => "run_command",
+            ToolCall::WebSearch(_) => "web_search",
+            ToolCall::Browse(_) => "browse",
+            ToolCall::Calculate(_) => "calculate",
+            ToolCall::Schedule(_) => "schedule",
+            ToolCall::SendEmail(_) => "send_email",
+        }
+    }
+}
+
+/// Why a tool call's arguments were rejected, sent back to the model as JSON so it can fix
+/// the call.
+#[derive(Debug, Clone)]
+pub struct ArgumentError {
+    pub tool: &'static str,
+    pub field: Option<String>,
+    pub message: String,
+}
+
+impl ArgumentError {
+    fn new(tool: &'static str, field: Option<&str>, message: String) -> Self {
+        ArgumentError { tool, field: field.map(str::to_string), message }
+    }
+
+    pub fn to_json(&self) -> Value {
+        json!({
+            "error": "invalid_arguments",
+            "tool": self.tool,
+            "field": self.field,
+            "message": self.message,
+            "schema": schema(self.tool),
+        })
+    }
+}
+
+impl fmt::Display for ArgumentError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.field {
+            Some(field) => write!(f, "{}.{}: {}", self.tool, field, self.message),
+            None => write!(f, "{}: {}", self.tool, self.message),
+        }
+    }
+}
+
+/// JSON Schema of a tool's arguments. The first required property is the one filled by the
+/// plain form (`[RUN_COMMAND ls -la]`); the JSON form (`[RUN_COMMAND {"command": "ls"}]`) can
+/// set any of them.
+pub fn schema(tool: &str) -> Value {
+    match tool {
+        "run_command" => json!({
+            "type": "object",
+            "properties": {
+                "command": {"type": "string", "minLength": 1, "description": "Shell command to run with sh -c"},
+                "workdir": {"type": "string", "minLength": 1, "description": "Directory to run the command in"},
+            },
+            "required": ["command"],
+            "additionalProperties": false,
+        }),
+        "web_search" => json!({
+            "type": "object",
+            "properties": {
+                "query": {"type": "string", "minLength": 1, "description": "Search query"},
+            },
+            "required": ["query"],
+            "additionalProperties": false,
+        }),
+        "browse" => json!({
+            "type": "object",
+            "properties": {
+                "url": {"type": "string", "minLength": 1, "description": "http or https page to render"},
+                "actions": {"type": "string", "minLength": 1, "description": "Steps after loading, separated by ';': click <css selector>, scroll <screens>, wait <ms>"},
+            },
+            "required": ["url"],
+            "additionalProperties": false,
+        }),
+        "calculate" => json!({
+            "type": "object",
+            "properties": {
+                "expression": {"type": "string", "minLength": 1, "description": "Arithmetic with optional units, dates and a trailing `to <unit>`"},
+            },
+            "required": ["expression"],
+            "additionalProperties": false,
+        }),
+        "schedule" => json!({
+            "type": "object",
+            "properties": {
+                "when": {"type": "string", "minLength": 1, "description": "in 10m, every 1h, 17:00 or 2025-07-01 09:30 (local time)"},
+                "reminder": {"type": "string", "minLength": 1, "description": "Reminder to post to this session"},
+                "command": {"type": "string", "minLength": 1, "description": "Shell command to run, with its output posted to this session"},
+            },
+            "required": ["when"],
+            "additionalProperties": false,
+        }),
+        "send_email" => json!({
+            "type": "object",
+            "properties": {
+                "to": {"type": "string", "minLength": 1, "description": "Recipient addresses, separated by commas"},
+                "subject": {"type": "string", "minLength": 1, "description": "Single-line subject"},
+                "body": {"type": "string", "minLength": 1, "description": "Plain-text body"},
+            },
+            "required": ["to", "subject", "body"],
+            "additionalProperties": false,
+        }),
+        _ => Value::Null,
+    }
+}
+
+/// What each tool does, for providers that take tools as function declarations.
+pub fn description(tool: &str) -> &'static str {
+    match tool {
+        "run_command" => "Run a shell command and read its output",
+        "web_search" => "Search the web for current information",
+        "browse" => "Render a web page, optionally clicking, scrolling or waiting, and read its text",
+        "calculate" => "Evaluate arithmetic exactly, with units and dates",
+        "schedule" => "Post a reminder or run a command later in this session",
+        "send_email" => "Send a plain-text email",
+        _ => "",
+    }
+}
+
+/// Tools offered in a session with tools enabled, in the order they are declared.
+pub fn offered(browse: bool, email: bool) -> Vec<&'static str> {
+    let mut tools = vec!["run_command", "web_search", "calculate", "schedule"];
+    if browse {
+        tools.push("browse");
+    }
+    if email {
+        tools.push("send_email");
+    }
+    tools
+}
+
+/// The bracket form of a native function call, so structured calls go through the same
+/// checks, permissions and execution as text ones. Unknown tools give `None`.
+pub fn marker(tool: &str, arguments: &Value) -> Option<String> {
+    let prefix = match tool {
+        "run_command" => "[RUN_COMMAND ",
+        "web_search" => "[SEARCH: ",
+        "browse" => "[BROWSE: ",
+        "calculate" => "[CALCULATE: ",
+        "schedule" => "[SCHEDULE: ",
+        "send_email" => "[SEND_EMAIL ",
+        _ => return None,
+    };
+    // OpenAI sends the arguments as a JSON string, Gemini as an object.
+    let arguments = match arguments {
+        Value::String(text) => serde_json::from_str(text).unwrap_or_else(|_| json!({})),
+        Value::Null => json!({}),
+        other => other.clone(),
+    };
+    Some(format!("{}{}]", prefix, arguments))
+}
+
+/// Coerces a value to the schema type where the intent is unambiguous: numbers and booleans
+/// become strings, and arrays of scalars (an argv list) are joined with spaces.
+fn coerce(tool: &'static str, field: &str, value: Value, spec: &Value) -> Result<Value, ArgumentError> {
+    let expected = spec["type"].as_str().unwrap_or("string");
+    let value = match (expected, value) {
+        ("string", Value::String(s)) => Value::String(s.trim().to_string()),
+        ("string", Value::Number(n)) => Value::String(n.to_string()),
+        ("string", Value::Bool(b)) => Value::String(b.to_string()),
+        ("string", Value::Array(items)) if items.iter().all(|i| !i.is_array() && !i.is_object() && !i.is_null()) => Value::String(
+            items.iter().map(|i| i.as_str().map(str::to_string).unwrap_or_else(|| i.to_string())).collect::<Vec<_>>().join(" "),
+        ),
+        (expected, other) => {
+            return Err(ArgumentError::new(tool, Some(field), format!("expected {}, got {}", expected, other)));
+        }
+    };
+    Ok(value)
+}
+
+/// Checks `args` against the tool's schema, coercing what it can, and deserializes the result.
+/// Unknown arguments are reported with the known ones; the rest is the shared schema validator.
+fn validate<T: DeserializeOwned>(tool: &'static str, args: Map<String, Value>) -> Result<T, ArgumentError> {
+    let schema = schema(tool);
+    let properties = schema["properties"].as_object().cloned().unwrap_or_default();
+    let mut checked = Map::new();
+    for (field, value) in args {
+        let Some(spec) = properties.get(&field) else {
+            let known = properties.keys().cloned().collect::<Vec<_>>().join(", ");
+            return Err(ArgumentError::new(tool, Some(&field), format!("unknown argument (expected one of: {})", known)));
+        };
+        if !value.is_null() {
+            checked.insert(field.clone(), coerce(tool, &field, value, spec)?);
+        }
+    }
+    let checked = Value::Object(checked);
+    if let Some(error) = json_schema::validate(&checked, &schema).into_iter().next() {
+        let field = error.path.strip_prefix("$.");
+        return Err(ArgumentError::new(tool, field, error.message));
+    }
+    serde_json::from_value(checked).map_err(|e| ArgumentError::new(tool, None, e.to_string()))
+}
+
+/// Builds the argument object from the text inside the brackets: a JSON object when it is one,
+/// otherwise the whole text as the tool's first required argument.
+fn arguments(tool: &'static str, text: &str) -> Result<Map<String, Value>, ArgumentError> {
+    let text = text.trim();
+    if text.starts_with('{') {
+        match serde_json::from_str::<Value>(text) {
+            Ok(Value::Object(map)) => return Ok(map),
+            Err(e) if text.starts_with("{\"") => {
+                return Err(ArgumentError::new(tool, None, format!("arguments are not valid JSON: {}", e)));
+            }
+            // Anything else is a shell group such as `{ ls; pwd; }`, taken as plain text.
+            _ => {}
+        }
+    }
+    // The plain schedule form is `<when> <reminder>`.
+    if tool == "schedule" {
+        let (when, reminder) = schedule::split_when(text).map_err(|e| ArgumentError::new(tool, Some("when"), e))?;
+        return Ok(Map::from_iter([("when".to_string(), Value::String(when)), ("reminder".to_string(), Value::String(reminder))]));
+    }
+    let primary = schema(tool)["required"][0].as_str().unwrap_or_default().to_string();
+    Ok(Map::from_iter([(primary, Value::String(text.to_string()))]))
+}
+
+/// Parses a reply into a typed tool call. Replies that are not tool calls give `Ok(None)`;
+/// the bracket format itself is checked by `tool_format::check_tool_call`.
+pub fn parse(reply: &str, search_enabled: bool) -> Result<Option<ToolCall>, ArgumentError> {
+    let trimmed = reply.trim().trim_matches(|c| c == '\'' || c == '\"' || c == '`');
+    let starts_with = |prefix: &str| trimmed.get(..prefix.len()).is_some_and(|p| p.eq_ignore_ascii_case(prefix));
+    let (tool, prefix) = if starts_with("[RUN_COMMAND") {
+        ("run_command", "[RUN_COMMAND".len())
+    } else if search_enabled && starts_with("[SEARCH:") {
+        ("web_search", "[SEARCH:".len())
+    } else if search_enabled && starts_with("[BROWSE:") {
+        ("browse", "[BROWSE:".len())
+    } else if starts_with("[CALCULATE:") {
+        ("calculate", "[CALCULATE:".len())
+    } else if starts_with("[SCHEDULE:") {
+        ("schedule", "[SCHEDULE:".len())
+    } else if starts_with("[SEND_EMAIL") {
+        ("send_email", "[SEND_EMAIL".len())
+    } else {
+        return Ok(None);
+    };
+    let inner = &trimmed[prefix..];
+    let inner = inner.strip_suffix(']').unwrap_or(inner);
+    let args = arguments(tool, inner)?;
+    Ok(Some(match tool {
+        "run_command" => ToolCall::RunCommand(validate(tool, args)?),
+        "web_search" => ToolCall::WebSearch(validate(tool, args)?),
+        "browse" => {
+            let args: BrowseArgs = validate(tool, args)?;
+            browser::check_url(&args.url).map_err(|e| ArgumentError::new(tool, Some("url"), e))?;
+            browser::parse_actions(args.actions.as_deref().unwrap_or_default(), &browser::Limits::from_env())
+                .map_err(|e| ArgumentError::new(tool, Some("actions"), e))?;
+            ToolCall::Browse(args)
+        }
+        "calculate" => ToolCall::Calculate(validate(tool, args)?),
+        "send_email" => ToolCall::SendEmail(validate(tool, args)?),
+        _ => {
+            let args: ScheduleArgs = validate(tool, args)?;
+            schedule::parse_when(&args.when).map_err(|e| ArgumentError::new(tool, Some("when"), e))?;
+            if args.reminder.is_some() == args.command.is_some() {
+                return Err(ArgumentError::new(tool, None, "give exactly one of reminder or command".to_string()));
+            }
+            ToolCall::Schedule(args)
+        }
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{offered, parse, schema};
+    use crate::json_schema;
+
+    #[test]
+    fn tool_schemas_are_supported() {
+        for tool in offered(true, true) {
+            assert_eq!(json_schema::check_supported(&schema(tool)), Ok(()), "{}", tool);
+        }
+    }
+
+    #[test]
+    fn reports_the_failing_argument() {
+        let error = |reply: &str| parse(reply, true).unwrap_err().to_string();
+        assert_eq!(error(r#"[RUN_COMMAND {"command": ""}]"#), "run_command.command: must not be empty");
+        assert_eq!(error(r#"[RUN_COMMAND {"workdir": "src"}]"#), "run_command.command: missing required property");
+        assert_eq!(error(r#"[SEARCH: {"query": "x", "limit": 3}]"#), "web_search.limit: unknown argument (expected one of: query)");
+        assert_eq!(parse(r#"[RUN_COMMAND {"command": ["ls", "-la"]}]"#, true).unwrap().unwrap().name(), "run_command");
+    }
+}
@@ -0,0 +1,142 @@
+use std::env;
+use std::fmt;
+
+use crate::tool_args::{self, ArgumentError};
+
+/// A reply that tried to call a tool but could not be run.
+#[derive(Debug, Clone)]
+pub enum ToolCallError {
+    /// The bracket format is wrong.
+    Format(String),
+    /// The format is right but the arguments do not match the tool's schema.
+    Arguments(ArgumentError),
+}
+
+impl fmt::Display for ToolCallError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ToolCallError::Format(error) => write!(f, "{}", error),
+            ToolCallError::Arguments(error) => write!(f, "{}", error),
+        }
+    }
+}
+
+/// How many times a malformed tool call is sent back for correction (`TOOL_REPAIR_ATTEMPTS`).
+pub fn repair_attempts() -> usize {
+    env::var("TOOL_REPAIR_ATTEMPTS")
+        .ok()
+        .and_then(|v| v.trim().parse().ok())
+        .unwrap_or(2)
+}
+
+/// Checks a reply that looks like a tool call against the expected format and the tool's
+/// argument schema. A reply looks like a call when it, or one of its lines, starts with a tool
+/// marker; replies that only mention a tool in prose are plain answers and always pass.
+pub fn check_tool_call(reply: &str, search_enabled: bool) -> Result<(), ToolCallError> {
+    check_format(reply, search_enabled).map_err(ToolCallError::Format)?;
+    tool_args::parse(reply, search_enabled).map(|_| ()).map_err(ToolCallError::Arguments)
+}
+
+/// Whether `text` starts with a tool marker, bracketed or not.
+fn starts_with_marker(text: &str, search_enabled: bool) -> bool {
+    let text = text.trim_start_matches(['\'', '"', '`']);
+    let search = search_enabled && (text.starts_with("[SEARCH") || text.starts_with("[BROWSE"));
+    search || ["[RUN_COMMAND", "(RUN_COMMAND", "{RUN_COMMAND", "RUN_COMMAND", "[CALCULATE", "[SCHEDULE", "[SEND_EMAIL"].iter().any(|m| text.starts_with(m))
+}
+
+fn check_format(reply: &str, search_enabled: bool) -> Result<(), String> {
+    let trimmed = reply.trim().trim_matches(|c| c == '\'' || c == '\"' || c == '`');
+    let upper = trimmed.to_uppercase();
+    if !upper.lines().any(|line| starts_with_marker(line.trim_start(), search_enabled)) {
+        return Ok(());
+    }
+
+    let inner = if let Some(rest) = upper.strip_prefix("[RUN_COMMAND") {
+        rest
+    } else if let Some(rest) = upper.strip_prefix("[SEARCH:").filter(|_| search_enabled) {
+        rest
+    } else if let Some(rest) = upper.strip_prefix("[BROWSE:").filter(|_| search_enabled) {
+        rest
+    } else if let Some(rest) = upper.strip_prefix("[CALCULATE:") {
+        rest
+    } else if let Some(rest) = upper.strip_prefix("[SCHEDULE:") {
+        rest
+    } else if let Some(rest) = upper.strip_prefix("[SEND_EMAIL") {
+        rest
+    } else if upper.starts_with("[SEARCH") && search_enabled {
+        return Err("missing ':' after SEARCH".to_string());
+    } else if upper.starts_with("[BROWSE") && search_enabled {
+        return Err("missing ':' after BROWSE".to_string());
+    } else if upper.starts_with("[CALCULATE") {
+        return Err("missing ':' after CALCULATE".to_string());
+    } else if upper.starts_with("[SCHEDULE") {
+        return Err("missing ':' after SCHEDULE".to_string());
+    } else if upper.starts_with("(RUN_COMMAND") || upper.starts_with("{RUN_COMMAND") || upper.starts_with("RUN_COMMAND") {
+        return Err("the tool call must be wrapped in square brackets".to_string());
+    } else {
+        return Err("the tool call must be the whole reply, with no text before it".to_string());
+    };
+
+    if !inner.ends_with(']') {
+        return Err(if inner.contains(']') {
+            "there is text after the closing bracket".to_string()
+        } else {
+            "the closing bracket is missing".to_string()
+        });
+    }
+    if inner.trim_end_matches(']').trim().is_empty() {
+        return Err("the tool call is empty".to_string());
+    }
+    Ok(())
+}
+
+/// Corrective message sent after a malformed tool call. Argument errors are sent as JSON with
+/// the tool's schema so the model can fix the call.
+pub fn correction(error: &ToolCallError, search_enabled: bool) -> String {
+    let error = match error {
+        ToolCallError::Format(error) => error,
+        ToolCallError::Arguments(error) => {
+            return format!(
+                "Your last tool call had invalid arguments:\n{}\nReply with only the corrected tool call, or answer in plain text.",
+                error.to_json()
+            );
+        }
+    };
+    let formats = if search_enabled {
+        "`[RUN_COMMAND ls -la]`, `[SEARCH: your query]` or `[CALCULATE: 2 h + 30 min to min]`"
+    } else {
+        "`[RUN_COMMAND ls -la]` or `[CALCULATE: 2 h + 30 min to min]`"
+    };
+    format!(
+        "Your last reply was not a valid tool call: {}. Reply with only the tool call, e.g. {}, or answer in plain text.",
+        error, formats
+    )
+}
+
+/// Message sent when a tool call with invalid arguments is given up on: the error as JSON, and a
+/// request to answer without the tool.
+pub fn rejection(error: &ArgumentError) -> String {
+    format!("Your last tool call had invalid arguments and was not run:\n{}\nAnswer in plain text without it.", error.to_json())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::check_format;
+
+    #[test]
+    fn only_markers_at_a_line_start_are_calls() {
+        let cases = [
+            ("[CALCULATE: 2 + 2]", Ok(())),
+            ("Use RUN_COMMAND to list files, or [SEARCH the web.", Ok(())),
+            ("The [CALCULATE: ...] tool does arithmetic.", Ok(())),
+            ("Let me check.\n[SEARCH: rust 2024]", Err("the tool call must be the whole reply, with no text before it")),
+            ("RUN_COMMAND: ls", Err("the tool call must be wrapped in square brackets")),
+            ("[CALCULATE 2 + 2]", Err("missing ':' after CALCULATE")),
+            ("[SCHEDULE: in 5m] then more", Err("there is text after the closing bracket")),
+        ];
+        for (reply, expected) in cases {
+            assert_eq!(check_format(reply, true), expected.map_err(String::from), "{}", reply);
+        }
+        assert_eq!(check_format("[SEARCH: rust]", false), Ok(()));
+    }
+}
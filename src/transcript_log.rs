@@ -0,0 +1,72 @@
+use std::env;
+use std::fs::OpenOptions;
+use std::io::Write;
+use std::path::PathBuf;
+
+use serde_json::{json, Value};
+
+use crate::events::{Event, Subscriber};
+
+/// Appends one JSON line describing `event` to the day's transcript log when
+/// `TRANSCRIPT_LOG_DIR` is set. Files are named `agent_bench-YYYY-MM-DD.jsonl` and are only
+/// ever appended to, independently of the SQLite database.
+pub fn log_event(session_id: &str, event: &str, fields: Value) {
+    let Ok(dir) = env::var("TRANSCRIPT_LOG_DIR") else {
+        return;
+    };
+    let now = chrono::Local::now();
+    let dir = PathBuf::from(dir);
+    let path = dir.join(format!("agent_bench-{}.jsonl", now.format("%Y-%m-%d")));
+
+    let mut line = json!({
+        "ts": now.to_rfc3339(),
+        "session_id": session_id,
+        "event": event,
+    });
+    if let (Some(line), Value::Object(fields)) = (line.as_object_mut(), fields) {
+        line.extend(fields);
+    }
+
+    let result = std::fs::create_dir_all(&dir)
+        .and_then(|_| OpenOptions::new().create(true).append(true).open(&path))
+        .and_then(|mut file| writeln!(file, "{}", line));
+    if let Err(e) = result {
+        eprintln!("Could not write transcript log {}: {}", path.display(), e);
+    }
+}
+
+/// Mirrors session events into the JSONL transcript log.
+pub struct TranscriptLogger;
+
+impl Subscriber for TranscriptLogger {
+    fn handle(&mut self, session_id: &str, event: &Event) {
+        match event {
+            Event::SessionStarted { model, settings } => {
+                let settings: serde_json::Map<String, serde_json::Value> =
+                    settings.iter().map(|(label, value)| (label.to_string(), json!(value))).collect();
+                log_event(session_id, "session_started", json!({"model": model, "settings": settings}))
+            }
+            Event::MessageSaved { role, content } => log_event(session_id, "message", json!({"role": role, "content": content})),
+            Event::ToolCompleted { tool, input, output, success, .. } => log_event(session_id, "tool", json!({
+                "tool": tool,
+                "input": input,
+                "success": success,
+                "output": output,
+            })),
+            Event::ToolDenied { tool, user, role } => log_event(session_id, "tool_denied", json!({"tool": tool, "user": user, "role": role})),
+            Event::Reasoning(text) => log_event(session_id, "reasoning", json!({"chars": text.chars().count(), "content": text})),
+            Event::TokenUsage(usage, cost) => {
+                let mut fields = usage.to_json();
+                if let Some(cost) = cost {
+                    fields["cost"] = json!(cost.reply);
+                    fields["session_cost"] = json!(cost.session);
+                    fields["currency"] = json!(cost.currency);
+                }
+                log_event(session_id, "usage", fields)
+            }
+            Event::FollowUps(prompts) => log_event(session_id, "follow_ups", json!({"prompts": prompts})),
+            Event::Latency(latency) => log_event(session_id, "latency", latency.to_json()),
+            _ => {}
+        }
+    }
+}
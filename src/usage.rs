@@ -0,0 +1,255 @@
+use std::collections::BTreeMap;
+use std::env;
+use std::time::{Duration, Instant};
+
+use rusqlite::{params, Connection};
+use serde_json::{Value, json};
+
+use crate::events::{Event, Subscriber};
+
+/// Tokens a provider reported for one call, or summed over the calls of a turn.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct TokenUsage {
+    pub prompt_tokens: u64,
+    pub completion_tokens: u64,
+}
+
+impl TokenUsage {
+    /// `12 prompt + 340 completion = 352 tokens`.
+    pub fn describe(&self) -> String {
+        format!(
+            "{} prompt + {} completion = {} tokens",
+            self.prompt_tokens,
+            self.completion_tokens,
+            self.prompt_tokens + self.completion_tokens
+        )
+    }
+
+    pub fn to_json(self) -> Value {
+        json!({"prompt_tokens": self.prompt_tokens, "completion_tokens": self.completion_tokens})
+    }
+}
+
+/// What a reply cost and the session's running total including it, in the pricing catalog's
+/// currency.
+#[derive(Debug, Clone)]
+pub struct ReplyCost {
+    pub reply: f64,
+    pub session: f64,
+    pub currency: String,
+}
+
+impl ReplyCost {
+    /// `0.000231 USD (session 0.0123 USD)`.
+    pub fn describe(&self) -> String {
+        format!("{:.6} {} (session {:.4} {})", self.reply, self.currency, self.session, self.currency)
+    }
+}
+
+/// The usage of two calls together. Calls without a usage block add nothing, and a turn none of
+/// whose calls reported usage has none.
+pub fn sum(a: Option<TokenUsage>, b: Option<TokenUsage>) -> Option<TokenUsage> {
+    match (a, b) {
+        (Some(a), Some(b)) => Some(TokenUsage {
+            prompt_tokens: a.prompt_tokens + b.prompt_tokens,
+            completion_tokens: a.completion_tokens + b.completion_tokens,
+        }),
+        (a, b) => a.or(b),
+    }
+}
+
+/// Stores the tokens used to produce an assistant message in `messages.prompt_tokens` and
+/// `messages.completion_tokens`, and in `messages.cost` what they cost at the prices of the
+/// time, when the model has one.
+pub fn record_tokens(conn: &Connection, message_id: i64, usage: &TokenUsage, cost: Option<f64>) {
+    conn.execute(
+        "UPDATE messages SET prompt_tokens = ?2, completion_tokens = ?3, cost = ?4 WHERE id = ?1",
+        params![message_id, usage.prompt_tokens as i64, usage.completion_tokens as i64, cost],
+    ).unwrap();
+}
+
+/// Cost of every priced reply in a session so far.
+pub fn session_cost(conn: &Connection, session_id: &str) -> f64 {
+    conn.query_row("SELECT COALESCE(SUM(cost), 0) FROM messages WHERE session_id = ?1", params![session_id], |row| row.get(0))
+        .unwrap()
+}
+
+/// How `cost` groups spend.
+#[derive(Debug, Clone, Copy)]
+pub enum SpendGrouping {
+    Provider,
+    Model,
+    Day,
+}
+
+impl SpendGrouping {
+    pub fn parse(value: &str) -> Option<Self> {
+        match value.trim().to_lowercase().as_str() {
+            "provider" => Some(SpendGrouping::Provider),
+            "model" => Some(SpendGrouping::Model),
+            "day" => Some(SpendGrouping::Day),
+            _ => None,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            SpendGrouping::Provider => "provider",
+            SpendGrouping::Model => "model",
+            SpendGrouping::Day => "day",
+        }
+    }
+}
+
+/// Replies, tokens and cost for one provider, model or day.
+#[derive(Debug, Default)]
+pub struct Spend {
+    pub key: String,
+    pub replies: i64,
+    pub prompt_tokens: i64,
+    pub completion_tokens: i64,
+    pub cost: f64,
+    /// Replies with token counts but no price for their model, left out of `cost`.
+    pub unpriced: i64,
+}
+
+/// Spend on replies with recorded token usage, for one session or every session when
+/// `session_id` is `None`, most expensive first. Each reply counts towards the `provider/model`
+/// that answered it: the backup or fan-out provider when there was one, otherwise the session's.
+pub fn spend_report(conn: &Connection, grouping: SpendGrouping, session_id: Option<&str>) -> Vec<Spend> {
+    let mut stmt = conn
+        .prepare(
+            "SELECT COALESCE(m.answered_by, s.provider || '/' || s.model, 'unknown/' || s.model, 'unknown'), date(m.created_at),
+                    m.prompt_tokens, COALESCE(m.completion_tokens, 0), m.cost
+             FROM messages m LEFT JOIN sessions s ON s.id = m.session_id
+             WHERE m.prompt_tokens IS NOT NULL AND (?1 IS NULL OR m.session_id = ?1)",
+        )
+        .unwrap();
+    let rows = stmt
+        .query_map(params![session_id], |row| {
+            Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?, row.get::<_, i64>(2)?, row.get::<_, i64>(3)?, row.get::<_, Option<f64>>(4)?))
+        })
+        .unwrap()
+        .filter_map(Result::ok);
+
+    let mut groups: BTreeMap<String, Spend> = BTreeMap::new();
+    for (answered_by, day, prompt_tokens, completion_tokens, cost) in rows {
+        let key = match grouping {
+            SpendGrouping::Provider => answered_by.split_once('/').map_or(answered_by.clone(), |(provider, _)| provider.to_string()),
+            SpendGrouping::Model => answered_by,
+            SpendGrouping::Day => day,
+        };
+        let spend = groups.entry(key.clone()).or_insert_with(|| Spend { key, ..Default::default() });
+        spend.replies += 1;
+        spend.prompt_tokens += prompt_tokens;
+        spend.completion_tokens += completion_tokens;
+        match cost {
+            Some(cost) => spend.cost += cost,
+            None => spend.unpriced += 1,
+        }
+    }
+    let mut report: Vec<Spend> = groups.into_values().collect();
+    if !matches!(grouping, SpendGrouping::Day) {
+        report.sort_by(|a, b| b.cost.total_cmp(&a.cost));
+    }
+    report
+}
+
+/// Price of one call to `tool` (`TOOL_COST_<TOOL>`, e.g. `TOOL_COST_WEB_SEARCH=0.005`), in the
+/// pricing catalog's currency. Tools without a price are free.
+pub fn tool_cost(tool: &str) -> f64 {
+    env::var(format!("TOOL_COST_{}", tool.to_uppercase()))
+        .ok()
+        .and_then(|v| v.trim().parse().ok())
+        .unwrap_or(0.0)
+}
+
+/// Stores one tool run in `tool_runs`.
+pub fn record(conn: &Connection, session_id: &str, tool: &str, duration: Duration, success: bool, cost: f64) {
+    conn.execute(
+        "INSERT INTO tool_runs (session_id, tool, duration_ms, success, cost) VALUES (?1, ?2, ?3, ?4, ?5)",
+        params![session_id, tool, duration.as_millis() as i64, success, cost],
+    ).unwrap();
+}
+
+/// Stores one tool run from a benchmark run in `tool_runs`.
+pub fn record_for_run(conn: &Connection, run_id: &str, tool: &str, duration: Duration, success: bool, cost: f64) {
+    conn.execute(
+        "INSERT INTO tool_runs (run_id, tool, duration_ms, success, cost) VALUES (?1, ?2, ?3, ?4, ?5)",
+        params![run_id, tool, duration.as_millis() as i64, success, cost],
+    ).unwrap();
+}
+
+/// Times each tool from its request to its result and records the run with its cost. Runs
+/// that wait for confirmation include the time spent deciding.
+pub struct ToolUsageRecorder {
+    conn: Connection,
+    started: Option<Instant>,
+}
+
+impl ToolUsageRecorder {
+    pub fn new(conn: Connection) -> Self {
+        ToolUsageRecorder { conn, started: None }
+    }
+}
+
+impl Subscriber for ToolUsageRecorder {
+    fn handle(&mut self, session_id: &str, event: &Event) {
+        match event {
+            Event::ToolRequested { .. } => self.started = Some(Instant::now()),
+            Event::ToolCompleted { tool, success, .. } => {
+                let duration = self.started.take().map(|started| started.elapsed()).unwrap_or_default();
+                record(&self.conn, session_id, tool, duration, *success, tool_cost(tool));
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Runs, failures, time and cost for one tool.
+#[derive(Debug)]
+pub struct ToolUsage {
+    pub tool: String,
+    pub runs: i64,
+    pub failures: i64,
+    pub total_ms: i64,
+    pub cost: f64,
+}
+
+impl ToolUsage {
+    pub fn average_ms(&self) -> i64 {
+        if self.runs == 0 { 0 } else { self.total_ms / self.runs }
+    }
+}
+
+/// Which tool runs a report covers.
+#[derive(Debug, Clone, Copy)]
+pub enum ToolScope<'a> {
+    /// Every session and benchmark run.
+    All,
+    Session(&'a str),
+    Run(&'a str),
+}
+
+/// Tool usage per tool over `scope`.
+pub fn report(conn: &Connection, scope: ToolScope) -> Vec<ToolUsage> {
+    let (column, id) = match scope {
+        ToolScope::All => ("NULL", None),
+        ToolScope::Session(id) => ("session_id", Some(id)),
+        ToolScope::Run(id) => ("run_id", Some(id)),
+    };
+    let mut stmt = conn
+        .prepare(&format!(
+            "SELECT tool, COUNT(*), SUM(NOT success), SUM(duration_ms), SUM(cost)
+             FROM tool_runs WHERE ?1 IS NULL OR {} = ?1
+             GROUP BY tool ORDER BY SUM(cost) DESC, SUM(duration_ms) DESC",
+            column
+        ))
+        .unwrap();
+    stmt.query_map(params![id], |row| {
+        Ok(ToolUsage { tool: row.get(0)?, runs: row.get(1)?, failures: row.get(2)?, total_ms: row.get(3)?, cost: row.get(4)? })
+    })
+    .unwrap()
+    .filter_map(Result::ok)
+    .collect()
+}
@@ -0,0 +1,63 @@
+use std::env;
+
+use regex::Regex;
+
+use crate::Message;
+use crate::prompts::model_key;
+use crate::{attachments, capabilities, tokenizer};
+
+const MIB: usize = 1024 * 1024;
+
+fn limit(var: &str, default: usize) -> usize {
+    env::var(var)
+        .ok()
+        .and_then(|v| v.trim().parse().ok())
+        .unwrap_or(default)
+}
+
+/// Checks a request before it is sent, so oversized payloads fail here with advice instead of
+/// as an opaque 400 from the provider.
+pub fn check_request(model_name: &str, history: &[Message]) -> Result<(), String> {
+    let max_request = limit("MAX_REQUEST_BYTES", 20 * MIB);
+    let request_bytes: usize = history.iter().map(|m| m.content.len()).sum();
+    if request_bytes > max_request {
+        return Err(format!(
+            "request is {} bytes, over the {} byte limit (MAX_REQUEST_BYTES); attach less content or start a new session",
+            request_bytes, max_request
+        ));
+    }
+
+    let capabilities = capabilities::for_model(model_name);
+    let max_image = limit("MAX_IMAGE_BYTES", 20 * MIB);
+    let data_url = Regex::new(r"data:image/[A-Za-z0-9.+-]+;base64,([A-Za-z0-9+/=]+)").unwrap();
+    for message in history {
+        for image in data_url.captures_iter(&message.content) {
+            if capabilities.vision == Some(false) {
+                return Err(format!(
+                    "{} does not accept images; remove the image or set CAPABILITIES_{}=vision if it does",
+                    model_name,
+                    model_key(model_name)
+                ));
+            }
+            let decoded_bytes = image[1].len() / 4 * 3;
+            if decoded_bytes > max_image {
+                return Err(format!(
+                    "an inline image is about {} bytes, over the {} byte limit (MAX_IMAGE_BYTES); resize or compress it first",
+                    decoded_bytes, max_image
+                ));
+            }
+        }
+    }
+
+    if let Some(window) = capabilities.context_tokens {
+        let tokenizer = tokenizer::for_model(model_name);
+        let tokens: usize = history.iter().map(|m| tokenizer.count(&attachments::without_images(&m.content))).sum();
+        if tokens > window {
+            return Err(format!(
+                "history is about {} tokens but {} accepts {}; lower CONTEXT_MAX_TOKENS (or CONTEXT_MAX_CHARS) or start a new session",
+                tokens, model_name, window
+            ));
+        }
+    }
+    Ok(())
+}
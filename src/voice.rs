@@ -0,0 +1,191 @@
+use std::env;
+use std::io::Write;
+use std::path::Path;
+use std::process::{Command, Stdio};
+use std::time::Duration;
+
+use uuid::Uuid;
+
+const DEFAULT_RECORD_COMMAND: &str = "rec -q -c 1 -r 16000 {file} trim 0 {seconds}";
+const DEFAULT_PLAY_COMMAND: &str = "play -q {file}";
+const DEFAULT_LOCAL_TTS_COMMAND: &str = "espeak --stdin";
+// The OpenAI speech endpoint rejects inputs longer than 4096 characters.
+const TTS_CHUNK_CHARS: usize = 4000;
+
+/// Records a clip from the microphone and returns its transcript.
+///
+/// Recording shells out to `VOICE_RECORD_COMMAND` (sox's `rec` by default). Transcription uses
+/// the OpenAI Whisper API unless `VOICE_BACKEND=whisper-cpp`, in which case the local
+/// `WHISPER_CPP_BIN` is run against `WHISPER_CPP_MODEL`.
+pub async fn record_and_transcribe() -> Result<String, Box<dyn std::error::Error>> {
+    let path = env::temp_dir().join(format!("agent_bench_voice_{}.wav", Uuid::new_v4()));
+    let result = match record(&path) {
+        Ok(()) => transcribe(&path).await,
+        Err(e) => Err(e),
+    };
+    let _ = std::fs::remove_file(&path);
+    result.map(|text| text.trim().to_string())
+}
+
+fn record(path: &Path) -> Result<(), Box<dyn std::error::Error>> {
+    let seconds = env::var("VOICE_RECORD_SECONDS").unwrap_or_else(|_| "5".to_string());
+    let command = env::var("VOICE_RECORD_COMMAND")
+        .unwrap_or_else(|_| DEFAULT_RECORD_COMMAND.to_string())
+        .replace("{file}", &path.to_string_lossy())
+        .replace("{seconds}", seconds.trim());
+
+    let output = Command::new("sh").arg("-c").arg(&command).output()?;
+    if !output.status.success() {
+        return Err(format!("Recording failed: {}", String::from_utf8_lossy(&output.stderr).trim()).into());
+    }
+    Ok(())
+}
+
+async fn transcribe(path: &Path) -> Result<String, Box<dyn std::error::Error>> {
+    match env::var("VOICE_BACKEND").unwrap_or_default().trim() {
+        "whisper-cpp" => transcribe_local(path),
+        _ => transcribe_api(path).await,
+    }
+}
+
+async fn transcribe_api(path: &Path) -> Result<String, Box<dyn std::error::Error>> {
+    let api_key = env::var("OPENAI_API_KEY").map_err(|_| "OPENAI_API_KEY not set in .env for voice input")?;
+    let audio = reqwest::multipart::Part::bytes(std::fs::read(path)?)
+        .file_name("voice.wav")
+        .mime_str("audio/wav")?;
+    let form = reqwest::multipart::Form::new()
+        .text("model", "whisper-1")
+        .text("response_format", "text")
+        .part("file", audio);
+
+    let client = reqwest::Client::builder()
+        .timeout(Duration::from_secs(60))
+        .build()?;
+    let res = client
+        .post("https://api.openai.com/v1/audio/transcriptions")
+        .bearer_auth(api_key)
+        .multipart(form)
+        .send()
+        .await?;
+
+    if !res.status().is_success() {
+        let status = res.status();
+        let error_text = res.text().await.unwrap_or_else(|_| "Could not read error body".to_string());
+        return Err(format!("Transcription Error: {} ({})", error_text, status).into());
+    }
+    Ok(res.text().await?)
+}
+
+fn transcribe_local(path: &Path) -> Result<String, Box<dyn std::error::Error>> {
+    let bin = env::var("WHISPER_CPP_BIN").unwrap_or_else(|_| "whisper-cli".to_string());
+    let model = env::var("WHISPER_CPP_MODEL").map_err(|_| "WHISPER_CPP_MODEL not set in .env for whisper.cpp")?;
+    let output = Command::new(bin)
+        .args(["-m", &model, "-f", &path.to_string_lossy(), "-nt", "-np"])
+        .output()?;
+    if !output.status.success() {
+        return Err(format!("whisper.cpp failed: {}", String::from_utf8_lossy(&output.stderr).trim()).into());
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).to_string())
+}
+
+/// Whether assistant replies are spoken when a session starts (`TTS_ENABLED`).
+pub fn tts_enabled_by_default() -> bool {
+    matches!(env::var("TTS_ENABLED").unwrap_or_default().trim(), "1" | "true" | "yes")
+}
+
+/// Speaks `text` aloud, using the OpenAI speech API unless `TTS_BACKEND=local`, in which case
+/// the text is piped to `TTS_LOCAL_COMMAND` (`espeak` by default).
+pub async fn speak(text: &str) -> Result<(), Box<dyn std::error::Error>> {
+    match env::var("TTS_BACKEND").unwrap_or_default().trim() {
+        "local" => speak_local(text),
+        _ => {
+            for chunk in sentence_chunks(text, TTS_CHUNK_CHARS) {
+                speak_api(&chunk).await?;
+            }
+            Ok(())
+        }
+    }
+}
+
+/// Splits `text` into pieces of at most `max_chars`, breaking after sentence ends where possible.
+fn sentence_chunks(text: &str, max_chars: usize) -> Vec<String> {
+    let mut chunks = Vec::new();
+    let mut current = String::new();
+    for sentence in text.split_inclusive(['.', '!', '?', '\n']) {
+        if !current.is_empty() && current.chars().count() + sentence.chars().count() > max_chars {
+            chunks.push(std::mem::take(&mut current));
+        }
+        current.push_str(sentence);
+        while current.chars().count() > max_chars {
+            let split_at = current.char_indices().nth(max_chars).map(|(i, _)| i).unwrap_or(current.len());
+            let rest = current.split_off(split_at);
+            chunks.push(std::mem::replace(&mut current, rest));
+        }
+    }
+    if !current.trim().is_empty() {
+        chunks.push(current);
+    }
+    chunks
+}
+
+async fn speak_api(text: &str) -> Result<(), Box<dyn std::error::Error>> {
+    let api_key = env::var("OPENAI_API_KEY").map_err(|_| "OPENAI_API_KEY not set in .env for text-to-speech")?;
+    let voice = env::var("TTS_VOICE").unwrap_or_else(|_| "alloy".to_string());
+    let body = serde_json::json!({
+        "model": "tts-1",
+        "voice": voice,
+        "input": text,
+        "response_format": "wav"
+    });
+
+    let client = reqwest::Client::builder()
+        .timeout(Duration::from_secs(60))
+        .build()?;
+    let res = client
+        .post("https://api.openai.com/v1/audio/speech")
+        .bearer_auth(api_key)
+        .json(&body)
+        .send()
+        .await?;
+
+    if !res.status().is_success() {
+        let status = res.status();
+        let error_text = res.text().await.unwrap_or_else(|_| "Could not read error body".to_string());
+        return Err(format!("Speech Error: {} ({})", error_text, status).into());
+    }
+
+    let path = env::temp_dir().join(format!("agent_bench_speech_{}.wav", Uuid::new_v4()));
+    std::fs::write(&path, res.bytes().await?)?;
+    let result = play(&path);
+    let _ = std::fs::remove_file(&path);
+    result
+}
+
+fn play(path: &Path) -> Result<(), Box<dyn std::error::Error>> {
+    let command = env::var("TTS_PLAY_COMMAND")
+        .unwrap_or_else(|_| DEFAULT_PLAY_COMMAND.to_string())
+        .replace("{file}", &path.to_string_lossy());
+    let output = Command::new("sh").arg("-c").arg(&command).output()?;
+    if !output.status.success() {
+        return Err(format!("Playback failed: {}", String::from_utf8_lossy(&output.stderr).trim()).into());
+    }
+    Ok(())
+}
+
+fn speak_local(text: &str) -> Result<(), Box<dyn std::error::Error>> {
+    let command = env::var("TTS_LOCAL_COMMAND").unwrap_or_else(|_| DEFAULT_LOCAL_TTS_COMMAND.to_string());
+    let mut child = Command::new("sh")
+        .arg("-c")
+        .arg(&command)
+        .stdin(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()?;
+    if let Some(mut stdin) = child.stdin.take() {
+        stdin.write_all(text.as_bytes())?;
+    }
+    let output = child.wait_with_output()?;
+    if !output.status.success() {
+        return Err(format!("Local TTS failed: {}", String::from_utf8_lossy(&output.stderr).trim()).into());
+    }
+    Ok(())
+}
@@ -0,0 +1,104 @@
+use std::collections::BTreeMap;
+use std::env;
+use std::path::PathBuf;
+use std::sync::OnceLock;
+
+use serde::{Deserialize, Serialize};
+
+/// Name of the workspace backed by the original `chat_sessions.db`.
+pub const DEFAULT_WORKSPACE: &str = "default";
+const DEFAULT_DATABASE: &str = "chat_sessions.db";
+
+/// Known workspaces and their database files, stored as `registry.json` in `WORKSPACE_DIR`
+/// (default `workspaces`), next to the workspace databases.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct Registry {
+    pub default: Option<String>,
+    pub workspaces: BTreeMap<String, String>,
+}
+
+fn workspace_dir() -> PathBuf {
+    PathBuf::from(env::var("WORKSPACE_DIR").unwrap_or_else(|_| "workspaces".to_string()))
+}
+
+impl Registry {
+    pub fn load() -> Self {
+        let mut registry: Registry = std::fs::read_to_string(workspace_dir().join("registry.json"))
+            .ok()
+            .and_then(|text| serde_json::from_str(&text).ok())
+            .unwrap_or_default();
+        registry.workspaces.entry(DEFAULT_WORKSPACE.to_string()).or_insert_with(|| DEFAULT_DATABASE.to_string());
+        registry
+    }
+
+    pub fn save(&self) -> Result<(), String> {
+        let dir = workspace_dir();
+        std::fs::create_dir_all(&dir).map_err(|e| e.to_string())?;
+        let text = serde_json::to_string_pretty(self).map_err(|e| e.to_string())?;
+        std::fs::write(dir.join("registry.json"), text).map_err(|e| e.to_string())
+    }
+
+    pub fn default_name(&self) -> &str {
+        self.default.as_deref().unwrap_or(DEFAULT_WORKSPACE)
+    }
+
+    /// Database path for `name`, registering a new workspace on first use.
+    fn path_for(&mut self, name: &str) -> Result<String, String> {
+        if !name.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_') || name.is_empty() {
+            return Err(format!("invalid workspace name '{}' (use letters, digits, '-' and '_')", name));
+        }
+        if let Some(path) = self.workspaces.get(name) {
+            return Ok(path.clone());
+        }
+        let path = workspace_dir().join(format!("{}.db", name)).to_string_lossy().to_string();
+        self.workspaces.insert(name.to_string(), path.clone());
+        self.save()?;
+        Ok(path)
+    }
+}
+
+/// The database this run works on: a workspace's (`--workspace <name>`, else the registry
+/// default) or an explicit file (`--db <path>`).
+#[derive(Debug)]
+pub struct ActiveDatabase {
+    pub label: String,
+    pub path: String,
+}
+
+static ACTIVE: OnceLock<ActiveDatabase> = OnceLock::new();
+
+/// Removes `--workspace <name>` and `--db <path>` from the arguments, so they can appear before
+/// or after a subcommand, and makes the selected database the active one.
+pub fn select_from_args(args: Vec<String>) -> Result<Vec<String>, String> {
+    let mut rest = Vec::new();
+    let mut workspace = None;
+    let mut database = None;
+    let mut iter = args.into_iter();
+    while let Some(arg) = iter.next() {
+        match arg.as_str() {
+            "--workspace" => workspace = Some(iter.next().ok_or("--workspace needs a name")?),
+            "--db" => database = Some(iter.next().ok_or("--db needs a file path")?),
+            _ => rest.push(arg),
+        }
+    }
+
+    let active = match (workspace, database) {
+        (Some(_), Some(_)) => return Err("use either --workspace or --db, not both".to_string()),
+        (None, Some(path)) => ActiveDatabase { label: path.clone(), path },
+        (workspace, None) => {
+            let mut registry = Registry::load();
+            let name = workspace.unwrap_or_else(|| registry.default_name().to_string());
+            let path = registry.path_for(&name)?;
+            ActiveDatabase { label: name, path }
+        }
+    };
+    if let Some(parent) = PathBuf::from(&active.path).parent().filter(|p| !p.as_os_str().is_empty()) {
+        std::fs::create_dir_all(parent).map_err(|e| e.to_string())?;
+    }
+    ACTIVE.set(active).map_err(|_| "database already selected".to_string())?;
+    Ok(rest)
+}
+
+pub fn active() -> &'static ActiveDatabase {
+    ACTIVE.get_or_init(|| ActiveDatabase { label: DEFAULT_WORKSPACE.to_string(), path: DEFAULT_DATABASE.to_string() })
+}